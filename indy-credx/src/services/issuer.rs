@@ -23,7 +23,7 @@ use indy_data_types::anoncreds::{
 };
 use indy_utils::{hash::SHA256, Qualifiable, Validatable};
 
-use super::tails::{TailsFileReader, TailsReader, TailsWriter};
+use super::tails::{TailsReader, TailsWriter};
 
 pub fn create_schema(
     origin_did: &DidValue,
@@ -236,7 +236,7 @@ where
 
     // now update registry to reflect issuance-by-default
     let (revoc_reg, revoc_init_delta) = if issuance_type == IssuanceType::ISSUANCE_BY_DEFAULT {
-        let tails_reader = TailsFileReader::new(&tails_location);
+        let tails_reader = tails_writer.reader(&tails_location)?;
         let issued = BTreeSet::from_iter((1..=max_cred_num).into_iter());
         update_revocation_registry(
             &revoc_reg_def,
@@ -623,4 +623,234 @@ mod tests {
             "99398763056634537812744552006896172984671876672520535998211840060697129507206"
         );
     }
+
+    #[test]
+    fn test_revocation_registry_tails_writers() {
+        use crate::services::tails::{TailsMemoryWriter, TailsStreamWriter};
+
+        let issuer_did = DidValue::new("NcYxiDXkpYi6ov5FcYDi1e", None);
+        let schema = create_schema(
+            &issuer_did,
+            "test",
+            "1.0",
+            vec!["name".to_owned()].into(),
+            None,
+        )
+        .unwrap();
+        let (cred_def, _, _) = create_credential_definition(
+            &issuer_did,
+            &schema,
+            "tag",
+            SignatureType::CL,
+            CredentialDefinitionConfig::new(true),
+        )
+        .unwrap();
+
+        let mut memory = TailsMemoryWriter::new(None);
+        let (reg_def, _, _, _) = create_revocation_registry(
+            &issuer_did,
+            &cred_def,
+            "default",
+            RegistryType::CL_ACCUM,
+            IssuanceType::ISSUANCE_BY_DEFAULT,
+            5,
+            &mut memory,
+        )
+        .unwrap();
+        let RevocationRegistryDefinition::RevocationRegistryDefinitionV1(reg_def) = reg_def;
+        assert_eq!(reg_def.value.tails_location, reg_def.value.tails_hash);
+        let tails = memory.into_tails();
+
+        let mut stream = TailsStreamWriter::new(Vec::new(), "s3://tails/default");
+        let (reg_def, _, _, _) = create_revocation_registry(
+            &issuer_did,
+            &cred_def,
+            "default",
+            RegistryType::CL_ACCUM,
+            IssuanceType::ISSUANCE_ON_DEMAND,
+            5,
+            &mut stream,
+        )
+        .unwrap();
+        let RevocationRegistryDefinition::RevocationRegistryDefinitionV1(reg_def) = reg_def;
+        assert_eq!(reg_def.value.tails_location, "s3://tails/default");
+        assert_eq!(stream.into_inner().len(), tails.len());
+
+        let mut stream = TailsStreamWriter::new(Vec::new(), "s3://tails/default");
+        assert!(create_revocation_registry(
+            &issuer_did,
+            &cred_def,
+            "default",
+            RegistryType::CL_ACCUM,
+            IssuanceType::ISSUANCE_BY_DEFAULT,
+            5,
+            &mut stream,
+        )
+        .is_err());
+    }
+
+    /// Create a revocation registry for five credentials, returning its
+    /// definition, initial state and delta, and a reader for its tails
+    fn _revocation_registry(
+        issuance_type: IssuanceType,
+    ) -> (
+        RevocationRegistryDefinition,
+        RevocationRegistry,
+        RevocationRegistryDelta,
+        TailsReader,
+    ) {
+        use crate::services::tails::TailsMemoryWriter;
+
+        let issuer_did = DidValue::new("NcYxiDXkpYi6ov5FcYDi1e", None);
+        let schema = create_schema(
+            &issuer_did,
+            "test",
+            "1.0",
+            vec!["name".to_owned()].into(),
+            None,
+        )
+        .unwrap();
+        let (cred_def, _, _) = create_credential_definition(
+            &issuer_did,
+            &schema,
+            "tag",
+            SignatureType::CL,
+            CredentialDefinitionConfig::new(true),
+        )
+        .unwrap();
+        let mut tails = TailsMemoryWriter::new(None);
+        let (reg_def, _, rev_reg, initial) = create_revocation_registry(
+            &issuer_did,
+            &cred_def,
+            "default",
+            RegistryType::CL_ACCUM,
+            issuance_type,
+            5,
+            &mut tails,
+        )
+        .unwrap();
+        let RevocationRegistryDefinition::RevocationRegistryDefinitionV1(reg_def_v1) = &reg_def;
+        let reader = tails.reader(&reg_def_v1.value.tails_location).unwrap();
+        (reg_def, rev_reg, initial, reader)
+    }
+
+    fn _accum(rev_reg: &RevocationRegistry) -> String {
+        serde_json::to_value(rev_reg).unwrap()["value"]["accum"]
+            .as_str()
+            .unwrap()
+            .to_owned()
+    }
+
+    fn _indices(indices: &[u32]) -> BTreeSet<u32> {
+        indices.iter().copied().collect()
+    }
+
+    #[test]
+    fn test_merge_revocation_registry_deltas() {
+        let (reg_def, rev_reg, initial, reader) =
+            _revocation_registry(IssuanceType::ISSUANCE_ON_DEMAND);
+
+        let (rev_reg, first) = update_revocation_registry(
+            &reg_def,
+            &rev_reg,
+            _indices(&[1, 2, 3]),
+            BTreeSet::new(),
+            &reader,
+        )
+        .unwrap();
+        let (rev_reg, second) = revoke_credential(&reg_def, &rev_reg, 2, &reader).unwrap();
+        let (rev_reg, third) =
+            update_revocation_registry(&reg_def, &rev_reg, _indices(&[4]), _indices(&[1]), &reader)
+                .unwrap();
+
+        let mut merged = initial.clone();
+        for delta in &[&first, &second, &third] {
+            merged.merge(delta).unwrap();
+        }
+        let value = merged.value().unwrap();
+        assert_eq!(value.prev_accum, None);
+        assert_eq!(value.accum, _accum(&rev_reg));
+        assert_eq!(value.issued, _indices(&[3, 4]));
+        assert_eq!(value.revoked, _indices(&[1, 2]));
+
+        // the typed merge agrees with the accumulator merge
+        let mut expected = initial;
+        for delta in &[&first, &second, &third] {
+            expected = merge_revocation_registry_deltas(&expected, delta).unwrap();
+        }
+        assert_eq!(expected.value().unwrap(), value);
+
+        // deltas must be consecutive
+        let mut skipped = first.clone();
+        assert!(skipped.merge(&third).is_err());
+        assert!(merge_revocation_registry_deltas(&first, &third).is_err());
+    }
+
+    #[test]
+    fn test_revocation_status_list_deltas() {
+        for &issuance_type in &[
+            IssuanceType::ISSUANCE_BY_DEFAULT,
+            IssuanceType::ISSUANCE_ON_DEMAND,
+        ] {
+            let (reg_def, rev_reg, initial, reader) = _revocation_registry(issuance_type);
+
+            let mut list = RevocationStatusList::from_delta(&reg_def, &initial, 10).unwrap();
+            assert_eq!(list.current_accumulator, Some(_accum(&rev_reg)));
+            let initially_revoked = if issuance_type == IssuanceType::ISSUANCE_ON_DEMAND {
+                5
+            } else {
+                0
+            };
+            assert_eq!(list.revoked().count(), initially_revoked);
+            assert_eq!(
+                list.revocation_list,
+                RevocationStatusList::new(&reg_def, None, None).revocation_list
+            );
+
+            let (rev_reg, first) = if issuance_type == IssuanceType::ISSUANCE_ON_DEMAND {
+                update_revocation_registry(
+                    &reg_def,
+                    &rev_reg,
+                    _indices(&[1, 2, 3, 4]),
+                    BTreeSet::new(),
+                    &reader,
+                )
+                .unwrap()
+            } else {
+                revoke_credential(&reg_def, &rev_reg, 5, &reader).unwrap()
+            };
+            list.apply_delta(&first, 20).unwrap();
+            let prev = list.clone();
+
+            let (rev_reg, second) = update_revocation_registry(
+                &reg_def,
+                &rev_reg,
+                BTreeSet::new(),
+                _indices(&[2, 4]),
+                &reader,
+            )
+            .unwrap();
+            list.apply_delta(&second, 30).unwrap();
+            assert_eq!(list.revoked().collect::<Vec<_>>(), vec![2, 4, 5]);
+            assert_eq!(list.current_accumulator, Some(_accum(&rev_reg)));
+
+            // the delta between two lists matches the registry update
+            let delta = list.to_delta(issuance_type, Some(&prev)).unwrap();
+            assert_eq!(delta.value().unwrap(), second.value().unwrap());
+
+            // a delta covering the whole history recreates the list
+            let full = list.to_delta(issuance_type, None).unwrap();
+            assert_eq!(full.value().unwrap().prev_accum, None);
+            assert_eq!(
+                RevocationStatusList::from_delta(&reg_def, &full, 30).unwrap(),
+                list
+            );
+
+            // deltas must start from the current accumulator
+            let mut replayed = prev.clone();
+            replayed.apply_delta(&second, 30).unwrap();
+            assert_eq!(replayed, list);
+            assert!(replayed.apply_delta(&second, 40).is_err());
+        }
+    }
 }
@@ -1,7 +1,10 @@
 mod helpers;
 
 pub mod issuer;
+#[cfg(feature = "async")]
+pub mod nonblocking;
 pub mod prover;
 pub mod tails;
 pub mod types;
 pub mod verifier;
+pub mod w3c;
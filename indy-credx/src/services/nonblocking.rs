@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use super::issuer::create_credential;
+use super::prover::create_presentation;
+use super::tails::TailsReader;
+use super::types::*;
+use super::verifier::verify_presentation;
+use crate::error::Result;
+
+/// An adapter for running blocking tasks on the thread pool of an async
+/// runtime, such as `tokio::task::spawn_blocking`
+pub trait BlockingRuntime: Send + Sync {
+    fn spawn_blocking(&self, task: Box<dyn FnOnce() + Send + 'static>);
+}
+
+/// A `BlockingRuntime` which runs each task on a new thread
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ThreadRuntime;
+
+impl BlockingRuntime for ThreadRuntime {
+    fn spawn_blocking(&self, task: Box<dyn FnOnce() + Send + 'static>) {
+        std::thread::spawn(task);
+    }
+}
+
+#[derive(Debug)]
+struct TaskState<T> {
+    result: Option<Result<T>>,
+    waker: Option<Waker>,
+    cancelled: bool,
+    finished: bool,
+}
+
+/// Marks the task as finished when dropped, including when the runtime
+/// drops the task without running it
+struct TaskCompletion<T> {
+    state: Arc<Mutex<TaskState<T>>>,
+}
+
+impl<T> Drop for TaskCompletion<T> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.finished = true;
+            if let Some(waker) = state.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// The future of a task running on a `BlockingRuntime`. Dropping it before
+/// the task has started cancels the task.
+#[derive(Debug)]
+pub struct BlockingTask<T> {
+    state: Arc<Mutex<TaskState<T>>>,
+}
+
+impl<T> Future for BlockingTask<T> {
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|_| err_msg!(Unexpected, "Blocking task state is poisoned"))?;
+        if let Some(result) = state.result.take() {
+            Poll::Ready(result)
+        } else if state.finished {
+            Poll::Ready(Err(err_msg!(Unexpected, "Blocking task did not complete")))
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl<T> Drop for BlockingTask<T> {
+    fn drop(&mut self) {
+        if let Ok(mut state) = self.state.lock() {
+            state.cancelled = true;
+        }
+    }
+}
+
+/// Run a blocking function on the thread pool of an async runtime
+pub fn spawn_blocking<R, F, T>(runtime: &R, f: F) -> BlockingTask<T>
+where
+    R: BlockingRuntime + ?Sized,
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let state = Arc::new(Mutex::new(TaskState {
+        result: None,
+        waker: None,
+        cancelled: false,
+        finished: false,
+    }));
+    let completion = TaskCompletion {
+        state: state.clone(),
+    };
+    runtime.spawn_blocking(Box::new(move || {
+        let cancelled = completion
+            .state
+            .lock()
+            .map(|state| state.cancelled)
+            .unwrap_or(true);
+        if !cancelled {
+            let result = f();
+            if let Ok(mut state) = completion.state.lock() {
+                state.result = Some(result);
+            }
+        }
+        drop(completion);
+    }));
+    BlockingTask { state }
+}
+
+/// The revocation configuration of `create_credential_async`
+#[derive(Debug)]
+pub struct OwnedRevocationConfig {
+    pub reg_def: RevocationRegistryDefinition,
+    pub reg_def_private: RevocationRegistryDefinitionPrivate,
+    pub registry: RevocationRegistry,
+    pub registry_idx: u32,
+    pub tails_reader: TailsReader,
+}
+
+/// A credential presented by `create_presentation_async`, with the
+/// referents of the requested attributes and predicates it satisfies
+#[derive(Debug)]
+pub struct PresentedCredential {
+    pub credential: Credential,
+    pub timestamp: Option<u64>,
+    pub rev_state: Option<CredentialRevocationState>,
+    pub requested_attributes: Vec<(String, bool)>,
+    pub requested_predicates: Vec<String>,
+}
+
+/// Create a credential on a `BlockingRuntime`
+pub async fn create_credential_async<R: BlockingRuntime + ?Sized>(
+    runtime: &R,
+    cred_def: CredentialDefinition,
+    cred_def_private: CredentialDefinitionPrivate,
+    cred_offer: CredentialOffer,
+    cred_request: CredentialRequest,
+    cred_values: CredentialValues,
+    revocation: Option<OwnedRevocationConfig>,
+) -> Result<(
+    Credential,
+    Option<RevocationRegistry>,
+    Option<RevocationRegistryDelta>,
+)> {
+    spawn_blocking(runtime, move || {
+        let revocation_config = revocation.map(|config| CredentialRevocationConfig {
+            reg_def: &config.reg_def,
+            reg_def_private: &config.reg_def_private,
+            registry: &config.registry,
+            registry_idx: config.registry_idx,
+            tails_reader: config.tails_reader,
+        });
+        create_credential(
+            &cred_def,
+            &cred_def_private,
+            &cred_offer,
+            &cred_request,
+            cred_values,
+            revocation_config,
+        )
+    })
+    .await
+}
+
+/// Create a presentation on a `BlockingRuntime`
+pub async fn create_presentation_async<R: BlockingRuntime + ?Sized>(
+    runtime: &R,
+    pres_req: PresentationRequest,
+    credentials: Vec<PresentedCredential>,
+    self_attested: Option<HashMap<String, String>>,
+    master_secret: MasterSecret,
+    schemas: HashMap<SchemaId, Schema>,
+    cred_defs: HashMap<CredentialDefinitionId, CredentialDefinition>,
+) -> Result<Presentation> {
+    spawn_blocking(runtime, move || {
+        let mut present = PresentCredentials::new();
+        for entry in credentials.iter() {
+            let mut add = present.add_credential(
+                &entry.credential,
+                entry.timestamp,
+                entry.rev_state.as_ref(),
+            );
+            for (referent, revealed) in entry.requested_attributes.iter() {
+                add.add_requested_attribute(referent.clone(), *revealed);
+            }
+            for referent in entry.requested_predicates.iter() {
+                add.add_requested_predicate(referent.clone());
+            }
+        }
+        create_presentation(
+            &pres_req,
+            present,
+            self_attested,
+            &master_secret,
+            &schemas.iter().map(|(id, s)| (id.clone(), s)).collect(),
+            &cred_defs.iter().map(|(id, c)| (id.clone(), c)).collect(),
+        )
+    })
+    .await
+}
+
+/// Verify a presentation on a `BlockingRuntime`
+pub async fn verify_presentation_async<R: BlockingRuntime + ?Sized>(
+    runtime: &R,
+    presentation: Presentation,
+    pres_req: PresentationRequest,
+    schemas: HashMap<SchemaId, Schema>,
+    cred_defs: HashMap<CredentialDefinitionId, CredentialDefinition>,
+    rev_reg_defs: Option<HashMap<RevocationRegistryId, RevocationRegistryDefinition>>,
+    rev_regs: Option<HashMap<RevocationRegistryId, HashMap<u64, RevocationRegistry>>>,
+) -> Result<bool> {
+    spawn_blocking(runtime, move || {
+        let rev_reg_defs = rev_reg_defs.as_ref().map(|defs| {
+            defs.iter()
+                .map(|(id, def)| (id.clone(), def))
+                .collect::<HashMap<_, _>>()
+        });
+        let rev_regs = rev_regs.as_ref().map(|regs| {
+            regs.iter()
+                .map(|(id, entries)| {
+                    let entries = entries
+                        .iter()
+                        .map(|(ts, reg)| (*ts, reg))
+                        .collect::<HashMap<_, _>>();
+                    (id.clone(), entries)
+                })
+                .collect::<HashMap<_, _>>()
+        });
+        verify_presentation(
+            &presentation,
+            &pres_req,
+            &schemas.iter().map(|(id, s)| (id.clone(), s)).collect(),
+            &cred_defs.iter().map(|(id, c)| (id.clone(), c)).collect(),
+            rev_reg_defs.as_ref(),
+            rev_regs.as_ref(),
+        )
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::task::{RawWaker, RawWakerVTable};
+
+    /// A runtime which holds tasks until they are run explicitly
+    #[derive(Default)]
+    struct DeferredRuntime {
+        tasks: Mutex<Vec<Box<dyn FnOnce() + Send + 'static>>>,
+    }
+
+    impl DeferredRuntime {
+        fn run_all(&self) {
+            let tasks: Vec<_> = self.tasks.lock().unwrap().drain(..).collect();
+            for task in tasks {
+                task();
+            }
+        }
+    }
+
+    impl BlockingRuntime for DeferredRuntime {
+        fn spawn_blocking(&self, task: Box<dyn FnOnce() + Send + 'static>) {
+            self.tasks.lock().unwrap().push(task);
+        }
+    }
+
+    fn noop_waker() -> Waker {
+        fn clone(_: *const ()) -> RawWaker {
+            RawWaker::new(std::ptr::null(), &VTABLE)
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        unsafe { Waker::from_raw(RawWaker::new(std::ptr::null(), &VTABLE)) }
+    }
+
+    fn poll<T>(task: &mut BlockingTask<T>) -> Poll<Result<T>> {
+        let waker = noop_waker();
+        Pin::new(task).poll(&mut Context::from_waker(&waker))
+    }
+
+    #[test]
+    fn blocking_task_completes() {
+        let runtime = DeferredRuntime::default();
+        let mut task = spawn_blocking(&runtime, || Ok(42));
+        assert!(poll(&mut task).is_pending());
+        runtime.run_all();
+        match poll(&mut task) {
+            Poll::Ready(Ok(value)) => assert_eq!(value, 42),
+            _ => panic!("Expected a result"),
+        }
+
+        let mut task = spawn_blocking(&ThreadRuntime, || Ok("done"));
+        let result = loop {
+            if let Poll::Ready(result) = poll(&mut task) {
+                break result;
+            }
+            std::thread::yield_now();
+        };
+        assert_eq!(result.unwrap(), "done");
+    }
+
+    #[test]
+    fn blocking_task_cancelled() {
+        let runtime = DeferredRuntime::default();
+        let ran = Arc::new(AtomicBool::new(false));
+        let flag = ran.clone();
+        let task = spawn_blocking(&runtime, move || {
+            flag.store(true, Ordering::SeqCst);
+            Ok(())
+        });
+        drop(task);
+        runtime.run_all();
+        assert!(!ran.load(Ordering::SeqCst));
+
+        // a task dropped by the runtime resolves to an error
+        let mut task = spawn_blocking(&runtime, || Ok(()));
+        runtime.tasks.lock().unwrap().clear();
+        match poll(&mut task) {
+            Poll::Ready(Err(_)) => (),
+            _ => panic!("Expected an error"),
+        }
+    }
+}
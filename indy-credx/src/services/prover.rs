@@ -6,19 +6,25 @@ use crate::services::helpers::*;
 use crate::ursa::cl::{
     issuer::Issuer as CryptoIssuer, prover::Prover as CryptoProver,
     verifier::Verifier as CryptoVerifier, CredentialPublicKey,
-    RevocationRegistry as CryptoRevocationRegistry, SubProofRequest, Witness,
+    RevocationRegistry as CryptoRevocationRegistry,
+    RevocationRegistryDelta as CryptoRevocationRegistryDelta, SubProofRequest, Witness,
 };
 use indy_data_types::anoncreds::{
     credential::AttributeValues,
-    pres_request::{PresentationRequestPayload, RequestedAttributeInfo, RequestedPredicateInfo},
+    pres_request::{
+        PredicateTypes, PresentationRequestPayload, RequestedAttributeInfo, RequestedPredicateInfo,
+    },
     presentation::{
         AttributeValue, Identifier, RequestedProof, RevealedAttributeGroupInfo,
         RevealedAttributeInfo, SubProofReferent,
     },
+    rev_reg_def::RevocationRegistryDefinitionV1,
 };
+use indy_utils::wql::Query;
 use indy_utils::{Qualifiable, Validatable};
 
 use super::tails::TailsReader;
+use super::verifier::{do_process_operator, Filter};
 
 pub fn create_master_secret() -> Result<MasterSecret> {
     MasterSecret::new().map_err(err_map!(Unexpected))
@@ -280,11 +286,10 @@ rev_reg_delta: {:?}, rev_reg_idx: {}, timestamp: {:?}, rev_state: {:?}",
     };
 
     let witness = match rev_state {
-        None => Witness::new(
-            rev_reg_idx,
-            revoc_reg_def.value.max_cred_num,
-            revoc_reg_def.value.issuance_type.to_bool(),
+        None => new_witness(
+            revoc_reg_def,
             &rev_reg_delta.value,
+            rev_reg_idx,
             &tails_reader,
         )?,
         Some(source_rev_state) => {
@@ -306,6 +311,100 @@ rev_reg_delta: {:?}, rev_reg_idx: {}, timestamp: {:?}, rev_state: {:?}",
     })
 }
 
+/// Create the non-revocation witness of a credential from the revocation
+/// status list of its registry
+pub fn create_witness(
+    revoc_reg_def: &RevocationRegistryDefinition,
+    rev_reg_idx: u32,
+    status_list: &RevocationStatusList,
+    tails_reader: &TailsReader,
+) -> Result<Witness> {
+    trace!(
+        "create_witness >>> revoc_reg_def: {:?}, rev_reg_idx: {}, status_list: {:?}, tails_reader: {:?}",
+        revoc_reg_def,
+        rev_reg_idx,
+        status_list,
+        tails_reader
+    );
+
+    let revoc_reg_def = match revoc_reg_def {
+        RevocationRegistryDefinition::RevocationRegistryDefinitionV1(v1) => v1,
+    };
+    if status_list.rev_reg_def_id != revoc_reg_def.id {
+        return Err(err_msg!(
+            "Revocation status list does not match the revocation registry definition"
+        ));
+    }
+    let rev_reg_delta = match status_list.to_delta(revoc_reg_def.value.issuance_type, None)? {
+        RevocationRegistryDelta::RevocationRegistryDeltaV1(v1) => v1,
+    };
+    let witness = new_witness(
+        revoc_reg_def,
+        &rev_reg_delta.value,
+        rev_reg_idx,
+        tails_reader,
+    )?;
+
+    trace!("create_witness <<< witness: {:?}", witness);
+
+    Ok(witness)
+}
+
+/// Update a non-revocation witness with a revocation registry delta, such as
+/// one computed between two revocation status lists, instead of recreating
+/// the witness from the whole history of the registry
+pub fn update_witness(
+    witness: &mut Witness,
+    revoc_reg_def: &RevocationRegistryDefinition,
+    rev_reg_delta: &RevocationRegistryDelta,
+    rev_reg_idx: u32,
+    tails_reader: &TailsReader,
+) -> Result<()> {
+    trace!(
+        "update_witness >>> witness: {:?}, revoc_reg_def: {:?}, rev_reg_delta: {:?}, rev_reg_idx: {}, tails_reader: {:?}",
+        witness,
+        revoc_reg_def,
+        rev_reg_delta,
+        rev_reg_idx,
+        tails_reader
+    );
+
+    let revoc_reg_def = match revoc_reg_def {
+        RevocationRegistryDefinition::RevocationRegistryDefinitionV1(v1) => v1,
+    };
+    let rev_reg_delta = match rev_reg_delta {
+        RevocationRegistryDelta::RevocationRegistryDeltaV1(v1) => v1,
+    };
+    witness.update(
+        rev_reg_idx,
+        revoc_reg_def.value.max_cred_num,
+        &rev_reg_delta.value,
+        tails_reader,
+    )?;
+
+    trace!("update_witness <<< witness: {:?}", witness);
+
+    Ok(())
+}
+
+fn new_witness(
+    revoc_reg_def: &RevocationRegistryDefinitionV1,
+    rev_reg_delta: &CryptoRevocationRegistryDelta,
+    rev_reg_idx: u32,
+    tails_reader: &TailsReader,
+) -> Result<Witness> {
+    if rev_reg_idx == 0 || rev_reg_idx > revoc_reg_def.value.max_cred_num {
+        return Err(err_msg!("Invalid revocation index: {}", rev_reg_idx));
+    }
+    Ok(Witness::new(
+        rev_reg_idx,
+        revoc_reg_def.value.max_cred_num,
+        revoc_reg_def.value.issuance_type.to_bool(),
+        rev_reg_delta,
+        tails_reader,
+    )?)
+}
+
 fn prepare_credential_for_proving(
     requested_attributes: HashSet<(String, bool)>,
     requested_predicates: HashSet<String>,
@@ -503,6 +602,188 @@ fn build_sub_proof_request(
     Ok(sub_proof_request)
 }
 
+/// Build the tags of a credential for wallet storage, matching those stored
+/// by Indy wallets
+pub fn credential_tags(credential: &Credential) -> Result<HashMap<String, String>> {
+    build_credential_tags(
+        &credential.schema_id,
+        &credential.cred_def_id,
+        credential.rev_reg_id.as_ref(),
+        credential
+            .values
+            .0
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.raw.as_str())),
+    )
+}
+
+fn build_credential_tags<'a>(
+    schema_id: &SchemaId,
+    cred_def_id: &CredentialDefinitionId,
+    rev_reg_id: Option<&RevocationRegistryId>,
+    attrs: impl Iterator<Item = (&'a str, &'a str)>,
+) -> Result<HashMap<String, String>> {
+    let invalid_schema_id =
+        || err_msg!("Invalid Schema ID `{}`: wrong number of parts", schema_id.0);
+    let issuer_did = cred_def_id.issuer_did().ok_or_else(|| {
+        err_msg!(
+            "Invalid Credential Definition ID `{}`: wrong number of parts",
+            cred_def_id.0
+        )
+    })?;
+
+    let mut tags = HashMap::new();
+    tags.insert("schema_id".to_owned(), schema_id.0.clone());
+    tags.insert(
+        "schema_issuer_did".to_owned(),
+        schema_id.issuer_did().ok_or_else(invalid_schema_id)?.0,
+    );
+    tags.insert(
+        "schema_name".to_owned(),
+        schema_id.name().ok_or_else(invalid_schema_id)?,
+    );
+    tags.insert(
+        "schema_version".to_owned(),
+        schema_id.version().ok_or_else(invalid_schema_id)?,
+    );
+    tags.insert("issuer_did".to_owned(), issuer_did.0);
+    tags.insert("cred_def_id".to_owned(), cred_def_id.0.clone());
+    if let Some(rev_reg_id) = rev_reg_id {
+        tags.insert("rev_reg_id".to_owned(), rev_reg_id.0.clone());
+    }
+    for (name, raw) in attrs {
+        let name = attr_common_view(name);
+        tags.insert(format!("attr::{}::marker", name), "1".to_owned());
+        tags.insert(format!("attr::{}::value", name), raw.to_owned());
+    }
+    Ok(tags)
+}
+
+/// Find the credentials which may satisfy each requested attribute and
+/// predicate of a presentation request, matching their attributes and the
+/// request restrictions. Predicates are evaluated against the raw attribute
+/// values. Revocation status is not considered.
+pub fn select_credentials(
+    pres_req: &PresentationRequest,
+    credentials: &[CredentialInfo],
+) -> Result<Vec<CandidateSet>> {
+    trace!(
+        "select_credentials >>> pres_req: {:?}, credentials: {:?}",
+        pres_req,
+        credentials
+    );
+
+    let pres_req = pres_req.value();
+    let attr_value = |credential: &CredentialInfo, name: &str| {
+        credential
+            .attrs
+            .iter()
+            .find(|(key, _)| attr_common_view(key) == attr_common_view(name))
+            .map(|(_, raw)| raw.clone())
+    };
+
+    let mut result = Vec::new();
+    for (referent, info) in pres_req.requested_attributes.iter() {
+        let names = match (&info.name, &info.names) {
+            (Some(name), _) => vec![name.clone()],
+            (None, Some(names)) => names.clone(),
+            (None, None) => vec![],
+        };
+        let mut matched = Vec::new();
+        for credential in credentials {
+            let values = names
+                .iter()
+                .map(|name| attr_value(credential, name))
+                .collect::<Option<Vec<_>>>();
+            let values = match values {
+                Some(values) if !names.is_empty() => values,
+                _ => continue,
+            };
+            let revealed: HashMap<String, Option<&str>> = names
+                .iter()
+                .cloned()
+                .zip(values.iter().map(|value| Some(value.as_str())))
+                .collect();
+            if restrictions_pass(&info.restrictions, credential, &revealed)? {
+                matched.push(credential.referent.clone());
+            }
+        }
+        let self_attestable = match &info.restrictions {
+            Some(Query::And(queries)) | Some(Query::Or(queries)) => queries.is_empty(),
+            Some(_) => false,
+            None => true,
+        } && info.name.is_some();
+        result.push(CandidateSet {
+            referent: referent.clone(),
+            predicate: false,
+            credentials: matched,
+            self_attestable,
+        });
+    }
+    for (referent, info) in pres_req.requested_predicates.iter() {
+        let mut matched = Vec::new();
+        for credential in credentials {
+            let satisfied = attr_value(credential, &info.name)
+                .and_then(|raw| raw.parse::<i32>().ok())
+                .map(|value| match info.p_type {
+                    PredicateTypes::GE => value >= info.p_value,
+                    PredicateTypes::GT => value > info.p_value,
+                    PredicateTypes::LE => value <= info.p_value,
+                    PredicateTypes::LT => value < info.p_value,
+                })
+                .unwrap_or(false);
+            // predicate values are never revealed to the verifier
+            let mut revealed: HashMap<String, Option<&str>> = HashMap::new();
+            revealed.insert(info.name.clone(), None);
+            if satisfied && restrictions_pass(&info.restrictions, credential, &revealed)? {
+                matched.push(credential.referent.clone());
+            }
+        }
+        result.push(CandidateSet {
+            referent: referent.clone(),
+            predicate: true,
+            credentials: matched,
+            self_attestable: false,
+        });
+    }
+    result.sort_by(|a, b| (a.predicate, &a.referent).cmp(&(b.predicate, &b.referent)));
+
+    trace!("select_credentials <<< result: {:?}", result);
+
+    Ok(result)
+}
+
+fn restrictions_pass(
+    restrictions: &Option<Query>,
+    credential: &CredentialInfo,
+    revealed: &HashMap<String, Option<&str>>,
+) -> Result<bool> {
+    match restrictions {
+        Some(query) => restrictions_match(
+            query,
+            &credential.schema_id,
+            &credential.cred_def_id,
+            revealed,
+        ),
+        None => Ok(true),
+    }
+}
+
+/// Check whether a presentation request restriction is satisfied by a
+/// credential, evaluating it exactly as the verifier will. `revealed` maps
+/// the attribute names requested by the referent to the raw values which
+/// will be revealed, or `None` when the value is not revealed. Operators
+/// rejected by the verifier, such as `$like` and `$gt`, never match.
+pub fn restrictions_match(
+    restrictions: &Query,
+    schema_id: &SchemaId,
+    cred_def_id: &CredentialDefinitionId,
+    revealed: &HashMap<String, Option<&str>>,
+) -> Result<bool> {
+    let filter = Filter::new(schema_id, cred_def_id)?;
+    Ok(do_process_operator(revealed, restrictions, &filter).is_ok())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -700,4 +981,243 @@ mod tests {
             assert_eq!(_attr_values(), res);
         }
     }
+
+    mod restrictions {
+        use super::*;
+
+        const SCHEMA_ID: &str = "NcYxiDXkpYi6ov5FcYDi1e:2:gvt:1.0";
+        const CRED_DEF_ID: &str =
+            "VsKV7grR1BUE29mG2Fm2kX:3:CL:NcYxiDXkpYi6ov5FcYDi1e:2:gvt:1.0:tag";
+
+        fn _tags() -> HashMap<String, String> {
+            build_credential_tags(
+                &SchemaId(SCHEMA_ID.to_string()),
+                &CredentialDefinitionId(CRED_DEF_ID.to_string()),
+                None,
+                vec![("First Name", "Alice"), ("age", "28")].into_iter(),
+            )
+            .unwrap()
+        }
+
+        fn _eq(name: &str, value: &str) -> Query {
+            Query::Eq(name.to_string(), value.to_string())
+        }
+
+        #[test]
+        fn credential_tags_from_identifiers() {
+            let tags = _tags();
+            assert_eq!(tags["schema_issuer_did"], "NcYxiDXkpYi6ov5FcYDi1e");
+            assert_eq!(tags["schema_name"], "gvt");
+            assert_eq!(tags["schema_version"], "1.0");
+            assert_eq!(tags["issuer_did"], "VsKV7grR1BUE29mG2Fm2kX");
+            assert_eq!(tags["attr::firstname::value"], "Alice");
+            assert_eq!(tags["attr::firstname::marker"], "1");
+            assert!(!tags.contains_key("rev_reg_id"));
+        }
+
+        #[test]
+        fn select_credentials_for_request() {
+            let credential = |referent: &str, name: &str, age: &str| CredentialInfo {
+                referent: referent.to_string(),
+                attrs: hashmap!(
+                    "First Name".to_string() => name.to_string(),
+                    "age".to_string() => age.to_string()
+                ),
+                schema_id: SchemaId(SCHEMA_ID.to_string()),
+                cred_def_id: CredentialDefinitionId(CRED_DEF_ID.to_string()),
+                rev_reg_id: None,
+                cred_rev_id: None,
+            };
+            let credentials = vec![
+                credential("cred1", "Alice", "28"),
+                credential("cred2", "Bob", "17"),
+            ];
+            let pres_req = PresentationRequestBuilder::new("proof", "1.0")
+                .nonce(new_nonce().unwrap())
+                .requested_attribute("firstname", Some(_eq("cred_def_id", CRED_DEF_ID)))
+                .requested_attribute("zip", None)
+                .requested_attribute("age", Some(_eq("attr::age::value", "17")))
+                .requested_predicate("age", PredicateTypes::GE, 18, None)
+                .requested_predicate("age", PredicateTypes::GE, 30, None)
+                .build()
+                .unwrap();
+
+            let selected = select_credentials(&pres_req, &credentials).unwrap();
+            let referents: Vec<(&str, &Vec<String>, bool)> = selected
+                .iter()
+                .map(|set| {
+                    (
+                        set.referent.as_str(),
+                        &set.credentials,
+                        set.is_unsatisfied(),
+                    )
+                })
+                .collect();
+            let both = vec!["cred1".to_string(), "cred2".to_string()];
+            assert_eq!(
+                referents,
+                vec![
+                    ("attr1_referent", &both, false),
+                    ("attr2_referent", &vec![], false),
+                    ("attr3_referent", &vec!["cred2".to_string()], false),
+                    ("predicate1_referent", &vec!["cred1".to_string()], false),
+                    ("predicate2_referent", &vec![], true),
+                ]
+            );
+            assert!(selected[1].self_attestable);
+        }
+
+        fn _matches(query: &Query, revealed: &[(&str, Option<&str>)]) -> bool {
+            let revealed = revealed
+                .iter()
+                .map(|(name, value)| (name.to_string(), *value))
+                .collect();
+            restrictions_match(
+                query,
+                &SchemaId(SCHEMA_ID.to_string()),
+                &CredentialDefinitionId(CRED_DEF_ID.to_string()),
+                &revealed,
+            )
+            .unwrap()
+        }
+
+        #[test]
+        fn restrictions_match_credential() {
+            let revealed = [("First Name", Some("Alice")), ("age", Some("28"))];
+            assert!(_matches(&_eq("cred_def_id", CRED_DEF_ID), &revealed));
+            assert!(!_matches(&_eq("issuer_did", "other"), &revealed));
+            assert!(_matches(
+                &_eq("attr::First Name::value", "Alice"),
+                &revealed
+            ));
+            assert!(!_matches(&_eq("attr::First Name::value", "Bob"), &revealed));
+            assert!(_matches(&_eq("attr::zip::marker", "1"), &revealed));
+            // the verifier does not check values which are not revealed
+            assert!(_matches(&_eq("attr::age::value", "30"), &[("age", None)]));
+            assert!(_matches(
+                &Query::And(vec![
+                    _eq("schema_name", "gvt"),
+                    Query::Or(vec![
+                        _eq("schema_version", "2.0"),
+                        _eq("schema_version", "1.0")
+                    ]),
+                    Query::Not(Box::new(_eq("attr::age::value", "30"))),
+                ]),
+                &revealed
+            ));
+            assert!(_matches(
+                &Query::In(
+                    "schema_id".to_string(),
+                    vec!["other".to_string(), SCHEMA_ID.to_string()]
+                ),
+                &revealed
+            ));
+        }
+
+        #[test]
+        fn restrictions_match_verifier_operators() {
+            let revealed = [("age", Some("28"))];
+
+            // an empty $or fails verification, while an empty $and passes
+            assert!(!_matches(&Query::Or(vec![]), &revealed));
+            assert!(_matches(&Query::And(vec![]), &revealed));
+
+            // $neq passes for a tag the verifier cannot match
+            assert!(_matches(
+                &Query::Neq("rev_reg_id".to_string(), "x".to_string()),
+                &revealed
+            ));
+
+            // operators rejected by the verifier never match
+            let tag = || "attr::age::value".to_string();
+            for query in [
+                Query::Like(tag(), "%2%".to_string()),
+                Query::Like(tag(), format!("{}b", "%a".repeat(64))),
+                Query::Gt(tag(), "1".to_string()),
+                Query::Gte(tag(), "1".to_string()),
+                Query::Lt(tag(), "9".to_string()),
+                Query::Lte(tag(), "9".to_string()),
+                Query::Exist(vec!["attr::age::marker".to_string()]),
+            ] {
+                assert!(!_matches(&query, &revealed), "{:?}", query);
+            }
+        }
+    }
+
+    mod witness {
+        use super::*;
+        use crate::services::issuer::{
+            create_credential_definition, create_revocation_registry, create_schema,
+            revoke_credential,
+        };
+        use crate::services::tails::{TailsMemoryWriter, TailsWriter};
+
+        fn _accum(registry: &RevocationRegistry) -> Option<String> {
+            serde_json::to_value(registry).unwrap()["value"]["accum"]
+                .as_str()
+                .map(str::to_owned)
+        }
+
+        #[test]
+        fn witness_from_status_list() {
+            let issuer_did = DidValue::new("NcYxiDXkpYi6ov5FcYDi1e", None);
+            let schema = create_schema(
+                &issuer_did,
+                "test",
+                "1.0",
+                vec!["name".to_owned()].into(),
+                None,
+            )
+            .unwrap();
+            let (cred_def, _, _) = create_credential_definition(
+                &issuer_did,
+                &schema,
+                "tag",
+                SignatureType::CL,
+                CredentialDefinitionConfig::new(true),
+            )
+            .unwrap();
+            let mut tails_writer = TailsMemoryWriter::new(None);
+            let (reg_def, _, registry, init_delta) = create_revocation_registry(
+                &issuer_did,
+                &cred_def,
+                "default",
+                RegistryType::CL_ACCUM,
+                IssuanceType::ISSUANCE_BY_DEFAULT,
+                5,
+                &mut tails_writer,
+            )
+            .unwrap();
+            let tails_reader = tails_writer.reader("").unwrap();
+
+            let mut status_list = RevocationStatusList::new(&reg_def, _accum(&registry), Some(10));
+            let mut witness = create_witness(&reg_def, 1, &status_list, &tails_reader).unwrap();
+            let rev_state = create_or_update_revocation_state(
+                tails_writer.reader("").unwrap(),
+                &reg_def,
+                &init_delta,
+                1,
+                10,
+                None,
+            )
+            .unwrap();
+            assert_eq!(
+                serde_json::to_value(&witness).unwrap(),
+                serde_json::to_value(rev_state.witness()).unwrap()
+            );
+            assert!(create_witness(&reg_def, 6, &status_list, &tails_reader).is_err());
+
+            let (registry, delta) =
+                revoke_credential(&reg_def, &registry, 2, &tails_reader).unwrap();
+            update_witness(&mut witness, &reg_def, &delta, 1, &tails_reader).unwrap();
+            status_list
+                .update(vec![], vec![2], _accum(&registry), 20)
+                .unwrap();
+            let recreated = create_witness(&reg_def, 1, &status_list, &tails_reader).unwrap();
+            assert_eq!(
+                serde_json::to_value(&witness).unwrap(),
+                serde_json::to_value(&recreated).unwrap()
+            );
+        }
+    }
 }
@@ -1,7 +1,9 @@
 use std::cell::RefCell;
 use std::fs::File;
 use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::Range;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use indy_utils::base58;
 use tempfile;
@@ -22,13 +24,16 @@ pub struct TailsReader {
 }
 
 impl TailsReader {
-    pub(crate) fn new<TR: TailsReaderImpl + 'static>(inner: TR) -> Self {
+    /// Wrap a custom tails reader implementation
+    pub fn new<TR: TailsReaderImpl + 'static>(inner: TR) -> Self {
         Self {
             inner: Box::new(RefCell::new(inner)),
         }
     }
 }
 
+/// A source of tails, read one tail at a time so that tails files do not
+/// need to be loaded into memory
 pub trait TailsReaderImpl: std::fmt::Debug + Send {
     fn hash(&mut self) -> Result<Vec<u8>>;
     fn read(&mut self, size: usize, offset: usize) -> Result<Vec<u8>>;
@@ -129,8 +134,177 @@ impl TailsReaderImpl for TailsFileReader {
     }
 }
 
+/// A function opening a stream of the remote tails file at a URL. When a
+/// byte range is given, the stream must start at the beginning of the range.
+pub type TailsFetch =
+    Box<dyn Fn(&str, Option<Range<usize>>) -> Result<Box<dyn Read>> + Send + Sync>;
+
+/// A tails reader for a remote tails file, fetching each tail with an HTTP
+/// range request. When the expected tails hash is provided, the whole file
+/// is streamed once to verify the hash before any tails are read.
+pub struct TailsHttpReader {
+    url: String,
+    expected_hash: Option<String>,
+    fetch: TailsFetch,
+    hash: Option<Vec<u8>>,
+}
+
+impl TailsHttpReader {
+    /// Fetch the tails file with HTTP GET requests, using a `Range` header
+    /// to fetch individual tails
+    #[cfg(feature = "tails-http")]
+    pub fn new(url: &str, tails_hash: Option<&str>) -> TailsReader {
+        Self::with_fetch(url, tails_hash, |url: &str, range: Option<Range<usize>>| {
+            let mut request = ureq::get(url);
+            if let Some(range) = range.as_ref() {
+                request = request.set("Range", &format!("bytes={}-{}", range.start, range.end - 1));
+            }
+            let response =
+                request
+                    .call()
+                    .map_err(err_map!(IOError, "Error fetching tails file: {}", url))?;
+            let partial = response.status() == 206;
+            let mut reader = response.into_reader();
+            if let Some(range) = range {
+                if !partial {
+                    // the server ignored the range, so skip to the requested offset
+                    std::io::copy(
+                        &mut (&mut reader).take(range.start as u64),
+                        &mut std::io::sink(),
+                    )?;
+                }
+            }
+            Ok(Box::new(reader) as Box<dyn Read>)
+        })
+    }
+
+    /// Fetch the tails file with a custom transport
+    pub fn with_fetch<F>(url: &str, tails_hash: Option<&str>, fetch: F) -> TailsReader
+    where
+        F: Fn(&str, Option<Range<usize>>) -> Result<Box<dyn Read>> + Send + Sync + 'static,
+    {
+        TailsReader::new(Self {
+            url: url.to_owned(),
+            expected_hash: tails_hash.map(str::to_owned),
+            fetch: Box::new(fetch),
+            hash: None,
+        })
+    }
+}
+
+impl std::fmt::Debug for TailsHttpReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TailsHttpReader")
+            .field("url", &self.url)
+            .field("expected_hash", &self.expected_hash)
+            .field("hash", &self.hash)
+            .finish()
+    }
+}
+
+impl TailsReaderImpl for TailsHttpReader {
+    fn hash(&mut self) -> Result<Vec<u8>> {
+        if let Some(hash) = self.hash.as_ref() {
+            return Ok(hash.clone());
+        }
+
+        let mut reader = (self.fetch)(&self.url, None)?;
+        let mut hasher = Sha256::default();
+        let mut buf = [0u8; 4096];
+        loop {
+            let sz = reader.read(&mut buf)?;
+            if sz == 0 {
+                break;
+            }
+            hasher.input(&buf[0..sz]);
+        }
+        let hash = hasher.result().to_vec();
+        if let Some(expected) = self.expected_hash.as_ref() {
+            if &base58::encode(&hash) != expected {
+                return Err(err_msg!(
+                    IOError,
+                    "Tails file hash mismatch for {}",
+                    self.url
+                ));
+            }
+        }
+        debug!("TailsHttpReader: verified tails file: {}", self.url);
+        self.hash.replace(hash.clone());
+        Ok(hash)
+    }
+
+    fn read(&mut self, size: usize, offset: usize) -> Result<Vec<u8>> {
+        if self.expected_hash.is_some() {
+            // the hash is only cached once it has been verified
+            self.hash()?;
+        }
+
+        let mut buf = vec![0u8; size];
+        if size > 0 {
+            let mut reader = (self.fetch)(&self.url, Some(offset..offset + size))?;
+            reader.read_exact(buf.as_mut_slice())?;
+        }
+
+        Ok(buf)
+    }
+}
+
+/// An in-memory tails file, as produced by `TailsMemoryWriter`
+#[derive(Debug)]
+pub struct TailsMemoryReader {
+    data: Arc<Vec<u8>>,
+}
+
+impl TailsMemoryReader {
+    pub fn new(data: Arc<Vec<u8>>) -> TailsReader {
+        TailsReader::new(Self { data })
+    }
+}
+
+impl TailsReaderImpl for TailsMemoryReader {
+    fn hash(&mut self) -> Result<Vec<u8>> {
+        Ok(Sha256::digest(self.data.as_slice()).to_vec())
+    }
+
+    fn read(&mut self, size: usize, offset: usize) -> Result<Vec<u8>> {
+        self.data
+            .get(offset..offset + size)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| err_msg!(IOError, "Tail offset out of range: {}", offset))
+    }
+}
+
+/// A destination for the tails generated when creating a revocation
+/// registry, returning the tails location and hash
 pub trait TailsWriter: std::fmt::Debug {
     fn write(&mut self, generator: &mut RevocationTailsGenerator) -> Result<(String, String)>;
+
+    /// Open a reader for the tails written to `location`, used to
+    /// initialize registries with the `ISSUANCE_BY_DEFAULT` issuance type
+    fn reader(&self, location: &str) -> Result<TailsReader> {
+        Ok(TailsFileReader::new(location))
+    }
+}
+
+/// Write the tails file format to a sink, returning the base58 tails hash
+/// and the number of bytes written
+fn write_tails<W: Write>(
+    generator: &mut RevocationTailsGenerator,
+    sink: &mut W,
+) -> Result<(String, usize)> {
+    let mut hasher = Sha256::default();
+    let version = &[0u8, 2u8];
+    sink.write_all(version)?;
+    hasher.input(version);
+    let mut size = version.len();
+    while let Some(tail) = generator.try_next()? {
+        let tail_bytes = tail.to_bytes()?;
+        sink.write_all(tail_bytes.as_slice())?;
+        hasher.input(&tail_bytes);
+        size += tail_bytes.len();
+    }
+    sink.flush()?;
+    Ok((base58::encode(hasher.result()), size))
 }
 
 #[derive(Debug)]
@@ -151,18 +325,7 @@ impl TailsFileWriter {
 impl TailsWriter for TailsFileWriter {
     fn write(&mut self, generator: &mut RevocationTailsGenerator) -> Result<(String, String)> {
         let mut tempf = tempfile::NamedTempFile::new_in(self.root_path.clone())?;
-        let file = tempf.as_file_mut();
-        let mut hasher = Sha256::default();
-        let version = &[0u8, 2u8];
-        file.write(version)?;
-        hasher.input(version);
-        while let Some(tail) = generator.try_next()? {
-            let tail_bytes = tail.to_bytes()?;
-            file.write(tail_bytes.as_slice())?;
-            hasher.input(tail_bytes);
-        }
-        let tails_size = &file.seek(SeekFrom::Current(0))?;
-        let hash = base58::encode(hasher.result());
+        let (hash, tails_size) = write_tails(generator, tempf.as_file_mut())?;
         let path = tempf.path().with_file_name(hash.clone());
         let _outf = match tempf.persist_noclobber(&path) {
             Ok(f) => f,
@@ -178,3 +341,327 @@ impl TailsWriter for TailsFileWriter {
         Ok((path, hash))
     }
 }
+
+/// A tails writer keeping the tails file in memory. The tails location is
+/// the tails hash, unless a location is provided.
+#[derive(Debug, Default)]
+pub struct TailsMemoryWriter {
+    location: Option<String>,
+    data: Arc<Vec<u8>>,
+}
+
+impl TailsMemoryWriter {
+    pub fn new(location: Option<String>) -> Self {
+        Self {
+            location,
+            data: Arc::default(),
+        }
+    }
+
+    /// The contents of the written tails file
+    pub fn tails(&self) -> &[u8] {
+        self.data.as_slice()
+    }
+
+    pub fn into_tails(self) -> Vec<u8> {
+        Arc::try_unwrap(self.data).unwrap_or_else(|data| data.as_ref().clone())
+    }
+}
+
+impl TailsWriter for TailsMemoryWriter {
+    fn write(&mut self, generator: &mut RevocationTailsGenerator) -> Result<(String, String)> {
+        let mut data = Vec::new();
+        let (hash, _) = write_tails(generator, &mut data)?;
+        self.data = Arc::new(data);
+        let location = self.location.clone().unwrap_or_else(|| hash.clone());
+        Ok((location, hash))
+    }
+
+    fn reader(&self, _location: &str) -> Result<TailsReader> {
+        Ok(TailsMemoryReader::new(self.data.clone()))
+    }
+}
+
+/// A tails writer streaming the tails file to a custom sink, such as an
+/// upload to object storage. The tails written to a sink cannot be read
+/// back, so a reader must be provided to create registries with the
+/// `ISSUANCE_BY_DEFAULT` issuance type.
+pub struct TailsStreamWriter<W: Write + std::fmt::Debug> {
+    sink: W,
+    location: String,
+    reader: Option<Box<dyn Fn(&str) -> Result<TailsReader> + Send + Sync>>,
+}
+
+impl<W: Write + std::fmt::Debug> TailsStreamWriter<W> {
+    pub fn new(sink: W, location: &str) -> Self {
+        Self {
+            sink,
+            location: location.to_owned(),
+            reader: None,
+        }
+    }
+
+    /// Set the function opening a reader for the tails at a location
+    pub fn read_with<F>(mut self, reader: F) -> Self
+    where
+        F: Fn(&str) -> Result<TailsReader> + Send + Sync + 'static,
+    {
+        self.reader = Some(Box::new(reader));
+        self
+    }
+
+    pub fn into_inner(self) -> W {
+        self.sink
+    }
+}
+
+impl<W: Write + std::fmt::Debug> std::fmt::Debug for TailsStreamWriter<W> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TailsStreamWriter")
+            .field("sink", &self.sink)
+            .field("location", &self.location)
+            .field("reader", &self.reader.is_some())
+            .finish()
+    }
+}
+
+impl<W: Write + std::fmt::Debug> TailsWriter for TailsStreamWriter<W> {
+    fn write(&mut self, generator: &mut RevocationTailsGenerator) -> Result<(String, String)> {
+        let (hash, tails_size) = write_tails(generator, &mut self.sink)?;
+        debug!(
+            "TailsStreamWriter: wrote tails [size {}]: {}",
+            tails_size, self.location
+        );
+        Ok((self.location.clone(), hash))
+    }
+
+    fn reader(&self, location: &str) -> Result<TailsReader> {
+        match self.reader.as_ref() {
+            Some(reader) => reader(location),
+            None => Err(err_msg!(
+                Input,
+                "Tails written to a stream cannot be read back: {}",
+                location
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::services::helpers::{build_credential_schema, build_non_credential_schema};
+    use crate::ursa::cl::issuer::Issuer as CryptoIssuer;
+
+    fn _generator() -> RevocationTailsGenerator {
+        let credential_schema =
+            build_credential_schema(&vec!["name".to_owned()].into_iter().collect()).unwrap();
+        let non_credential_schema = build_non_credential_schema().unwrap();
+        let (credential_pub_key, _, _) =
+            CryptoIssuer::new_credential_def(&credential_schema, &non_credential_schema, true)
+                .unwrap();
+        let (_, _, _, generator) =
+            CryptoIssuer::new_revocation_registry_def(&credential_pub_key, 5, false).unwrap();
+        generator
+    }
+
+    fn _tails() -> (Vec<u8>, String) {
+        let mut writer = TailsMemoryWriter::new(None);
+        let (_, hash) = writer.write(&mut _generator()).unwrap();
+        (writer.into_tails(), hash)
+    }
+
+    /// Count the full and ranged fetches of the tails file
+    #[derive(Debug, Default)]
+    struct Fetches {
+        full: AtomicUsize,
+        ranged: AtomicUsize,
+    }
+
+    fn _http_reader(tails: Vec<u8>, tails_hash: Option<&str>) -> (TailsHttpReader, Arc<Fetches>) {
+        let fetches = Arc::new(Fetches::default());
+        let counter = fetches.clone();
+        let reader = TailsHttpReader {
+            url: "https://tails.example/default".to_owned(),
+            expected_hash: tails_hash.map(str::to_owned),
+            fetch: Box::new(move |url: &str, range: Option<Range<usize>>| {
+                assert_eq!(url, "https://tails.example/default");
+                let data = match range {
+                    Some(range) => {
+                        counter.ranged.fetch_add(1, Ordering::SeqCst);
+                        tails.get(range).unwrap_or_default().to_vec()
+                    }
+                    None => {
+                        counter.full.fetch_add(1, Ordering::SeqCst);
+                        tails.clone()
+                    }
+                };
+                Ok(Box::new(std::io::Cursor::new(data)) as Box<dyn Read>)
+            }),
+            hash: None,
+        };
+        (reader, fetches)
+    }
+
+    #[test]
+    fn write_tails_format() {
+        let mut generator = _generator();
+        let mut data = Vec::new();
+        let (hash, size) = write_tails(&mut generator, &mut data).unwrap();
+        assert_eq!(size, data.len());
+        // a registry for 5 credentials has 2 * 5 + 1 tails
+        assert_eq!(size, TAILS_BLOB_TAG_SZ as usize + TAIL_SIZE * 11);
+        assert_eq!(&data[..TAILS_BLOB_TAG_SZ as usize], &[0u8, 2u8]);
+        assert_eq!(hash, base58::encode(Sha256::digest(&data)));
+        assert!(generator.try_next().unwrap().is_none());
+    }
+
+    #[test]
+    fn memory_writer() {
+        let mut writer = TailsMemoryWriter::new(None);
+        let (location, hash) = writer.write(&mut _generator()).unwrap();
+        assert_eq!(location, hash);
+        assert_eq!(base58::encode(Sha256::digest(writer.tails())), hash);
+
+        let reader = writer.reader(&location).unwrap();
+        let mut tail = None;
+        reader
+            .access_tail(0, &mut |t| tail = Some(t.to_bytes().unwrap()))
+            .unwrap();
+        assert_eq!(
+            tail.as_deref(),
+            writer
+                .tails()
+                .get(TAILS_BLOB_TAG_SZ as usize..TAILS_BLOB_TAG_SZ as usize + TAIL_SIZE)
+        );
+        let mut accessed = false;
+        assert!(reader.access_tail(11, &mut |_| accessed = true).is_err());
+        assert!(!accessed);
+
+        let mut writer = TailsMemoryWriter::new(Some("memory://tails".to_owned()));
+        let (location, _) = writer.write(&mut _generator()).unwrap();
+        assert_eq!(location, "memory://tails");
+        assert_eq!(
+            writer.into_tails().len(),
+            TAILS_BLOB_TAG_SZ as usize + TAIL_SIZE * 11
+        );
+    }
+
+    #[test]
+    fn stream_writer() {
+        let mut writer = TailsStreamWriter::new(Vec::new(), "s3://tails/default");
+        let (location, hash) = writer.write(&mut _generator()).unwrap();
+        assert_eq!(location, "s3://tails/default");
+        assert!(writer.reader(&location).is_err());
+        let tails = writer.into_inner();
+        assert_eq!(base58::encode(Sha256::digest(&tails)), hash);
+
+        // the reader may capture state, such as the uploaded tails
+        let uploaded = Arc::new(tails);
+        let writer = TailsStreamWriter::new(Vec::new(), "s3://tails/default").read_with({
+            let uploaded = uploaded.clone();
+            move |location| {
+                assert_eq!(location, "s3://tails/default");
+                Ok(TailsMemoryReader::new(uploaded.clone()))
+            }
+        });
+        let reader = writer.reader("s3://tails/default").unwrap();
+        let mut tail = None;
+        reader
+            .access_tail(1, &mut |t| tail = Some(t.to_bytes().unwrap()))
+            .unwrap();
+        let offset = TAILS_BLOB_TAG_SZ as usize + TAIL_SIZE;
+        assert_eq!(tail.as_deref(), uploaded.get(offset..offset + TAIL_SIZE));
+    }
+
+    #[test]
+    fn http_reader_range_requests() {
+        let (tails, hash) = _tails();
+        let (mut reader, fetches) = _http_reader(tails.clone(), Some(&hash));
+        let mut expected = TailsMemoryReader {
+            data: Arc::new(tails),
+        };
+
+        for offset in &[TAILS_BLOB_TAG_SZ as usize, TAIL_SIZE * 3 + 2, 2] {
+            assert_eq!(
+                reader.read(TAIL_SIZE, *offset).unwrap(),
+                expected.read(TAIL_SIZE, *offset).unwrap()
+            );
+        }
+        assert_eq!(reader.hash().unwrap(), expected.hash().unwrap());
+        assert_eq!(base58::encode(reader.hash().unwrap()), hash);
+        // the file is only streamed in full once, to verify the hash
+        assert_eq!(fetches.full.load(Ordering::SeqCst), 1);
+        assert_eq!(fetches.ranged.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn http_reader_without_hash() {
+        let (tails, hash) = _tails();
+        let (mut reader, fetches) = _http_reader(tails, None);
+        reader.read(TAIL_SIZE, TAILS_BLOB_TAG_SZ as usize).unwrap();
+        assert_eq!(fetches.full.load(Ordering::SeqCst), 0);
+        assert_eq!(base58::encode(reader.hash().unwrap()), hash);
+        assert_eq!(fetches.full.load(Ordering::SeqCst), 1);
+        assert_eq!(fetches.ranged.load(Ordering::SeqCst), 1);
+        assert!(reader
+            .read(TAIL_SIZE, TAILS_BLOB_TAG_SZ as usize + TAIL_SIZE * 11)
+            .is_err());
+    }
+
+    #[test]
+    fn http_reader_verifies_hash() {
+        let (mut tails, hash) = _tails();
+        tails[TAILS_BLOB_TAG_SZ as usize] ^= 1;
+        let (mut reader, fetches) = _http_reader(tails, Some(&hash));
+        assert!(reader.read(TAIL_SIZE, TAILS_BLOB_TAG_SZ as usize).is_err());
+        assert!(reader.hash().is_err());
+        assert_eq!(fetches.ranged.load(Ordering::SeqCst), 0);
+
+        // truncated files fail verification rather than individual reads
+        let (tails, hash) = _tails();
+        let (mut reader, _) = _http_reader(tails[..tails.len() - 1].to_vec(), Some(&hash));
+        assert!(reader.read(TAIL_SIZE, TAILS_BLOB_TAG_SZ as usize).is_err());
+    }
+
+    #[test]
+    fn http_reader_fetch_error() {
+        let reader =
+            TailsHttpReader::with_fetch("https://tails.example/missing", None, |url, _range| {
+                Err(err_msg!(IOError, "Error fetching tails file: {}", url))
+            });
+        let mut accessed = false;
+        assert!(reader.access_tail(0, &mut |_| accessed = true).is_err());
+        assert!(!accessed);
+    }
+
+    #[test]
+    fn http_reader_access_tail() {
+        let (tails, hash) = _tails();
+        let data = Arc::new(tails.clone());
+        let reader = TailsHttpReader::with_fetch(
+            "https://tails.example/default",
+            Some(&hash),
+            move |_, range| {
+                let data = match range {
+                    Some(range) => tails[range].to_vec(),
+                    None => tails.clone(),
+                };
+                Ok(Box::new(std::io::Cursor::new(data)) as Box<dyn Read>)
+            },
+        );
+        let expected = TailsMemoryReader::new(data);
+        let mut tail = None;
+        reader
+            .access_tail(4, &mut |t| tail = Some(t.to_bytes().unwrap()))
+            .unwrap();
+        let mut expected_tail = None;
+        expected
+            .access_tail(4, &mut |t| expected_tail = Some(t.to_bytes().unwrap()))
+            .unwrap();
+        assert!(tail.is_some());
+        assert_eq!(tail, expected_tail);
+    }
+}
@@ -9,15 +9,16 @@ pub use indy_data_types::{
         },
         cred_offer::CredentialOffer,
         cred_request::{CredentialRequest, CredentialRequestMetadata},
-        credential::{AttributeValues, Credential, CredentialValues},
+        credential::{AttributeValues, Credential, CredentialInfo, CredentialValues},
         master_secret::MasterSecret,
-        pres_request::PresentationRequest,
+        pres_request::{PresentationRequest, PresentationRequestBuilder},
         presentation::Presentation,
         rev_reg::{RevocationRegistry, RevocationRegistryDelta},
         rev_reg_def::{
             IssuanceType, RegistryType, RevocationRegistryDefinition,
             RevocationRegistryDefinitionPrivate,
         },
+        rev_status_list::RevocationStatusList,
         schema::{AttributeNames, Schema},
     },
     CredentialDefinitionId, RevocationRegistryId, SchemaId,
@@ -25,7 +26,8 @@ pub use indy_data_types::{
 pub use indy_utils::did::DidValue;
 use indy_utils::{invalid, Validatable, ValidationError};
 
-use crate::ursa::cl::{RevocationRegistry as CryptoRevocationRegistry, Witness};
+use crate::ursa::cl::RevocationRegistry as CryptoRevocationRegistry;
+pub use crate::ursa::cl::Witness;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CredentialDefinitionConfig {
@@ -114,6 +116,104 @@ impl Validatable for PresentCredentials<'_> {
     }
 }
 
+/// The credentials which may satisfy a requested attribute or predicate
+/// of a presentation request
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CandidateSet {
+    pub referent: String,
+    pub predicate: bool,
+    /// The referents of the matching credentials
+    pub credentials: Vec<String>,
+    /// Whether the attribute may be self-attested, having no restrictions
+    pub self_attestable: bool,
+}
+
+impl CandidateSet {
+    /// Check whether the referent cannot be satisfied
+    pub fn is_unsatisfied(&self) -> bool {
+        self.credentials.is_empty() && !self.self_attestable
+    }
+}
+
+/// The outcome of a detailed presentation verification
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerificationResult {
+    /// Whether the presentation is valid, with no failed checks
+    pub valid: bool,
+    /// Whether the CL proof of the presentation verified
+    pub signature: bool,
+    /// The outcomes for each requested and presented referent
+    pub referents: Vec<ReferentResult>,
+}
+
+impl VerificationResult {
+    /// All failed checks of the presentation
+    pub fn failures(&self) -> impl Iterator<Item = (&str, &VerificationFailure)> {
+        self.referents.iter().flat_map(|result| {
+            result
+                .failures
+                .iter()
+                .map(move |failure| (result.referent.as_str(), failure))
+        })
+    }
+
+    /// All warnings for the presentation
+    pub fn warnings(&self) -> impl Iterator<Item = (&str, &VerificationWarning)> {
+        self.referents.iter().flat_map(|result| {
+            result
+                .warnings
+                .iter()
+                .map(move |warning| (result.referent.as_str(), warning))
+        })
+    }
+}
+
+/// The outcome of the checks for a requested attribute or predicate
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReferentResult {
+    pub referent: String,
+    pub predicate: bool,
+    pub failures: Vec<VerificationFailure>,
+    pub warnings: Vec<VerificationWarning>,
+}
+
+impl ReferentResult {
+    pub fn is_valid(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+/// A failed presentation check
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "check", rename_all = "snake_case")]
+pub enum VerificationFailure {
+    /// The requested referent was not presented
+    Missing,
+    /// The referent was presented but not requested
+    NotRequested,
+    /// The restrictions of the request are not satisfied
+    Restrictions { reason: String },
+    /// A non-revocation interval was requested but no timestamp presented
+    NonRevocationInterval,
+    /// A revealed value differs from the value in the CL proof
+    RevealedValue { name: String },
+    /// A revealed raw value does not have the standard encoding of the
+    /// revealed encoded value, so the raw value is not covered by the proof
+    AttributeEncoding { name: String },
+    /// The CL proof does not prove the requested predicate
+    Predicate { name: String },
+}
+
+/// A presentation check which passed with a caveat
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "check", rename_all = "snake_case")]
+pub enum VerificationWarning {
+    /// The attribute was self-attested, with an empty list of restrictions
+    SelfAttestedWithRestrictions,
+    /// The presented timestamp falls outside the requested interval
+    TimestampOutsideInterval { timestamp: u64 },
+}
+
 #[derive(Debug)]
 pub(crate) struct PresentCredential<'p> {
     pub cred: &'p Credential,
@@ -175,6 +275,13 @@ pub struct CredentialRevocationState {
     pub(crate) timestamp: u64,
 }
 
+impl CredentialRevocationState {
+    /// The non-revocation witness of the credential
+    pub fn witness(&self) -> &Witness {
+        &self.witness
+    }
+}
+
 impl Validatable for CredentialRevocationState {
     fn validate(&self) -> std::result::Result<(), ValidationError> {
         if self.timestamp == 0 {
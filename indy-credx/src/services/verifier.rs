@@ -4,8 +4,9 @@ use once_cell::sync::Lazy;
 use regex::Regex;
 
 use super::helpers::*;
+use super::issuer::encode_credential_attribute;
 use super::types::*;
-use crate::error::Result;
+use crate::error::{ErrorKind, Result};
 use crate::ursa::cl::{verifier::Verifier as CryptoVerifier, CredentialPublicKey};
 use indy_data_types::anoncreds::{
     nonce::Nonce,
@@ -24,6 +25,33 @@ pub struct Filter {
     cred_def_id: String,
 }
 
+impl Filter {
+    /// Build the restriction filter for a credential from its identifiers
+    pub(crate) fn new(schema_id: &SchemaId, cred_def_id: &CredentialDefinitionId) -> Result<Self> {
+        let invalid_schema_id =
+            || err_msg!("Invalid Schema ID `{}`: wrong number of parts", schema_id.0);
+        let schema_issuer_did = schema_id.issuer_did().ok_or_else(invalid_schema_id)?;
+        let schema_name = schema_id.name().ok_or_else(invalid_schema_id)?;
+        let schema_version = schema_id.version().ok_or_else(invalid_schema_id)?;
+
+        let issuer_did = cred_def_id.issuer_did().ok_or_else(|| {
+            err_msg!(
+                "Invalid Credential Definition ID `{}`: wrong number of parts",
+                cred_def_id.0
+            )
+        })?;
+
+        Ok(Filter {
+            schema_id: schema_id.0.to_string(),
+            schema_name,
+            schema_issuer_did: schema_issuer_did.0,
+            schema_version,
+            cred_def_id: cred_def_id.0.to_string(),
+            issuer_did: issuer_did.0,
+        })
+    }
+}
+
 static INTERNAL_TAG_MATCHER: Lazy<Regex> =
     Lazy::new(|| Regex::new("^attr::([^:]+)::(value|marker)$").unwrap());
 
@@ -73,6 +101,185 @@ pub fn verify_presentation(
         &received_predicates,
     )?;
 
+    let valid = verify_proof(
+        presentation,
+        pres_req,
+        schemas,
+        cred_defs,
+        rev_reg_defs,
+        rev_regs,
+    )?;
+
+    trace!("verify <<< valid: {:?}", valid);
+
+    Ok(valid)
+}
+
+/// Verify a presentation, reporting the outcome of the checks for each
+/// requested attribute and predicate instead of failing on the first one
+pub fn verify_presentation_detailed(
+    presentation: &Presentation,
+    pres_req: &PresentationRequest,
+    schemas: &HashMap<SchemaId, &Schema>,
+    cred_defs: &HashMap<CredentialDefinitionId, &CredentialDefinition>,
+    rev_reg_defs: Option<&HashMap<RevocationRegistryId, &RevocationRegistryDefinition>>,
+    rev_regs: Option<&HashMap<RevocationRegistryId, HashMap<u64, &RevocationRegistry>>>,
+) -> Result<VerificationResult> {
+    trace!("verify_presentation_detailed >>> presentation: {:?}, pres_req: {:?}, schemas: {:?}, cred_defs: {:?}, rev_reg_defs: {:?} rev_regs: {:?}",
+    presentation, pres_req, schemas, cred_defs, rev_reg_defs, rev_regs);
+
+    let pres_req_value = pres_req.value();
+    let requested_proof = &presentation.requested_proof;
+    let received_revealed_attrs = received_revealed_attrs(&presentation)?;
+    let received_unrevealed_attrs = received_unrevealed_attrs(&presentation)?;
+    let received_predicates = received_predicates(&presentation)?;
+    let received_self_attested_attrs = received_self_attested_attrs(&presentation);
+    let proof_json = serde_json::to_value(&presentation.proof)
+        .map_err(err_map!(Unexpected, "Error serializing proof"))?;
+    let proof_attr_identifiers: HashMap<String, Identifier> = received_revealed_attrs
+        .iter()
+        .chain(&received_unrevealed_attrs)
+        .map(|(r, id)| (r.to_string(), id.clone()))
+        .collect();
+
+    let mut referents = Vec::new();
+
+    let mut requested_attrs = pres_req_value
+        .requested_attributes
+        .iter()
+        .collect::<Vec<_>>();
+    requested_attrs.sort_by_key(|(referent, _)| referent.as_str());
+    for (referent, info) in requested_attrs {
+        let mut result = ReferentResult {
+            referent: referent.clone(),
+            predicate: false,
+            failures: vec![],
+            warnings: vec![],
+        };
+        if let Some(identifier) = proof_attr_identifiers.get(referent) {
+            check_revealed_values(referent, info, presentation, &mut result)?;
+            if let Err(err) = check_attribute_restrictions(
+                referent,
+                info,
+                requested_proof,
+                &proof_attr_identifiers,
+            ) {
+                result.failures.push(VerificationFailure::Restrictions {
+                    reason: err.to_string(),
+                });
+            }
+            check_timestamp(
+                identifier,
+                &pres_req_value.non_revoked,
+                &info.non_revoked,
+                &mut result,
+            );
+        } else if received_self_attested_attrs.contains(referent) {
+            if !is_self_attested(referent, info, &received_self_attested_attrs) {
+                result.failures.push(VerificationFailure::Restrictions {
+                    reason: "Attribute with restrictions was self-attested".to_owned(),
+                });
+            } else if info.restrictions.is_some() {
+                result
+                    .warnings
+                    .push(VerificationWarning::SelfAttestedWithRestrictions);
+            }
+        } else {
+            result.failures.push(VerificationFailure::Missing);
+        }
+        referents.push(result);
+    }
+
+    let mut requested_predicates = pres_req_value
+        .requested_predicates
+        .iter()
+        .collect::<Vec<_>>();
+    requested_predicates.sort_by_key(|(referent, _)| referent.as_str());
+    for (referent, info) in requested_predicates {
+        let mut result = ReferentResult {
+            referent: referent.clone(),
+            predicate: true,
+            failures: vec![],
+            warnings: vec![],
+        };
+        if let Some(identifier) = received_predicates.get(referent) {
+            check_predicate(referent, info, requested_proof, &proof_json, &mut result);
+            if let Some(ref query) = info.restrictions {
+                if let Err(err) = gather_filter_info(&referent, &received_predicates)
+                    .and_then(|filter| process_operator(&info.name, &query, &filter, None))
+                {
+                    result.failures.push(VerificationFailure::Restrictions {
+                        reason: err.to_string(),
+                    });
+                }
+            }
+            check_timestamp(
+                identifier,
+                &pres_req_value.non_revoked,
+                &info.non_revoked,
+                &mut result,
+            );
+        } else {
+            result.failures.push(VerificationFailure::Missing);
+        }
+        referents.push(result);
+    }
+
+    let mut not_requested = proof_attr_identifiers
+        .keys()
+        .chain(&received_self_attested_attrs)
+        .filter(|referent| !pres_req_value.requested_attributes.contains_key(*referent))
+        .map(|referent| (referent, false))
+        .chain(
+            received_predicates
+                .keys()
+                .filter(|referent| !pres_req_value.requested_predicates.contains_key(*referent))
+                .map(|referent| (referent, true)),
+        )
+        .collect::<Vec<_>>();
+    not_requested.sort();
+    not_requested.dedup();
+    for (referent, predicate) in not_requested {
+        referents.push(ReferentResult {
+            referent: referent.clone(),
+            predicate,
+            failures: vec![VerificationFailure::NotRequested],
+            warnings: vec![],
+        });
+    }
+
+    let signature = match verify_proof(
+        presentation,
+        pres_req_value,
+        schemas,
+        cred_defs,
+        rev_reg_defs,
+        rev_regs,
+    ) {
+        Ok(valid) => valid,
+        Err(err) if err.kind() == ErrorKind::ProofRejected => false,
+        Err(err) => return Err(err),
+    };
+
+    let result = VerificationResult {
+        valid: signature && referents.iter().all(ReferentResult::is_valid),
+        signature,
+        referents,
+    };
+
+    trace!("verify_presentation_detailed <<< result: {:?}", result);
+
+    Ok(result)
+}
+
+fn verify_proof(
+    presentation: &Presentation,
+    pres_req: &PresentationRequestPayload,
+    schemas: &HashMap<SchemaId, &Schema>,
+    cred_defs: &HashMap<CredentialDefinitionId, &CredentialDefinition>,
+    rev_reg_defs: Option<&HashMap<RevocationRegistryId, &RevocationRegistryDefinition>>,
+    rev_regs: Option<&HashMap<RevocationRegistryId, HashMap<u64, &RevocationRegistry>>>,
+) -> Result<bool> {
     let mut proof_verifier = CryptoVerifier::new_proof_verifier()?;
     let non_credential_schema = build_non_credential_schema()?;
 
@@ -178,11 +385,119 @@ pub fn verify_presentation(
         )?;
     }
 
-    let valid = proof_verifier.verify(&presentation.proof, pres_req.nonce.as_native())?;
+    Ok(proof_verifier.verify(&presentation.proof, pres_req.nonce.as_native())?)
+}
 
-    trace!("verify <<< valid: {:?}", valid);
+fn check_revealed_values(
+    referent: &str,
+    info: &AttributeInfo,
+    proof: &Presentation,
+    result: &mut ReferentResult,
+) -> Result<()> {
+    let mut revealed = vec![];
+    if let (Some(name), Some(attr_info)) = (
+        info.name.as_ref(),
+        proof.requested_proof.revealed_attrs.get(referent),
+    ) {
+        revealed.push((name.clone(), Some(attr_info.clone())));
+    }
+    if let (Some(names), Some(attr_infos)) = (
+        info.names.as_ref(),
+        proof.requested_proof.revealed_attr_groups.get(referent),
+    ) {
+        for name in names {
+            let attr_info = attr_infos
+                .values
+                .get(name)
+                .map(|attr_info| RevealedAttributeInfo {
+                    sub_proof_index: attr_infos.sub_proof_index,
+                    raw: attr_info.raw.clone(),
+                    encoded: attr_info.encoded.clone(),
+                });
+            revealed.push((name.clone(), attr_info));
+        }
+    }
 
-    Ok(valid)
+    for (name, attr_info) in revealed {
+        let attr_info = match attr_info {
+            Some(attr_info) => attr_info,
+            None => {
+                result
+                    .failures
+                    .push(VerificationFailure::RevealedValue { name });
+                continue;
+            }
+        };
+        if encode_credential_attribute(&attr_info.raw)? != attr_info.encoded {
+            result
+                .failures
+                .push(VerificationFailure::AttributeEncoding { name: name.clone() });
+        }
+        if verify_revealed_attribute_value(&name, proof, &attr_info).is_err() {
+            result
+                .failures
+                .push(VerificationFailure::RevealedValue { name });
+        }
+    }
+    Ok(())
+}
+
+/// Check that the sub-proof referenced by a requested predicate proves that
+/// predicate, so that a mismatch is reported against the predicate itself
+fn check_predicate(
+    referent: &str,
+    info: &PredicateInfo,
+    requested_proof: &RequestedProof,
+    proof_json: &serde_json::Value,
+    result: &mut ReferentResult,
+) {
+    let attr_name = attr_common_view(&info.name);
+    let p_type = info.p_type.to_string();
+    let proven = requested_proof
+        .predicates
+        .get(referent)
+        .and_then(|sub_proof| {
+            proof_json["proofs"][sub_proof.sub_proof_index as usize]["primary_proof"]["ge_proofs"]
+                .as_array()
+        })
+        .map(|ge_proofs| {
+            ge_proofs.iter().any(|ge_proof| {
+                let predicate = &ge_proof["predicate"];
+                predicate["attr_name"] == attr_name.as_str()
+                    && predicate["p_type"] == p_type.as_str()
+                    && predicate["value"] == info.p_value
+            })
+        })
+        .unwrap_or(false);
+    if !proven {
+        result.failures.push(VerificationFailure::Predicate {
+            name: info.name.clone(),
+        });
+    }
+}
+
+fn check_timestamp(
+    identifier: &Identifier,
+    global_interval: &Option<NonRevocedInterval>,
+    local_interval: &Option<NonRevocedInterval>,
+    result: &mut ReferentResult,
+) {
+    if let Some(interval) = get_non_revoc_interval(global_interval, local_interval) {
+        match identifier.timestamp {
+            None => result
+                .failures
+                .push(VerificationFailure::NonRevocationInterval),
+            Some(timestamp) => {
+                if interval.from.map(|from| timestamp < from).unwrap_or(false)
+                    || interval.to.map(|to| timestamp > to).unwrap_or(false)
+                {
+                    result
+                        .warnings
+                        .push(VerificationWarning::TimestampOutsideInterval { timestamp });
+                }
+            }
+        }
+    }
 }
 
 pub fn generate_nonce() -> Result<Nonce> {
@@ -561,45 +876,7 @@ fn verify_requested_restrictions(
         .collect();
 
     for (referent, info) in requested_attrs {
-        if let Some(ref query) = info.restrictions {
-            let filter = gather_filter_info(&referent, &proof_attr_identifiers)?;
-
-            let name_value_map: HashMap<String, Option<&str>> = if let Some(name) = info.name {
-                let mut map = HashMap::new();
-                map.insert(
-                    name.clone(),
-                    requested_proof
-                        .revealed_attrs
-                        .get(&referent)
-                        .map(|attr| attr.raw.as_str()),
-                );
-                map
-            } else if let Some(names) = info.names {
-                let mut map = HashMap::new();
-                let attrs = requested_proof
-                    .revealed_attr_groups
-                    .get(&referent)
-                    .ok_or_else(|| err_msg!("Proof does not have referent from proof request"))?;
-                for name in names {
-                    let val = attrs.values.get(&name).map(|attr| attr.raw.as_str());
-                    map.insert(name, val);
-                }
-                map
-            } else {
-                error!(
-                    r#"Proof Request attribute restriction should contain "name" or "names" param. Current proof request: {:?}"#,
-                    pres_req
-                );
-                return Err(err_msg!(
-                    r#"Proof Request attribute restriction should contain "name" or "names" param"#,
-                ));
-            };
-
-            do_process_operator(&name_value_map, &query, &filter).map_err(err_map!(
-                "Requested restriction validation failed for \"{:?}\" attributes",
-                &name_value_map
-            ))?;
-        }
+        check_attribute_restrictions(&referent, &info, requested_proof, &proof_attr_identifiers)?;
     }
 
     for (referent, info) in pres_req.requested_predicates.iter() {
@@ -616,6 +893,55 @@ fn verify_requested_restrictions(
     Ok(())
 }
 
+fn check_attribute_restrictions(
+    referent: &str,
+    info: &AttributeInfo,
+    requested_proof: &RequestedProof,
+    proof_attr_identifiers: &HashMap<String, Identifier>,
+) -> Result<()> {
+    if let Some(ref query) = info.restrictions {
+        let filter = gather_filter_info(referent, proof_attr_identifiers)?;
+
+        let name_value_map: HashMap<String, Option<&str>> = if let Some(ref name) = info.name {
+            let mut map = HashMap::new();
+            map.insert(
+                name.clone(),
+                requested_proof
+                    .revealed_attrs
+                    .get(referent)
+                    .map(|attr| attr.raw.as_str()),
+            );
+            map
+        } else if let Some(ref names) = info.names {
+            let mut map = HashMap::new();
+            let attrs = requested_proof
+                .revealed_attr_groups
+                .get(referent)
+                .ok_or_else(|| err_msg!("Proof does not have referent from proof request"))?;
+            for name in names {
+                let val = attrs.values.get(name).map(|attr| attr.raw.as_str());
+                map.insert(name.clone(), val);
+            }
+            map
+        } else {
+            error!(
+                r#"Proof Request attribute restriction should contain "name" or "names" param. Current attribute: {:?}"#,
+                info
+            );
+            return Err(err_msg!(
+                r#"Proof Request attribute restriction should contain "name" or "names" param"#,
+            ));
+        };
+
+        do_process_operator(&name_value_map, &query, &filter).map_err(err_map!(
+            "Requested restriction validation failed for \"{:?}\" attributes",
+            &name_value_map
+        ))?;
+    }
+
+    Ok(())
+}
+
 fn is_self_attested(
     referent: &str,
     info: &AttributeInfo,
@@ -639,29 +965,7 @@ fn gather_filter_info(referent: &str, identifiers: &HashMap<String, Identifier>)
         )
     })?;
 
-    let (_, schema_issuer_did, schema_name, schema_version) =
-        identifier.schema_id.parts().ok_or_else(|| {
-            err_msg!(
-                "Invalid Schema ID `{}`: wrong number of parts",
-                identifier.schema_id.0
-            )
-        })?;
-
-    let issuer_did = identifier.cred_def_id.issuer_did().ok_or_else(|| {
-        err_msg!(
-            "Invalid Credential Definition ID `{}`: wrong number of parts",
-            identifier.cred_def_id.0
-        )
-    })?;
-
-    Ok(Filter {
-        schema_id: identifier.schema_id.0.to_string(),
-        schema_name,
-        schema_issuer_did: schema_issuer_did.0,
-        schema_version,
-        cred_def_id: identifier.cred_def_id.0.to_string(),
-        issuer_did: issuer_did.0,
-    })
+    Filter::new(&identifier.schema_id, &identifier.cred_def_id)
 }
 
 fn process_operator(
@@ -675,7 +979,10 @@ fn process_operator(
     do_process_operator(&attr_value_map, restriction_op, filter)
 }
 
-fn do_process_operator(
+/// Evaluate a restriction against the identifiers of a credential and the
+/// values revealed for the requested attributes. This is shared with the
+/// prover so that credentials are selected as they will be verified.
+pub(crate) fn do_process_operator(
     attr_value_map: &HashMap<String, Option<&str>>,
     restriction_op: &Query,
     filter: &Filter,
@@ -1169,4 +1476,283 @@ mod tests {
         validate_timestamp(&_received(), "referent_2", &None, &Some(_interval())).unwrap_err();
         validate_timestamp(&_received(), "referent_3", &None, &Some(_interval())).unwrap_err();
     }
+
+    #[test]
+    fn check_timestamp_reports_outcomes() {
+        let received = _received();
+        let check = |referent: &str, interval: NonRevocedInterval| {
+            let mut result = ReferentResult {
+                referent: referent.to_owned(),
+                predicate: false,
+                failures: vec![],
+                warnings: vec![],
+            };
+            check_timestamp(&received[referent], &None, &Some(interval), &mut result);
+            result
+        };
+
+        let result = check("referent_1", _interval());
+        assert!(result.is_valid());
+        assert!(result.warnings.is_empty());
+
+        let result = check(
+            "referent_1",
+            NonRevocedInterval {
+                from: Some(2000),
+                to: Some(3000),
+            },
+        );
+        assert!(result.is_valid());
+        assert_eq!(
+            result.warnings,
+            vec![VerificationWarning::TimestampOutsideInterval { timestamp: 1234 }]
+        );
+
+        let result = check("referent_2", _interval());
+        assert_eq!(
+            result.failures,
+            vec![VerificationFailure::NonRevocationInterval]
+        );
+    }
+
+    #[test]
+    fn verify_presentation_detailed_outcomes() {
+        use crate::services::issuer::{
+            create_credential, create_credential_definition, create_credential_offer, create_schema,
+        };
+        use crate::services::prover::{
+            create_credential_request, create_master_secret, create_presentation,
+            process_credential,
+        };
+        use indy_data_types::anoncreds::pres_request::PredicateTypes;
+
+        let issuer_did = DidValue::new("NcYxiDXkpYi6ov5FcYDi1e", None);
+        let schema = create_schema(
+            &issuer_did,
+            "test",
+            "1.0",
+            vec!["name".to_owned(), "age".to_owned()].into(),
+            None,
+        )
+        .unwrap();
+        let (cred_def, cred_def_private, correctness_proof) = create_credential_definition(
+            &issuer_did,
+            &schema,
+            "tag",
+            SignatureType::CL,
+            CredentialDefinitionConfig::new(false),
+        )
+        .unwrap();
+        let Schema::SchemaV1(schema_v1) = &schema;
+        let cred_offer =
+            create_credential_offer(&schema_v1.id, &cred_def, &correctness_proof).unwrap();
+        let master_secret = create_master_secret().unwrap();
+        let (cred_request, cred_request_metadata) = create_credential_request(
+            &DidValue::new("VsKV7grR1BUE29mG2Fm2kX", None),
+            &cred_def,
+            &master_secret,
+            "default",
+            &cred_offer,
+        )
+        .unwrap();
+        let cred_values = CredentialValues(
+            [("name", "Alice"), ("age", "28")]
+                .iter()
+                .map(|(name, raw)| {
+                    (
+                        name.to_string(),
+                        AttributeValues {
+                            raw: raw.to_string(),
+                            encoded: encode_credential_attribute(raw).unwrap(),
+                        },
+                    )
+                })
+                .collect(),
+        );
+        let (mut credential, _, _) = create_credential(
+            &cred_def,
+            &cred_def_private,
+            &cred_offer,
+            &cred_request,
+            cred_values,
+            None,
+        )
+        .unwrap();
+        process_credential(
+            &mut credential,
+            &cred_request_metadata,
+            &master_secret,
+            &cred_def,
+            None,
+        )
+        .unwrap();
+
+        let nonce = generate_nonce().unwrap();
+        let request = |extra: Option<&str>, nonce: Nonce| {
+            let mut builder = PresentationRequestBuilder::new("proof", "1.0")
+                .nonce(nonce)
+                .requested_attribute("name", None)
+                .requested_attribute("age", None)
+                .requested_predicate("age", PredicateTypes::GE, 18, None);
+            if let Some(name) = extra {
+                builder = builder.requested_attribute(name, None);
+            }
+            builder.build().unwrap()
+        };
+        let pres_req = request(None, nonce.try_clone().unwrap());
+
+        let mut schemas = HashMap::new();
+        schemas.insert(cred_offer.schema_id.clone(), &schema);
+        let mut cred_defs = HashMap::new();
+        cred_defs.insert(cred_offer.cred_def_id.clone(), &cred_def);
+        let mut present = PresentCredentials::new();
+        {
+            let mut cred = present.add_credential(&credential, None, None);
+            cred.add_requested_attribute("attr1_referent".to_owned(), true);
+            cred.add_requested_attribute("attr2_referent".to_owned(), true);
+            cred.add_requested_predicate("predicate1_referent".to_owned());
+        }
+        let mut presentation = create_presentation(
+            &pres_req,
+            present,
+            None,
+            &master_secret,
+            &schemas,
+            &cred_defs,
+        )
+        .unwrap();
+
+        let verify = |presentation: &Presentation, pres_req: &PresentationRequest| {
+            let result = verify_presentation_detailed(
+                presentation,
+                pres_req,
+                &schemas,
+                &cred_defs,
+                None,
+                None,
+            )
+            .unwrap();
+            let failures = result
+                .failures()
+                .map(|(referent, failure)| (referent.to_owned(), failure.clone()))
+                .collect::<Vec<_>>();
+            let warnings = result
+                .warnings()
+                .map(|(referent, warning)| (referent.to_owned(), warning.clone()))
+                .collect::<Vec<_>>();
+            (result, failures, warnings)
+        };
+
+        let (result, failures, warnings) = verify(&presentation, &pres_req);
+        assert!(result.valid);
+        assert!(result.signature);
+        assert_eq!(result.referents.len(), 3);
+        assert!(failures.is_empty());
+        assert!(warnings.is_empty());
+        assert!(
+            verify_presentation(&presentation, &pres_req, &schemas, &cred_defs, None, None)
+                .unwrap()
+        );
+
+        // a requested attribute which was not presented
+        let extended = request(Some("phone"), nonce.try_clone().unwrap());
+        let (result, failures, _) = verify(&presentation, &extended);
+        assert!(!result.valid);
+        assert!(result.signature);
+        assert_eq!(
+            failures,
+            vec![("attr3_referent".to_owned(), VerificationFailure::Missing)]
+        );
+
+        // a presented attribute which was not requested
+        presentation
+            .requested_proof
+            .self_attested_attrs
+            .insert("attr9_referent".to_owned(), "value".to_owned());
+        let (result, failures, _) = verify(&presentation, &pres_req);
+        assert!(!result.valid);
+        assert!(result.signature);
+        assert_eq!(
+            failures,
+            vec![(
+                "attr9_referent".to_owned(),
+                VerificationFailure::NotRequested
+            )]
+        );
+        presentation.requested_proof.self_attested_attrs.clear();
+
+        // a raw value which does not match its encoding
+        let revealed = presentation.requested_proof.revealed_attrs["attr1_referent"].clone();
+        presentation
+            .requested_proof
+            .revealed_attrs
+            .get_mut("attr1_referent")
+            .unwrap()
+            .raw = "Bob".to_owned();
+        let (result, failures, warnings) = verify(&presentation, &pres_req);
+        assert!(!result.valid);
+        assert!(result.signature);
+        assert_eq!(
+            failures,
+            vec![(
+                "attr1_referent".to_owned(),
+                VerificationFailure::AttributeEncoding {
+                    name: "name".to_owned()
+                }
+            )]
+        );
+        assert!(warnings.is_empty());
+
+        // a revealed value which differs from the proof
+        presentation
+            .requested_proof
+            .revealed_attrs
+            .get_mut("attr1_referent")
+            .unwrap()
+            .encoded = encode_credential_attribute("Bob").unwrap();
+        let (result, failures, warnings) = verify(&presentation, &pres_req);
+        assert!(!result.valid);
+        assert_eq!(
+            failures,
+            vec![(
+                "attr1_referent".to_owned(),
+                VerificationFailure::RevealedValue {
+                    name: "name".to_owned()
+                }
+            )]
+        );
+        assert!(warnings.is_empty());
+        presentation
+            .requested_proof
+            .revealed_attrs
+            .insert("attr1_referent".to_owned(), revealed);
+
+        // a predicate which differs from the proven predicate
+        let stricter = PresentationRequestBuilder::new("proof", "1.0")
+            .nonce(nonce.try_clone().unwrap())
+            .requested_attribute("name", None)
+            .requested_attribute("age", None)
+            .requested_predicate("age", PredicateTypes::GE, 21, None)
+            .build()
+            .unwrap();
+        let (result, failures, _) = verify(&presentation, &stricter);
+        assert!(!result.valid);
+        assert!(!result.signature);
+        assert_eq!(
+            failures,
+            vec![(
+                "predicate1_referent".to_owned(),
+                VerificationFailure::Predicate {
+                    name: "age".to_owned()
+                }
+            )]
+        );
+
+        // a proof created for a different nonce
+        let other = request(None, generate_nonce().unwrap());
+        let (result, failures, _) = verify(&presentation, &other);
+        assert!(!result.valid);
+        assert!(!result.signature);
+        assert!(failures.is_empty());
+    }
 }
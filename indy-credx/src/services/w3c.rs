@@ -0,0 +1,266 @@
+use serde_json::{json, Map, Value};
+
+use super::issuer::encode_credential_attribute;
+use super::types::*;
+use crate::error::Result;
+use crate::ursa::cl::{
+    CredentialSignature, RevocationRegistry as CryptoRevocationRegistry, SignatureCorrectnessProof,
+    Witness,
+};
+use indy_utils::base64;
+use indy_utils::vc::{Proof, VerifiableCredential, DATA_INTEGRITY_PROOF, PROOF_PURPOSE_ASSERTION};
+use indy_utils::{Qualifiable, Validatable};
+
+/// The JSON-LD context of AnonCreds credentials in the W3C format
+pub const ANONCREDS_W3C_CONTEXT: &'static str =
+    "https://raw.githubusercontent.com/hyperledger/anoncreds-spec/main/data/anoncreds-w3c-context.json";
+pub const ANONCREDS_CREDENTIAL: &'static str = "AnonCredsCredential";
+pub const ANONCREDS_DEFINITION: &'static str = "AnonCredsDefinition";
+/// The cryptosuite of the data integrity proof holding the CL signature
+pub const ANONCREDS_CRYPTOSUITE: &'static str = "anoncreds-2023";
+
+/// The `credentialSchema` of an AnonCreds credential in the W3C format
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AnonCredsCredentialSchema {
+    #[serde(rename = "type")]
+    type_: String,
+    definition: CredentialDefinitionId,
+    schema: SchemaId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    revocation_registry: Option<RevocationRegistryId>,
+}
+
+/// The CL signature and revocation witness carried by the proof value
+#[derive(Debug, Deserialize)]
+struct CredentialSignatureProof {
+    signature: CredentialSignature,
+    signature_correctness_proof: SignatureCorrectnessProof,
+    #[serde(default)]
+    rev_reg: Option<CryptoRevocationRegistry>,
+    #[serde(default)]
+    witness: Option<Witness>,
+}
+
+/// Convert an AnonCreds credential to the W3C format, issued at the
+/// RFC 3339 date-time `issuance_date`. The CL signature is preserved in a
+/// `DataIntegrityProof`, encoded as base64url JSON. Only credentials using
+/// the standard attribute encoding can be converted.
+pub fn convert_to_w3c(
+    credential: &Credential,
+    issuance_date: &str,
+) -> Result<VerifiableCredential> {
+    trace!(
+        "convert_to_w3c >>> credential: {:?}, issuance_date: {:?}",
+        credential,
+        issuance_date
+    );
+
+    credential.validate()?;
+    let issuer = credential
+        .cred_def_id
+        .issuer_did()
+        .ok_or_else(|| err_msg!("Invalid credential definition ID"))?
+        .default_method(Some("sov"));
+
+    let mut subject = Map::new();
+    for (name, value) in credential.values.0.iter() {
+        if encode_credential_attribute(&value.raw)? != value.encoded {
+            return Err(err_msg!(
+                "Attribute `{}` does not use the standard encoding",
+                name
+            ));
+        }
+        subject.insert(name.clone(), Value::String(value.raw.clone()));
+    }
+
+    let schema = AnonCredsCredentialSchema {
+        type_: ANONCREDS_DEFINITION.to_owned(),
+        definition: credential.cred_def_id.clone(),
+        schema: credential.schema_id.clone(),
+        revocation_registry: credential.rev_reg_id.clone(),
+    };
+    let signature = json!({
+        "signature": credential.signature,
+        "signature_correctness_proof": credential.signature_correctness_proof,
+        "rev_reg": credential.rev_reg,
+        "witness": credential.witness,
+    });
+    let proof = Proof {
+        type_: DATA_INTEGRITY_PROOF.to_owned(),
+        cryptosuite: Some(ANONCREDS_CRYPTOSUITE.to_owned()),
+        created: None,
+        verification_method: credential.cred_def_id.to_string(),
+        proof_purpose: PROOF_PURPOSE_ASSERTION.to_owned(),
+        challenge: None,
+        domain: None,
+        proof_value: format!(
+            "u{}",
+            base64::encode_urlsafe_nopad(serde_json::to_vec(&signature)?)
+        ),
+    };
+
+    let mut w3c = VerifiableCredential::new(&issuer, issuance_date, Value::Object(subject))
+        .context(ANONCREDS_W3C_CONTEXT)
+        .type_(ANONCREDS_CREDENTIAL)
+        .credential_schema(serde_json::to_value(schema)?);
+    w3c.proof = Some(proof);
+
+    trace!("convert_to_w3c <<< w3c: {:?}", w3c);
+
+    Ok(w3c)
+}
+
+/// Convert an AnonCreds credential in the W3C format back to an AnonCreds
+/// credential, recovering the CL signature from its `DataIntegrityProof`
+pub fn convert_from_w3c(credential: &VerifiableCredential) -> Result<Credential> {
+    trace!("convert_from_w3c >>> credential: {:?}", credential);
+
+    credential.validate()?;
+    let proof = credential
+        .proof
+        .as_ref()
+        .filter(|proof| {
+            proof.type_ == DATA_INTEGRITY_PROOF
+                && proof.cryptosuite.as_deref() == Some(ANONCREDS_CRYPTOSUITE)
+        })
+        .ok_or_else(|| err_msg!("Credential has no AnonCreds signature proof"))?;
+    let schema: AnonCredsCredentialSchema = serde_json::from_value(
+        credential
+            .credential_schema
+            .clone()
+            .ok_or_else(|| err_msg!("Credential has no credential schema"))?,
+    )
+    .map_err(err_map!("Invalid credential schema"))?;
+    if schema.type_ != ANONCREDS_DEFINITION {
+        return Err(err_msg!("Unsupported credential schema: {}", schema.type_));
+    }
+    if !proof.proof_value.starts_with('u') {
+        return Err(err_msg!("Expected a base64url multibase proof value"));
+    }
+    let signature =
+        base64::decode_urlsafe(&proof.proof_value[1..]).map_err(err_map!("Invalid proof value"))?;
+    let signature: CredentialSignatureProof =
+        serde_json::from_slice(&signature).map_err(err_map!("Invalid proof value"))?;
+
+    let subject = credential
+        .credential_subject
+        .as_object()
+        .ok_or_else(|| err_msg!("Credential subject must be an object"))?;
+    let mut values = CredentialValues(Default::default());
+    for (name, value) in subject {
+        let raw = match value {
+            Value::String(raw) => raw.clone(),
+            Value::Number(raw) => raw.to_string(),
+            _ => return Err(err_msg!("Unsupported value for attribute `{}`", name)),
+        };
+        let encoded = encode_credential_attribute(&raw)?;
+        values
+            .0
+            .insert(name.clone(), AttributeValues { raw, encoded });
+    }
+
+    let result = Credential {
+        schema_id: schema.schema,
+        cred_def_id: schema.definition,
+        rev_reg_id: schema.revocation_registry,
+        values,
+        signature: signature.signature,
+        signature_correctness_proof: signature.signature_correctness_proof,
+        rev_reg: signature.rev_reg,
+        witness: signature.witness,
+    };
+    result.validate()?;
+
+    trace!("convert_from_w3c <<< result: {:?}", result);
+
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::issuer::{
+        create_credential, create_credential_definition, create_credential_offer, create_schema,
+    };
+    use crate::services::prover::{create_credential_request, create_master_secret};
+
+    const ISSUER_DID: &'static str = "NcYxiDXkpYi6ov5FcYDi1e";
+    const ISSUED: &'static str = "2021-01-01T00:00:00Z";
+
+    fn credential_values(values: &[(&str, &str)]) -> CredentialValues {
+        CredentialValues(
+            values
+                .iter()
+                .map(|(name, raw)| {
+                    let value = AttributeValues {
+                        raw: raw.to_string(),
+                        encoded: encode_credential_attribute(raw).unwrap(),
+                    };
+                    (name.to_string(), value)
+                })
+                .collect(),
+        )
+    }
+
+    fn issue_credential(values: CredentialValues) -> Credential {
+        let issuer_did = DidValue::new(ISSUER_DID, None);
+        let attrs = vec!["name".to_owned(), "age".to_owned()];
+        let schema = create_schema(&issuer_did, "test", "1.0", attrs.into(), None).unwrap();
+        let schema_id = match &schema {
+            Schema::SchemaV1(s) => s.id.clone(),
+        };
+        let (cred_def, cred_def_priv, key_proof) = create_credential_definition(
+            &issuer_did,
+            &schema,
+            "tag",
+            SignatureType::CL,
+            CredentialDefinitionConfig::new(false),
+        )
+        .unwrap();
+        let offer = create_credential_offer(&schema_id, &cred_def, &key_proof).unwrap();
+        let master_secret = create_master_secret().unwrap();
+        let prover_did = DidValue::new("VsKV7grR1BUE29mG2Fm2kX", None);
+        let (request, _) =
+            create_credential_request(&prover_did, &cred_def, &master_secret, "default", &offer)
+                .unwrap();
+        let (credential, _, _) =
+            create_credential(&cred_def, &cred_def_priv, &offer, &request, values, None).unwrap();
+        credential
+    }
+
+    #[test]
+    fn w3c_round_trip() {
+        let credential = issue_credential(credential_values(&[("name", "Alice"), ("age", "28")]));
+        let w3c = convert_to_w3c(&credential, ISSUED).unwrap();
+        let json = serde_json::to_value(&w3c).unwrap();
+        assert_eq!(json["@context"][1], ANONCREDS_W3C_CONTEXT);
+        assert_eq!(json["type"][1], ANONCREDS_CREDENTIAL);
+        assert_eq!(json["issuer"], format!("did:sov:{}", ISSUER_DID));
+        assert_eq!(json["credentialSubject"]["name"], "Alice");
+        assert_eq!(json["credentialSchema"]["type"], ANONCREDS_DEFINITION);
+        assert_eq!(
+            json["credentialSchema"]["definition"],
+            credential.cred_def_id.to_string()
+        );
+        assert_eq!(json["proof"]["cryptosuite"], ANONCREDS_CRYPTOSUITE);
+
+        let w3c: VerifiableCredential = serde_json::from_value(json).unwrap();
+        let restored = convert_from_w3c(&w3c).unwrap();
+        assert_eq!(
+            serde_json::to_value(&restored).unwrap(),
+            serde_json::to_value(&credential).unwrap()
+        );
+
+        let mut missing = w3c.clone();
+        missing.proof = None;
+        assert!(convert_from_w3c(&missing).is_err());
+    }
+
+    #[test]
+    fn w3c_requires_standard_encoding() {
+        let mut values = credential_values(&[("name", "Alice"), ("age", "28")]);
+        values.0.get_mut("age").unwrap().encoded = "1028".to_owned();
+        let credential = issue_credential(values);
+        assert!(convert_to_w3c(&credential, ISSUED).is_err());
+    }
+}
@@ -32,6 +32,9 @@ pub mod rev_reg;
 /// Revocation registry definitions
 pub mod rev_reg_def;
 
+/// Revocation status lists
+pub mod rev_status_list;
+
 #[cfg(any(feature = "rich_schema", test))]
 /// Rich schemas
 pub mod rich_schema;
@@ -12,7 +12,7 @@ use crate::identifiers::cred_def::CredentialDefinitionId;
 use crate::identifiers::rev_reg::RevocationRegistryId;
 use crate::identifiers::schema::SchemaId;
 use crate::utils::{qualifiable, Qualifiable};
-use crate::{Validatable, ValidationError};
+use crate::{ConversionError, Validatable, ValidationError};
 use indy_utils::did::DidValue;
 use indy_utils::invalid;
 use indy_utils::wql::Query;
@@ -126,6 +126,134 @@ impl Serialize for PresentationRequest {
     }
 }
 
+/// Builder for presentation requests, assigning a referent to each
+/// requested attribute and predicate
+#[derive(Debug)]
+pub struct PresentationRequestBuilder {
+    name: String,
+    version: String,
+    request_version: PresentationRequestVersion,
+    nonce: Option<Nonce>,
+    requested_attributes: HashMap<String, AttributeInfo>,
+    requested_predicates: HashMap<String, PredicateInfo>,
+    non_revoked: Option<NonRevocedInterval>,
+}
+
+impl PresentationRequestBuilder {
+    pub fn new(name: &str, version: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            version: version.to_owned(),
+            request_version: PresentationRequestVersion::V1,
+            nonce: None,
+            requested_attributes: HashMap::new(),
+            requested_predicates: HashMap::new(),
+            non_revoked: None,
+        }
+    }
+
+    /// Set the request format version. Fully qualified identifiers in
+    /// restrictions require `PresentationRequestVersion::V2`.
+    pub fn request_version(mut self, request_version: PresentationRequestVersion) -> Self {
+        self.request_version = request_version;
+        self
+    }
+
+    /// Set the request nonce. A new nonce is generated if none is provided.
+    pub fn nonce(mut self, nonce: Nonce) -> Self {
+        self.nonce = Some(nonce);
+        self
+    }
+
+    /// Request an attribute, with the referent `attr{N}_referent`
+    pub fn requested_attribute(mut self, name: &str, restrictions: Option<Query>) -> Self {
+        let referent = format!("attr{}_referent", self.requested_attributes.len() + 1);
+        self.requested_attributes.insert(
+            referent,
+            AttributeInfo {
+                name: Some(name.to_owned()),
+                names: None,
+                restrictions,
+                non_revoked: None,
+            },
+        );
+        self
+    }
+
+    /// Request a group of attributes which must be presented from a single
+    /// credential, with the referent `attr{N}_referent`
+    pub fn requested_attribute_group(
+        mut self,
+        names: &[&str],
+        restrictions: Option<Query>,
+    ) -> Self {
+        let referent = format!("attr{}_referent", self.requested_attributes.len() + 1);
+        self.requested_attributes.insert(
+            referent,
+            AttributeInfo {
+                name: None,
+                names: Some(names.iter().map(|name| name.to_string()).collect()),
+                restrictions,
+                non_revoked: None,
+            },
+        );
+        self
+    }
+
+    /// Request a predicate, with the referent `predicate{N}_referent`
+    pub fn requested_predicate(
+        mut self,
+        name: &str,
+        p_type: PredicateTypes,
+        p_value: i32,
+        restrictions: Option<Query>,
+    ) -> Self {
+        let referent = format!("predicate{}_referent", self.requested_predicates.len() + 1);
+        self.requested_predicates.insert(
+            referent,
+            PredicateInfo {
+                name: name.to_owned(),
+                p_type,
+                p_value,
+                restrictions,
+                non_revoked: None,
+            },
+        );
+        self
+    }
+
+    /// Require non-revocation of the presented credentials over an interval
+    pub fn non_revoked(mut self, interval: NonRevocedInterval) -> Self {
+        self.non_revoked = Some(interval);
+        self
+    }
+
+    /// Create the presentation request and validate it
+    pub fn build(self) -> Result<PresentationRequest, ConversionError> {
+        let nonce = match self.nonce {
+            Some(nonce) => nonce,
+            #[cfg(any(feature = "cl", feature = "cl_native"))]
+            None => Nonce::new()?,
+            #[cfg(not(any(feature = "cl", feature = "cl_native")))]
+            None => return Err("A nonce must be provided".into()),
+        };
+        let payload = PresentationRequestPayload {
+            nonce,
+            name: self.name,
+            version: self.version,
+            requested_attributes: self.requested_attributes,
+            requested_predicates: self.requested_predicates,
+            non_revoked: self.non_revoked,
+        };
+        let request = match self.request_version {
+            PresentationRequestVersion::V1 => PresentationRequest::PresentationRequestV1(payload),
+            PresentationRequestVersion::V2 => PresentationRequest::PresentationRequestV2(payload),
+        };
+        request.validate()?;
+        Ok(request)
+    }
+}
+
 #[allow(unused)]
 pub type PresentationRequestExtraQuery = HashMap<String, Query>;
 
@@ -389,6 +517,8 @@ fn _check_restriction(
 mod tests {
     use super::*;
 
+    const CRED_DEF_ID: &str = "NcYxiDXkpYi6ov5FcYDi1e:3:CL:NcYxiDXkpYi6ov5FcYDi1e:2:gvt:1.0:tag";
+
     #[cfg(feature = "serde")]
     mod invalid_nonce {
         use super::*;
@@ -428,6 +558,60 @@ mod tests {
         }
     }
 
+    #[test]
+    fn build_presentation_request() {
+        let restrictions = Query::Eq("cred_def_id".to_string(), CRED_DEF_ID.to_string());
+        let request = PresentationRequestBuilder::new("proof", "1.0")
+            .nonce(Nonce::from_dec("112233445566").unwrap())
+            .requested_attribute("name", Some(restrictions.clone()))
+            .requested_attribute_group(&["street", "city"], None)
+            .requested_predicate("age", PredicateTypes::GE, 18, Some(restrictions))
+            .non_revoked(NonRevocedInterval {
+                from: None,
+                to: Some(1600000000),
+            })
+            .build()
+            .unwrap();
+        let value = request.value();
+        assert_eq!(request.version(), PresentationRequestVersion::V1);
+        assert_eq!(&*value.nonce, "112233445566");
+        assert_eq!(
+            value.requested_attributes["attr1_referent"].name.as_deref(),
+            Some("name")
+        );
+        assert_eq!(
+            value.requested_attributes["attr2_referent"].names,
+            Some(vec!["street".to_string(), "city".to_string()])
+        );
+        assert_eq!(
+            value.requested_predicates["predicate1_referent"].p_value,
+            18
+        );
+        assert_eq!(value.non_revoked.as_ref().unwrap().to, Some(1600000000));
+
+        // fully qualified identifiers require a V2 request
+        let qualified = Query::Eq(
+            "issuer_did".to_string(),
+            "did:sov:NcYxiDXkpYi6ov5FcYDi1e".to_string(),
+        );
+        let builder = || {
+            PresentationRequestBuilder::new("proof", "1.0")
+                .nonce(Nonce::from_dec("112233445566").unwrap())
+                .requested_attribute("name", Some(qualified.clone()))
+        };
+        assert!(builder().build().is_err());
+        let request = builder()
+            .request_version(PresentationRequestVersion::V2)
+            .build()
+            .unwrap();
+        assert_eq!(request.version(), PresentationRequestVersion::V2);
+
+        assert!(PresentationRequestBuilder::new("proof", "1.0")
+            .nonce(Nonce::from_dec("112233445566").unwrap())
+            .build()
+            .is_err());
+    }
+
     mod to_unqualified {
         use super::*;
 
@@ -1,7 +1,11 @@
+#[cfg(feature = "serde")]
+use std::collections::BTreeSet;
 #[cfg(any(feature = "cl", feature = "cl_native"))]
 use std::collections::HashSet;
 
 use crate::Validatable;
+#[cfg(feature = "serde")]
+use crate::{invalid, ValidationError};
 
 #[derive(Clone, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -43,6 +47,36 @@ pub enum RevocationRegistryDelta {
     RevocationRegistryDeltaV1(RevocationRegistryDeltaV1),
 }
 
+#[cfg(feature = "serde")]
+impl RevocationRegistryDelta {
+    /// Get the typed contents of the delta
+    pub fn value(&self) -> Result<RevocationRegistryDeltaValue, ValidationError> {
+        match self {
+            Self::RevocationRegistryDeltaV1(v1) => serde_json::to_value(&v1.value)
+                .and_then(serde_json::from_value)
+                .map_err(|err| invalid!("Invalid revocation registry delta: {}", err)),
+        }
+    }
+
+    /// Create a delta from its typed contents
+    pub fn from_value(value: RevocationRegistryDeltaValue) -> Result<Self, ValidationError> {
+        let value = serde_json::to_value(value)
+            .and_then(serde_json::from_value)
+            .map_err(|err| invalid!("Invalid revocation registry delta: {}", err))?;
+        Ok(Self::RevocationRegistryDeltaV1(RevocationRegistryDeltaV1 {
+            value,
+        }))
+    }
+
+    /// Merge a later delta for the same revocation registry into this one
+    pub fn merge(&mut self, other: &Self) -> Result<(), ValidationError> {
+        let mut value = self.value()?;
+        value.merge(&other.value()?)?;
+        *self = Self::from_value(value)?;
+        Ok(())
+    }
+}
+
 impl Validatable for RevocationRegistryDelta {}
 
 #[derive(Clone, Debug)]
@@ -51,3 +85,90 @@ impl Validatable for RevocationRegistryDelta {}
 pub struct RevocationRegistryDeltaV1 {
     pub value: ursa_cl!(RevocationRegistryDelta),
 }
+
+/// The contents of a revocation registry delta, with the accumulator values
+/// left in their encoded form
+#[cfg(feature = "serde")]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevocationRegistryDeltaValue {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prev_accum: Option<String>,
+    pub accum: String,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub issued: BTreeSet<u32>,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub revoked: BTreeSet<u32>,
+}
+
+#[cfg(feature = "serde")]
+impl RevocationRegistryDeltaValue {
+    /// Merge a later delta into this one. The later delta must start from
+    /// the accumulator this one ends with, unless it covers the whole history
+    /// of the registry, in which case it replaces this delta. Indices issued
+    /// or revoked by the later delta take precedence.
+    pub fn merge(&mut self, other: &Self) -> Result<(), ValidationError> {
+        match other.prev_accum.as_ref() {
+            None => {
+                *self = other.clone();
+                return Ok(());
+            }
+            Some(prev_accum) if *prev_accum != self.accum => {
+                return Err(invalid!(
+                    "Revocation registry deltas are not consecutive and cannot be merged"
+                ));
+            }
+            Some(_) => (),
+        }
+        for index in &other.issued {
+            self.revoked.remove(index);
+            self.issued.insert(*index);
+        }
+        for index in &other.revoked {
+            self.issued.remove(index);
+            self.revoked.insert(*index);
+        }
+        self.accum = other.accum.clone();
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    fn delta(
+        prev_accum: Option<&str>,
+        accum: &str,
+        issued: &[u32],
+        revoked: &[u32],
+    ) -> RevocationRegistryDeltaValue {
+        RevocationRegistryDeltaValue {
+            prev_accum: prev_accum.map(str::to_owned),
+            accum: accum.to_owned(),
+            issued: issued.iter().copied().collect(),
+            revoked: revoked.iter().copied().collect(),
+        }
+    }
+
+    #[test]
+    fn merge_delta_values() {
+        let mut merged = delta(None, "1 2 3", &[1, 2, 3], &[]);
+        merged
+            .merge(&delta(Some("1 2 3"), "4 5 6", &[4], &[2, 3]))
+            .unwrap();
+        merged
+            .merge(&delta(Some("4 5 6"), "7 8 9", &[3], &[1]))
+            .unwrap();
+        assert_eq!(merged, delta(None, "7 8 9", &[3, 4], &[1, 2]));
+
+        // deltas must be consecutive
+        assert!(merged
+            .merge(&delta(Some("1 2 3"), "4 5 6", &[5], &[]))
+            .is_err());
+
+        // a delta from the start of the registry replaces the merged delta
+        merged.merge(&delta(None, "1 1 1", &[9], &[])).unwrap();
+        assert_eq!(merged, delta(None, "1 1 1", &[9], &[]));
+    }
+}
@@ -0,0 +1,272 @@
+#[cfg(feature = "serde")]
+use std::collections::BTreeSet;
+
+#[cfg(feature = "serde")]
+use super::rev_reg::{RevocationRegistryDelta, RevocationRegistryDeltaValue};
+use super::rev_reg_def::{IssuanceType, RevocationRegistryDefinition};
+use crate::identifiers::rev_reg::RevocationRegistryId;
+use crate::{invalid, Validatable, ValidationError};
+
+/// A revocation status list (AnonCreds 1.0), recording whether each
+/// credential of a revocation registry is revoked. Entry `i` of the list
+/// holds the status of the credential with revocation index `i + 1`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(Deserialize, Serialize),
+    serde(rename_all = "camelCase")
+)]
+pub struct RevocationStatusList {
+    pub rev_reg_def_id: RevocationRegistryId,
+    #[cfg_attr(feature = "serde", serde(with = "bit_list"))]
+    pub revocation_list: Vec<bool>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub current_accumulator: Option<String>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub timestamp: Option<u64>,
+}
+
+impl RevocationStatusList {
+    /// Create the initial status list of a revocation registry. All
+    /// credentials are initially revoked, unless they are issued by default.
+    pub fn new(
+        rev_reg_def: &RevocationRegistryDefinition,
+        current_accumulator: Option<String>,
+        timestamp: Option<u64>,
+    ) -> Self {
+        let RevocationRegistryDefinition::RevocationRegistryDefinitionV1(def) = rev_reg_def;
+        let revoked = def.value.issuance_type == IssuanceType::ISSUANCE_ON_DEMAND;
+        Self {
+            rev_reg_def_id: def.id.clone(),
+            revocation_list: vec![revoked; def.value.max_cred_num as usize],
+            current_accumulator,
+            timestamp,
+        }
+    }
+
+    /// Check whether the credential with a revocation index is revoked
+    pub fn is_revoked(&self, idx: u32) -> Result<bool, ValidationError> {
+        Ok(self.revocation_list[self.position(idx)?])
+    }
+
+    pub fn revoke(&mut self, idx: u32) -> Result<(), ValidationError> {
+        let pos = self.position(idx)?;
+        self.revocation_list[pos] = true;
+        Ok(())
+    }
+
+    pub fn unrevoke(&mut self, idx: u32) -> Result<(), ValidationError> {
+        let pos = self.position(idx)?;
+        self.revocation_list[pos] = false;
+        Ok(())
+    }
+
+    /// The revocation indices of the revoked credentials
+    pub fn revoked(&self) -> impl Iterator<Item = u32> + '_ {
+        self.revocation_list
+            .iter()
+            .enumerate()
+            .filter(|(_, revoked)| **revoked)
+            .map(|(pos, _)| pos as u32 + 1)
+    }
+
+    /// Transition to a new registry state, published at `timestamp` with
+    /// the resulting accumulator
+    pub fn update(
+        &mut self,
+        issued: impl IntoIterator<Item = u32>,
+        revoked: impl IntoIterator<Item = u32>,
+        current_accumulator: Option<String>,
+        timestamp: u64,
+    ) -> Result<(), ValidationError> {
+        if self.timestamp.map(|prev| timestamp < prev).unwrap_or(false) {
+            return Err(invalid!(
+                "Revocation status list timestamp cannot move backwards"
+            ));
+        }
+        let mut next = self.revocation_list.clone();
+        for idx in issued {
+            next[self.position(idx)?] = false;
+        }
+        for idx in revoked {
+            next[self.position(idx)?] = true;
+        }
+        self.revocation_list = next;
+        self.current_accumulator = current_accumulator;
+        self.timestamp = Some(timestamp);
+        Ok(())
+    }
+
+    /// Create the status list of a registry from a legacy revocation
+    /// registry delta covering its whole history
+    #[cfg(feature = "serde")]
+    pub fn from_delta(
+        rev_reg_def: &RevocationRegistryDefinition,
+        delta: &RevocationRegistryDelta,
+        timestamp: u64,
+    ) -> Result<Self, ValidationError> {
+        let mut list = Self::new(rev_reg_def, None, None);
+        list.apply_delta(delta, timestamp)?;
+        Ok(list)
+    }
+
+    /// Apply a legacy revocation registry delta, which must start from the
+    /// current accumulator of the list when it has one
+    #[cfg(feature = "serde")]
+    pub fn apply_delta(
+        &mut self,
+        delta: &RevocationRegistryDelta,
+        timestamp: u64,
+    ) -> Result<(), ValidationError> {
+        let value = delta.value()?;
+        if let (Some(prev), Some(current)) = (&value.prev_accum, &self.current_accumulator) {
+            if prev != current {
+                return Err(invalid!(
+                    "Revocation registry delta does not start from the current accumulator"
+                ));
+            }
+        }
+        self.update(value.issued, value.revoked, Some(value.accum), timestamp)
+    }
+
+    /// Convert to a legacy revocation registry delta. Relative to a previous
+    /// status list, the delta records the changes since that list; otherwise
+    /// it covers the whole history of the registry.
+    #[cfg(feature = "serde")]
+    pub fn to_delta(
+        &self,
+        issuance_type: IssuanceType,
+        prev: Option<&Self>,
+    ) -> Result<RevocationRegistryDelta, ValidationError> {
+        let accum = self
+            .current_accumulator
+            .clone()
+            .ok_or_else(|| invalid!("Revocation status list has no accumulator"))?;
+        let mut issued = BTreeSet::new();
+        let mut revoked = BTreeSet::new();
+        let prev_accum = match prev {
+            Some(prev) => {
+                if prev.rev_reg_def_id != self.rev_reg_def_id
+                    || prev.revocation_list.len() != self.revocation_list.len()
+                {
+                    return Err(invalid!(
+                        "Revocation status lists are for different registries"
+                    ));
+                }
+                let changes = prev.revocation_list.iter().zip(&self.revocation_list);
+                for (pos, (before, after)) in changes.enumerate() {
+                    match (before, after) {
+                        (true, false) => issued.insert(pos as u32 + 1),
+                        (false, true) => revoked.insert(pos as u32 + 1),
+                        _ => false,
+                    };
+                }
+                Some(prev.current_accumulator.clone().ok_or_else(|| {
+                    invalid!("Previous revocation status list has no accumulator")
+                })?)
+            }
+            None => {
+                for (pos, state) in self.revocation_list.iter().enumerate() {
+                    let idx = pos as u32 + 1;
+                    match (issuance_type.to_bool(), state) {
+                        (true, true) => revoked.insert(idx),
+                        (false, false) => issued.insert(idx),
+                        _ => false,
+                    };
+                }
+                None
+            }
+        };
+        RevocationRegistryDelta::from_value(RevocationRegistryDeltaValue {
+            prev_accum,
+            accum,
+            issued,
+            revoked,
+        })
+    }
+
+    fn position(&self, idx: u32) -> Result<usize, ValidationError> {
+        if idx == 0 || idx as usize > self.revocation_list.len() {
+            return Err(invalid!("Invalid revocation index: {}", idx));
+        }
+        Ok(idx as usize - 1)
+    }
+}
+
+impl Validatable for RevocationStatusList {
+    fn validate(&self) -> Result<(), ValidationError> {
+        self.rev_reg_def_id.validate()
+    }
+}
+
+/// Serialization of the revocation list as an array of 0 and 1 values
+#[cfg(feature = "serde")]
+mod bit_list {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(list: &[bool], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(list.iter().map(|revoked| *revoked as u8))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<bool>, D::Error> {
+        Vec::<u8>::deserialize(deserializer)?
+            .into_iter()
+            .map(|entry| match entry {
+                0 => Ok(false),
+                1 => Ok(true),
+                _ => Err(de::Error::custom("Invalid revocation list entry")),
+            })
+            .collect()
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    const REV_REG_ID: &str =
+        "NcYxiDXkpYi6ov5FcYDi1e:4:NcYxiDXkpYi6ov5FcYDi1e:3:CL:1:tag:CL_ACCUM:TAG_1";
+
+    #[test]
+    fn update_status_list() {
+        let mut list = RevocationStatusList {
+            rev_reg_def_id: RevocationRegistryId(REV_REG_ID.to_owned()),
+            revocation_list: vec![false; 4],
+            current_accumulator: Some("1 2 3".to_owned()),
+            timestamp: Some(10),
+        };
+        list.revoke(2).unwrap();
+        list.revoke(4).unwrap();
+        list.unrevoke(4).unwrap();
+        assert!(list.is_revoked(2).unwrap());
+        assert!(!list.is_revoked(4).unwrap());
+        assert!(list.revoke(0).is_err());
+        assert!(list.revoke(5).is_err());
+
+        let json = serde_json::to_value(&list).unwrap();
+        assert_eq!(json["revRegDefId"], REV_REG_ID);
+        assert_eq!(json["revocationList"], json!([0, 1, 0, 0]));
+        assert_eq!(json["currentAccumulator"], "1 2 3");
+        assert_eq!(
+            serde_json::from_value::<RevocationStatusList>(json).unwrap(),
+            list
+        );
+        assert!(serde_json::from_value::<RevocationStatusList>(json!({
+            "revRegDefId": REV_REG_ID,
+            "revocationList": [0, 2],
+        }))
+        .is_err());
+
+        assert!(list.update(vec![], vec![1], None, 5).is_err());
+        list.update(vec![2], vec![1, 3], Some("4 5 6".to_owned()), 20)
+            .unwrap();
+        assert_eq!(list.revoked().collect::<Vec<_>>(), vec![1, 3]);
+        assert_eq!(list.timestamp, Some(20));
+    }
+}
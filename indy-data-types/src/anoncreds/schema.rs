@@ -81,7 +81,7 @@ impl Validatable for SchemaV1 {
     fn validate(&self) -> Result<(), ValidationError> {
         self.attr_names.validate()?;
         self.id.validate()?;
-        if let Some((_, _, name, version)) = self.id.parts() {
+        if let (Some(name), Some(version)) = (self.id.name(), self.id.version()) {
             if name != self.name {
                 return Err(format!(
                     "Inconsistent Schema Id and Schema Name: {:?} and {}",
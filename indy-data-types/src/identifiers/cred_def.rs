@@ -4,6 +4,7 @@ use crate::{Validatable, ValidationError};
 use indy_utils::did::DidValue;
 use indy_utils::qualifiable_type;
 
+use super::did_url::{IndyObjectUrl, SIGNATURE_TYPE_CL};
 use super::DELIMITER;
 
 qualifiable_type!(CredentialDefinitionId, "A credential definition identifier");
@@ -42,6 +43,9 @@ impl CredentialDefinitionId {
     }
 
     pub fn parts(&self) -> Option<(Option<&str>, DidValue, String, SchemaId, String)> {
+        if self.is_did_indy() {
+            return None;
+        }
         let parts = self.0.split_terminator(DELIMITER).collect::<Vec<&str>>();
 
         if parts.len() == 4 {
@@ -139,8 +143,53 @@ impl CredentialDefinitionId {
         None
     }
 
+    /// Check whether the identifier is a did:indy DID URL
+    pub fn is_did_indy(&self) -> bool {
+        self.0.starts_with("did:indy:")
+    }
+
+    /// The DID of the credential definition issuer
     pub fn issuer_did(&self) -> Option<DidValue> {
-        self.parts().map(|(_, did, _, _, _)| did)
+        self.components().map(|(did, _, _, _)| did)
+    }
+
+    pub fn signature_type(&self) -> Option<String> {
+        self.components()
+            .map(|(_, signature_type, _, _)| signature_type)
+    }
+
+    /// The schema identifier, which may be the schema sequence number
+    pub fn schema_id(&self) -> Option<SchemaId> {
+        self.components().map(|(_, _, schema_id, _)| schema_id)
+    }
+
+    pub fn tag(&self) -> Option<String> {
+        self.components().map(|(_, _, _, tag)| tag)
+    }
+
+    /// The issuer DID, signature type, schema identifier and tag of a legacy
+    /// or did:indy identifier
+    fn components(&self) -> Option<(DidValue, String, SchemaId, String)> {
+        if self.is_did_indy() {
+            match IndyObjectUrl::parse(&self.0).ok()? {
+                IndyObjectUrl::CredentialDefinition {
+                    did,
+                    schema_seq_no,
+                    tag,
+                } => Some((
+                    DidValue(did.to_string()),
+                    SIGNATURE_TYPE_CL.to_owned(),
+                    SchemaId(schema_seq_no.to_string()),
+                    tag,
+                )),
+                _ => None,
+            }
+        } else {
+            self.parts()
+                .map(|(_, did, signature_type, schema_id, tag)| {
+                    (did, signature_type, schema_id, tag)
+                })
+        }
     }
 }
 
@@ -177,7 +226,7 @@ impl Qualifiable for CredentialDefinitionId {
 
 impl Validatable for CredentialDefinitionId {
     fn validate(&self) -> Result<(), ValidationError> {
-        self.parts().ok_or(format!(
+        self.components().ok_or(format!(
             "Credential Definition Id validation failed: {:?}, doesn't match pattern",
             self.0
         ))?;
@@ -408,6 +457,40 @@ mod tests {
         }
     }
 
+    mod accessors {
+        use super::*;
+
+        #[test]
+        fn test_cred_def_id_accessors() {
+            let id = _cred_def_id_unqualified();
+            assert_eq!(id.issuer_did().unwrap(), _did());
+            assert_eq!(id.signature_type().unwrap(), _signature_type());
+            assert_eq!(id.schema_id().unwrap(), _schema_id_unqualified());
+            assert_eq!(id.tag().unwrap(), _tag());
+            assert_eq!(
+                _cred_def_id_unqualified_with_schema_as_seq_no_without_tag()
+                    .tag()
+                    .unwrap(),
+                ""
+            );
+
+            let id = CredentialDefinitionId(
+                "did:indy:sovrin:NcYxiDXkpYi6ov5FcYDi1e/anoncreds/v0/CLAIM_DEF/1/tag".to_string(),
+            );
+            id.validate().unwrap();
+            assert!(id.is_did_indy());
+            assert!(id.parts().is_none());
+            assert_eq!(
+                id.issuer_did().unwrap(),
+                DidValue("did:indy:sovrin:NcYxiDXkpYi6ov5FcYDi1e".to_string())
+            );
+            assert_eq!(id.signature_type().unwrap(), _signature_type());
+            assert_eq!(id.schema_id().unwrap(), _schema_id_seq_no());
+            assert_eq!(id.tag().unwrap(), _tag());
+            assert_eq!(id.to_unqualified(), id);
+        }
+    }
+
     mod to_qualified {
         use super::*;
 
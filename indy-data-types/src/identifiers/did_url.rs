@@ -0,0 +1,391 @@
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use super::cred_def::CredentialDefinitionId;
+use super::rev_reg::RevocationRegistryId;
+use super::schema::SchemaId;
+use crate::{invalid, ValidationError};
+use indy_utils::did::{DidValue, DID, DID_METHOD_INDY};
+
+/// The path prefix of did:indy DID URLs for anoncreds ledger objects
+pub const ANONCREDS_PATH: &'static str = "anoncreds/v0";
+
+const OBJECT_SCHEMA: &'static str = "SCHEMA";
+const OBJECT_CRED_DEF: &'static str = "CLAIM_DEF";
+const OBJECT_REV_REG_DEF: &'static str = "REV_REG_DEF";
+
+pub(crate) const SIGNATURE_TYPE_CL: &'static str = "CL";
+pub(crate) const REV_REG_TYPE_CL_ACCUM: &'static str = "CL_ACCUM";
+
+/// A did:indy DID URL identifying an anoncreds object on the ledger, such as
+/// `did:indy:sovrin:F72i3Y3Q4i466efjYJYCHM/anoncreds/v0/SCHEMA/npdb/4.3.4`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum IndyObjectUrl {
+    Schema {
+        did: DID,
+        name: String,
+        version: String,
+    },
+    CredentialDefinition {
+        did: DID,
+        schema_seq_no: u64,
+        tag: String,
+    },
+    RevocationRegistryDefinition {
+        did: DID,
+        schema_seq_no: u64,
+        cred_def_tag: String,
+        tag: String,
+    },
+}
+
+impl IndyObjectUrl {
+    /// Parse a did:indy DID URL for an anoncreds object
+    pub fn parse(url: &str) -> Result<Self, ValidationError> {
+        let pos = url
+            .find('/')
+            .ok_or_else(|| invalid!("Invalid DID URL: {}. Missing path", url))?;
+        let did = DID::parse(&url[..pos])?;
+        if did.method != DID_METHOD_INDY {
+            return Err(invalid!(
+                "Invalid DID URL: {}. Expected a did:indy DID",
+                url
+            ));
+        }
+        let path = url[pos + 1..]
+            .strip_prefix(ANONCREDS_PATH)
+            .and_then(|path| path.strip_prefix('/'))
+            .ok_or_else(|| invalid!("Invalid DID URL: {}. Expected an anoncreds path", url))?;
+        let segments = path
+            .split('/')
+            .map(decode_segment)
+            .collect::<Result<Vec<_>, _>>()?;
+        let seq_no = |seq_no: &str| {
+            seq_no.parse::<u64>().map_err(|_| {
+                invalid!(
+                    "Invalid DID URL: {}. Expected a schema sequence number",
+                    url
+                )
+            })
+        };
+        match segments.as_slice() {
+            [object, name, version] if *object == OBJECT_SCHEMA => Ok(Self::Schema {
+                did,
+                name: name.clone(),
+                version: version.clone(),
+            }),
+            [object, schema_seq_no, tag] if *object == OBJECT_CRED_DEF => {
+                Ok(Self::CredentialDefinition {
+                    did,
+                    schema_seq_no: seq_no(schema_seq_no)?,
+                    tag: tag.clone(),
+                })
+            }
+            [object, schema_seq_no, cred_def_tag, tag] if *object == OBJECT_REV_REG_DEF => {
+                Ok(Self::RevocationRegistryDefinition {
+                    did,
+                    schema_seq_no: seq_no(schema_seq_no)?,
+                    cred_def_tag: cred_def_tag.clone(),
+                    tag: tag.clone(),
+                })
+            }
+            _ => Err(invalid!(
+                "Invalid DID URL: {}. Unsupported anoncreds object path",
+                url
+            )),
+        }
+    }
+
+    /// The did:indy DID of the object's author
+    pub fn did(&self) -> &DID {
+        match self {
+            Self::Schema { did, .. } => did,
+            Self::CredentialDefinition { did, .. } => did,
+            Self::RevocationRegistryDefinition { did, .. } => did,
+        }
+    }
+
+    /// Convert a legacy schema identifier such as `<did>:2:<name>:<version>`,
+    /// given the namespace of the ledger it was written to
+    pub fn from_schema_id(id: &SchemaId, namespace: &str) -> Result<Self, ValidationError> {
+        let (_, did, name, version) = id
+            .parts()
+            .ok_or_else(|| invalid!("Invalid schema identifier: {}", id))?;
+        Ok(Self::Schema {
+            did: indy_did(&did, namespace)?,
+            name,
+            version,
+        })
+    }
+
+    /// Convert a legacy credential definition identifier such as
+    /// `<did>:3:CL:<seq_no>:<tag>`, given the namespace of the ledger it was
+    /// written to. The schema must be referenced by its sequence number.
+    pub fn from_cred_def_id(
+        id: &CredentialDefinitionId,
+        namespace: &str,
+    ) -> Result<Self, ValidationError> {
+        let (did, schema_seq_no, tag) = cred_def_parts(id)?;
+        Ok(Self::CredentialDefinition {
+            did: indy_did(&did, namespace)?,
+            schema_seq_no,
+            tag,
+        })
+    }
+
+    /// Convert a legacy revocation registry identifier such as
+    /// `<did>:4:<did>:3:CL:<seq_no>:<cred_def_tag>:CL_ACCUM:<tag>`, given the
+    /// namespace of the ledger it was written to
+    pub fn from_rev_reg_id(
+        id: &RevocationRegistryId,
+        namespace: &str,
+    ) -> Result<Self, ValidationError> {
+        let (did, cred_def_id, rev_reg_type, tag) = id
+            .parts()
+            .ok_or_else(|| invalid!("Invalid revocation registry identifier: {}", id))?;
+        if rev_reg_type != REV_REG_TYPE_CL_ACCUM {
+            return Err(invalid!(
+                "Unsupported revocation registry type: {}",
+                rev_reg_type
+            ));
+        }
+        let (cred_def_did, schema_seq_no, cred_def_tag) = cred_def_parts(&cred_def_id)?;
+        if cred_def_did.to_unqualified() != did.to_unqualified() {
+            return Err(invalid!(
+                "Revocation registry and credential definition authors differ: {}",
+                id
+            ));
+        }
+        Ok(Self::RevocationRegistryDefinition {
+            did: indy_did(&did, namespace)?,
+            schema_seq_no,
+            cred_def_tag,
+            tag,
+        })
+    }
+
+    /// Get the legacy identifier of a schema
+    pub fn to_schema_id(&self) -> Option<SchemaId> {
+        match self {
+            Self::Schema { did, name, version } => {
+                Some(SchemaId::new(&legacy_did(did), name, version))
+            }
+            _ => None,
+        }
+    }
+
+    /// Get the legacy identifier of a credential definition
+    pub fn to_cred_def_id(&self) -> Option<CredentialDefinitionId> {
+        match self {
+            Self::CredentialDefinition {
+                did,
+                schema_seq_no,
+                tag,
+            } => Some(legacy_cred_def_id(did, *schema_seq_no, tag)),
+            _ => None,
+        }
+    }
+
+    /// Get the legacy identifier of a revocation registry definition
+    pub fn to_rev_reg_id(&self) -> Option<RevocationRegistryId> {
+        match self {
+            Self::RevocationRegistryDefinition {
+                did,
+                schema_seq_no,
+                cred_def_tag,
+                tag,
+            } => Some(RevocationRegistryId::new(
+                &legacy_did(did),
+                &legacy_cred_def_id(did, *schema_seq_no, cred_def_tag),
+                REV_REG_TYPE_CL_ACCUM,
+                tag,
+            )),
+            _ => None,
+        }
+    }
+}
+
+impl Display for IndyObjectUrl {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let (object, segments) = match self {
+            Self::Schema { name, version, .. } => {
+                (OBJECT_SCHEMA, vec![name.clone(), version.clone()])
+            }
+            Self::CredentialDefinition {
+                schema_seq_no, tag, ..
+            } => (
+                OBJECT_CRED_DEF,
+                vec![schema_seq_no.to_string(), tag.clone()],
+            ),
+            Self::RevocationRegistryDefinition {
+                schema_seq_no,
+                cred_def_tag,
+                tag,
+                ..
+            } => (
+                OBJECT_REV_REG_DEF,
+                vec![schema_seq_no.to_string(), cred_def_tag.clone(), tag.clone()],
+            ),
+        };
+        write!(f, "{}/{}/{}", self.did(), ANONCREDS_PATH, object)?;
+        for segment in segments {
+            write!(f, "/{}", encode_segment(&segment))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for IndyObjectUrl {
+    type Err = ValidationError;
+
+    fn from_str(url: &str) -> Result<Self, Self::Err> {
+        Self::parse(url)
+    }
+}
+
+fn indy_did(did: &DidValue, namespace: &str) -> Result<DID, ValidationError> {
+    DID::from_unqualified(&did.to_short(), Some(namespace))
+}
+
+fn legacy_did(did: &DID) -> DidValue {
+    DidValue(did.id.clone())
+}
+
+fn legacy_cred_def_id(did: &DID, schema_seq_no: u64, tag: &str) -> CredentialDefinitionId {
+    CredentialDefinitionId::new(
+        &legacy_did(did),
+        &SchemaId(schema_seq_no.to_string()),
+        SIGNATURE_TYPE_CL,
+        tag,
+    )
+}
+
+fn cred_def_parts(id: &CredentialDefinitionId) -> Result<(DidValue, u64, String), ValidationError> {
+    let (_, did, signature_type, schema_id, tag) = id
+        .parts()
+        .ok_or_else(|| invalid!("Invalid credential definition identifier: {}", id))?;
+    if signature_type != SIGNATURE_TYPE_CL {
+        return Err(invalid!("Unsupported signature type: {}", signature_type));
+    }
+    let schema_seq_no = schema_id.0.parse::<u64>().map_err(|_| {
+        invalid!(
+            "Credential definition identifier must reference a schema sequence number: {}",
+            id
+        )
+    })?;
+    Ok((did, schema_seq_no, tag))
+}
+
+/// Percent-encode the characters of a path segment outside of the unreserved set
+fn encode_segment(segment: &str) -> String {
+    let mut result = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                result.push(byte as char)
+            }
+            _ => result.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    result
+}
+
+fn decode_segment(segment: &str) -> Result<String, ValidationError> {
+    let mut result = Vec::with_capacity(segment.len());
+    let mut bytes = segment.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte == b'%' {
+            let hex = [
+                bytes.next().unwrap_or_default(),
+                bytes.next().unwrap_or_default(),
+            ];
+            let decoded = std::str::from_utf8(&hex)
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| invalid!("Invalid percent-encoding in DID URL: {}", segment))?;
+            result.push(decoded);
+        } else {
+            result.push(byte);
+        }
+    }
+    String::from_utf8(result)
+        .map_err(|_| invalid!("Invalid percent-encoding in DID URL: {}", segment))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_DID: &'static str = "did:indy:sovrin:F72i3Y3Q4i466efjYJYCHM";
+
+    #[test]
+    fn schema_url() {
+        let url = format!("{}/anoncreds/v0/SCHEMA/npdb/4.3.4", TEST_DID);
+        let parsed = IndyObjectUrl::parse(&url).unwrap();
+        assert_eq!(parsed.to_string(), url);
+        let schema_id = parsed.to_schema_id().unwrap();
+        assert_eq!(schema_id.0, "F72i3Y3Q4i466efjYJYCHM:2:npdb:4.3.4");
+        assert_eq!(
+            IndyObjectUrl::from_schema_id(&schema_id, "sovrin").unwrap(),
+            parsed
+        );
+        assert_eq!(parsed.to_cred_def_id(), None);
+
+        // names are percent-encoded
+        let schema_id = SchemaId("F72i3Y3Q4i466efjYJYCHM:2:My Schema:1.0".to_string());
+        let url = IndyObjectUrl::from_schema_id(&schema_id, "sovrin").unwrap();
+        assert_eq!(
+            url.to_string(),
+            format!("{}/anoncreds/v0/SCHEMA/My%20Schema/1.0", TEST_DID)
+        );
+        assert_eq!(IndyObjectUrl::parse(&url.to_string()).unwrap(), url);
+    }
+
+    #[test]
+    fn cred_def_url() {
+        let url = format!("{}/anoncreds/v0/CLAIM_DEF/56495/npdb", TEST_DID);
+        let parsed = IndyObjectUrl::parse(&url).unwrap();
+        assert_eq!(parsed.to_string(), url);
+        let cred_def_id = parsed.to_cred_def_id().unwrap();
+        assert_eq!(cred_def_id.0, "F72i3Y3Q4i466efjYJYCHM:3:CL:56495:npdb");
+        assert_eq!(
+            IndyObjectUrl::from_cred_def_id(&cred_def_id, "sovrin").unwrap(),
+            parsed
+        );
+
+        let by_schema_id = CredentialDefinitionId(
+            "F72i3Y3Q4i466efjYJYCHM:3:CL:F72i3Y3Q4i466efjYJYCHM:2:npdb:4.3.4:npdb".to_string(),
+        );
+        assert!(IndyObjectUrl::from_cred_def_id(&by_schema_id, "sovrin").is_err());
+    }
+
+    #[test]
+    fn rev_reg_def_url() {
+        let url = format!("{}/anoncreds/v0/REV_REG_DEF/56495/npdb/TAG1", TEST_DID);
+        let parsed = IndyObjectUrl::parse(&url).unwrap();
+        assert_eq!(parsed.to_string(), url);
+        let rev_reg_id = parsed.to_rev_reg_id().unwrap();
+        assert_eq!(
+            rev_reg_id.0,
+            "F72i3Y3Q4i466efjYJYCHM:4:F72i3Y3Q4i466efjYJYCHM:3:CL:56495:npdb:CL_ACCUM:TAG1"
+        );
+        assert_eq!(
+            IndyObjectUrl::from_rev_reg_id(&rev_reg_id, "sovrin").unwrap(),
+            parsed
+        );
+    }
+
+    #[test]
+    fn invalid_urls() {
+        for url in &[
+            "did:sov:F72i3Y3Q4i466efjYJYCHM/anoncreds/v0/SCHEMA/npdb/4.3.4",
+            "did:indy:sovrin:F72i3Y3Q4i466efjYJYCHM",
+            "did:indy:sovrin:F72i3Y3Q4i466efjYJYCHM/anoncreds/v1/SCHEMA/npdb/4.3.4",
+            "did:indy:sovrin:F72i3Y3Q4i466efjYJYCHM/anoncreds/v0/SCHEMA/npdb",
+            "did:indy:sovrin:F72i3Y3Q4i466efjYJYCHM/anoncreds/v0/CLAIM_DEF/abc/npdb",
+            "did:indy:sovrin:F72i3Y3Q4i466efjYJYCHM/anoncreds/v0/SCHEMA/np%2/4.3.4",
+        ] {
+            assert!(IndyObjectUrl::parse(url).is_err(), "{}", url);
+        }
+    }
+}
@@ -1,5 +1,7 @@
 /// Credential definition identifiers
 pub mod cred_def;
+/// did:indy DID URLs for ledger objects
+pub mod did_url;
 /// Revocation registry identifiers
 pub mod rev_reg;
 /// V1 schema identifiers
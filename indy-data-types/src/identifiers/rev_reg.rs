@@ -3,6 +3,7 @@ use once_cell::sync::Lazy;
 use regex::Regex;
 
 use super::cred_def::CredentialDefinitionId;
+use super::did_url::{IndyObjectUrl, REV_REG_TYPE_CL_ACCUM};
 use super::DELIMITER;
 use crate::utils::{qualifiable, Qualifiable};
 use crate::{Validatable, ValidationError};
@@ -45,6 +46,9 @@ impl RevocationRegistryId {
     }
 
     pub fn parts(&self) -> Option<(DidValue, CredentialDefinitionId, String, String)> {
+        if self.is_did_indy() {
+            return None;
+        }
         match QUALIFIED_REV_REG_ID.captures(&self.0) {
             Some(caps) => Some((
                 DidValue(caps["did"].to_string()),
@@ -55,6 +59,60 @@ impl RevocationRegistryId {
             None => None,
         }
     }
+
+    /// Check whether the identifier is a did:indy DID URL
+    pub fn is_did_indy(&self) -> bool {
+        self.0.starts_with("did:indy:")
+    }
+
+    /// The DID of the revocation registry issuer
+    pub fn issuer_did(&self) -> Option<DidValue> {
+        self.components().map(|(did, _, _, _)| did)
+    }
+
+    pub fn cred_def_id(&self) -> Option<CredentialDefinitionId> {
+        self.components().map(|(_, cred_def_id, _, _)| cred_def_id)
+    }
+
+    pub fn rev_reg_type(&self) -> Option<String> {
+        self.components()
+            .map(|(_, _, rev_reg_type, _)| rev_reg_type)
+    }
+
+    pub fn tag(&self) -> Option<String> {
+        self.components().map(|(_, _, _, tag)| tag)
+    }
+
+    /// The issuer DID, credential definition identifier, registry type and
+    /// tag of a legacy or did:indy identifier
+    fn components(&self) -> Option<(DidValue, CredentialDefinitionId, String, String)> {
+        if self.is_did_indy() {
+            match IndyObjectUrl::parse(&self.0).ok()? {
+                IndyObjectUrl::RevocationRegistryDefinition {
+                    did,
+                    schema_seq_no,
+                    cred_def_tag,
+                    tag,
+                } => {
+                    let issuer_did = DidValue(did.to_string());
+                    let cred_def_id = IndyObjectUrl::CredentialDefinition {
+                        did,
+                        schema_seq_no,
+                        tag: cred_def_tag,
+                    };
+                    Some((
+                        issuer_did,
+                        CredentialDefinitionId(cred_def_id.to_string()),
+                        REV_REG_TYPE_CL_ACCUM.to_owned(),
+                        tag,
+                    ))
+                }
+                _ => None,
+            }
+        } else {
+            self.parts()
+        }
+    }
 }
 
 impl Qualifiable for RevocationRegistryId {
@@ -90,7 +148,7 @@ impl Qualifiable for RevocationRegistryId {
 
 impl Validatable for RevocationRegistryId {
     fn validate(&self) -> Result<(), ValidationError> {
-        self.parts().ok_or(format!(
+        self.components().ok_or(format!(
             "Revocation Registry Id validation failed: {:?}, doesn't match pattern",
             self.0
         ))?;
@@ -192,6 +250,39 @@ mod tests {
         }
     }
 
+    mod accessors {
+        use super::*;
+
+        #[test]
+        fn test_rev_reg_id_accessors() {
+            let id = _rev_reg_id_unqualified();
+            assert_eq!(id.issuer_did().unwrap(), _did());
+            assert_eq!(id.cred_def_id().unwrap(), _cred_def_id_unqualified());
+            assert_eq!(id.rev_reg_type().unwrap(), _rev_reg_type());
+            assert_eq!(id.tag().unwrap(), _tag());
+
+            let id = RevocationRegistryId(
+                "did:indy:sovrin:NcYxiDXkpYi6ov5FcYDi1e/anoncreds/v0/REV_REG_DEF/1/tag/TAG_1"
+                    .to_string(),
+            );
+            id.validate().unwrap();
+            assert!(id.parts().is_none());
+            assert_eq!(
+                id.issuer_did().unwrap(),
+                DidValue("did:indy:sovrin:NcYxiDXkpYi6ov5FcYDi1e".to_string())
+            );
+            assert_eq!(
+                id.cred_def_id().unwrap(),
+                CredentialDefinitionId(
+                    "did:indy:sovrin:NcYxiDXkpYi6ov5FcYDi1e/anoncreds/v0/CLAIM_DEF/1/tag"
+                        .to_string()
+                )
+            );
+            assert_eq!(id.rev_reg_type().unwrap(), _rev_reg_type());
+            assert_eq!(id.tag().unwrap(), _tag());
+        }
+    }
+
     mod to_qualified {
         use super::*;
 
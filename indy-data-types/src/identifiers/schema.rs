@@ -3,6 +3,7 @@ use crate::{Validatable, ValidationError};
 use indy_utils::did::DidValue;
 use indy_utils::qualifiable_type;
 
+use super::did_url::IndyObjectUrl;
 use super::DELIMITER;
 
 qualifiable_type!(SchemaId, "A V1 schema identifier");
@@ -30,6 +31,9 @@ impl SchemaId {
     }
 
     pub fn parts(&self) -> Option<(Option<&str>, DidValue, String, String)> {
+        if self.is_did_indy() {
+            return None;
+        }
         let parts = self.0.split_terminator(DELIMITER).collect::<Vec<&str>>();
 
         if parts.len() == 1 {
@@ -56,6 +60,39 @@ impl SchemaId {
 
         None
     }
+
+    /// Check whether the identifier is a did:indy DID URL
+    pub fn is_did_indy(&self) -> bool {
+        self.0.starts_with("did:indy:")
+    }
+
+    /// The DID of the schema author
+    pub fn issuer_did(&self) -> Option<DidValue> {
+        self.components().map(|(did, _, _)| did)
+    }
+
+    pub fn name(&self) -> Option<String> {
+        self.components().map(|(_, name, _)| name)
+    }
+
+    pub fn version(&self) -> Option<String> {
+        self.components().map(|(_, _, version)| version)
+    }
+
+    /// The author DID, name and version of a legacy or did:indy identifier
+    fn components(&self) -> Option<(DidValue, String, String)> {
+        if self.is_did_indy() {
+            match IndyObjectUrl::parse(&self.0).ok()? {
+                IndyObjectUrl::Schema { did, name, version } => {
+                    Some((DidValue(did.to_string()), name, version))
+                }
+                _ => None,
+            }
+        } else {
+            self.parts()
+                .map(|(_, did, name, version)| (did, name, version))
+        }
+    }
 }
 
 impl Qualifiable for SchemaId {
@@ -94,7 +131,7 @@ impl Validatable for SchemaId {
             return Ok(());
         }
 
-        self.parts().ok_or(format!(
+        self.components().ok_or(format!(
             "SchemaId validation failed: {:?}, doesn't match pattern",
             self.0
         ))?;
@@ -127,6 +164,10 @@ mod tests {
         SchemaId("schema:sov:did:sov:NcYxiDXkpYi6ov5FcYDi1e:2:gvt:1.0".to_string())
     }
 
+    fn _schema_id_did_indy() -> SchemaId {
+        SchemaId("did:indy:sovrin:NcYxiDXkpYi6ov5FcYDi1e/anoncreds/v0/SCHEMA/gvt/1.0".to_string())
+    }
+
     fn _schema_id_invalid() -> SchemaId {
         SchemaId("NcYxiDXkpYi6ov5FcYDi1e:2".to_string())
     }
@@ -205,6 +246,19 @@ mod tests {
             _schema_id_qualified().validate().unwrap();
         }
 
+        #[test]
+        fn test_validate_schema_id_as_did_indy() {
+            _schema_id_did_indy().validate().unwrap();
+        }
+
+        #[test]
+        fn test_validate_schema_id_for_invalid_did_indy() {
+            let id = SchemaId(
+                "did:indy:sovrin:NcYxiDXkpYi6ov5FcYDi1e/anoncreds/v0/CLAIM_DEF/1/tag".to_string(),
+            );
+            id.validate().unwrap_err();
+        }
+
         #[test]
         fn test_validate_schema_id_for_invalid_unqualified() {
             _schema_id_invalid().validate().unwrap_err();
@@ -217,6 +271,36 @@ mod tests {
         }
     }
 
+    mod accessors {
+        use super::*;
+
+        #[test]
+        fn test_schema_id_accessors() {
+            for id in &[_schema_id_unqualified(), _schema_id_qualified()] {
+                assert_eq!(id.name().unwrap(), "gvt");
+                assert_eq!(id.version().unwrap(), "1.0");
+            }
+            assert_eq!(_schema_id_unqualified().issuer_did().unwrap(), _did());
+            assert_eq!(
+                _schema_id_qualified().issuer_did().unwrap(),
+                _did_qualified()
+            );
+
+            let id = _schema_id_did_indy();
+            assert!(id.is_did_indy());
+            assert!(id.parts().is_none());
+            assert_eq!(
+                id.issuer_did().unwrap(),
+                DidValue("did:indy:sovrin:NcYxiDXkpYi6ov5FcYDi1e".to_string())
+            );
+            assert_eq!(id.name().unwrap(), "gvt");
+            assert_eq!(id.version().unwrap(), "1.0");
+            assert_eq!(id.to_unqualified(), id);
+
+            assert!(_schema_id_seq_no().name().is_none());
+        }
+    }
+
     mod to_qualified {
         use super::*;
 
@@ -33,6 +33,7 @@ pub mod merkle_tree;
 mod identifiers;
 
 pub use identifiers::cred_def::*;
+pub use identifiers::did_url::*;
 pub use identifiers::rev_reg::*;
 pub use identifiers::schema::*;
 
@@ -0,0 +1,189 @@
+use chacha20poly1305::{
+    aead::{generic_array::GenericArray, Aead, NewAead, Payload},
+    ChaCha20Poly1305,
+};
+use once_cell::sync::OnceCell;
+use subtle::ConstantTimeEq;
+use ursa::signatures::{ed25519::Ed25519Sha512, SignatureScheme};
+
+use crate::error::ConversionError;
+use crate::keys::SharedSecret;
+use crate::random;
+
+/// The key size for the backend AEAD cipher (ChaCha20-Poly1305)
+pub const AEAD_KEY_SIZE: usize = 32;
+/// The nonce size for the backend AEAD cipher
+pub const AEAD_NONCE_SIZE: usize = 12;
+
+/// The cryptographic primitives used by the key types, which may be provided
+/// by an alternative implementation such as a FIPS validated module
+pub trait CryptoBackend: Send + Sync {
+    /// A short name for the backend, used in diagnostics
+    fn name(&self) -> &str;
+
+    /// Fill a buffer with random data
+    fn fill_random(&self, buf: &mut [u8]);
+
+    /// Sign a message with an Ed25519 secret key in the 64 byte
+    /// seed-and-public-key form
+    fn ed25519_sign(&self, sk: &[u8], message: &[u8]) -> Result<Vec<u8>, ConversionError>;
+
+    /// Verify an Ed25519 signature
+    fn ed25519_verify(
+        &self,
+        vk: &[u8],
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<bool, ConversionError>;
+
+    /// Perform a raw X25519 Diffie-Hellman exchange
+    fn x25519(&self, sk: &[u8; 32], pk: &[u8; 32]) -> Result<SharedSecret, ConversionError>;
+
+    /// Encrypt a message with ChaCha20-Poly1305, appending the tag
+    fn aead_encrypt(
+        &self,
+        key: &[u8],
+        nonce: &[u8],
+        aad: &[u8],
+        message: &[u8],
+    ) -> Result<Vec<u8>, ConversionError>;
+
+    /// Decrypt and authenticate a ChaCha20-Poly1305 ciphertext
+    fn aead_decrypt(
+        &self,
+        key: &[u8],
+        nonce: &[u8],
+        aad: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, ConversionError>;
+}
+
+/// The default pure-Rust backend, built on ursa and the RustCrypto crates
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RustCryptoBackend;
+
+impl CryptoBackend for RustCryptoBackend {
+    fn name(&self) -> &str {
+        "rust"
+    }
+
+    fn fill_random(&self, buf: &mut [u8]) {
+        random::fill_random(buf)
+    }
+
+    fn ed25519_sign(&self, sk: &[u8], message: &[u8]) -> Result<Vec<u8>, ConversionError> {
+        let sk = ursa::keys::PrivateKey(sk.to_vec());
+        Ok(Ed25519Sha512::new()
+            .sign(message, &sk)
+            .map_err(|err| format!("Error signing payload: {}", err))?)
+    }
+
+    fn ed25519_verify(
+        &self,
+        vk: &[u8],
+        message: &[u8],
+        signature: &[u8],
+    ) -> Result<bool, ConversionError> {
+        let vk = ursa::keys::PublicKey(vk.to_vec());
+        Ok(Ed25519Sha512::new()
+            .verify(message, signature, &vk)
+            .map_err(|err| format!("Error validating message signature: {}", err))?)
+    }
+
+    fn x25519(&self, sk: &[u8; 32], pk: &[u8; 32]) -> Result<SharedSecret, ConversionError> {
+        let secret = x25519_dalek::StaticSecret::from(*sk);
+        let shared = secret.diffie_hellman(&x25519_dalek::PublicKey::from(*pk));
+        // reject low-order points, which produce an all-zero shared secret
+        if bool::from(shared.as_bytes().ct_eq(&[0u8; 32])) {
+            return Err("Invalid public key for key exchange".into());
+        }
+        Ok(SharedSecret::new(*shared.as_bytes()))
+    }
+
+    fn aead_encrypt(
+        &self,
+        key: &[u8],
+        nonce: &[u8],
+        aad: &[u8],
+        message: &[u8],
+    ) -> Result<Vec<u8>, ConversionError> {
+        check_aead_params(key, nonce)?;
+        Ok(ChaCha20Poly1305::new(GenericArray::from_slice(key))
+            .encrypt(
+                GenericArray::from_slice(nonce),
+                Payload { aad, msg: message },
+            )
+            .map_err(|_| "Error encrypting message")?)
+    }
+
+    fn aead_decrypt(
+        &self,
+        key: &[u8],
+        nonce: &[u8],
+        aad: &[u8],
+        ciphertext: &[u8],
+    ) -> Result<Vec<u8>, ConversionError> {
+        check_aead_params(key, nonce)?;
+        Ok(ChaCha20Poly1305::new(GenericArray::from_slice(key))
+            .decrypt(
+                GenericArray::from_slice(nonce),
+                Payload {
+                    aad,
+                    msg: ciphertext,
+                },
+            )
+            .map_err(|_| "Error decrypting message")?)
+    }
+}
+
+fn check_aead_params(key: &[u8], nonce: &[u8]) -> Result<(), ConversionError> {
+    if key.len() != AEAD_KEY_SIZE {
+        return Err("Invalid AEAD key length".into());
+    }
+    if nonce.len() != AEAD_NONCE_SIZE {
+        return Err("Invalid AEAD nonce length".into());
+    }
+    Ok(())
+}
+
+static DEFAULT_BACKEND: RustCryptoBackend = RustCryptoBackend;
+
+static INSTALLED_BACKEND: OnceCell<&'static dyn CryptoBackend> = OnceCell::new();
+
+/// Install an alternative crypto backend for the process. This must be done
+/// before any keys are used, and may only be done once.
+pub fn install_backend(backend: &'static dyn CryptoBackend) -> Result<(), ConversionError> {
+    INSTALLED_BACKEND
+        .set(backend)
+        .map_err(|_| "A crypto backend has already been installed".into())
+}
+
+/// Get the active crypto backend
+pub fn backend() -> &'static dyn CryptoBackend {
+    INSTALLED_BACKEND.get().copied().unwrap_or(&DEFAULT_BACKEND)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_backend_round_trip() {
+        let backend = backend();
+        assert_eq!(backend.name(), "rust");
+
+        let key = [7u8; AEAD_KEY_SIZE];
+        let nonce = [1u8; AEAD_NONCE_SIZE];
+        let enc = backend
+            .aead_encrypt(&key, &nonce, b"aad", b"message")
+            .unwrap();
+        assert_eq!(
+            backend.aead_decrypt(&key, &nonce, b"aad", &enc).unwrap(),
+            b"message"
+        );
+        assert!(backend.aead_decrypt(&key, &nonce, b"other", &enc).is_err());
+        assert!(backend
+            .aead_encrypt(&key[..16], &nonce, b"aad", b"message")
+            .is_err());
+    }
+}
@@ -16,3 +16,7 @@ pub fn decode_urlsafe<T: AsRef<[u8]>>(val: T) -> Result<Vec<u8>, ConversionError
 pub fn encode_urlsafe<T: AsRef<[u8]>>(val: T) -> String {
     base64::encode_config(val, base64::URL_SAFE)
 }
+
+pub fn encode_urlsafe_nopad<T: AsRef<[u8]>>(val: T) -> String {
+    base64::encode_config(val, base64::URL_SAFE_NO_PAD)
+}
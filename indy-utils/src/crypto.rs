@@ -0,0 +1,257 @@
+pub use crate::pack::nacl_box::{
+    crypto_box, crypto_box_key_exchange, crypto_box_nonce, crypto_box_open, crypto_box_seal,
+    crypto_box_seal_open, crypto_box_seal_xchacha, crypto_box_seal_xchacha_open, crypto_box_shared,
+    CBOX_KEY_SIZE, CBOX_NONCE_SIZE,
+};
+
+use chacha20poly1305::{
+    aead::{generic_array::GenericArray, Aead, NewAead, Payload},
+    XChaCha20Poly1305,
+};
+use x25519_dalek::{PublicKey as XPublicKey, StaticSecret};
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::error::ConversionError;
+use crate::kdf::hkdf_sha256;
+use crate::keys::{EncodedVerKey, KeyType, PrivateKey, VerKey};
+use crate::nonce::XChaChaNonce;
+use crate::random::fill_random;
+
+/// The algorithm used for anonymous encryption by `seal`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SealAlg {
+    /// XSalsa20-Poly1305, compatible with libsodium `crypto_box_seal`
+    NaCl,
+    /// XChaCha20-Poly1305, as in `crypto_box_seal_xchacha`
+    XChaCha20Poly1305,
+}
+
+impl SealAlg {
+    /// The algorithm tag prepended to sealed messages
+    pub fn tag(self) -> u8 {
+        match self {
+            Self::NaCl => 0x01,
+            Self::XChaCha20Poly1305 => 0x02,
+        }
+    }
+
+    /// Look up an algorithm by its tag
+    pub fn from_tag(tag: u8) -> Option<Self> {
+        match tag {
+            0x01 => Some(Self::NaCl),
+            0x02 => Some(Self::XChaCha20Poly1305),
+            _ => None,
+        }
+    }
+}
+
+impl Default for SealAlg {
+    fn default() -> Self {
+        Self::XChaCha20Poly1305
+    }
+}
+
+/// Anonymously encrypt a message for an X25519 public key. The result is
+/// prefixed with the algorithm tag so that `seal_open` can select the
+/// matching primitive.
+pub fn seal(
+    recip_pk: &[u8],
+    message: &[u8],
+    alg: Option<SealAlg>,
+) -> Result<Vec<u8>, ConversionError> {
+    let alg = alg.unwrap_or_default();
+    let sealed = match alg {
+        SealAlg::NaCl => crypto_box_seal(recip_pk, message)?,
+        SealAlg::XChaCha20Poly1305 => crypto_box_seal_xchacha(recip_pk, message)?,
+    };
+    let mut result = Vec::with_capacity(sealed.len() + 1);
+    result.push(alg.tag());
+    result.extend_from_slice(&sealed);
+    Ok(result)
+}
+
+/// Decrypt a message produced by `seal`, returning the plaintext and the
+/// algorithm that was used
+pub fn seal_open(
+    recip_pk: &[u8],
+    recip_sk: &[u8],
+    ciphertext: &[u8],
+) -> Result<(Vec<u8>, SealAlg), ConversionError> {
+    let (tag, sealed) = ciphertext
+        .split_first()
+        .ok_or("Invalid sealed box length")?;
+    let alg = SealAlg::from_tag(*tag).ok_or("Unsupported sealed box algorithm")?;
+    let message = match alg {
+        SealAlg::NaCl => crypto_box_seal_open(recip_pk, recip_sk, sealed)?,
+        SealAlg::XChaCha20Poly1305 => crypto_box_seal_xchacha_open(recip_pk, recip_sk, sealed)?,
+    };
+    Ok((message, alg))
+}
+
+/// The version byte of the `encrypt_for` format
+const HYBRID_VERSION: u8 = 0x01;
+/// The algorithm byte for ephemeral X25519, HKDF-SHA256 and XChaCha20-Poly1305
+const HYBRID_ALG_X25519_XC20P: u8 = 0x01;
+const HYBRID_KDF_INFO: &[u8] = b"indy-hybrid-encryption-v1";
+const HYBRID_KEY_SIZE: usize = 32;
+const HYBRID_NONCE_SIZE: usize = 24;
+const HYBRID_HEADER_SIZE: usize = 2 + HYBRID_KEY_SIZE + HYBRID_NONCE_SIZE;
+
+/// Encrypt a payload for the holder of a verkey using an ephemeral X25519
+/// key agreement, HKDF-SHA256 and XChaCha20-Poly1305. Ed25519 verkeys are
+/// converted to X25519 keys. The output consists of a version byte, an
+/// algorithm byte, the ephemeral public key and the nonce, which are all
+/// authenticated, followed by the ciphertext and tag.
+pub fn encrypt_for(verkey: &EncodedVerKey, data: &[u8]) -> Result<Vec<u8>, ConversionError> {
+    let recip_pk = hybrid_recipient_key(verkey)?;
+
+    let mut ephem = [0u8; HYBRID_KEY_SIZE];
+    fill_random(&mut ephem);
+    let ephem_pk = XPublicKey::from(&StaticSecret::from(ephem));
+    let ephem_sk = PrivateKey::new(&ephem, Some(KeyType::X25519));
+    ephem.zeroize();
+    let shared = ephem_sk.dh(verkey)?;
+
+    let mut header = Vec::with_capacity(HYBRID_HEADER_SIZE);
+    header.push(HYBRID_VERSION);
+    header.push(HYBRID_ALG_X25519_XC20P);
+    header.extend_from_slice(ephem_pk.as_bytes());
+    header.extend_from_slice(XChaChaNonce::generate().as_slice());
+
+    let key = hybrid_key(shared.as_ref(), ephem_pk.as_bytes(), &recip_pk)?;
+    let ciphertext = XChaCha20Poly1305::new(GenericArray::from_slice(&key[..]))
+        .encrypt(
+            GenericArray::from_slice(&header[2 + HYBRID_KEY_SIZE..]),
+            Payload {
+                aad: &header,
+                msg: data,
+            },
+        )
+        .map_err(|_| "Error encrypting payload")?;
+    let mut result = header;
+    result.extend_from_slice(&ciphertext);
+    Ok(result)
+}
+
+/// Decrypt a payload produced by `encrypt_for` using the recipient's
+/// Ed25519 or X25519 private key
+pub fn decrypt_with(sk: &PrivateKey, data: &[u8]) -> Result<Vec<u8>, ConversionError> {
+    if data.len() < HYBRID_HEADER_SIZE {
+        return Err("Invalid length for encrypted payload".into());
+    }
+    let (header, ciphertext) = data.split_at(HYBRID_HEADER_SIZE);
+    if header[0] != HYBRID_VERSION {
+        return Err(format!("Unsupported encrypted payload version: {}", header[0]).into());
+    }
+    if header[1] != HYBRID_ALG_X25519_XC20P {
+        return Err("Unsupported encrypted payload algorithm".into());
+    }
+    let ephem_pk = &header[2..2 + HYBRID_KEY_SIZE];
+    let nonce = &header[2 + HYBRID_KEY_SIZE..];
+
+    let recip_pk = match sk.alg {
+        KeyType::ED25519 => sk.public_key()?.key_exchange()?.key_bytes(),
+        KeyType::X25519 => {
+            let mut secret = [0u8; HYBRID_KEY_SIZE];
+            secret.copy_from_slice(sk.key.get(..HYBRID_KEY_SIZE).ok_or("Invalid key length")?);
+            let pk = XPublicKey::from(&StaticSecret::from(secret));
+            secret.zeroize();
+            pk.as_bytes().to_vec()
+        }
+        _ => return Err("Unsupported key type for decryption".into()),
+    };
+    let ephem_vk = VerKey::new(ephem_pk, Some(KeyType::X25519)).as_base58()?;
+    let shared = sk.dh(&ephem_vk)?;
+    let key = hybrid_key(shared.as_ref(), ephem_pk, &recip_pk)?;
+    Ok(XChaCha20Poly1305::new(GenericArray::from_slice(&key[..]))
+        .decrypt(
+            GenericArray::from_slice(nonce),
+            Payload {
+                aad: header,
+                msg: ciphertext,
+            },
+        )
+        .map_err(|_| "Error decrypting payload")?)
+}
+
+/// Get the X25519 public key bytes for a recipient verkey
+fn hybrid_recipient_key(verkey: &EncodedVerKey) -> Result<Vec<u8>, ConversionError> {
+    match verkey.alg {
+        KeyType::ED25519 => Ok(verkey.key_exchange()?.key_bytes()),
+        KeyType::X25519 => verkey.key_bytes(),
+        _ => Err("Unsupported verkey type for encryption".into()),
+    }
+}
+
+/// Derive the payload key, binding both public keys to the shared secret
+fn hybrid_key(
+    shared: &[u8],
+    ephem_pk: &[u8],
+    recip_pk: &[u8],
+) -> Result<Zeroizing<Vec<u8>>, ConversionError> {
+    let mut salt = ephem_pk.to_vec();
+    salt.extend_from_slice(recip_pk);
+    hkdf_sha256(Some(&salt), shared, HYBRID_KDF_INFO, HYBRID_KEY_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::PrivateKey;
+
+    #[test]
+    fn hybrid_round_trip() {
+        let sk = PrivateKey::generate(None).unwrap();
+        let vk = sk.public_key().unwrap().as_base58().unwrap();
+        let data = b"wallet-to-wallet blob";
+
+        let enc = encrypt_for(&vk, data).unwrap();
+        assert_eq!(enc[0], HYBRID_VERSION);
+        assert_eq!(decrypt_with(&sk, &enc).unwrap(), data);
+
+        // the converted X25519 keys are interchangeable
+        let x_sk = sk.key_exchange().unwrap();
+        let x_vk = vk.key_exchange_encoded().unwrap();
+        assert_eq!(decrypt_with(&x_sk, &enc).unwrap(), data);
+        let enc = encrypt_for(&x_vk, data).unwrap();
+        assert_eq!(decrypt_with(&sk, &enc).unwrap(), data);
+
+        let mut tampered = enc.clone();
+        tampered[1] ^= 0xff;
+        assert!(decrypt_with(&sk, &tampered).is_err());
+        let mut tampered = enc.clone();
+        tampered[10] ^= 0x01;
+        assert!(decrypt_with(&sk, &tampered).is_err());
+        let other = PrivateKey::generate(None).unwrap();
+        assert!(decrypt_with(&other, &enc).is_err());
+    }
+
+    #[test]
+    fn seal_tagged() {
+        let sk = PrivateKey::generate(None).unwrap();
+        let sk_x = sk.key_exchange().unwrap();
+        let pk_x = sk.public_key().unwrap().key_exchange().unwrap();
+        let message = b"hello there";
+
+        for alg in &[SealAlg::NaCl, SealAlg::XChaCha20Poly1305] {
+            let sealed = seal(pk_x.as_ref(), message, Some(*alg)).unwrap();
+            assert_eq!(sealed[0], alg.tag());
+            let (open, open_alg) = seal_open(pk_x.as_ref(), sk_x.as_ref(), &sealed).unwrap();
+            assert_eq!(open, message);
+            assert_eq!(open_alg, *alg);
+        }
+
+        // altering the box after the tag and ephemeral key fails authentication
+        let mut sealed = seal(pk_x.as_ref(), message, None).unwrap();
+        sealed[1 + 32] ^= 0x01;
+        assert!(seal_open(pk_x.as_ref(), sk_x.as_ref(), &sealed).is_err());
+
+        // the tag itself is not authenticated, but rewriting it selects a
+        // primitive which cannot open the box, and unknown tags are rejected
+        let mut sealed = seal(pk_x.as_ref(), message, None).unwrap();
+        sealed[0] = SealAlg::NaCl.tag();
+        assert!(seal_open(pk_x.as_ref(), sk_x.as_ref(), &sealed).is_err());
+        sealed[0] = 0xff;
+        assert!(seal_open(pk_x.as_ref(), sk_x.as_ref(), &sealed).is_err());
+    }
+}
@@ -3,10 +3,51 @@ use once_cell::sync::Lazy;
 use regex::Regex;
 
 use crate::base58;
+#[cfg(feature = "hash")]
+use crate::hash::SHA256;
+use crate::keys::{EncodedVerKey, KeyType};
 #[cfg(feature = "ed25519")]
-use crate::keys::{KeyType, PrivateKey, VerKey};
+use crate::keys::{PrivateKey, VerKey};
+use crate::ConversionError;
 use crate::{Qualifiable, Validatable, ValidationError};
 
+/// DID Documents generated from public keys
+#[cfg(all(feature = "ed25519", feature = "serde_support"))]
+pub mod document;
+
+/// Generation and resolution of did:key identifiers
+pub mod key;
+
+/// Linking DIDs to web origins with DID Configuration resources
+#[cfg(feature = "pack")]
+pub mod linked_domains;
+
+/// Signed DID rotation messages
+#[cfg(feature = "pack")]
+pub mod rotate;
+
+/// Service endpoints and DIDComm service blocks
+#[cfg(feature = "serde_support")]
+pub mod service;
+
+/// Creation and resolution of did:peer identifiers
+#[cfg(all(
+    feature = "base64",
+    feature = "ed25519",
+    feature = "hash",
+    feature = "serde_support"
+))]
+pub mod peer;
+
+/// Resolution of DIDs to their DID Documents
+#[cfg(all(
+    feature = "base64",
+    feature = "ed25519",
+    feature = "hash",
+    feature = "serde_support"
+))]
+pub mod resolver;
+
 /// The default identifier DID used when submitting ledger read requests
 pub static DEFAULT_LIBINDY_DID: Lazy<DidValue> =
     Lazy::new(|| DidValue::new("LibindyDid111111111111", None));
@@ -125,10 +166,289 @@ impl Validatable for ShortDidValue {
     }
 }
 
+/// The DID method for legacy Sovrin DIDs
+pub const DID_METHOD_SOV: &'static str = "sov";
+/// The DID method for Indy networks
+pub const DID_METHOD_INDY: &'static str = "indy";
+/// The DID method for self-certifying public keys
+pub const DID_METHOD_KEY: &'static str = "key";
+/// The DID method for peer DIDs
+pub const DID_METHOD_PEER: &'static str = "peer";
+
+/// A parsed, fully-qualified DID
+///
+/// For `did:indy` and `did:sov` the network namespace is split from the
+/// method-specific identifier, so that `did:indy:sovrin:staging:<id>` has
+/// the namespace `sovrin:staging`. For other methods the whole
+/// method-specific identifier is stored in `id`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DID {
+    pub method: String,
+    pub namespace: Option<String>,
+    pub id: String,
+}
+
+impl DID {
+    /// Create and validate a DID from its components
+    pub fn new(method: &str, namespace: Option<&str>, id: &str) -> Result<Self, ValidationError> {
+        let did = Self {
+            method: method.to_owned(),
+            namespace: namespace.map(str::to_owned),
+            id: id.to_owned(),
+        };
+        did.validate()?;
+        Ok(did)
+    }
+
+    /// Parse a DID string according to the DID syntax ABNF, applying
+    /// additional validation for the did:sov, did:indy, did:key and did:peer
+    /// methods
+    pub fn parse(did: &str) -> Result<Self, ValidationError> {
+        let rest = did
+            .strip_prefix("did:")
+            .ok_or_else(|| invalid!("Invalid DID: {}. Expected the prefix 'did:'", did))?;
+        let (method, msid) = match rest.find(':') {
+            Some(pos) => (&rest[..pos], &rest[pos + 1..]),
+            None => return Err(invalid!("Invalid DID: {}. Missing method-specific ID", did)),
+        };
+        let (namespace, id) = match method {
+            DID_METHOD_INDY | DID_METHOD_SOV => match msid.rfind(':') {
+                Some(pos) => (Some(&msid[..pos]), &msid[pos + 1..]),
+                None => (None, msid),
+            },
+            _ => (None, msid),
+        };
+        Self::new(method, namespace, id)
+    }
+
+    /// Convert a legacy unqualified Indy DID to a qualified DID. A `did:indy`
+    /// DID is produced when a namespace is provided, otherwise `did:sov`.
+    pub fn from_unqualified(
+        did: &ShortDidValue,
+        namespace: Option<&str>,
+    ) -> Result<Self, ValidationError> {
+        match namespace {
+            Some(namespace) => Self::new(DID_METHOD_INDY, Some(namespace), did),
+            None => Self::new(DID_METHOD_SOV, None, did),
+        }
+    }
+
+    /// Get the legacy unqualified form of a `did:sov` or `did:indy` DID
+    pub fn to_unqualified(&self) -> Option<ShortDidValue> {
+        match self.method.as_str() {
+            DID_METHOD_INDY | DID_METHOD_SOV => Some(ShortDidValue(self.id.clone())),
+            _ => None,
+        }
+    }
+
+    /// The method-specific identifier, including any namespace
+    pub fn method_specific_id(&self) -> String {
+        match self.namespace {
+            Some(ref namespace) => format!("{}:{}", namespace, self.id),
+            None => self.id.clone(),
+        }
+    }
+
+    pub fn is_legacy_indy(&self) -> bool {
+        self.to_unqualified().is_some()
+    }
+
+    /// Derive the `did:sov` DID for a verkey following the NYM rule: the
+    /// base58 encoding of the first 16 bytes of the key
+    pub fn from_verkey(verkey: &EncodedVerKey) -> Result<Self, ConversionError> {
+        let key = verkey_bytes(verkey)?;
+        Ok(Self::new(
+            DID_METHOD_SOV,
+            None,
+            &base58::encode(&key[..16]),
+        )?)
+    }
+
+    /// Derive a self-certifying `did:indy` DID for a verkey: the base58
+    /// encoding of the first 16 bytes of the SHA-256 digest of the key
+    #[cfg(feature = "hash")]
+    pub fn self_certifying(
+        verkey: &EncodedVerKey,
+        namespace: &str,
+    ) -> Result<Self, ConversionError> {
+        let key = verkey_bytes(verkey)?;
+        let digest = SHA256::digest(&key);
+        Ok(Self::new(
+            DID_METHOD_INDY,
+            Some(namespace),
+            &base58::encode(&digest[..16]),
+        )?)
+    }
+
+    /// Check whether the DID was derived from the verkey by the NYM rule
+    pub fn is_derived_from(&self, verkey: &EncodedVerKey) -> Result<bool, ConversionError> {
+        let key = verkey_bytes(verkey)?;
+        Ok(self.is_legacy_indy() && self.id == base58::encode(&key[..16]))
+    }
+
+    /// Check whether the DID is the self-certifying `did:indy` DID of the verkey
+    #[cfg(feature = "hash")]
+    pub fn is_self_certifying(&self, verkey: &EncodedVerKey) -> Result<bool, ConversionError> {
+        let key = verkey_bytes(verkey)?;
+        Ok(
+            self.method == DID_METHOD_INDY
+                && self.id == base58::encode(&SHA256::digest(&key)[..16]),
+        )
+    }
+
+    /// Check that the DID corresponds to its verkey. A `did:sov` DID must be
+    /// derived by the NYM rule, while a `did:indy` DID may also be
+    /// self-certifying.
+    pub fn validate_verkey(&self, verkey: &EncodedVerKey) -> Result<(), ConversionError> {
+        if self.is_derived_from(verkey)? {
+            return Ok(());
+        }
+        #[cfg(feature = "hash")]
+        {
+            if self.is_self_certifying(verkey)? {
+                return Ok(());
+            }
+        }
+        Err(format!("DID {} does not correspond to verkey {}", self, verkey.key).into())
+    }
+}
+
+/// Get the raw bytes of an Ed25519 verkey used to derive a DID
+fn verkey_bytes(verkey: &EncodedVerKey) -> Result<Vec<u8>, ConversionError> {
+    if verkey.alg != KeyType::ED25519 {
+        return Err("DIDs may only be derived from ed25519 verkeys".into());
+    }
+    let key = verkey.key_bytes()?;
+    if key.len() != 32 {
+        return Err("Expected a 32-byte verkey".into());
+    }
+    Ok(key)
+}
+
+impl Validatable for DID {
+    fn validate(&self) -> Result<(), ValidationError> {
+        static REGEX_NAMESPACE: Lazy<Regex> =
+            Lazy::new(|| Regex::new("^[a-z0-9_-]+(:[a-z0-9_-]+)*$").unwrap());
+        // method-specific-id = *( *idchar ":" ) 1*idchar
+        static REGEX_MSID: Lazy<Regex> = Lazy::new(|| {
+            Regex::new("^(([A-Za-z0-9._-]|%[0-9A-Fa-f]{2})*:)*([A-Za-z0-9._-]|%[0-9A-Fa-f]{2})+$")
+                .unwrap()
+        });
+
+        DidMethod(self.method.clone()).validate()?;
+        if let Some(ref namespace) = self.namespace {
+            if !REGEX_NAMESPACE.is_match(namespace) {
+                return Err(invalid!("Invalid DID namespace: {}", namespace));
+            }
+        }
+        if !REGEX_MSID.is_match(&self.method_specific_id()) {
+            return Err(invalid!(
+                "Invalid DID: {}. The method-specific ID contains invalid characters",
+                self
+            ));
+        }
+        match self.method.as_str() {
+            DID_METHOD_INDY | DID_METHOD_SOV => {
+                if self.method == DID_METHOD_INDY && self.namespace.is_none() {
+                    return Err(invalid!(
+                        "Invalid DID: {}. Missing did:indy namespace",
+                        self
+                    ));
+                }
+                ShortDidValue(self.id.clone()).validate()
+            }
+            DID_METHOD_KEY => {
+                if self.namespace.is_some() {
+                    return Err(invalid!("Unexpected namespace for did:key"));
+                }
+                let key = self
+                    .id
+                    .strip_prefix('z')
+                    .ok_or_else(|| invalid!("Invalid did:key: {}. Expected base58btc", self))?;
+                let key = base58::decode(key).map_err(ValidationError::from_msg)?;
+                if KeyType::from_multicodec(&key).is_none() {
+                    return Err(invalid!("Invalid did:key: {}. Unknown key type", self));
+                }
+                Ok(())
+            }
+            DID_METHOD_PEER => {
+                if self.namespace.is_some() {
+                    return Err(invalid!("Unexpected namespace for did:peer"));
+                }
+                match self.id.chars().next() {
+                    Some('0'..='4') if self.id.len() > 1 => Ok(()),
+                    _ => Err(invalid!(
+                        "Invalid did:peer: {}. Unsupported numeric algorithm",
+                        self
+                    )),
+                }
+            }
+            _ => {
+                if self.namespace.is_some() {
+                    return Err(invalid!(
+                        "DID namespaces are only supported for did:sov and did:indy"
+                    ));
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for DID {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "did:{}:{}", self.method, self.method_specific_id())
+    }
+}
+
+impl std::str::FromStr for DID {
+    type Err = ValidationError;
+
+    fn from_str(did: &str) -> Result<Self, Self::Err> {
+        Self::parse(did)
+    }
+}
+
+impl From<&DID> for DidValue {
+    fn from(did: &DID) -> Self {
+        DidValue(did.to_string())
+    }
+}
+
+impl std::convert::TryFrom<&DidValue> for DID {
+    type Error = ValidationError;
+
+    /// Parse a DID value, treating unqualified DIDs as `did:sov`
+    fn try_from(did: &DidValue) -> Result<Self, Self::Error> {
+        if did.is_fully_qualified() {
+            Self::parse(did)
+        } else {
+            Self::from_unqualified(&did.to_short(), None)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for DID {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for DID {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let did = String::deserialize(deserializer)?;
+        Self::parse(&did).map_err(serde::de::Error::custom)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::keys::EncodedVerKey;
+    #[cfg(feature = "ed25519")]
+    use crate::keys::test_fixtures::{TEST1, TEST2};
+    use std::convert::TryFrom;
 
     #[test]
     fn generate_abbreviate() {
@@ -141,4 +461,98 @@ mod tests {
         let cmp_vk = vk_long.decode().unwrap();
         assert_eq!(vk, cmp_vk);
     }
+
+    #[test]
+    fn abbreviate_qualified() {
+        let (did, _sk, vk) = generate_did(None).unwrap();
+        let vk_b58 = vk.as_base58().unwrap();
+        let qualified = format!("did:sov:{}", &*did);
+        let vk_short = vk_b58.abbreviate_for(&qualified).unwrap();
+        assert_eq!(vk_short, vk_b58.abbreviate_for(&did).unwrap());
+        assert_eq!(
+            EncodedVerKey::from_abbreviated(&qualified, &vk_short).unwrap(),
+            vk_b58
+        );
+
+        // keys not derived from the DID are not abbreviated
+        let (other_did, _, _) = generate_did(None).unwrap();
+        assert_eq!(
+            vk_b58.abbreviate_for(&other_did).unwrap(),
+            vk_b58.key.as_str()
+        );
+        assert!(EncodedVerKey::from_abbreviated(&did, &vk_b58.key).is_err());
+        assert!(vk_b58.abbreviate_for("did:key:abc").is_err());
+    }
+
+    #[cfg(feature = "ed25519")]
+    #[test]
+    fn did_from_verkey() {
+        let vk = TEST1.encoded_verkey();
+        let did = DID::from_verkey(&vk).unwrap();
+        assert_eq!(did.to_string(), "did:sov:QWFZKRPHTmvZCdJrC69vbZ");
+        assert!(did.is_derived_from(&vk).unwrap());
+        did.validate_verkey(&vk).unwrap();
+        assert!(did.validate_verkey(&TEST2.encoded_verkey()).is_err());
+
+        let indy = DID::from_unqualified(&TEST1.did(), Some("sovrin")).unwrap();
+        indy.validate_verkey(&vk).unwrap();
+
+        #[cfg(feature = "hash")]
+        {
+            let did = DID::self_certifying(&vk, "sovrin").unwrap();
+            assert_eq!(did.to_string(), "did:indy:sovrin:8LgdU4yoSK4U1i7H3nVEgR");
+            assert!(did.is_self_certifying(&vk).unwrap());
+            assert!(!did.is_derived_from(&vk).unwrap());
+            did.validate_verkey(&vk).unwrap();
+            assert!(DID::parse("did:sov:8LgdU4yoSK4U1i7H3nVEgR")
+                .unwrap()
+                .validate_verkey(&vk)
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn parse_dids() {
+        let did = DID::parse("did:sov:VsKV7grR1BUE29mG2Fm2kX").unwrap();
+        assert_eq!(did.method, DID_METHOD_SOV);
+        assert_eq!(did.namespace, None);
+        assert_eq!(did.to_string(), "did:sov:VsKV7grR1BUE29mG2Fm2kX");
+
+        let did: DID = "did:indy:sovrin:staging:VsKV7grR1BUE29mG2Fm2kX"
+            .parse()
+            .unwrap();
+        assert_eq!(did.namespace.as_deref(), Some("sovrin:staging"));
+        assert_eq!(did.id, "VsKV7grR1BUE29mG2Fm2kX");
+        assert_eq!(
+            did.to_unqualified(),
+            Some(ShortDidValue::from("VsKV7grR1BUE29mG2Fm2kX".to_string()))
+        );
+        assert_eq!(
+            DID::from_unqualified(&did.to_unqualified().unwrap(), Some("sovrin:staging")).unwrap(),
+            did
+        );
+
+        let did = DID::parse("did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK").unwrap();
+        assert_eq!(did.to_unqualified(), None);
+        DID::parse("did:peer:2.Ez6LSbysY2xFMRpGMhb7tFTLMpeuPRaqaWM1yECx2AtzE3KCc").unwrap();
+        DID::parse("did:example:123:456%20").unwrap();
+
+        for invalid in &[
+            "VsKV7grR1BUE29mG2Fm2kX",
+            "did:sov:",
+            "did:Sov:VsKV7grR1BUE29mG2Fm2kX",
+            "did:indy:VsKV7grR1BUE29mG2Fm2kX",
+            "did:sov:abc",
+            "did:key:abc",
+            "did:peer:9abc",
+            "did:example:abc:",
+            "did:example:a b",
+        ] {
+            assert!(DID::parse(invalid).is_err(), "{}", invalid);
+        }
+
+        let legacy = DidValue::new("VsKV7grR1BUE29mG2Fm2kX", None);
+        let did = DID::try_from(&legacy).unwrap();
+        assert_eq!(DidValue::from(&did).0, "did:sov:VsKV7grR1BUE29mG2Fm2kX");
+    }
 }
@@ -0,0 +1,460 @@
+//! W3C DID Documents generated from a DID and its public keys
+
+use std::collections::HashSet;
+
+pub use super::service::Service;
+use super::DID;
+use crate::error::ConversionError;
+use crate::keys::{EncodedVerKey, KeyEncoding, KeyType, VerKey};
+use crate::{Validatable, ValidationError};
+
+pub const DID_CONTEXT_V1: &'static str = "https://www.w3.org/ns/did/v1";
+pub const ED25519_2020_CONTEXT: &'static str = "https://w3id.org/security/suites/ed25519-2020/v1";
+pub const X25519_2020_CONTEXT: &'static str = "https://w3id.org/security/suites/x25519-2020/v1";
+pub const MULTIKEY_CONTEXT: &'static str = "https://w3id.org/security/multikey/v1";
+
+pub const ED25519_VERIFICATION_KEY_2020: &'static str = "Ed25519VerificationKey2020";
+pub const X25519_KEY_AGREEMENT_KEY_2020: &'static str = "X25519KeyAgreementKey2020";
+pub const BLS12381_G2_KEY_2020: &'static str = "Bls12381G2Key2020";
+/// The generic verification method type, for any multicodec-prefixed key
+pub const MULTIKEY: &'static str = "Multikey";
+
+/// A DID Document as defined by the W3C DID Core specification
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DidDocument {
+    #[serde(rename = "@context")]
+    pub context: Vec<String>,
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub also_known_as: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub verification_method: Vec<VerificationMethod>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authentication: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assertion_method: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub key_agreement: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub service: Vec<Service>,
+}
+
+impl DidDocument {
+    /// Create an empty DID Document with the base DID context
+    pub fn new(id: &str) -> Self {
+        Self {
+            context: vec![DID_CONTEXT_V1.to_owned()],
+            id: id.to_owned(),
+            also_known_as: Vec::new(),
+            verification_method: Vec::new(),
+            authentication: Vec::new(),
+            assertion_method: Vec::new(),
+            key_agreement: Vec::new(),
+            service: Vec::new(),
+        }
+    }
+
+    /// Look up a verification method by its identifier, which may be
+    /// relative to the document DID
+    pub fn verification_method(&self, id: &str) -> Option<&VerificationMethod> {
+        let id = absolute_id(&self.id, id);
+        self.verification_method
+            .iter()
+            .find(|method| method.id == id)
+    }
+}
+
+/// A public key listed in a DID Document
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct VerificationMethod {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub controller: String,
+    pub public_key_multibase: String,
+}
+
+impl VerificationMethod {
+    /// Decode the public key of the verification method
+    pub fn public_key(&self) -> Result<VerKey, ConversionError> {
+        let expected = match self.type_.as_str() {
+            ED25519_VERIFICATION_KEY_2020 => Some(KeyType::ED25519),
+            X25519_KEY_AGREEMENT_KEY_2020 => Some(KeyType::X25519),
+            BLS12381_G2_KEY_2020 => Some(KeyType::Bls12381G2),
+            MULTIKEY => None,
+            other => return Err(format!("Unsupported verification method type: {}", other).into()),
+        };
+        let key = EncodedVerKey::from_multibase(&self.public_key_multibase)?.decode()?;
+        if expected.map(|alg| alg != key.alg).unwrap_or(false) {
+            return Err("Verification method key does not match its type".into());
+        }
+        Ok(key)
+    }
+}
+
+/// Build a DID Document for a DID from its public keys and services.
+///
+/// Ed25519 keys are listed for authentication and assertion, and the
+/// derived X25519 key is added for key agreement. X25519 keys are listed
+/// for key agreement only. Relative identifiers of services are resolved
+/// against the DID.
+pub fn build(
+    did: &DID,
+    keys: &[VerKey],
+    services: &[Service],
+) -> Result<DidDocument, ConversionError> {
+    let id = did.to_string();
+    let mut doc = DidDocument::new(&id);
+    let add_method = |doc: &mut DidDocument, key: &VerKey, type_: &str| {
+        let method_id = format!("{}#key-{}", id, doc.verification_method.len() + 1);
+        doc.verification_method.push(VerificationMethod {
+            id: method_id.clone(),
+            type_: type_.to_owned(),
+            controller: id.clone(),
+            public_key_multibase: key.encode(&KeyEncoding::MULTIBASE)?.key,
+        });
+        Result::<_, ConversionError>::Ok(method_id)
+    };
+
+    for key in keys {
+        match key.alg {
+            KeyType::ED25519 => {
+                let method_id = add_method(&mut doc, key, ED25519_VERIFICATION_KEY_2020)?;
+                doc.authentication.push(method_id.clone());
+                doc.assertion_method.push(method_id);
+                let method_id = add_method(
+                    &mut doc,
+                    &key.key_exchange()?,
+                    X25519_KEY_AGREEMENT_KEY_2020,
+                )?;
+                doc.key_agreement.push(method_id);
+            }
+            KeyType::X25519 => {
+                let method_id = add_method(&mut doc, key, X25519_KEY_AGREEMENT_KEY_2020)?;
+                doc.key_agreement.push(method_id);
+            }
+            _ => {
+                return Err(format!(
+                    "Unsupported key type for DID Document: {}",
+                    key.alg.as_str()
+                )
+                .into())
+            }
+        }
+    }
+    if !doc.authentication.is_empty() {
+        doc.context.push(ED25519_2020_CONTEXT.to_owned());
+    }
+    if !doc.key_agreement.is_empty() {
+        doc.context.push(X25519_2020_CONTEXT.to_owned());
+    }
+
+    for service in services {
+        service.validate()?;
+        let mut service = service.clone();
+        service.id = absolute_id(&doc.id, &service.id);
+        doc.service.push(service);
+    }
+    Ok(doc)
+}
+
+/// Check the invariants required of a DID Document: the document and
+/// controller identifiers must be DIDs, verification method and service
+/// identifiers must be unique, and verification relationships must refer to
+/// listed verification methods.
+pub fn validate(doc: &DidDocument) -> Result<(), ValidationError> {
+    DID::parse(&doc.id)?;
+    let mut method_ids = HashSet::new();
+    for method in &doc.verification_method {
+        if !method_ids.insert(absolute_id(&doc.id, &method.id)) {
+            return Err(invalid!("Duplicate verification method: {}", method.id));
+        }
+        DID::parse(&method.controller).map_err(|_| {
+            invalid!(
+                "Invalid controller for {}: {}",
+                method.id,
+                method.controller
+            )
+        })?;
+    }
+    for relationship in VerificationRelationship::ALL.iter() {
+        for reference in relationship.references(doc) {
+            if !method_ids.contains(&absolute_id(&doc.id, reference)) {
+                return Err(invalid!(
+                    "Unknown verification method in {}: {}",
+                    relationship.as_str(),
+                    reference
+                ));
+            }
+        }
+    }
+    let mut service_ids = HashSet::new();
+    for service in &doc.service {
+        if !service_ids.insert(absolute_id(&doc.id, &service.id)) {
+            return Err(invalid!("Duplicate service: {}", service.id));
+        }
+        service.validate()?;
+    }
+    Ok(())
+}
+
+/// The verification relationships listed in a DID Document
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum VerificationRelationship {
+    Authentication,
+    AssertionMethod,
+    KeyAgreement,
+}
+
+impl VerificationRelationship {
+    const ALL: [Self; 3] = [
+        Self::Authentication,
+        Self::AssertionMethod,
+        Self::KeyAgreement,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Authentication => "authentication",
+            Self::AssertionMethod => "assertionMethod",
+            Self::KeyAgreement => "keyAgreement",
+        }
+    }
+
+    fn references<'d>(&self, doc: &'d DidDocument) -> &'d [String] {
+        match self {
+            Self::Authentication => &doc.authentication,
+            Self::AssertionMethod => &doc.assertion_method,
+            Self::KeyAgreement => &doc.key_agreement,
+        }
+    }
+}
+
+/// Entries added, removed or changed between two versions of a DID Document
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Changes<T> {
+    pub added: Vec<T>,
+    pub removed: Vec<T>,
+    /// Pairs of the old and new versions of entries with the same identifier
+    pub changed: Vec<(T, T)>,
+}
+
+impl<T> Changes<T> {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl<T> Default for Changes<T> {
+    fn default() -> Self {
+        Self {
+            added: Vec::new(),
+            removed: Vec::new(),
+            changed: Vec::new(),
+        }
+    }
+}
+
+/// The changes between two versions of a DID Document. Identifiers are
+/// compared after resolving them against the document DID.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DocumentDiff {
+    pub verification_method: Changes<VerificationMethod>,
+    pub service: Changes<Service>,
+    /// Verification method identifiers added to a relationship
+    pub added_relationships: Vec<(VerificationRelationship, String)>,
+    /// Verification method identifiers removed from a relationship
+    pub removed_relationships: Vec<(VerificationRelationship, String)>,
+}
+
+impl DocumentDiff {
+    pub fn is_empty(&self) -> bool {
+        self.verification_method.is_empty()
+            && self.service.is_empty()
+            && self.added_relationships.is_empty()
+            && self.removed_relationships.is_empty()
+    }
+}
+
+/// Compare two versions of a DID Document, such as the current document and
+/// a ledger update
+pub fn diff(old: &DidDocument, new: &DidDocument) -> DocumentDiff {
+    let mut result = DocumentDiff {
+        verification_method: diff_entries(
+            &old.verification_method,
+            &new.verification_method,
+            |method| absolute_id(&old.id, &method.id),
+            |method| absolute_id(&new.id, &method.id),
+        ),
+        service: diff_entries(
+            &old.service,
+            &new.service,
+            |service| absolute_id(&old.id, &service.id),
+            |service| absolute_id(&new.id, &service.id),
+        ),
+        ..Default::default()
+    };
+    for relationship in VerificationRelationship::ALL.iter() {
+        let old_refs = relationship
+            .references(old)
+            .iter()
+            .map(|id| absolute_id(&old.id, id))
+            .collect::<Vec<_>>();
+        let new_refs = relationship
+            .references(new)
+            .iter()
+            .map(|id| absolute_id(&new.id, id))
+            .collect::<Vec<_>>();
+        for id in &new_refs {
+            if !old_refs.contains(id) {
+                result.added_relationships.push((*relationship, id.clone()));
+            }
+        }
+        for id in &old_refs {
+            if !new_refs.contains(id) {
+                result
+                    .removed_relationships
+                    .push((*relationship, id.clone()));
+            }
+        }
+    }
+    result
+}
+
+fn diff_entries<T: Clone + PartialEq>(
+    old: &[T],
+    new: &[T],
+    old_id: impl Fn(&T) -> String,
+    new_id: impl Fn(&T) -> String,
+) -> Changes<T> {
+    let mut changes = Changes::default();
+    for entry in new {
+        let id = new_id(entry);
+        match old.iter().find(|prev| old_id(prev) == id) {
+            Some(prev) if prev != entry => changes.changed.push((prev.clone(), entry.clone())),
+            Some(_) => (),
+            None => changes.added.push(entry.clone()),
+        }
+    }
+    for entry in old {
+        let id = old_id(entry);
+        if !new.iter().any(|next| new_id(next) == id) {
+            changes.removed.push(entry.clone());
+        }
+    }
+    changes
+}
+
+/// Resolve a fragment identifier such as `#key-1` against a DID
+pub(crate) fn absolute_id(did: &str, id: &str) -> String {
+    if id.starts_with('#') {
+        format!("{}{}", did, id)
+    } else {
+        id.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::test_fixtures::TEST1;
+
+    #[test]
+    fn build_document() {
+        let did = DID::from_unqualified(&TEST1.did(), None).unwrap();
+        let vk = TEST1.verkey();
+        let mut service = Service::new("#didcomm", "did-communication", "https://example.com");
+        service.recipient_keys.push(format!("{}#key-2", did));
+        let doc = build(&did, &[vk.clone()], &[service]).unwrap();
+
+        let json = serde_json::to_value(&doc).unwrap();
+        assert_eq!(json["id"], did.to_string());
+        assert_eq!(json["@context"].as_array().unwrap().len(), 3);
+        assert_eq!(
+            json["verificationMethod"][0]["type"],
+            ED25519_VERIFICATION_KEY_2020
+        );
+        assert_eq!(
+            json["verificationMethod"][1]["type"],
+            X25519_KEY_AGREEMENT_KEY_2020
+        );
+        assert_eq!(json["authentication"][0], format!("{}#key-1", did));
+        assert_eq!(json["keyAgreement"][0], format!("{}#key-2", did));
+        assert_eq!(json["service"][0]["id"], format!("{}#didcomm", did));
+        assert_eq!(json["service"][0]["serviceEndpoint"], "https://example.com");
+        assert_eq!(serde_json::from_value::<DidDocument>(json).unwrap(), doc);
+
+        assert_eq!(
+            doc.verification_method("#key-1")
+                .unwrap()
+                .public_key()
+                .unwrap(),
+            vk
+        );
+        assert_eq!(
+            doc.verification_method("#key-2")
+                .unwrap()
+                .public_key()
+                .unwrap(),
+            vk.key_exchange().unwrap()
+        );
+    }
+
+    #[test]
+    fn validate_and_diff_documents() {
+        let did = DID::from_unqualified(&TEST1.did(), None).unwrap();
+        let service = Service::new("#didcomm", "did-communication", "https://example.com");
+        let doc = build(&did, &[TEST1.verkey()], &[service]).unwrap();
+        validate(&doc).unwrap();
+        assert!(diff(&doc, &doc).is_empty());
+
+        let mut invalid = doc.clone();
+        invalid.authentication.push("#key-3".to_owned());
+        assert!(validate(&invalid).is_err());
+        let mut invalid = doc.clone();
+        invalid.verification_method[1].id = "#key-1".to_owned();
+        assert!(validate(&invalid).is_err());
+        let mut invalid = doc.clone();
+        invalid.verification_method[0].controller = "controller".to_owned();
+        assert!(validate(&invalid).is_err());
+        let mut invalid = doc.clone();
+        invalid.service.push(invalid.service[0].clone());
+        assert!(validate(&invalid).is_err());
+
+        let mut updated = doc.clone();
+        updated.assertion_method.clear();
+        updated.service[0].service_endpoint = "https://example.com/v2".to_owned();
+        updated
+            .service
+            .push(Service::new("#web", "LinkedDomains", "https://example.com"));
+        updated.verification_method.pop();
+        updated.key_agreement.clear();
+        let changes = diff(&doc, &updated);
+        assert_eq!(changes.verification_method.added.len(), 0);
+        assert_eq!(
+            changes.verification_method.removed,
+            vec![doc.verification_method[1].clone()]
+        );
+        assert_eq!(changes.service.added[0].id, "#web");
+        assert_eq!(
+            changes.service.changed,
+            vec![(doc.service[0].clone(), updated.service[0].clone())]
+        );
+        assert!(changes.added_relationships.is_empty());
+        assert_eq!(
+            changes.removed_relationships,
+            vec![
+                (
+                    VerificationRelationship::AssertionMethod,
+                    format!("{}#key-1", did)
+                ),
+                (
+                    VerificationRelationship::KeyAgreement,
+                    format!("{}#key-2", did)
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! The did:key method, in which the method-specific identifier is the
+//! multibase, multicodec-prefixed public key
+
+use super::{DID, DID_METHOD_KEY};
+use crate::error::ConversionError;
+use crate::keys::{EncodedVerKey, VerKey};
+
+#[cfg(all(feature = "ed25519", feature = "serde_support"))]
+use super::document::{
+    DidDocument, VerificationMethod, BLS12381_G2_KEY_2020, ED25519_2020_CONTEXT,
+    ED25519_VERIFICATION_KEY_2020, MULTIKEY, MULTIKEY_CONTEXT, X25519_2020_CONTEXT,
+    X25519_KEY_AGREEMENT_KEY_2020,
+};
+#[cfg(feature = "ed25519")]
+use crate::keys::KeyType;
+
+/// Generate the did:key for a verkey
+pub fn did_key(verkey: &EncodedVerKey) -> Result<DID, ConversionError> {
+    Ok(DID::new(DID_METHOD_KEY, None, &verkey.fingerprint()?)?)
+}
+
+/// Resolve a did:key to the public key it encodes
+pub fn resolve_verkey(did: &DID) -> Result<VerKey, ConversionError> {
+    if did.method != DID_METHOD_KEY {
+        return Err(format!("Expected a did:key, found did:{}", did.method).into());
+    }
+    EncodedVerKey::from_multibase(&did.id)?.decode()
+}
+
+/// Format a verkey as a did:key DID URL for use as a DIDComm v2 `kid` or
+/// `skid`. The fragment of an ed25519 key names its X25519 key agreement
+/// key, while the DID itself retains the ed25519 key.
+pub fn verkey_to_kid(verkey: &EncodedVerKey) -> Result<String, ConversionError> {
+    let did = did_key(verkey)?;
+    #[cfg(feature = "ed25519")]
+    {
+        if verkey.alg == KeyType::ED25519 {
+            let agreement = verkey.key_exchange()?.as_base58()?.fingerprint()?;
+            return Ok(format!("{}#{}", did, agreement));
+        }
+    }
+    Ok(format!("{}#{}", did, did.id))
+}
+
+/// Recover the base58 verkey from a did:key `kid`, checking that the
+/// fragment, if any, names the key or its key agreement key
+pub fn kid_to_verkey(kid: &str) -> Result<EncodedVerKey, ConversionError> {
+    let mut parts = kid.splitn(2, '#');
+    let did = DID::parse(parts.next().unwrap_or_default())?;
+    let verkey = resolve_verkey(&did)?.as_base58()?;
+    if let Some(fragment) = parts.next() {
+        let mut matched = fragment == did.id;
+        #[cfg(feature = "ed25519")]
+        {
+            if !matched && verkey.alg == KeyType::ED25519 {
+                matched = fragment == verkey.key_exchange()?.as_base58()?.fingerprint()?;
+            }
+        }
+        if !matched {
+            return Err(format!("Key identifier fragment does not match the DID: {}", kid).into());
+        }
+    }
+    Ok(verkey)
+}
+
+/// Resolve a did:key to its DID Document. Ed25519 keys are also listed for
+/// key agreement in their X25519 form.
+#[cfg(all(feature = "ed25519", feature = "serde_support"))]
+pub fn resolve_document(did: &DID) -> Result<DidDocument, ConversionError> {
+    let verkey = resolve_verkey(did)?;
+    let id = did.to_string();
+    let method = |key: &VerKey, type_: &str| -> Result<VerificationMethod, ConversionError> {
+        let fingerprint = key.as_base58()?.fingerprint()?;
+        Ok(VerificationMethod {
+            id: format!("{}#{}", id, fingerprint),
+            type_: type_.to_owned(),
+            controller: id.clone(),
+            public_key_multibase: fingerprint,
+        })
+    };
+
+    let mut doc = DidDocument::new(&id);
+    match verkey.alg {
+        KeyType::ED25519 => {
+            let signing = method(&verkey, ED25519_VERIFICATION_KEY_2020)?;
+            let agreement = method(&verkey.key_exchange()?, X25519_KEY_AGREEMENT_KEY_2020)?;
+            doc.context.push(ED25519_2020_CONTEXT.to_owned());
+            doc.context.push(X25519_2020_CONTEXT.to_owned());
+            doc.authentication.push(signing.id.clone());
+            doc.assertion_method.push(signing.id.clone());
+            doc.key_agreement.push(agreement.id.clone());
+            doc.verification_method.push(signing);
+            doc.verification_method.push(agreement);
+        }
+        KeyType::X25519 => {
+            let agreement = method(&verkey, X25519_KEY_AGREEMENT_KEY_2020)?;
+            doc.context.push(X25519_2020_CONTEXT.to_owned());
+            doc.key_agreement.push(agreement.id.clone());
+            doc.verification_method.push(agreement);
+        }
+        KeyType::P256 => {
+            let signing = method(&verkey, MULTIKEY)?;
+            doc.context.push(MULTIKEY_CONTEXT.to_owned());
+            doc.authentication.push(signing.id.clone());
+            doc.assertion_method.push(signing.id.clone());
+            doc.key_agreement.push(signing.id.clone());
+            doc.verification_method.push(signing);
+        }
+        KeyType::Bls12381G2 => {
+            let signing = method(&verkey, BLS12381_G2_KEY_2020)?;
+            doc.assertion_method.push(signing.id.clone());
+            doc.verification_method.push(signing);
+        }
+        _ => {
+            return Err(format!("Unsupported key type for did:key: {}", verkey.alg.as_str()).into())
+        }
+    }
+    Ok(doc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::{KeyEncoding, KeyType};
+
+    #[test]
+    fn did_key_round_trip() {
+        // did:key test vector for an Ed25519 key
+        let did = DID::parse("did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp").unwrap();
+        let verkey = resolve_verkey(&did).unwrap();
+        assert_eq!(verkey.alg, KeyType::ED25519);
+        assert_eq!(did_key(&verkey.as_base58().unwrap()).unwrap(), did);
+
+        for (alg, key) in &[
+            (KeyType::X25519, vec![9u8; 32]),
+            (KeyType::Bls12381G2, vec![7u8; 96]),
+        ] {
+            let vk = VerKey::new(key, Some(alg.clone()));
+            let did = did_key(&vk.encode(&KeyEncoding::MULTIBASE).unwrap()).unwrap();
+            assert_eq!(resolve_verkey(&did).unwrap(), vk);
+        }
+        assert!(resolve_verkey(&DID::parse("did:sov:VsKV7grR1BUE29mG2Fm2kX").unwrap()).is_err());
+    }
+
+    #[cfg(all(feature = "ed25519", feature = "serde_support"))]
+    #[test]
+    fn did_key_document() {
+        let did = DID::parse("did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp").unwrap();
+        let doc = resolve_document(&did).unwrap();
+        assert_eq!(
+            doc.verification_method[0].id,
+            "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp#z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp"
+        );
+        assert_eq!(
+            doc.key_agreement[0],
+            "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp#z6LSbysY2xFMRpGMhb7tFTLMpeuPRaqaWM1yECx2AtzE3KCc"
+        );
+    }
+
+    #[test]
+    fn kid_round_trip() {
+        let did = "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp";
+        let verkey =
+            EncodedVerKey::from_str("4zvwRjXUKGfvwnParsHAS3HuSVzV5cA4McphgmoCtajS").unwrap();
+        let kid = verkey_to_kid(&verkey).unwrap();
+        #[cfg(feature = "ed25519")]
+        assert_eq!(
+            kid,
+            format!("{}#z6LSbysY2xFMRpGMhb7tFTLMpeuPRaqaWM1yECx2AtzE3KCc", did)
+        );
+        assert_eq!(kid_to_verkey(&kid).unwrap(), verkey);
+        assert_eq!(kid_to_verkey(did).unwrap(), verkey);
+        assert_eq!(
+            kid_to_verkey(&format!("{}#{}", did, &did[8..])).unwrap(),
+            verkey
+        );
+        assert!(kid_to_verkey(&format!("{}#key-1", did)).is_err());
+        assert!(kid_to_verkey("did:sov:VsKV7grR1BUE29mG2Fm2kX#key-1").is_err());
+    }
+}
@@ -0,0 +1,321 @@
+//! Linking a DID to a web origin with a DID Configuration resource
+//! (DIF Well Known DID Configuration), using Domain Linkage Credentials in
+//! the JWT format.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::document::absolute_id;
+use super::DID;
+use crate::base64;
+use crate::error::ConversionError;
+use crate::jose::{sign_compact, verify_compact};
+use crate::keys::{PrivateKey, VerKey};
+use crate::ValidationError;
+
+pub const DID_CONFIGURATION_CONTEXT: &'static str =
+    "https://identity.foundation/.well-known/did-configuration/v1";
+pub const CREDENTIALS_CONTEXT_V1: &'static str = "https://www.w3.org/2018/credentials/v1";
+pub const VERIFIABLE_CREDENTIAL: &'static str = "VerifiableCredential";
+pub const DOMAIN_LINKAGE_CREDENTIAL: &'static str = "DomainLinkageCredential";
+/// The service type advertising the origins linked to a DID
+pub const LINKED_DOMAINS: &'static str = "LinkedDomains";
+/// The path of the DID Configuration resource relative to an origin
+pub const DID_CONFIGURATION_PATH: &'static str = "/.well-known/did-configuration.json";
+
+/// A DID Configuration resource listing Domain Linkage Credentials
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DidConfiguration {
+    #[serde(rename = "@context")]
+    pub context: String,
+    pub linked_dids: Vec<String>,
+}
+
+impl DidConfiguration {
+    pub fn new(linked_dids: Vec<String>) -> Self {
+        Self {
+            context: DID_CONFIGURATION_CONTEXT.to_owned(),
+            linked_dids,
+        }
+    }
+
+    pub fn from_json(json: &[u8]) -> Result<Self, ConversionError> {
+        serde_json::from_slice(json)
+            .map_err(|err| ConversionError::from_msg_err("Invalid DID Configuration", err))
+    }
+
+    /// Verify that the resource links the DID to the origin with at least
+    /// one valid Domain Linkage Credential signed by the verkey
+    pub fn verify(
+        &self,
+        did: &DID,
+        origin: &str,
+        verkey: &VerKey,
+        now: Option<u64>,
+    ) -> Result<(), ConversionError> {
+        let mut result = Err(format!("No Domain Linkage Credential found for {}", did).into());
+        for jwt in &self.linked_dids {
+            let claims = match decode_claims(jwt) {
+                Ok(claims) => claims,
+                Err(_) => continue,
+            };
+            if claims.iss != did.to_string() {
+                continue;
+            }
+            result = verify_domain_linkage(jwt, origin, verkey, now).map(|_| ());
+            if result.is_ok() {
+                break;
+            }
+        }
+        result
+    }
+}
+
+/// The claims of a Domain Linkage Credential in the JWT format
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct DomainLinkageClaims {
+    pub iss: String,
+    pub sub: String,
+    pub nbf: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exp: Option<u64>,
+    pub vc: DomainLinkageCredential,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DomainLinkageCredential {
+    #[serde(rename = "@context")]
+    pub context: Vec<String>,
+    pub issuer: String,
+    pub issuance_date: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiration_date: Option<String>,
+    #[serde(rename = "type")]
+    pub type_: Vec<String>,
+    pub credential_subject: LinkedOrigin,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct LinkedOrigin {
+    pub id: String,
+    pub origin: String,
+}
+
+/// The URL of the DID Configuration resource for an origin
+pub fn did_configuration_url(origin: &str) -> Result<String, ValidationError> {
+    Ok(format!(
+        "{}{}",
+        normalize_origin(origin)?,
+        DID_CONFIGURATION_PATH
+    ))
+}
+
+/// Create a Domain Linkage Credential linking a DID to an origin, signed by
+/// the key of the verification method `kid`. The credential is valid from
+/// `issued` (defaulting to the current time) until `expires`, if provided.
+pub fn create_domain_linkage(
+    did: &DID,
+    origin: &str,
+    key: &PrivateKey,
+    kid: &str,
+    issued: Option<u64>,
+    expires: Option<u64>,
+) -> Result<String, ConversionError> {
+    let origin = normalize_origin(origin)?;
+    let issued = issued.unwrap_or_else(current_time);
+    let did = did.to_string();
+    let claims = DomainLinkageClaims {
+        iss: did.clone(),
+        sub: did.clone(),
+        nbf: issued,
+        exp: expires,
+        vc: DomainLinkageCredential {
+            context: vec![
+                CREDENTIALS_CONTEXT_V1.to_owned(),
+                DID_CONFIGURATION_CONTEXT.to_owned(),
+            ],
+            issuer: did.clone(),
+            issuance_date: format_timestamp(issued),
+            expiration_date: expires.map(format_timestamp),
+            type_: vec![
+                VERIFIABLE_CREDENTIAL.to_owned(),
+                DOMAIN_LINKAGE_CREDENTIAL.to_owned(),
+            ],
+            credential_subject: LinkedOrigin {
+                id: did.clone(),
+                origin,
+            },
+        },
+    };
+    let claims = serde_json::to_vec(&claims)
+        .map_err(|err| ConversionError::from_msg_err("Error encoding credential", err))?;
+    sign_compact(&claims, key, Some(&absolute_id(&did, kid)))
+}
+
+/// Verify a Domain Linkage Credential against an origin and the verkey of
+/// the linked DID at time `now` (defaulting to the current time), returning
+/// the DID
+pub fn verify_domain_linkage(
+    jwt: &str,
+    origin: &str,
+    verkey: &VerKey,
+    now: Option<u64>,
+) -> Result<DID, ConversionError> {
+    let (header, payload) = verify_compact(jwt, verkey)?;
+    let claims: DomainLinkageClaims = serde_json::from_slice(&payload)
+        .map_err(|err| ConversionError::from_msg_err("Invalid Domain Linkage Credential", err))?;
+    let did = DID::parse(&claims.iss)?;
+    let subject = &claims.vc.credential_subject;
+    if claims.sub != claims.iss || claims.vc.issuer != claims.iss || subject.id != claims.iss {
+        return Err("Credential issuer and subject must be the linked DID".into());
+    }
+    let kid = header.kid.unwrap_or_default();
+    if kid.split('#').next() != Some(claims.iss.as_str()) {
+        return Err(format!("Key identifier does not belong to the DID: {}", kid).into());
+    }
+    if !claims
+        .vc
+        .type_
+        .iter()
+        .any(|type_| type_ == DOMAIN_LINKAGE_CREDENTIAL)
+    {
+        return Err("Expected a Domain Linkage Credential".into());
+    }
+    let origin = normalize_origin(origin)?;
+    if subject.origin != origin {
+        return Err(format!("Credential is not valid for origin: {}", origin).into());
+    }
+    let now = now.unwrap_or_else(current_time);
+    if now < claims.nbf || claims.exp.map(|exp| now >= exp).unwrap_or(false) {
+        return Err("Credential is not valid at this time".into());
+    }
+    Ok(did)
+}
+
+/// Decode the claims of a Domain Linkage Credential without verifying it
+fn decode_claims(jwt: &str) -> Result<DomainLinkageClaims, ConversionError> {
+    let payload = jwt
+        .split('.')
+        .nth(1)
+        .ok_or_else(|| ConversionError::from_msg("Invalid compact JWS"))?;
+    serde_json::from_slice(&base64::decode_urlsafe(payload)?)
+        .map_err(|err| ConversionError::from_msg_err("Invalid Domain Linkage Credential", err))
+}
+
+/// Origins are a scheme and host, with an optional port but no path
+fn normalize_origin(origin: &str) -> Result<String, ValidationError> {
+    let origin = origin.trim_end_matches('/');
+    let host = if origin.starts_with("https://") {
+        &origin[8..]
+    } else if origin.starts_with("http://") {
+        &origin[7..]
+    } else {
+        return Err(invalid!("Invalid origin: {}", origin));
+    };
+    if host.is_empty()
+        || host.contains(|c: char| c == '/' || c == '?' || c == '#' || c.is_whitespace())
+    {
+        return Err(invalid!("Invalid origin: {}", origin));
+    }
+    Ok(origin.to_ascii_lowercase())
+}
+
+fn current_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|dur| dur.as_secs())
+        .unwrap_or_default()
+}
+
+/// Format a Unix timestamp as an RFC 3339 date-time in UTC
+fn format_timestamp(timestamp: u64) -> String {
+    let (days, secs) = ((timestamp / 86400) as i64, timestamp % 86400);
+    // convert days since the epoch to a civil date in the proleptic Gregorian calendar
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::did::key::did_key;
+    use crate::keys::test_fixtures::{TEST1, TEST2};
+
+    const ISSUED: u64 = 1_609_459_200;
+    const EXPIRES: u64 = 1_924_991_999;
+
+    #[test]
+    fn timestamps() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(ISSUED), "2021-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(EXPIRES), "2030-12-31T23:59:59Z");
+        assert_eq!(format_timestamp(951_825_600), "2000-02-29T12:00:00Z");
+    }
+
+    #[test]
+    fn domain_linkage_round_trip() {
+        let did = did_key(&TEST1.encoded_verkey()).unwrap();
+        let kid = format!("#{}", did.id);
+        let jwt = create_domain_linkage(
+            &did,
+            "https://Example.com/",
+            &TEST1.private_key(),
+            &kid,
+            Some(ISSUED),
+            Some(EXPIRES),
+        )
+        .unwrap();
+        let claims = decode_claims(&jwt).unwrap();
+        let (header, _) = verify_compact(&jwt, &TEST1.verkey()).unwrap();
+        assert_eq!(header.kid.unwrap(), format!("{}{}", did, kid));
+        assert_eq!(claims.vc.credential_subject.origin, "https://example.com");
+        assert_eq!(
+            claims.vc.expiration_date.as_deref(),
+            Some("2030-12-31T23:59:59Z")
+        );
+
+        let vk = TEST1.verkey();
+        let now = Some(ISSUED + 1);
+        assert_eq!(
+            verify_domain_linkage(&jwt, "https://example.com", &vk, now).unwrap(),
+            did
+        );
+        assert!(verify_domain_linkage(&jwt, "https://example.org", &vk, now).is_err());
+        assert!(verify_domain_linkage(&jwt, "https://example.com", &TEST2.verkey(), now).is_err());
+        assert!(verify_domain_linkage(&jwt, "https://example.com", &vk, Some(EXPIRES)).is_err());
+
+        let config = DidConfiguration::new(vec![jwt]);
+        let json = serde_json::to_vec(&config).unwrap();
+        let config = DidConfiguration::from_json(&json).unwrap();
+        config
+            .verify(&did, "https://example.com", &vk, now)
+            .unwrap();
+        let other = did_key(&TEST2.encoded_verkey()).unwrap();
+        assert!(config
+            .verify(&other, "https://example.com", &TEST2.verkey(), now)
+            .is_err());
+
+        assert_eq!(
+            did_configuration_url("https://example.com").unwrap(),
+            "https://example.com/.well-known/did-configuration.json"
+        );
+        assert!(did_configuration_url("https://example.com/path").is_err());
+        assert!(did_configuration_url("example.com").is_err());
+    }
+}
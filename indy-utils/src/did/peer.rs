@@ -0,0 +1,391 @@
+//! Creation and resolution of did:peer identifiers using numeric algorithm 2
+//! (inline keys and services) and numeric algorithm 4 (hashed long-form
+//! documents)
+
+use serde_json::{Map, Value};
+use ursa::hash::sha2::{Digest, Sha256};
+
+use super::document::{
+    absolute_id, DidDocument, Service, VerificationMethod, ED25519_2020_CONTEXT,
+    ED25519_VERIFICATION_KEY_2020, MULTIKEY, MULTIKEY_CONTEXT, X25519_2020_CONTEXT,
+    X25519_KEY_AGREEMENT_KEY_2020,
+};
+use super::{DID, DID_METHOD_PEER};
+use crate::error::ConversionError;
+use crate::keys::{EncodedVerKey, KeyEncoding, KeyType, VerKey};
+use crate::{base58, base64};
+
+/// The multicodec prefix for JSON data, used to encode did:peer:4 documents
+const MULTICODEC_JSON: &[u8] = &[0x80, 0x04];
+/// The multihash prefix for a SHA-256 digest
+const MULTIHASH_SHA256: &[u8] = &[0x12, 0x20];
+
+/// Abbreviations applied to encoded did:peer:2 services
+const SERVICE_ABBREVIATIONS: &[(&str, &str)] = &[
+    ("type", "t"),
+    ("serviceEndpoint", "s"),
+    ("routingKeys", "r"),
+    ("accept", "a"),
+    ("DIDCommMessaging", "dm"),
+];
+
+/// The verification relationship of a key in a peer DID
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PeerPurpose {
+    Authentication,
+    AssertionMethod,
+    KeyAgreement,
+}
+
+impl PeerPurpose {
+    /// The did:peer:2 purpose code
+    pub fn code(&self) -> char {
+        match self {
+            Self::Authentication => 'V',
+            Self::AssertionMethod => 'A',
+            Self::KeyAgreement => 'E',
+        }
+    }
+
+    pub fn from_code(code: char) -> Option<Self> {
+        match code {
+            'V' => Some(Self::Authentication),
+            'A' => Some(Self::AssertionMethod),
+            'E' => Some(Self::KeyAgreement),
+            _ => None,
+        }
+    }
+}
+
+/// Create a did:peer:2 identifier encoding the keys and services inline
+pub fn create_peer2(
+    keys: &[(PeerPurpose, VerKey)],
+    services: &[Service],
+) -> Result<DID, ConversionError> {
+    let mut id = "2".to_owned();
+    for (purpose, key) in keys {
+        id.push('.');
+        id.push(purpose.code());
+        id.push_str(&key.encode(&KeyEncoding::MULTIBASE)?.key);
+    }
+    for service in services {
+        let service = serde_json::to_value(service)
+            .map_err(|err| ConversionError::from_msg_err("Error encoding service", err))?;
+        id.push_str(".S");
+        id.push_str(&base64::encode_urlsafe_nopad(
+            abbreviate(service, false).to_string(),
+        ));
+    }
+    Ok(DID::new(DID_METHOD_PEER, None, &id)?)
+}
+
+/// Create a did:peer:4 identifier for the keys and services, returning the
+/// long form, which embeds the document, and the short form
+pub fn create_peer4(
+    keys: &[(PeerPurpose, VerKey)],
+    services: &[Service],
+) -> Result<(DID, DID), ConversionError> {
+    let doc = assemble("", keys.iter().cloned(), services.to_vec())?;
+    let mut input = serde_json::to_value(&doc)
+        .map_err(|err| ConversionError::from_msg_err("Error encoding DID Document", err))?;
+    if let Some(input) = input.as_object_mut() {
+        input.remove("id");
+        if let Some(Value::Array(methods)) = input.get_mut("verificationMethod") {
+            for method in methods.iter_mut().filter_map(Value::as_object_mut) {
+                method.remove("controller");
+            }
+        }
+    }
+    let mut encoded = MULTICODEC_JSON.to_vec();
+    encoded.extend_from_slice(input.to_string().as_bytes());
+    let encoded = format!("z{}", base58::encode(encoded));
+    let hash = peer4_hash(&encoded);
+    Ok((
+        DID::new(DID_METHOD_PEER, None, &format!("4{}:{}", hash, encoded))?,
+        DID::new(DID_METHOD_PEER, None, &format!("4{}", hash))?,
+    ))
+}
+
+/// Resolve a did:peer:2 or long-form did:peer:4 identifier to its DID
+/// Document. Short-form did:peer:4 identifiers cannot be resolved without
+/// the long form.
+pub fn resolve_peer(did: &DID) -> Result<DidDocument, ConversionError> {
+    if did.method != DID_METHOD_PEER {
+        return Err(format!("Expected a did:peer, found did:{}", did.method).into());
+    }
+    match did.id.chars().next() {
+        Some('2') => resolve_peer2(did),
+        Some('4') => resolve_peer4(did),
+        _ => Err(format!("Unsupported did:peer numeric algorithm: {}", did).into()),
+    }
+}
+
+/// Get the short form of a long-form did:peer:4 identifier
+pub fn peer4_short_form(did: &DID) -> Result<DID, ConversionError> {
+    let (hash, _) = split_peer4(did)?;
+    Ok(DID::new(DID_METHOD_PEER, None, &format!("4{}", hash))?)
+}
+
+fn resolve_peer2(did: &DID) -> Result<DidDocument, ConversionError> {
+    let mut elements = did.id.split('.');
+    if elements.next() != Some("2") {
+        return Err(format!("Invalid did:peer:2: {}", did).into());
+    }
+    let mut keys = Vec::new();
+    let mut services = Vec::new();
+    for element in elements {
+        let mut chars = element.chars();
+        match chars.next() {
+            Some('S') => {
+                let service: Value = serde_json::from_slice(&base64::decode_urlsafe(
+                    chars.as_str(),
+                )?)
+                .map_err(|err| ConversionError::from_msg_err("Invalid did:peer:2 service", err))?;
+                let mut service = abbreviate(service, true);
+                if let Some(service) = service.as_object_mut() {
+                    if !service.contains_key("id") {
+                        let id = match services.len() {
+                            0 => "#service".to_owned(),
+                            n => format!("#service-{}", n),
+                        };
+                        service.insert("id".to_owned(), Value::String(id));
+                    }
+                }
+                services.push(serde_json::from_value(service).map_err(|err| {
+                    ConversionError::from_msg_err("Unsupported did:peer:2 service", err)
+                })?);
+            }
+            Some(code) => {
+                let purpose = PeerPurpose::from_code(code)
+                    .ok_or_else(|| format!("Unsupported did:peer:2 purpose code: {}", code))?;
+                let key = EncodedVerKey::from_multibase(chars.as_str())?.decode()?;
+                keys.push((purpose, key));
+            }
+            None => return Err(format!("Invalid did:peer:2: {}", did).into()),
+        }
+    }
+    assemble(&did.to_string(), keys.into_iter(), services)
+}
+
+fn resolve_peer4(did: &DID) -> Result<DidDocument, ConversionError> {
+    let (hash, encoded) = split_peer4(did)?;
+    let encoded =
+        encoded.ok_or("A short-form did:peer:4 cannot be resolved without its long form")?;
+    if peer4_hash(encoded) != hash {
+        return Err("The did:peer:4 hash does not match the encoded document".into());
+    }
+    let decoded = base58::decode(
+        encoded
+            .strip_prefix('z')
+            .ok_or("Invalid did:peer:4 document encoding")?,
+    )?;
+    if !decoded.starts_with(MULTICODEC_JSON) {
+        return Err("Invalid did:peer:4 document encoding".into());
+    }
+    let mut input: Map<String, Value> =
+        serde_json::from_slice(&decoded[MULTICODEC_JSON.len()..])
+            .map_err(|err| ConversionError::from_msg_err("Invalid did:peer:4 document", err))?;
+
+    // contextualize the input document
+    let id = did.to_string();
+    input.insert("id".to_owned(), Value::String(id.clone()));
+    input.insert(
+        "alsoKnownAs".to_owned(),
+        Value::Array(vec![Value::String(format!("did:peer:4{}", hash))]),
+    );
+    if let Some(Value::Array(methods)) = input.get_mut("verificationMethod") {
+        for method in methods.iter_mut().filter_map(Value::as_object_mut) {
+            method
+                .entry("controller")
+                .or_insert_with(|| Value::String(id.clone()));
+        }
+    }
+    let mut doc: DidDocument = serde_json::from_value(Value::Object(input))
+        .map_err(|err| ConversionError::from_msg_err("Invalid did:peer:4 document", err))?;
+    for method in doc.verification_method.iter_mut() {
+        method.id = absolute_id(&id, &method.id);
+    }
+    for reference in doc
+        .authentication
+        .iter_mut()
+        .chain(doc.assertion_method.iter_mut())
+        .chain(doc.key_agreement.iter_mut())
+    {
+        *reference = absolute_id(&id, reference);
+    }
+    for service in doc.service.iter_mut() {
+        service.id = absolute_id(&id, &service.id);
+    }
+    Ok(doc)
+}
+
+/// Build a DID Document listing each key as `#key-N` under its purpose
+fn assemble(
+    id: &str,
+    keys: impl Iterator<Item = (PeerPurpose, VerKey)>,
+    services: Vec<Service>,
+) -> Result<DidDocument, ConversionError> {
+    let mut doc = DidDocument::new(id);
+    for (purpose, key) in keys {
+        let (type_, context) = match key.alg {
+            KeyType::ED25519 => (ED25519_VERIFICATION_KEY_2020, ED25519_2020_CONTEXT),
+            KeyType::X25519 => (X25519_KEY_AGREEMENT_KEY_2020, X25519_2020_CONTEXT),
+            _ => (MULTIKEY, MULTIKEY_CONTEXT),
+        };
+        if !doc.context.iter().any(|ctx| ctx == context) {
+            doc.context.push(context.to_owned());
+        }
+        let method_id = absolute_id(id, &format!("#key-{}", doc.verification_method.len() + 1));
+        doc.verification_method.push(VerificationMethod {
+            id: method_id.clone(),
+            type_: type_.to_owned(),
+            controller: id.to_owned(),
+            public_key_multibase: key.encode(&KeyEncoding::MULTIBASE)?.key,
+        });
+        match purpose {
+            PeerPurpose::Authentication => doc.authentication.push(method_id),
+            PeerPurpose::AssertionMethod => doc.assertion_method.push(method_id),
+            PeerPurpose::KeyAgreement => doc.key_agreement.push(method_id),
+        }
+    }
+    for mut service in services {
+        service.id = absolute_id(id, &service.id);
+        doc.service.push(service);
+    }
+    Ok(doc)
+}
+
+/// Apply or reverse the did:peer:2 service abbreviations
+fn abbreviate(value: Value, expand: bool) -> Value {
+    let replace = |s: String| {
+        SERVICE_ABBREVIATIONS
+            .iter()
+            .find(|(long, short)| if expand { s == *short } else { s == *long })
+            .map(|(long, short)| (if expand { *long } else { *short }).to_owned())
+            .unwrap_or(s)
+    };
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .map(|(k, v)| {
+                    let v = match (k.as_str(), v) {
+                        ("type", Value::String(t)) | ("t", Value::String(t)) => {
+                            Value::String(replace(t))
+                        }
+                        (_, v) => abbreviate(v, expand),
+                    };
+                    (replace(k), v)
+                })
+                .collect(),
+        ),
+        Value::Array(items) => {
+            Value::Array(items.into_iter().map(|v| abbreviate(v, expand)).collect())
+        }
+        other => other,
+    }
+}
+
+fn peer4_hash(encoded: &str) -> String {
+    let mut hash = MULTIHASH_SHA256.to_vec();
+    hash.extend_from_slice(&Sha256::digest(encoded.as_bytes()));
+    format!("z{}", base58::encode(hash))
+}
+
+fn split_peer4(did: &DID) -> Result<(&str, Option<&str>), ConversionError> {
+    let id = did
+        .id
+        .strip_prefix('4')
+        .ok_or_else(|| format!("Invalid did:peer:4: {}", did))?;
+    Ok(match id.find(':') {
+        Some(pos) => (&id[..pos], Some(&id[pos + 1..])),
+        None => (id, None),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::test_fixtures::{TEST1, TEST2};
+
+    fn test_keys() -> Vec<(PeerPurpose, VerKey)> {
+        vec![
+            (PeerPurpose::Authentication, TEST1.verkey()),
+            (
+                PeerPurpose::KeyAgreement,
+                TEST2.verkey().key_exchange().unwrap(),
+            ),
+        ]
+    }
+
+    fn test_service() -> Service {
+        let mut service = Service::new("#didcomm", "DIDCommMessaging", "https://example.com");
+        service
+            .routing_keys
+            .push("did:example:mediator#key-1".to_owned());
+        service
+    }
+
+    #[test]
+    fn peer2_round_trip() {
+        let did = create_peer2(&test_keys(), &[test_service()]).unwrap();
+        assert!(did.id.starts_with("2.Vz6Mk"));
+        assert!(did.id.contains(".Ez6LS"));
+        assert_eq!(DID::parse(&did.to_string()).unwrap(), did);
+
+        let doc = resolve_peer(&did).unwrap();
+        assert_eq!(doc.id, did.to_string());
+        assert_eq!(doc.authentication, vec![format!("{}#key-1", did)]);
+        assert_eq!(doc.key_agreement, vec![format!("{}#key-2", did)]);
+        assert_eq!(
+            doc.verification_method("#key-1")
+                .unwrap()
+                .public_key()
+                .unwrap(),
+            TEST1.verkey()
+        );
+        assert_eq!(doc.service[0].id, format!("{}#didcomm", did));
+        assert_eq!(doc.service[0].type_, "DIDCommMessaging");
+        assert_eq!(doc.service[0].routing_keys, test_service().routing_keys);
+
+        // services without an identifier are assigned one
+        let service = base64::encode_urlsafe_nopad(r#"{"t":"dm","s":"https://example.com"}"#);
+        let did = DID::parse(&format!("did:peer:2.S{}.S{}", service, service)).unwrap();
+        let doc = resolve_peer(&did).unwrap();
+        assert_eq!(doc.service[0].id, format!("{}#service", did));
+        assert_eq!(doc.service[1].id, format!("{}#service-1", did));
+    }
+
+    #[test]
+    fn peer4_round_trip() {
+        let (long, short) = create_peer4(&test_keys(), &[test_service()]).unwrap();
+        assert!(short.id.starts_with("4zQm"));
+        assert_eq!(peer4_short_form(&long).unwrap(), short);
+        assert_eq!(DID::parse(&long.to_string()).unwrap(), long);
+
+        let doc = resolve_peer(&long).unwrap();
+        assert_eq!(doc.id, long.to_string());
+        assert_eq!(doc.also_known_as, vec![short.to_string()]);
+        assert_eq!(doc.verification_method[0].controller, long.to_string());
+        assert_eq!(
+            doc.verification_method("#key-2")
+                .unwrap()
+                .public_key()
+                .unwrap(),
+            TEST2.verkey().key_exchange().unwrap()
+        );
+        assert_eq!(doc.service[0].id, format!("{}#didcomm", long));
+        assert!(resolve_peer(&short).is_err());
+
+        // a document which does not match the hash is rejected
+        let (other, _) = create_peer4(&test_keys()[..1], &[]).unwrap();
+        let (_, encoded) = split_peer4(&other).unwrap();
+        let (hash, _) = split_peer4(&long).unwrap();
+        let tampered = DID::new(
+            DID_METHOD_PEER,
+            None,
+            &format!("4{}:{}", hash, encoded.unwrap()),
+        )
+        .unwrap();
+        assert!(resolve_peer(&tampered).is_err());
+    }
+}
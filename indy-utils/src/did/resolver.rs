@@ -0,0 +1,153 @@
+//! Resolution of DIDs to their DID Documents
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+
+use super::document::{absolute_id, DidDocument};
+use super::{key, peer, DID, DID_METHOD_KEY, DID_METHOD_PEER};
+use crate::error::ConversionError;
+use crate::keys::{KeyType, VerKey};
+
+pub type ResolveResult = Result<DidDocument, ConversionError>;
+
+/// The future returned by a `DidResolver`
+pub type ResolveFuture<'a> = Pin<Box<dyn Future<Output = ResolveResult> + Send + 'a>>;
+
+/// A trait for DID resolver implementations
+pub trait DidResolver: Send + Sync {
+    /// Resolve a DID to its DID Document
+    fn resolve<'a>(&'a self, did: &'a DID) -> ResolveFuture<'a>;
+}
+
+/// A `DidResolver` for did:key identifiers
+#[derive(Clone, Copy, Debug, Default)]
+pub struct KeyResolver;
+
+impl DidResolver for KeyResolver {
+    fn resolve<'a>(&'a self, did: &'a DID) -> ResolveFuture<'a> {
+        Box::pin(async move { key::resolve_document(did) })
+    }
+}
+
+/// A `DidResolver` for did:peer:2 and long-form did:peer:4 identifiers
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PeerResolver;
+
+impl DidResolver for PeerResolver {
+    fn resolve<'a>(&'a self, did: &'a DID) -> ResolveFuture<'a> {
+        Box::pin(async move { peer::resolve_peer(did) })
+    }
+}
+
+/// A `DidResolver` which selects a resolver by DID method. The did:key and
+/// did:peer methods are supported by default.
+pub struct UniversalResolver {
+    methods: HashMap<String, Box<dyn DidResolver>>,
+}
+
+impl UniversalResolver {
+    pub fn new() -> Self {
+        Self {
+            methods: HashMap::new(),
+        }
+        .method(DID_METHOD_KEY, KeyResolver)
+        .method(DID_METHOD_PEER, PeerResolver)
+    }
+
+    /// Add or replace the resolver for a DID method
+    pub fn method<R: DidResolver + 'static>(mut self, method: &str, resolver: R) -> Self {
+        self.methods.insert(method.to_owned(), Box::new(resolver));
+        self
+    }
+
+    /// Check whether a resolver is registered for a DID method
+    pub fn supports(&self, method: &str) -> bool {
+        self.methods.contains_key(method)
+    }
+}
+
+impl Default for UniversalResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DidResolver for UniversalResolver {
+    fn resolve<'a>(&'a self, did: &'a DID) -> ResolveFuture<'a> {
+        match self.methods.get(did.method.as_str()) {
+            Some(resolver) => resolver.resolve(did),
+            None => {
+                Box::pin(
+                    async move { Err(format!("Unsupported DID method: {}", did.method).into()) },
+                )
+            }
+        }
+    }
+}
+
+/// Collect the key agreement keys of a DID Document with their verification
+/// method identifiers. An X25519 key derived from an ed25519 verification
+/// method of the document is returned as the ed25519 key, so that it matches
+/// lookups by verkey.
+pub fn key_agreement_keys(doc: &DidDocument) -> Result<Vec<(String, VerKey)>, ConversionError> {
+    let mut signing = Vec::new();
+    for method in &doc.verification_method {
+        let key = method.public_key()?;
+        if key.alg == KeyType::ED25519 {
+            let agreement = key.key_exchange()?;
+            signing.push((key, agreement));
+        }
+    }
+    let mut keys = Vec::new();
+    for reference in &doc.key_agreement {
+        let method = doc
+            .verification_method(reference)
+            .ok_or_else(|| format!("Unknown verification method: {}", reference))?;
+        let key = method.public_key()?;
+        let key = signing
+            .iter()
+            .find(|(_, agreement)| *agreement == key)
+            .map(|(signing, _)| signing.clone())
+            .unwrap_or(key);
+        keys.push((absolute_id(&doc.id, &method.id), key));
+    }
+    Ok(keys)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::did::peer::{create_peer2, PeerPurpose};
+    use crate::keys::test_fixtures::TEST1;
+    use async_global_executor::block_on;
+
+    #[test]
+    fn resolve_by_method() {
+        let resolver = UniversalResolver::new();
+        let vk = TEST1.verkey();
+
+        let did = key::did_key(&TEST1.encoded_verkey()).unwrap();
+        let doc = block_on(resolver.resolve(&did)).unwrap();
+        let keys = key_agreement_keys(&doc).unwrap();
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].0, doc.key_agreement[0]);
+        assert_eq!(keys[0].1, vk);
+
+        let did = create_peer2(
+            &[
+                (PeerPurpose::Authentication, vk.clone()),
+                (PeerPurpose::KeyAgreement, vk.key_exchange().unwrap()),
+            ],
+            &[],
+        )
+        .unwrap();
+        let doc = block_on(resolver.resolve(&did)).unwrap();
+        let keys = key_agreement_keys(&doc).unwrap();
+        assert_eq!(keys, vec![(format!("{}#key-2", did), vk)]);
+
+        let did = DID::parse("did:sov:VsKV7grR1BUE29mG2Fm2kX").unwrap();
+        assert!(block_on(resolver.resolve(&did)).is_err());
+        assert!(!resolver.supports("sov"));
+    }
+}
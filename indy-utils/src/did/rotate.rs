@@ -0,0 +1,124 @@
+//! Signed DID rotation messages for the DID Rotate protocol (Aries RFC 0794).
+//! The new DID is attested by a JWS from the key of the DID being rotated.
+
+use super::DID;
+use crate::base64;
+use crate::keys::{PrivateKey, VerKey};
+use crate::pack::{
+    random_message_id, sign_message, verify_message, Attachment, AttachmentData, PackError,
+    UnpackError,
+};
+
+/// Message type of a DID Rotate `rotate` message
+pub const DID_ROTATE_TYPE: &'static str = "https://didcomm.org/did-rotate/1.0/rotate";
+
+/// The media type of the signed DID attachment
+const ROTATE_ATTACHMENT_MIME_TYPE: &'static str = "text/string";
+
+/// A `rotate` message announcing the new DID for a connection
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RotateMessage {
+    #[serde(rename = "@type")]
+    pub msg_type: String,
+    #[serde(rename = "@id")]
+    pub id: String,
+    pub to_did: DID,
+    #[serde(rename = "did_rotate~attach")]
+    pub did_rotate_attach: Attachment,
+}
+
+impl RotateMessage {
+    /// Create a `rotate` message for the new DID, signed with the key of
+    /// the DID being rotated
+    pub fn new(to_did: &DID, from_key: &PrivateKey) -> Result<Self, PackError> {
+        Ok(Self {
+            msg_type: DID_ROTATE_TYPE.to_owned(),
+            id: random_message_id(),
+            to_did: to_did.clone(),
+            did_rotate_attach: sign_rotation(to_did, from_key)?,
+        })
+    }
+
+    /// Verify the rotation against the verkey of the DID being rotated,
+    /// returning the new DID
+    pub fn verify(&self, from_verkey: &VerKey) -> Result<DID, UnpackError> {
+        let to_did = verify_rotation(&self.did_rotate_attach, from_verkey)?;
+        if to_did != self.to_did {
+            return Err(UnpackError::MalformedField("to_did".to_string()));
+        }
+        Ok(to_did)
+    }
+}
+
+/// Create the signed attachment attesting to a new DID
+pub fn sign_rotation(to_did: &DID, from_key: &PrivateKey) -> Result<Attachment, PackError> {
+    let jws = sign_message(to_did.to_string(), from_key)?;
+    Ok(Attachment {
+        id: random_message_id(),
+        mime_type: Some(ROTATE_ATTACHMENT_MIME_TYPE.to_owned()),
+        enc: None,
+        data: AttachmentData {
+            base64: Some(jws.payload.clone()),
+            jws: Some(jws),
+            ..Default::default()
+        },
+    })
+}
+
+/// Verify a signed DID rotation attachment against the verkey of the DID
+/// being rotated, returning the new DID
+pub fn verify_rotation(attachment: &Attachment, from_verkey: &VerKey) -> Result<DID, UnpackError> {
+    let jws = attachment
+        .data
+        .jws
+        .as_ref()
+        .ok_or_else(|| UnpackError::MalformedField("jws".to_string()))?;
+    let data = attachment
+        .data
+        .base64
+        .as_ref()
+        .ok_or_else(|| UnpackError::MalformedField("base64".to_string()))?;
+    let (payload, signer) = verify_message(jws)?;
+    if signer != from_verkey.as_base58().map_err(UnpackError::InvalidKey)? {
+        return Err(UnpackError::InvalidSignature);
+    }
+    if base64::decode_urlsafe(data).map_err(|_| UnpackError::MalformedBase64("base64"))? != payload
+    {
+        return Err(UnpackError::MalformedField("base64".to_string()));
+    }
+    let to_did = String::from_utf8(payload)
+        .map_err(|_| UnpackError::MalformedField("to_did".to_string()))?;
+    DID::parse(&to_did).map_err(|err| UnpackError::MalformedField(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::did::key::did_key;
+    use crate::keys::test_fixtures::{TEST1, TEST2};
+
+    #[test]
+    fn rotate_round_trip() {
+        let to_did = did_key(&TEST2.encoded_verkey()).unwrap();
+        let message = RotateMessage::new(&to_did, &TEST1.private_key()).unwrap();
+        let json = serde_json::to_value(&message).unwrap();
+        assert_eq!(json["@type"], DID_ROTATE_TYPE);
+        assert_eq!(json["to_did"], to_did.to_string());
+        assert_eq!(json["did_rotate~attach"]["mime-type"], "text/string");
+
+        let message: RotateMessage = serde_json::from_value(json).unwrap();
+        assert_eq!(message.verify(&TEST1.verkey()).unwrap(), to_did);
+        assert!(matches!(
+            message.verify(&TEST2.verkey()),
+            Err(UnpackError::InvalidSignature)
+        ));
+
+        // the announced DID must match the attested DID
+        let mut tampered = message.clone();
+        tampered.to_did = DID::from_verkey(&TEST1.encoded_verkey()).unwrap();
+        assert!(tampered.verify(&TEST1.verkey()).is_err());
+        let mut tampered = message;
+        tampered.did_rotate_attach.data.base64 = Some(base64::encode_urlsafe_nopad("did:sov:x"));
+        assert!(tampered.verify(&TEST1.verkey()).is_err());
+    }
+}
@@ -0,0 +1,243 @@
+//! Service endpoints listed in DID Documents and out-of-band invitations
+
+use std::convert::TryFrom;
+
+use super::DID;
+use crate::base58;
+use crate::{Validatable, ValidationError};
+
+/// The service type for DIDComm v1 endpoints
+pub const DID_COMMUNICATION: &'static str = "did-communication";
+/// The service type for DIDComm v2 endpoints
+pub const DIDCOMM_MESSAGING: &'static str = "DIDCommMessaging";
+
+/// A service endpoint listed in a DID Document
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Service {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    pub service_endpoint: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u32>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipient_keys: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub routing_keys: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accept: Vec<String>,
+}
+
+impl Service {
+    pub fn new(id: &str, type_: &str, service_endpoint: &str) -> Self {
+        Self {
+            id: id.to_owned(),
+            type_: type_.to_owned(),
+            service_endpoint: service_endpoint.to_owned(),
+            priority: None,
+            recipient_keys: Vec::new(),
+            routing_keys: Vec::new(),
+            accept: Vec::new(),
+        }
+    }
+}
+
+impl Validatable for Service {
+    fn validate(&self) -> Result<(), ValidationError> {
+        if self.id.is_empty() {
+            return Err(invalid!("Service identifier must not be empty"));
+        }
+        if self.type_.is_empty() {
+            return Err(invalid!("Service {}: type must not be empty", self.id));
+        }
+        validate_endpoint(&self.service_endpoint)?;
+        for key in self.recipient_keys.iter().chain(self.routing_keys.iter()) {
+            validate_key_reference(key)?;
+        }
+        Ok(())
+    }
+}
+
+/// A DIDComm service block, as listed in DID Documents and out-of-band
+/// invitations
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct DidCommService {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default)]
+    pub priority: u32,
+    pub recipient_keys: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub routing_keys: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accept: Vec<String>,
+    pub service_endpoint: String,
+}
+
+impl DidCommService {
+    /// Create a DIDComm v1 service block
+    pub fn new(id: &str, service_endpoint: &str) -> Self {
+        Self {
+            id: id.to_owned(),
+            type_: DID_COMMUNICATION.to_owned(),
+            priority: 0,
+            recipient_keys: Vec::new(),
+            routing_keys: Vec::new(),
+            accept: Vec::new(),
+            service_endpoint: service_endpoint.to_owned(),
+        }
+    }
+
+    /// Add a recipient key, as a did:key, a DID URL or a base58 verkey
+    pub fn recipient_key<K: Into<String>>(mut self, key: K) -> Self {
+        self.recipient_keys.push(key.into());
+        self
+    }
+
+    /// Add a mediator routing key, as a did:key, a DID URL or a base58 verkey
+    pub fn routing_key<K: Into<String>>(mut self, key: K) -> Self {
+        self.routing_keys.push(key.into());
+        self
+    }
+
+    /// Add an accepted media type profile, such as `didcomm/aip2;env=rfc19`
+    pub fn accept<A: Into<String>>(mut self, accept: A) -> Self {
+        self.accept.push(accept.into());
+        self
+    }
+
+    pub fn priority(mut self, priority: u32) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+impl Validatable for DidCommService {
+    fn validate(&self) -> Result<(), ValidationError> {
+        if self.type_ != DID_COMMUNICATION && self.type_ != DIDCOMM_MESSAGING {
+            return Err(invalid!("Unsupported DIDComm service type: {}", self.type_));
+        }
+        if self.recipient_keys.is_empty() && self.type_ == DID_COMMUNICATION {
+            return Err(invalid!(
+                "DIDComm service {} must list at least one recipient key",
+                self.id
+            ));
+        }
+        Service::from(self.clone()).validate()
+    }
+}
+
+impl From<DidCommService> for Service {
+    fn from(service: DidCommService) -> Self {
+        Self {
+            id: service.id,
+            type_: service.type_,
+            service_endpoint: service.service_endpoint,
+            priority: Some(service.priority),
+            recipient_keys: service.recipient_keys,
+            routing_keys: service.routing_keys,
+            accept: service.accept,
+        }
+    }
+}
+
+impl TryFrom<Service> for DidCommService {
+    type Error = ValidationError;
+
+    fn try_from(service: Service) -> Result<Self, Self::Error> {
+        let service = Self {
+            id: service.id,
+            type_: service.type_,
+            priority: service.priority.unwrap_or_default(),
+            recipient_keys: service.recipient_keys,
+            routing_keys: service.routing_keys,
+            accept: service.accept,
+            service_endpoint: service.service_endpoint,
+        };
+        service.validate()?;
+        Ok(service)
+    }
+}
+
+/// Endpoints must be absolute URIs, or DIDs for services reached through a
+/// mediator's DID
+fn validate_endpoint(endpoint: &str) -> Result<(), ValidationError> {
+    let scheme = endpoint
+        .find(':')
+        .map(|pos| &endpoint[..pos])
+        .filter(|scheme| {
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+        })
+        .ok_or_else(|| invalid!("Invalid service endpoint: {}", endpoint))?;
+    if scheme == "did" {
+        DID::parse(endpoint)?;
+    } else if endpoint.len() == scheme.len() + 1 || endpoint.contains(char::is_whitespace) {
+        return Err(invalid!("Invalid service endpoint: {}", endpoint));
+    }
+    Ok(())
+}
+
+/// Keys may be given as DID URLs, such as did:key identifiers with or
+/// without a fragment, or as legacy base58 verkeys
+fn validate_key_reference(key: &str) -> Result<(), ValidationError> {
+    if key.starts_with("did:") {
+        let did = key.split('#').next().unwrap_or_default();
+        DID::parse(did)?;
+    } else {
+        let decoded = base58::decode(key).map_err(ValidationError::from_msg)?;
+        if decoded.len() != 32 {
+            return Err(invalid!("Invalid service key: {}", key));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_KEY: &'static str = "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp";
+
+    #[test]
+    fn didcomm_service() {
+        let service = DidCommService::new("#inline", "https://example.com/endpoint")
+            .recipient_key(TEST_KEY)
+            .routing_key("GAnfrJJm6fJeh2mFcANEpuW4X7EVWJ5B2F7D7iC8ihpt")
+            .accept("didcomm/aip2;env=rfc19");
+        service.validate().unwrap();
+
+        let json = serde_json::to_value(&service).unwrap();
+        assert_eq!(json["type"], DID_COMMUNICATION);
+        assert_eq!(json["recipientKeys"][0], TEST_KEY);
+        assert_eq!(json["serviceEndpoint"], "https://example.com/endpoint");
+        assert_eq!(
+            serde_json::from_value::<DidCommService>(json).unwrap(),
+            service
+        );
+
+        let generic = Service::from(service.clone());
+        assert_eq!(generic.priority, Some(0));
+        assert_eq!(DidCommService::try_from(generic).unwrap(), service);
+    }
+
+    #[test]
+    fn invalid_services() {
+        let service = DidCommService::new("#inline", "https://example.com");
+        assert!(service.validate().is_err());
+        for endpoint in &["", "example.com", "https:", "did:invalid", "http://a b"] {
+            let service = DidCommService::new("#inline", endpoint).recipient_key(TEST_KEY);
+            assert!(service.validate().is_err(), "{}", endpoint);
+        }
+        let service = DidCommService::new("#inline", "ws://example.com").recipient_key("abc");
+        assert!(service.validate().is_err());
+        let service = DidCommService::new("#inline", "did:sov:VsKV7grR1BUE29mG2Fm2kX")
+            .recipient_key(format!("{}#key-1", TEST_KEY));
+        service.validate().unwrap();
+    }
+}
@@ -0,0 +1,151 @@
+//! Compact JWS signing and verification with Ed25519 keys (RFC 7515 and
+//! RFC 8037), including detached unencoded payloads (RFC 7797)
+
+use crate::base64;
+use crate::error::ConversionError;
+use crate::keys::{PrivateKey, VerKey};
+use crate::pack::jws::{create_signature, decode_protected, encode_protected, verify_signature};
+
+pub use crate::pack::jws::{JwsProtectedHeader as JwsHeader, JWS_ALG_EDDSA};
+
+/// The header parameter used to sign an unencoded payload
+const HEADER_B64: &'static str = "b64";
+
+impl JwsHeader {
+    fn is_encoded(&self) -> bool {
+        self.b64.unwrap_or(true)
+    }
+}
+
+/// Sign a payload, producing a compact JWS with an attached payload
+pub fn sign_compact(
+    payload: &[u8],
+    key: &PrivateKey,
+    kid: Option<&str>,
+) -> Result<String, ConversionError> {
+    let protected = eddsa_header(kid, false)?;
+    let payload = base64::encode_urlsafe_nopad(payload);
+    let signature = create_signature(key, &protected, payload.as_bytes())?;
+    Ok(format!("{}.{}.{}", protected, payload, signature))
+}
+
+/// Sign a payload without encoding it, producing a compact JWS with the
+/// payload detached (`b64: false`), as used for ledger endorsements and
+/// JSON-LD proofs
+pub fn sign_detached(
+    payload: &[u8],
+    key: &PrivateKey,
+    kid: Option<&str>,
+) -> Result<String, ConversionError> {
+    let protected = eddsa_header(kid, true)?;
+    let signature = create_signature(key, &protected, payload)?;
+    Ok(format!("{}..{}", protected, signature))
+}
+
+/// Verify a compact JWS with an attached payload, returning the protected
+/// header and the decoded payload
+pub fn verify_compact(jws: &str, verkey: &VerKey) -> Result<(JwsHeader, Vec<u8>), ConversionError> {
+    let (protected, payload, signature) = split_compact(jws)?;
+    let header = decode_header(protected)?;
+    if !header.is_encoded() {
+        return Err("Unencoded JWS payloads must be detached".into());
+    }
+    verify_signature(verkey, protected, payload.as_bytes(), signature)?;
+    Ok((header, base64::decode_urlsafe(payload)?))
+}
+
+/// Verify a compact JWS with a detached payload, returning the protected header
+pub fn verify_detached(
+    jws: &str,
+    payload: &[u8],
+    verkey: &VerKey,
+) -> Result<JwsHeader, ConversionError> {
+    let (protected, attached, signature) = split_compact(jws)?;
+    if !attached.is_empty() {
+        return Err("Expected a JWS with a detached payload".into());
+    }
+    let header = decode_header(protected)?;
+    if header.is_encoded() {
+        let payload = base64::encode_urlsafe_nopad(payload);
+        verify_signature(verkey, protected, payload.as_bytes(), signature)?;
+    } else {
+        verify_signature(verkey, protected, payload, signature)?;
+    }
+    Ok(header)
+}
+
+fn eddsa_header(kid: Option<&str>, unencoded: bool) -> Result<String, ConversionError> {
+    let header = JwsHeader {
+        alg: JWS_ALG_EDDSA.to_string(),
+        kid: kid.map(str::to_string),
+        b64: if unencoded { Some(false) } else { None },
+        crit: if unencoded {
+            Some(vec![HEADER_B64.to_string()])
+        } else {
+            None
+        },
+        ..Default::default()
+    };
+    encode_protected(&header)
+        .map_err(|err| ConversionError::from_msg_err("Error encoding JWS header", err))
+}
+
+fn decode_header(protected: &str) -> Result<JwsHeader, ConversionError> {
+    let header = decode_protected(protected)?;
+    // RFC 7797 requires that b64 is listed as critical when it is present
+    let crit = header.crit.as_deref().unwrap_or_default();
+    if crit.iter().any(|param| param != HEADER_B64) {
+        return Err("Unsupported critical JWS header parameter".into());
+    }
+    if header.b64.is_some() && !crit.iter().any(|param| param == HEADER_B64) {
+        return Err("The b64 JWS header parameter must be critical".into());
+    }
+    Ok(header)
+}
+
+fn split_compact(jws: &str) -> Result<(&str, &str, &str), ConversionError> {
+    let mut parts = jws.split('.');
+    match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(protected), Some(payload), Some(signature), None) => {
+            Ok((protected, payload, signature))
+        }
+        _ => Err("Invalid compact JWS".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::test_fixtures::{TEST1, TEST2};
+
+    #[test]
+    fn compact_round_trip() {
+        let sk = TEST1.private_key();
+        let vk = TEST1.verkey();
+        let jws = sign_compact(b"hello there", &sk, Some("key-1")).unwrap();
+        let (header, payload) = verify_compact(&jws, &vk).unwrap();
+        assert_eq!(header.kid.as_deref(), Some("key-1"));
+        assert_eq!(payload, b"hello there");
+        assert!(verify_compact(&jws, &TEST2.verkey()).is_err());
+
+        // an attached payload can also be verified in detached form
+        let mut parts = jws.split('.');
+        let (protected, _, signature) = (parts.next(), parts.next(), parts.next());
+        let detached = format!("{}..{}", protected.unwrap(), signature.unwrap());
+        verify_detached(&detached, b"hello there", &vk).unwrap();
+    }
+
+    #[test]
+    fn detached_unencoded_payload() {
+        let sk = TEST1.private_key();
+        let vk = TEST1.verkey();
+        let payload = br#"{"txn": "payload.with.dots"}"#;
+        let jws = sign_detached(payload, &sk, None).unwrap();
+        assert!(jws.contains(".."));
+        let header = verify_detached(&jws, payload, &vk).unwrap();
+        assert_eq!(header.b64, Some(false));
+        assert_eq!(header.crit, Some(vec!["b64".to_string()]));
+        assert!(verify_detached(&jws, b"other payload", &vk).is_err());
+        assert!(verify_compact(&jws, &vk).is_err());
+    }
+}
@@ -0,0 +1,1188 @@
+//! Conversion of JSON-LD 1.1 documents to RDF datasets, covering the
+//! features used by the verifiable credential contexts.
+//!
+//! Remote contexts are never fetched: they are resolved by a
+//! [`ContextLoader`], which includes the credentials and Ed25519 2020 suite
+//! contexts. Processing is strict, so terms, types and identifiers which do
+//! not expand to absolute IRIs are rejected rather than dropped, and every
+//! property of a signed document is covered by its signature.
+
+use std::collections::HashMap;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use crate::error::ConversionError;
+use crate::rdfc::{
+    self, Quad, Term, RDF_FIRST, RDF_LANG_STRING, RDF_NIL, RDF_REST, RDF_TYPE, XSD_BOOLEAN,
+    XSD_DOUBLE, XSD_INTEGER, XSD_STRING,
+};
+
+pub const CREDENTIALS_V1_URL: &'static str = "https://www.w3.org/2018/credentials/v1";
+pub const ED25519_2020_URL: &'static str = "https://w3id.org/security/suites/ed25519-2020/v1";
+
+/// The maximum nesting of remote contexts, which guards against cycles
+const MAX_REMOTE_CONTEXTS: usize = 16;
+
+const KEYWORDS: &[&str] = &[
+    "@base",
+    "@container",
+    "@context",
+    "@direction",
+    "@graph",
+    "@id",
+    "@import",
+    "@included",
+    "@index",
+    "@json",
+    "@language",
+    "@list",
+    "@nest",
+    "@none",
+    "@prefix",
+    "@propagate",
+    "@protected",
+    "@reverse",
+    "@set",
+    "@type",
+    "@value",
+    "@version",
+    "@vocab",
+];
+
+static ABSOLUTE_IRI: Lazy<Regex> = Lazy::new(|| Regex::new("^[a-zA-Z][a-zA-Z0-9+.-]*:").unwrap());
+static KEYWORD_FORM: Lazy<Regex> = Lazy::new(|| Regex::new("^@[a-zA-Z]+$").unwrap());
+
+/// The credential and presentation terms of the credentials v1 context. The
+/// signature suite terms of the published document are omitted, so
+/// documents using them are rejected rather than misread.
+const CREDENTIALS_V1: &'static str = r#"{
+  "@context": {
+    "@version": 1.1,
+    "@protected": true,
+    "id": "@id",
+    "type": "@type",
+    "VerifiableCredential": {
+      "@id": "https://www.w3.org/2018/credentials#VerifiableCredential",
+      "@context": {
+        "@version": 1.1,
+        "@protected": true,
+        "id": "@id",
+        "type": "@type",
+        "cred": "https://www.w3.org/2018/credentials#",
+        "sec": "https://w3id.org/security#",
+        "xsd": "http://www.w3.org/2001/XMLSchema#",
+        "credentialSchema": {
+          "@id": "cred:credentialSchema",
+          "@type": "@id",
+          "@context": {
+            "@version": 1.1,
+            "@protected": true,
+            "id": "@id",
+            "type": "@type",
+            "cred": "https://www.w3.org/2018/credentials#",
+            "JsonSchemaValidator2018": "cred:JsonSchemaValidator2018"
+          }
+        },
+        "credentialStatus": {"@id": "cred:credentialStatus", "@type": "@id"},
+        "credentialSubject": {"@id": "cred:credentialSubject", "@type": "@id"},
+        "evidence": {"@id": "cred:evidence", "@type": "@id"},
+        "expirationDate": {"@id": "cred:expirationDate", "@type": "xsd:dateTime"},
+        "holder": {"@id": "cred:holder", "@type": "@id"},
+        "issued": {"@id": "cred:issued", "@type": "xsd:dateTime"},
+        "issuer": {"@id": "cred:issuer", "@type": "@id"},
+        "issuanceDate": {"@id": "cred:issuanceDate", "@type": "xsd:dateTime"},
+        "proof": {"@id": "sec:proof", "@type": "@id", "@container": "@graph"},
+        "refreshService": {
+          "@id": "cred:refreshService",
+          "@type": "@id",
+          "@context": {
+            "@version": 1.1,
+            "@protected": true,
+            "id": "@id",
+            "type": "@type",
+            "cred": "https://www.w3.org/2018/credentials#",
+            "ManualRefreshService2018": "cred:ManualRefreshService2018"
+          }
+        },
+        "termsOfUse": {"@id": "cred:termsOfUse", "@type": "@id"},
+        "validFrom": {"@id": "cred:validFrom", "@type": "xsd:dateTime"},
+        "validUntil": {"@id": "cred:validUntil", "@type": "xsd:dateTime"}
+      }
+    },
+    "VerifiablePresentation": {
+      "@id": "https://www.w3.org/2018/credentials#VerifiablePresentation",
+      "@context": {
+        "@version": 1.1,
+        "@protected": true,
+        "id": "@id",
+        "type": "@type",
+        "cred": "https://www.w3.org/2018/credentials#",
+        "sec": "https://w3id.org/security#",
+        "holder": {"@id": "cred:holder", "@type": "@id"},
+        "proof": {"@id": "sec:proof", "@type": "@id", "@container": "@graph"},
+        "verifiableCredential": {"@id": "cred:verifiableCredential", "@type": "@id", "@container": "@graph"}
+      }
+    }
+  }
+}"#;
+
+const ED25519_2020: &'static str = r#"{
+  "@context": {
+    "id": "@id",
+    "type": "@type",
+    "@protected": true,
+    "proof": {
+      "@id": "https://w3id.org/security#proof",
+      "@type": "@id",
+      "@container": "@graph"
+    },
+    "Ed25519VerificationKey2020": {
+      "@id": "https://w3id.org/security#Ed25519VerificationKey2020",
+      "@context": {
+        "@protected": true,
+        "id": "@id",
+        "type": "@type",
+        "controller": {
+          "@id": "https://w3id.org/security#controller",
+          "@type": "@id"
+        },
+        "revoked": {
+          "@id": "https://w3id.org/security#revoked",
+          "@type": "http://www.w3.org/2001/XMLSchema#dateTime"
+        },
+        "publicKeyMultibase": {
+          "@id": "https://w3id.org/security#publicKeyMultibase",
+          "@type": "https://w3id.org/security#multibase"
+        }
+      }
+    },
+    "Ed25519Signature2020": {
+      "@id": "https://w3id.org/security#Ed25519Signature2020",
+      "@context": {
+        "@protected": true,
+        "id": "@id",
+        "type": "@type",
+        "challenge": "https://w3id.org/security#challenge",
+        "created": {
+          "@id": "http://purl.org/dc/terms/created",
+          "@type": "http://www.w3.org/2001/XMLSchema#dateTime"
+        },
+        "domain": "https://w3id.org/security#domain",
+        "expires": {
+          "@id": "https://w3id.org/security#expiration",
+          "@type": "http://www.w3.org/2001/XMLSchema#dateTime"
+        },
+        "nonce": "https://w3id.org/security#nonce",
+        "proofPurpose": {
+          "@id": "https://w3id.org/security#proofPurpose",
+          "@type": "@vocab",
+          "@context": {
+            "@protected": true,
+            "id": "@id",
+            "type": "@type",
+            "assertionMethod": {
+              "@id": "https://w3id.org/security#assertionMethod",
+              "@type": "@id",
+              "@container": "@set"
+            },
+            "authentication": {
+              "@id": "https://w3id.org/security#authenticationMethod",
+              "@type": "@id",
+              "@container": "@set"
+            },
+            "capabilityInvocation": {
+              "@id": "https://w3id.org/security#capabilityInvocationMethod",
+              "@type": "@id",
+              "@container": "@set"
+            },
+            "capabilityDelegation": {
+              "@id": "https://w3id.org/security#capabilityDelegationMethod",
+              "@type": "@id",
+              "@container": "@set"
+            },
+            "keyAgreement": {
+              "@id": "https://w3id.org/security#keyAgreementMethod",
+              "@type": "@id",
+              "@container": "@set"
+            }
+          }
+        },
+        "proofValue": {
+          "@id": "https://w3id.org/security#proofValue",
+          "@type": "https://w3id.org/security#multibase"
+        },
+        "verificationMethod": {
+          "@id": "https://w3id.org/security#verificationMethod",
+          "@type": "@id"
+        }
+      }
+    }
+  }
+}"#;
+
+/// Resolves the remote contexts referenced by JSON-LD documents from a set
+/// of preloaded context documents
+#[derive(Clone, Debug)]
+pub struct ContextLoader {
+    contexts: HashMap<String, Value>,
+}
+
+impl ContextLoader {
+    /// Create a loader without any contexts
+    pub fn empty() -> Self {
+        Self {
+            contexts: HashMap::new(),
+        }
+    }
+
+    /// Add a context document, which must contain a `@context` entry
+    pub fn with_context(mut self, url: &str, document: Value) -> Result<Self, ConversionError> {
+        if document.get("@context").is_none() {
+            return Err(format!("Context document has no @context: {}", url).into());
+        }
+        self.contexts.insert(url.to_owned(), document);
+        Ok(self)
+    }
+
+    fn load(&self, url: &str) -> Result<&Value, ConversionError> {
+        self.contexts
+            .get(url)
+            .map(|document| &document["@context"])
+            .ok_or_else(|| format!("Unknown remote context: {}", url).into())
+    }
+}
+
+impl Default for ContextLoader {
+    /// Create a loader for the credentials v1 and Ed25519 2020 suite contexts
+    fn default() -> Self {
+        let mut contexts = HashMap::new();
+        for (url, document) in &[
+            (CREDENTIALS_V1_URL, CREDENTIALS_V1),
+            (ED25519_2020_URL, ED25519_2020),
+        ] {
+            contexts.insert(
+                (*url).to_owned(),
+                serde_json::from_str(document).expect("Invalid bundled context"),
+            );
+        }
+        Self { contexts }
+    }
+}
+
+/// Convert a JSON-LD document to an RDF dataset
+pub fn to_rdf<T: Serialize>(
+    document: &T,
+    loader: &ContextLoader,
+) -> Result<Vec<Quad>, ConversionError> {
+    let document = serde_json::to_value(document)?;
+    let processor = Processor { loader };
+    let expanded = processor.expand(&Context::default(), None, &document)?;
+    let mut builder = RdfBuilder::default();
+    for node in as_array(expanded) {
+        builder.node(&node, &None)?;
+    }
+    Ok(builder.quads)
+}
+
+/// Convert a JSON-LD document to an RDF dataset and serialize it as
+/// canonical N-Quads (URDNA2015)
+pub fn canonicalize<T: Serialize>(
+    document: &T,
+    loader: &ContextLoader,
+) -> Result<String, ConversionError> {
+    rdfc::canonicalize(&to_rdf(document, loader)?)
+}
+
+fn is_keyword(value: &str) -> bool {
+    KEYWORDS.contains(&value)
+}
+
+fn is_absolute_iri(value: &str) -> bool {
+    ABSOLUTE_IRI.is_match(value)
+}
+
+fn as_array(value: Value) -> Vec<Value> {
+    match value {
+        Value::Null => vec![],
+        Value::Array(items) => items,
+        value => vec![value],
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct TermDefinition {
+    /// The expanded IRI or keyword, or `None` for a term explicitly mapped
+    /// to null
+    iri: Option<String>,
+    prefix: bool,
+    protected: bool,
+    type_mapping: Option<String>,
+    container: Vec<String>,
+    context: Option<Value>,
+    language: Option<Option<String>>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Context {
+    terms: HashMap<String, TermDefinition>,
+    vocab: Option<String>,
+    language: Option<String>,
+    /// The context to restore for nested nodes, when type-scoped
+    previous: Option<Box<Context>>,
+}
+
+impl Context {
+    fn term(&self, term: &str) -> Option<&TermDefinition> {
+        self.terms.get(term)
+    }
+
+    /// Expand a term, compact IRI or keyword to an IRI. Values which only
+    /// look like keywords, and terms mapped to null, are ignored.
+    fn expand_iri(&self, value: &str, vocab: bool) -> Option<String> {
+        if is_keyword(value) {
+            return Some(value.to_owned());
+        }
+        if KEYWORD_FORM.is_match(value) {
+            return None;
+        }
+        if vocab {
+            if let Some(definition) = self.term(value) {
+                return definition.iri.clone();
+            }
+        }
+        if let Some(idx) = value
+            .get(1..)
+            .and_then(|rest| rest.find(':'))
+            .map(|idx| idx + 1)
+        {
+            let (prefix, suffix) = (&value[..idx], &value[idx + 1..]);
+            if prefix == "_" || suffix.starts_with("//") {
+                return Some(value.to_owned());
+            }
+            if let Some(definition) = self.term(prefix) {
+                if let (Some(iri), true) = (&definition.iri, definition.prefix) {
+                    return Some(format!("{}{}", iri, suffix));
+                }
+            }
+            if is_absolute_iri(value) {
+                return Some(value.to_owned());
+            }
+        }
+        if vocab {
+            if let Some(vocab) = &self.vocab {
+                return Some(format!("{}{}", vocab, value));
+            }
+        }
+        Some(value.to_owned())
+    }
+
+    fn has_protected(&self) -> bool {
+        self.terms.values().any(|definition| definition.protected)
+    }
+}
+
+struct Processor<'l> {
+    loader: &'l ContextLoader,
+}
+
+impl Processor<'_> {
+    fn process_context(
+        &self,
+        active: &Context,
+        local: &Value,
+        propagate: bool,
+        override_protected: bool,
+        remote_depth: usize,
+    ) -> Result<Context, ConversionError> {
+        let mut result = active.clone();
+        let propagate = local
+            .get("@propagate")
+            .and_then(Value::as_bool)
+            .unwrap_or(propagate);
+        if !propagate && result.previous.is_none() {
+            result.previous = Some(Box::new(active.clone()));
+        }
+        let locals = match local {
+            Value::Array(items) => items.clone(),
+            local => vec![local.clone()],
+        };
+        for local in locals {
+            match local {
+                Value::Null => {
+                    if !override_protected && result.has_protected() {
+                        return Err("Invalid context nullification".into());
+                    }
+                    result = Context {
+                        previous: result.previous.take(),
+                        ..Context::default()
+                    };
+                }
+                Value::String(url) => {
+                    if remote_depth >= MAX_REMOTE_CONTEXTS {
+                        return Err("Context overflow".into());
+                    }
+                    let loaded = self.loader.load(&url)?;
+                    result = self.process_context(
+                        &result,
+                        loaded,
+                        true,
+                        override_protected,
+                        remote_depth + 1,
+                    )?;
+                }
+                Value::Object(local) => {
+                    self.process_local_context(&mut result, &local, override_protected)?;
+                }
+                _ => return Err("Invalid local context".into()),
+            }
+        }
+        Ok(result)
+    }
+
+    fn process_local_context(
+        &self,
+        result: &mut Context,
+        local: &Map<String, Value>,
+        override_protected: bool,
+    ) -> Result<(), ConversionError> {
+        match local.get("@version") {
+            None => (),
+            Some(version) if version.as_f64() == Some(1.1) => (),
+            Some(_) => return Err("Invalid @version value".into()),
+        }
+        for unsupported in &["@base", "@direction", "@import"] {
+            if local.contains_key(*unsupported) {
+                return Err(format!("Unsupported context entry: {}", unsupported).into());
+            }
+        }
+        match local.get("@vocab") {
+            None => (),
+            Some(Value::Null) => result.vocab = None,
+            Some(Value::String(vocab)) => {
+                let vocab = result
+                    .expand_iri(vocab, true)
+                    .filter(|vocab| is_absolute_iri(vocab))
+                    .ok_or("Invalid vocab mapping")?;
+                result.vocab = Some(vocab);
+            }
+            Some(_) => return Err("Invalid vocab mapping".into()),
+        }
+        match local.get("@language") {
+            None => (),
+            Some(Value::Null) => result.language = None,
+            Some(Value::String(language)) => result.language = Some(language.clone()),
+            Some(_) => return Err("Invalid default language".into()),
+        }
+        let protected = match local.get("@protected") {
+            None => false,
+            Some(Value::Bool(protected)) => *protected,
+            Some(_) => return Err("Invalid @protected value".into()),
+        };
+
+        let mut defined = HashMap::new();
+        for term in local.keys() {
+            if !matches!(
+                term.as_str(),
+                "@language" | "@propagate" | "@protected" | "@version" | "@vocab"
+            ) {
+                self.create_term(
+                    result,
+                    local,
+                    term,
+                    &mut defined,
+                    protected,
+                    override_protected,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Expand an IRI within a local context, first defining the terms it
+    /// depends on
+    fn expand_local_iri(
+        &self,
+        active: &mut Context,
+        local: &Map<String, Value>,
+        value: &str,
+        defined: &mut HashMap<String, bool>,
+        protected: bool,
+        override_protected: bool,
+    ) -> Result<Option<String>, ConversionError> {
+        if local.contains_key(value) && defined.get(value) != Some(&true) {
+            self.create_term(active, local, value, defined, protected, override_protected)?;
+        }
+        if let Some(idx) = value.get(1..).and_then(|rest| rest.find(':')) {
+            let prefix = &value[..idx + 1];
+            if local.contains_key(prefix) && defined.get(prefix) != Some(&true) {
+                self.create_term(
+                    active,
+                    local,
+                    prefix,
+                    defined,
+                    protected,
+                    override_protected,
+                )?;
+            }
+        }
+        Ok(active.expand_iri(value, true))
+    }
+
+    fn create_term(
+        &self,
+        active: &mut Context,
+        local: &Map<String, Value>,
+        term: &str,
+        defined: &mut HashMap<String, bool>,
+        protected: bool,
+        override_protected: bool,
+    ) -> Result<(), ConversionError> {
+        match defined.get(term) {
+            Some(true) => return Ok(()),
+            Some(false) => return Err(format!("Cyclic IRI mapping: {}", term).into()),
+            None => (),
+        }
+        defined.insert(term.to_owned(), false);
+        if is_keyword(term) {
+            return Err(format!("Keyword redefinition: {}", term).into());
+        }
+        if KEYWORD_FORM.is_match(term) {
+            defined.insert(term.to_owned(), true);
+            return Ok(());
+        }
+        let previous = active.terms.remove(term);
+
+        let (value, simple) = match &local[term] {
+            Value::Null => {
+                let mut value = Map::new();
+                value.insert("@id".to_owned(), Value::Null);
+                (value, false)
+            }
+            Value::String(id) => {
+                let mut value = Map::new();
+                value.insert("@id".to_owned(), Value::String(id.clone()));
+                (value, true)
+            }
+            Value::Object(value) => (value.clone(), false),
+            _ => return Err(format!("Invalid term definition: {}", term).into()),
+        };
+        for key in value.keys() {
+            if !matches!(
+                key.as_str(),
+                "@id"
+                    | "@type"
+                    | "@language"
+                    | "@context"
+                    | "@container"
+                    | "@protected"
+                    | "@prefix"
+            ) {
+                return Err(format!("Unsupported term definition entry: {}", key).into());
+            }
+        }
+
+        let mut definition = TermDefinition {
+            iri: None,
+            prefix: false,
+            protected: match value.get("@protected") {
+                None => protected,
+                Some(Value::Bool(protected)) => *protected,
+                Some(_) => return Err("Invalid @protected value".into()),
+            },
+            type_mapping: None,
+            container: vec![],
+            context: value.get("@context").cloned(),
+            language: match value.get("@language") {
+                None => None,
+                Some(Value::Null) => Some(None),
+                Some(Value::String(language)) => Some(Some(language.clone())),
+                Some(_) => return Err("Invalid language mapping".into()),
+            },
+        };
+
+        if let Some(type_) = value.get("@type") {
+            let type_ = type_.as_str().ok_or("Invalid type mapping")?;
+            let type_ = self
+                .expand_local_iri(active, local, type_, defined, protected, override_protected)?
+                .filter(|type_| {
+                    matches!(type_.as_str(), "@id" | "@vocab" | "@none") || is_absolute_iri(type_)
+                })
+                .ok_or_else(|| format!("Invalid type mapping: {}", type_))?;
+            definition.type_mapping = Some(type_);
+        }
+
+        match value.get("@id") {
+            Some(Value::Null) => (),
+            Some(Value::String(id)) if id != term => {
+                if !is_keyword(id) && KEYWORD_FORM.is_match(id) {
+                    defined.insert(term.to_owned(), true);
+                    return Ok(());
+                }
+                let iri = self
+                    .expand_local_iri(active, local, id, defined, protected, override_protected)?
+                    .filter(|iri| {
+                        (is_keyword(iri) && iri != "@context")
+                            || iri.starts_with("_:")
+                            || is_absolute_iri(iri)
+                    })
+                    .ok_or_else(|| format!("Invalid IRI mapping: {}", term))?;
+                definition.prefix = simple
+                    && !term.contains(':')
+                    && !term.contains('/')
+                    && (iri.starts_with("_:")
+                        || iri.ends_with(&[':', '/', '?', '#', '[', ']', '@'][..]));
+                definition.iri = Some(iri);
+            }
+            Some(Value::String(_)) | None => {
+                if let Some(idx) = term.get(1..).and_then(|rest| rest.find(':')) {
+                    let prefix = &term[..idx + 1];
+                    if local.contains_key(prefix) {
+                        self.create_term(
+                            active,
+                            local,
+                            prefix,
+                            defined,
+                            protected,
+                            override_protected,
+                        )?;
+                    }
+                    definition.iri = active.expand_iri(term, true);
+                } else if term.contains('/') {
+                    return Err(format!("Unsupported relative IRI term: {}", term).into());
+                } else if let Some(vocab) = &active.vocab {
+                    definition.iri = Some(format!("{}{}", vocab, term));
+                } else {
+                    return Err(format!("Invalid IRI mapping: {}", term).into());
+                }
+            }
+            Some(_) => return Err(format!("Invalid IRI mapping: {}", term).into()),
+        }
+
+        if let Some(container) = value.get("@container") {
+            let container = match container {
+                Value::String(container) => vec![container.clone()],
+                Value::Array(items) => items
+                    .iter()
+                    .map(|item| item.as_str().map(str::to_owned))
+                    .collect::<Option<Vec<_>>>()
+                    .ok_or("Invalid container mapping")?,
+                _ => return Err("Invalid container mapping".into()),
+            };
+            for entry in &container {
+                if !matches!(entry.as_str(), "@graph" | "@list" | "@set") {
+                    return Err(format!("Unsupported container mapping: {}", entry).into());
+                }
+            }
+            definition.container = container;
+        }
+        if let Some(prefix) = value.get("@prefix") {
+            definition.prefix = prefix.as_bool().ok_or("Invalid @prefix value")?;
+        }
+
+        if let Some(previous) = previous.filter(|previous| previous.protected) {
+            if !override_protected {
+                let mut compare = definition.clone();
+                compare.protected = true;
+                if compare != previous {
+                    return Err(format!("Protected term redefinition: {}", term).into());
+                }
+                definition = previous;
+            }
+        }
+        active.terms.insert(term.to_owned(), definition);
+        defined.insert(term.to_owned(), true);
+        Ok(())
+    }
+
+    fn expand(
+        &self,
+        active: &Context,
+        property: Option<&str>,
+        element: &Value,
+    ) -> Result<Value, ConversionError> {
+        let definition = property.and_then(|property| active.term(property));
+        let scoped_context = definition.and_then(|definition| definition.context.as_ref());
+
+        match element {
+            Value::Null => Ok(Value::Null),
+            Value::Array(items) => {
+                let list = definition
+                    .map(|def| def.container.iter().any(|c| c == "@list"))
+                    .unwrap_or(false);
+                let mut result = vec![];
+                for item in items {
+                    let mut expanded = self.expand(active, property, item)?;
+                    if list && expanded.is_array() {
+                        expanded = json!({ "@list": expanded });
+                    }
+                    match expanded {
+                        Value::Null => (),
+                        Value::Array(expanded) => result.extend(expanded),
+                        expanded => result.push(expanded),
+                    }
+                }
+                Ok(Value::Array(result))
+            }
+            Value::Object(element) => {
+                let mut active = active.clone();
+                if active.previous.is_some() {
+                    let expands_to = |key: &String, keyword: &str| {
+                        active.expand_iri(key, true).as_deref() == Some(keyword)
+                    };
+                    let value_object = element.keys().any(|key| expands_to(key, "@value"));
+                    let reference =
+                        element.len() == 1 && element.keys().all(|key| expands_to(key, "@id"));
+                    if !value_object && !reference {
+                        active = *active.previous.take().unwrap_or_default();
+                    }
+                }
+                if let Some(scoped_context) = scoped_context {
+                    active = self.process_context(&active, scoped_context, true, true, 0)?;
+                }
+                if let Some(local) = element.get("@context") {
+                    active = self.process_context(&active, local, true, false, 0)?;
+                }
+                self.expand_object(active, property, element)
+            }
+            scalar => {
+                if property.is_none() || property == Some("@graph") {
+                    return Err("Free-floating values are not supported".into());
+                }
+                let mut active = active.clone();
+                if let Some(scoped_context) = scoped_context {
+                    active = self.process_context(&active, scoped_context, true, true, 0)?;
+                }
+                self.expand_value(&active, property.unwrap_or_default(), scalar)
+            }
+        }
+    }
+
+    fn expand_object(
+        &self,
+        mut active: Context,
+        property: Option<&str>,
+        element: &Map<String, Value>,
+    ) -> Result<Value, ConversionError> {
+        let type_scoped = active.clone();
+        let mut keys = element.keys().collect::<Vec<_>>();
+        keys.sort();
+        for key in &keys {
+            if active.expand_iri(key, true).as_deref() == Some("@type") {
+                let mut types = as_array(element[key.as_str()].clone())
+                    .iter()
+                    .filter_map(|type_| type_.as_str().map(str::to_owned))
+                    .collect::<Vec<_>>();
+                types.sort();
+                for type_ in types {
+                    if let Some(context) = type_scoped
+                        .term(&type_)
+                        .and_then(|definition| definition.context.as_ref())
+                    {
+                        active = self.process_context(&active, context, false, false, 0)?;
+                    }
+                }
+            }
+        }
+
+        let mut result = Map::new();
+        for key in keys {
+            if key == "@context" {
+                continue;
+            }
+            let value = &element[key.as_str()];
+            let expanded_key = active
+                .expand_iri(key, true)
+                .filter(|iri| is_keyword(iri) || is_absolute_iri(iri))
+                .ok_or_else(|| format!("Undefined term: {}", key))?;
+
+            if is_keyword(&expanded_key) {
+                let expanded = match expanded_key.as_str() {
+                    "@id" => {
+                        let id = value.as_str().ok_or("Invalid @id value")?;
+                        Value::String(expand_id(&active, id, false)?)
+                    }
+                    "@type" => {
+                        let types = as_array(value.clone())
+                            .iter()
+                            .map(|type_| {
+                                let type_ = type_.as_str().ok_or("Invalid type value")?;
+                                expand_id(&type_scoped, type_, true).map(Value::String)
+                            })
+                            .collect::<Result<Vec<_>, ConversionError>>()?;
+                        Value::Array(types)
+                    }
+                    "@graph" => {
+                        Value::Array(as_array(self.expand(&active, Some("@graph"), value)?))
+                    }
+                    "@value" => {
+                        if value.is_array() || value.is_object() {
+                            return Err("Invalid value object value".into());
+                        }
+                        value.clone()
+                    }
+                    "@language" => Value::String(
+                        value
+                            .as_str()
+                            .ok_or("Invalid language-tagged string")?
+                            .to_owned(),
+                    ),
+                    "@index" => {
+                        Value::String(value.as_str().ok_or("Invalid @index value")?.to_owned())
+                    }
+                    "@list" => {
+                        if property.is_none() || property == Some("@graph") {
+                            return Err("Free-floating lists are not supported".into());
+                        }
+                        Value::Array(as_array(self.expand(&active, property, value)?))
+                    }
+                    "@set" => self.expand(&active, property, value)?,
+                    other => return Err(format!("Unsupported keyword: {}", other).into()),
+                };
+                result.insert(expanded_key, expanded);
+                continue;
+            }
+
+            let container = active
+                .term(key)
+                .map(|definition| definition.container.clone())
+                .unwrap_or_default();
+            let mut expanded = self.expand(&active, Some(key.as_str()), value)?;
+            if expanded.is_null() {
+                continue;
+            }
+            if container.iter().any(|c| c == "@list") && expanded.get("@list").is_none() {
+                expanded = json!({ "@list": as_array(expanded) });
+            }
+            if container.iter().any(|c| c == "@graph") {
+                expanded = Value::Array(
+                    as_array(expanded)
+                        .into_iter()
+                        .map(|item| json!({ "@graph": as_array(item) }))
+                        .collect(),
+                );
+            }
+            let entry = result
+                .entry(expanded_key)
+                .or_insert_with(|| Value::Array(vec![]));
+            if let Value::Array(items) = entry {
+                items.extend(as_array(expanded));
+            }
+        }
+
+        if let Some(value) = result.get("@value") {
+            if result
+                .keys()
+                .any(|key| !matches!(key.as_str(), "@value" | "@type" | "@language" | "@index"))
+            {
+                return Err("Invalid value object".into());
+            }
+            if value.is_null() {
+                return Ok(Value::Null);
+            }
+            if result.contains_key("@language") && !value.is_string() {
+                return Err("Invalid language-tagged value".into());
+            }
+            if let Some(types) = result.get_mut("@type") {
+                let type_ = match types.as_array().map(Vec::as_slice) {
+                    Some([type_]) => type_.clone(),
+                    _ => return Err("Invalid typed value".into()),
+                };
+                *types = type_;
+            }
+        } else if let Some(set) = result.remove("@set") {
+            return Ok(set);
+        } else if result.contains_key("@list") && result.len() > 1 {
+            return Err("Invalid list object".into());
+        }
+        if result.len() == 1 && result.contains_key("@language") {
+            return Ok(Value::Null);
+        }
+        if property.is_none() || property == Some("@graph") {
+            // drop free-floating values and node references
+            if result.is_empty()
+                || result.contains_key("@value")
+                || result.contains_key("@list")
+                || (result.len() == 1 && result.contains_key("@id"))
+            {
+                return Ok(Value::Null);
+            }
+        }
+        if property.is_none() {
+            if let (1, Some(graph)) = (result.len(), result.get("@graph")) {
+                return Ok(graph.clone());
+            }
+        }
+        Ok(Value::Object(result))
+    }
+
+    fn expand_value(
+        &self,
+        active: &Context,
+        property: &str,
+        value: &Value,
+    ) -> Result<Value, ConversionError> {
+        let definition = active.term(property);
+        let type_mapping = definition.and_then(|definition| definition.type_mapping.as_deref());
+        if let (Some(id), Some(mapping)) = (value.as_str(), type_mapping) {
+            if mapping == "@id" || mapping == "@vocab" {
+                return Ok(json!({ "@id": expand_id(active, id, mapping == "@vocab")? }));
+            }
+        }
+        let mut result = Map::new();
+        result.insert("@value".to_owned(), value.clone());
+        match type_mapping {
+            Some("@id") | Some("@vocab") | Some("@none") | None => {
+                if value.is_string() {
+                    let language = match definition.and_then(|def| def.language.as_ref()) {
+                        Some(language) => language.as_ref(),
+                        None => active.language.as_ref(),
+                    };
+                    if let Some(language) = language {
+                        result.insert("@language".to_owned(), Value::String(language.clone()));
+                    }
+                }
+            }
+            Some(type_) => {
+                result.insert("@type".to_owned(), Value::String(type_.to_owned()));
+            }
+        }
+        Ok(Value::Object(result))
+    }
+}
+
+/// Expand a node identifier or type, which must resolve to an absolute IRI
+/// or blank node identifier as documents have no base IRI
+fn expand_id(active: &Context, value: &str, vocab: bool) -> Result<String, ConversionError> {
+    active
+        .expand_iri(value, vocab)
+        .filter(|iri| iri.starts_with("_:") || is_absolute_iri(iri))
+        .ok_or_else(|| format!("Not an absolute IRI: {}", value).into())
+}
+
+#[derive(Default)]
+struct RdfBuilder {
+    quads: Vec<Quad>,
+    /// Blank nodes allocated for the blank node identifiers of the document
+    blank_ids: HashMap<String, Term>,
+    blank_count: usize,
+}
+
+impl RdfBuilder {
+    fn new_blank(&mut self) -> Term {
+        let blank = Term::Blank(format!("b{}", self.blank_count));
+        self.blank_count += 1;
+        blank
+    }
+
+    fn id_term(&mut self, id: &str) -> Term {
+        if !id.starts_with("_:") {
+            return Term::iri(id);
+        }
+        if let Some(blank) = self.blank_ids.get(id) {
+            return blank.clone();
+        }
+        let blank = self.new_blank();
+        self.blank_ids.insert(id.to_owned(), blank.clone());
+        blank
+    }
+
+    fn push(&mut self, subject: &Term, predicate: &str, object: Term, graph: &Option<Term>) {
+        self.quads.push(Quad {
+            subject: subject.clone(),
+            predicate: Term::iri(predicate),
+            object,
+            graph: graph.clone(),
+        });
+    }
+
+    fn node(&mut self, node: &Value, graph: &Option<Term>) -> Result<Term, ConversionError> {
+        let node = node.as_object().ok_or("Expected a node object")?;
+        let subject = match node.get("@id").and_then(Value::as_str) {
+            Some(id) => self.id_term(id),
+            None => self.new_blank(),
+        };
+        for type_ in node
+            .get("@type")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            let object = self.id_term(type_.as_str().unwrap_or_default());
+            self.push(&subject, RDF_TYPE, object, graph);
+        }
+        for (property, values) in node {
+            if property.starts_with('@') || property.starts_with("_:") {
+                continue;
+            }
+            for value in values.as_array().into_iter().flatten() {
+                let object = self.object(value, graph)?;
+                self.push(&subject, property, object, graph);
+            }
+        }
+        if let Some(items) = node.get("@graph").and_then(Value::as_array) {
+            let graph = Some(subject.clone());
+            for item in items {
+                self.node(item, &graph)?;
+            }
+        }
+        Ok(subject)
+    }
+
+    fn object(&mut self, value: &Value, graph: &Option<Term>) -> Result<Term, ConversionError> {
+        if let Some(literal) = value.get("@value") {
+            return literal_term(
+                literal,
+                value.get("@type").and_then(Value::as_str),
+                value.get("@language").and_then(Value::as_str),
+            );
+        }
+        if let Some(items) = value.get("@list").and_then(Value::as_array) {
+            let mut head = Term::iri(RDF_NIL);
+            for item in items.iter().rev() {
+                let object = self.object(item, graph)?;
+                let node = self.new_blank();
+                self.push(&node, RDF_FIRST, object, graph);
+                self.push(&node, RDF_REST, head, graph);
+                head = node;
+            }
+            return Ok(head);
+        }
+        self.node(value, graph)
+    }
+}
+
+fn literal_term(
+    value: &Value,
+    datatype: Option<&str>,
+    language: Option<&str>,
+) -> Result<Term, ConversionError> {
+    let (value, default_type) = match value {
+        Value::Bool(value) => (value.to_string(), XSD_BOOLEAN),
+        Value::Number(number) => {
+            let float = number.as_f64().ok_or("Invalid number value")?;
+            let integral =
+                number.is_i64() || number.is_u64() || (float.fract() == 0.0 && float.abs() < 1e21);
+            if integral && datatype != Some(XSD_DOUBLE) {
+                let value = match (number.as_i64(), number.as_u64()) {
+                    (Some(value), _) => value.to_string(),
+                    (_, Some(value)) => value.to_string(),
+                    _ => format!("{:.0}", float),
+                };
+                (value, XSD_INTEGER)
+            } else {
+                // canonical xsd:double, with at least one fractional digit
+                let formatted = format!("{:E}", float);
+                let value = match formatted.find('E') {
+                    Some(idx) if !formatted[..idx].contains('.') => {
+                        format!("{}.0{}", &formatted[..idx], &formatted[idx..])
+                    }
+                    _ => formatted,
+                };
+                (value, XSD_DOUBLE)
+            }
+        }
+        Value::String(value) => {
+            if let Some(language) = language {
+                return Ok(Term::Literal {
+                    value: value.clone(),
+                    datatype: RDF_LANG_STRING.to_owned(),
+                    language: Some(language.to_owned()),
+                });
+            }
+            (value.clone(), XSD_STRING)
+        }
+        _ => return Err("Invalid value object value".into()),
+    };
+    Ok(Term::typed(value, datatype.unwrap_or(default_type)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_proof_options() {
+        let options = json!({
+            "@context": [CREDENTIALS_V1_URL, ED25519_2020_URL],
+            "type": "Ed25519Signature2020",
+            "created": "2021-01-01T00:00:00Z",
+            "verificationMethod": "did:example:issuer#key-1",
+            "proofPurpose": "assertionMethod"
+        });
+        assert_eq!(
+            canonicalize(&options, &ContextLoader::default()).unwrap(),
+            "_:c14n0 <http://purl.org/dc/terms/created> \"2021-01-01T00:00:00Z\"^^<http://www.w3.org/2001/XMLSchema#dateTime> .\n\
+             _:c14n0 <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <https://w3id.org/security#Ed25519Signature2020> .\n\
+             _:c14n0 <https://w3id.org/security#proofPurpose> <https://w3id.org/security#assertionMethod> .\n\
+             _:c14n0 <https://w3id.org/security#verificationMethod> <did:example:issuer#key-1> .\n"
+        );
+    }
+
+    #[test]
+    fn expand_graph_containers() {
+        let loader = ContextLoader::default()
+            .with_context(
+                "https://example.com/v1",
+                json!({"@context": {"name": "https://schema.org/name"}}),
+            )
+            .unwrap();
+        let credential = json!({
+            "@context": [CREDENTIALS_V1_URL, "https://example.com/v1", ED25519_2020_URL],
+            "id": "urn:uuid:1",
+            "type": "VerifiableCredential",
+            "issuer": "did:example:issuer",
+            "issuanceDate": "2021-01-01T00:00:00Z",
+            "credentialSubject": {"name": "Alice"},
+            "proof": {
+                "type": "Ed25519Signature2020",
+                "proofValue": "z1"
+            }
+        });
+        let quads = to_rdf(&credential, &loader).unwrap();
+        let subject = Term::iri("urn:uuid:1");
+        let proof = quads
+            .iter()
+            .find(|quad| quad.predicate == Term::iri("https://w3id.org/security#proof"))
+            .unwrap();
+        assert_eq!(proof.subject, subject);
+        // the proof node is held in the named graph referenced by the property
+        assert!(quads.iter().any(|quad| {
+            quad.graph.as_ref() == Some(&proof.object)
+                && quad.predicate == Term::iri("https://w3id.org/security#proofValue")
+                && quad.object == Term::typed("z1", "https://w3id.org/security#multibase")
+        }));
+        assert!(quads.iter().any(|quad| {
+            quad.graph.is_none()
+                && quad.predicate == Term::iri("https://schema.org/name")
+                && quad.object == Term::typed("Alice", XSD_STRING)
+        }));
+    }
+
+    #[test]
+    fn reject_undefined_terms() {
+        let document = json!({
+            "@context": CREDENTIALS_V1_URL,
+            "type": "VerifiableCredential",
+            "issuer": "did:example:issuer",
+            "credentialSubject": {"name": "Alice"}
+        });
+        assert!(to_rdf(&document, &ContextLoader::default()).is_err());
+        let document = json!({
+            "@context": "https://example.com/v1",
+            "type": "VerifiableCredential"
+        });
+        assert!(to_rdf(&document, &ContextLoader::default()).is_err());
+    }
+
+    #[test]
+    fn reject_protected_redefinition() {
+        let document = json!({
+            "@context": [CREDENTIALS_V1_URL, {"VerifiableCredential": "https://example.com/vc"}],
+            "type": "VerifiableCredential"
+        });
+        assert!(to_rdf(&document, &ContextLoader::default()).is_err());
+    }
+}
@@ -0,0 +1,218 @@
+use aead::generic_array::typenum::U32;
+use hmac::{Hmac, Mac};
+use ursa::hash::sha2::{Digest, Sha256, Sha512};
+use zeroize::Zeroizing;
+
+use crate::error::ConversionError;
+use crate::keys::ArrayKey;
+
+/// Derive HKDF functions for a hash algorithm (RFC 5869)
+macro_rules! hkdf_impl {
+    ($extract:ident, $expand:ident, $hkdf:ident, $digest:ty, $size:expr, $name:expr) => {
+        #[doc = concat!("HKDF-Extract using ", $name, ", producing a pseudorandom key")]
+        pub fn $extract(salt: Option<&[u8]>, ikm: &[u8]) -> Zeroizing<Vec<u8>> {
+            let salt = salt.unwrap_or(&[0u8; $size]);
+            let mut mac =
+                Hmac::<$digest>::new_varkey(salt).expect("HMAC accepts keys of any length");
+            mac.input(ikm);
+            Zeroizing::new(mac.result().code().to_vec())
+        }
+
+        #[doc = concat!("HKDF-Expand using ", $name, ", producing `len` bytes of output")]
+        pub fn $expand(
+            prk: &[u8],
+            info: &[u8],
+            len: usize,
+        ) -> Result<Zeroizing<Vec<u8>>, ConversionError> {
+            if len > 255 * $size {
+                return Err("Requested HKDF output is too long".into());
+            }
+            if prk.len() < $size {
+                return Err("HKDF pseudorandom key is too short".into());
+            }
+            let mut okm = Zeroizing::new(Vec::with_capacity(len));
+            let mut block = Zeroizing::new(Vec::with_capacity($size));
+            let mut counter = 1u8;
+            while okm.len() < len {
+                let mut mac =
+                    Hmac::<$digest>::new_varkey(prk).expect("HMAC accepts keys of any length");
+                mac.input(&block);
+                mac.input(info);
+                mac.input(&[counter]);
+                block.clear();
+                block.extend_from_slice(&mac.result().code());
+                let take = std::cmp::min($size, len - okm.len());
+                okm.extend_from_slice(&block[..take]);
+                counter = counter.wrapping_add(1);
+            }
+            Ok(okm)
+        }
+
+        #[doc = concat!("HKDF-Extract followed by HKDF-Expand using ", $name)]
+        pub fn $hkdf(
+            salt: Option<&[u8]>,
+            ikm: &[u8],
+            info: &[u8],
+            len: usize,
+        ) -> Result<Zeroizing<Vec<u8>>, ConversionError> {
+            $expand(&$extract(salt, ikm), info, len)
+        }
+    };
+}
+
+hkdf_impl!(
+    hkdf_sha256_extract,
+    hkdf_sha256_expand,
+    hkdf_sha256,
+    Sha256,
+    32,
+    "SHA-256"
+);
+hkdf_impl!(
+    hkdf_sha512_extract,
+    hkdf_sha512_expand,
+    hkdf_sha512,
+    Sha512,
+    64,
+    "SHA-512"
+);
+
+/// Concat KDF with SHA-256 as defined by NIST SP 800-56A and RFC 7518
+/// section 4.6.2, as used for ECDH-ES key agreement. `key_len` is the length
+/// of the derived key in bytes.
+pub fn concat_kdf(
+    z: &[u8],
+    alg: &str,
+    apu: &[u8],
+    apv: &[u8],
+    key_len: usize,
+) -> Zeroizing<Vec<u8>> {
+    concat_kdf_supp(z, alg, apu, apv, None, key_len)
+}
+
+/// Concat KDF for ECDH-1PU key wrapping, which appends the authentication
+/// tag of the encrypted content (`cctag`) to the SuppPubInfo
+pub fn concat_kdf_tagged(
+    z: &[u8],
+    alg: &str,
+    apu: &[u8],
+    apv: &[u8],
+    cctag: &[u8],
+    key_len: usize,
+) -> Zeroizing<Vec<u8>> {
+    concat_kdf_supp(z, alg, apu, apv, Some(cctag), key_len)
+}
+
+fn concat_kdf_supp(
+    z: &[u8],
+    alg: &str,
+    apu: &[u8],
+    apv: &[u8],
+    cctag: Option<&[u8]>,
+    key_len: usize,
+) -> Zeroizing<Vec<u8>> {
+    let mut result = Zeroizing::new(Vec::with_capacity(key_len));
+    let mut round = 1u32;
+    while result.len() < key_len {
+        let mut hasher = Sha256::new();
+        hasher.input(round.to_be_bytes());
+        hasher.input(z);
+        for info in &[alg.as_bytes(), apu, apv] {
+            hasher.input((info.len() as u32).to_be_bytes());
+            hasher.input(info);
+        }
+        hasher.input(((key_len * 8) as u32).to_be_bytes());
+        if let Some(cctag) = cctag {
+            hasher.input((cctag.len() as u32).to_be_bytes());
+            hasher.input(cctag);
+        }
+        let block = hasher.result();
+        let take = std::cmp::min(block.len(), key_len - result.len());
+        result.extend_from_slice(&block[..take]);
+        round += 1;
+    }
+    result
+}
+
+const CONNECTION_KEY_SALT: &[u8] = b"indy-connection-key";
+
+/// Derive a 32 byte seed for the key of a single connection from a master
+/// secret, so that pairwise keys can be regenerated from one backed-up secret
+pub fn derive_connection_seed(
+    master: &[u8],
+    connection_id: &str,
+) -> Result<ArrayKey<U32>, ConversionError> {
+    if master.len() < 32 {
+        return Err("Master secret must be at least 32 bytes".into());
+    }
+    if connection_id.is_empty() {
+        return Err("Connection identifier must not be empty".into());
+    }
+    let okm = hkdf_sha256(
+        Some(CONNECTION_KEY_SALT),
+        master,
+        connection_id.as_bytes(),
+        32,
+    )?;
+    ArrayKey::try_from_slice(&okm[..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hkdf_sha256_rfc5869() {
+        // RFC 5869 test case 1
+        let ikm = [0x0b; 22];
+        let salt = hex::decode("000102030405060708090a0b0c").unwrap();
+        let info = hex::decode("f0f1f2f3f4f5f6f7f8f9").unwrap();
+        let prk = hkdf_sha256_extract(Some(&salt), &ikm);
+        assert_eq!(
+            hex::encode(&prk[..]),
+            "077709362c2e32df0ddc3f0dc47bba6390b6c73bb50f9c3122ec844ad7c2b3e5"
+        );
+        let okm = hkdf_sha256_expand(&prk, &info, 42).unwrap();
+        assert_eq!(
+            hex::encode(&okm[..]),
+            "3cb25f25faacd57a90434f64d0362f2a2d2d0a90cf1a5a4c5db02d56ecc4c5bf34007208d5b887185865"
+        );
+
+        // RFC 5869 test case 3, with no salt and no info
+        let okm = hkdf_sha256(None, &ikm, &[], 42).unwrap();
+        assert_eq!(
+            hex::encode(&okm[..]),
+            "8da4e775a563c18f715f802a063c5a31b8a11f5c5ee1879ec3454e5f3c738d2d9d201395faa4b61a96c8"
+        );
+        assert!(hkdf_sha256_expand(&prk, &info, 255 * 32 + 1).is_err());
+    }
+
+    #[test]
+    fn hkdf_sha512_lengths() {
+        let okm = hkdf_sha512(Some(b"salt"), b"input key material", b"info", 100).unwrap();
+        assert_eq!(okm.len(), 100);
+        let short = hkdf_sha512(Some(b"salt"), b"input key material", b"info", 10).unwrap();
+        assert_eq!(&okm[..10], &short[..]);
+    }
+
+    #[test]
+    fn concat_kdf_rfc7518() {
+        // shared secret from RFC 7518 appendix C, with an A128GCM key length
+        let z = [
+            158, 86, 217, 29, 129, 113, 53, 211, 114, 131, 66, 131, 191, 132, 38, 156, 251, 49,
+            110, 163, 218, 128, 106, 72, 246, 218, 167, 121, 140, 254, 144, 196,
+        ];
+        let key = concat_kdf(&z, "A128GCM", b"Alice", b"Bob", 16);
+        assert_eq!(hex::encode(&key[..]), "56aa8deaf8236d205c2228cd71a7101a");
+    }
+
+    #[test]
+    fn connection_seeds() {
+        let master = [1u8; 32];
+        let seed1 = derive_connection_seed(&master, "conn-1").unwrap();
+        let seed2 = derive_connection_seed(&master, "conn-2").unwrap();
+        assert_ne!(seed1, seed2);
+        assert_eq!(seed1, derive_connection_seed(&master, "conn-1").unwrap());
+        assert!(derive_connection_seed(&master[..16], "conn-1").is_err());
+    }
+}
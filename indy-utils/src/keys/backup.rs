@@ -0,0 +1,281 @@
+use std::collections::BTreeMap;
+
+use chacha20poly1305::{
+    aead::{generic_array::GenericArray, Aead, NewAead, Payload},
+    ChaCha20Poly1305,
+};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use ursa::hash::sha2::Sha256;
+use zeroize::Zeroizing;
+
+use super::envelope::{
+    check_version, decode_nonce, envelope_aad, KdfParams, ENVELOPE_VERSION, NONCE_SIZE,
+};
+use super::passphrase::Argon2Params;
+use super::{KeyType, PrivateKey};
+use crate::base64;
+use crate::error::ConversionError;
+use crate::kdf::hkdf_sha256;
+use crate::random::random_vec;
+
+const BACKUP_ENC: &str = "C20P";
+const BACKUP_MAC: &str = "HS256";
+const ENC_KEY_INFO: &[u8] = b"indy-key-backup-enc";
+const MAC_KEY_INFO: &[u8] = b"indy-key-backup-mac";
+
+/// A private key stored in a backup, with its identifier and metadata
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BackupEntry {
+    pub id: String,
+    pub key: PrivateKey,
+    pub metadata: BTreeMap<String, String>,
+}
+
+/// A set of private keys and wallet metadata for migration between devices
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeyBackup {
+    pub entries: Vec<BackupEntry>,
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl KeyBackup {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a key to the backup, replacing any existing key with the same identifier
+    pub fn add_key<S: Into<String>>(
+        &mut self,
+        id: S,
+        key: PrivateKey,
+        metadata: Option<BTreeMap<String, String>>,
+    ) {
+        let id = id.into();
+        self.entries.retain(|entry| entry.id != id);
+        self.entries.push(BackupEntry {
+            id,
+            key,
+            metadata: metadata.unwrap_or_default(),
+        });
+    }
+
+    pub fn get(&self, id: &str) -> Option<&PrivateKey> {
+        self.entries
+            .iter()
+            .find(|entry| entry.id == id)
+            .map(|entry| &entry.key)
+    }
+}
+
+/// The serialized plaintext of a backup
+#[derive(Serialize, Deserialize)]
+struct BackupContents {
+    keys: Vec<BackupKey>,
+    metadata: BTreeMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BackupKey {
+    id: String,
+    alg: String,
+    key: String,
+    metadata: BTreeMap<String, String>,
+}
+
+impl Drop for BackupKey {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.key)
+    }
+}
+
+/// The JSON container for an encrypted backup
+#[derive(Serialize, Deserialize)]
+struct EncryptedBackup {
+    version: u32,
+    kdf: KdfParams,
+    enc: String,
+    mac_alg: String,
+    nonce: String,
+    ciphertext: String,
+    mac: String,
+}
+
+impl EncryptedBackup {
+    /// The associated data binds the container version and algorithm
+    /// parameters to the ciphertext
+    fn aad(&self) -> Result<Vec<u8>, ConversionError> {
+        envelope_aad(self.version, &[&self.enc, &self.mac_alg], &self.kdf)
+    }
+
+    /// Compute the MAC over the associated data, nonce and ciphertext
+    fn compute_mac(&self, mac_key: &[u8]) -> Result<Hmac<Sha256>, ConversionError> {
+        let mut mac = Hmac::<Sha256>::new_varkey(mac_key).map_err(|_| "Invalid MAC key")?;
+        mac.input(&self.aad()?);
+        mac.input(b":");
+        mac.input(self.nonce.as_bytes());
+        mac.input(b":");
+        mac.input(self.ciphertext.as_bytes());
+        Ok(mac)
+    }
+}
+
+/// Derive the encryption and MAC keys for a backup from a passphrase
+fn backup_keys(
+    passphrase: &[u8],
+    kdf: &KdfParams,
+) -> Result<(Zeroizing<Vec<u8>>, Zeroizing<Vec<u8>>), ConversionError> {
+    let master = kdf.stretch(passphrase)?;
+    let enc_key = hkdf_sha256(None, &master[..], ENC_KEY_INFO, 32)?;
+    let mac_key = hkdf_sha256(None, &master[..], MAC_KEY_INFO, 32)?;
+    Ok((enc_key, mac_key))
+}
+
+/// Serialize and encrypt a key backup with keys derived from a passphrase.
+/// The default Argon2id parameters are used unless specified.
+pub fn export_backup(
+    backup: &KeyBackup,
+    passphrase: &[u8],
+    params: Option<Argon2Params>,
+) -> Result<String, ConversionError> {
+    let params = params.unwrap_or_default();
+    let contents = BackupContents {
+        keys: backup
+            .entries
+            .iter()
+            .map(|entry| BackupKey {
+                id: entry.id.clone(),
+                alg: entry.key.alg.to_string(),
+                key: base64::encode_urlsafe(&entry.key.key),
+                metadata: entry.metadata.clone(),
+            })
+            .collect(),
+        metadata: backup.metadata.clone(),
+    };
+    let plaintext = Zeroizing::new(
+        serde_json::to_vec(&contents)
+            .map_err(|err| ConversionError::from_msg_err("Error encoding key backup", err))?,
+    );
+
+    let nonce = random_vec(NONCE_SIZE);
+    let mut container = EncryptedBackup {
+        version: ENVELOPE_VERSION,
+        kdf: KdfParams::generate(params)?,
+        enc: BACKUP_ENC.to_string(),
+        mac_alg: BACKUP_MAC.to_string(),
+        nonce: base64::encode_urlsafe(&nonce),
+        ciphertext: String::new(),
+        mac: String::new(),
+    };
+    let (enc_key, mac_key) = backup_keys(passphrase, &container.kdf)?;
+    let aad = container.aad()?;
+    let ciphertext = ChaCha20Poly1305::new(GenericArray::from_slice(&enc_key[..]))
+        .encrypt(
+            GenericArray::from_slice(&nonce),
+            Payload {
+                aad: &aad,
+                msg: &plaintext,
+            },
+        )
+        .map_err(|_| "Error encrypting key backup")?;
+    container.ciphertext = base64::encode_urlsafe(ciphertext);
+    let mac = container.compute_mac(&mac_key)?.result().code();
+    container.mac = base64::encode_urlsafe(mac);
+    serde_json::to_string(&container)
+        .map_err(|err| ConversionError::from_msg_err("Error encoding key backup", err))
+}
+
+/// Verify and decrypt a key backup produced by `export_backup`, rejecting
+/// containers whose KDF parameters exceed `Argon2Params::MAX`
+pub fn import_backup(exported: &str, passphrase: &[u8]) -> Result<KeyBackup, ConversionError> {
+    let container: EncryptedBackup = serde_json::from_str(exported)
+        .map_err(|err| ConversionError::from_msg_err("Invalid key backup format", err))?;
+    check_version(container.version)?;
+    if container.enc != BACKUP_ENC || container.mac_alg != BACKUP_MAC {
+        return Err("Unsupported key backup algorithm".into());
+    }
+    let nonce = decode_nonce(&container.nonce)?;
+    let (enc_key, mac_key) = backup_keys(passphrase, &container.kdf)?;
+    let mac = base64::decode_urlsafe(&container.mac)?;
+    container
+        .compute_mac(&mac_key)?
+        .verify(&mac)
+        .map_err(|_| "Error verifying key backup: invalid passphrase or container")?;
+
+    let ciphertext = base64::decode_urlsafe(&container.ciphertext)?;
+    let aad = container.aad()?;
+    let plaintext = Zeroizing::new(
+        ChaCha20Poly1305::new(GenericArray::from_slice(&enc_key[..]))
+            .decrypt(
+                GenericArray::from_slice(&nonce),
+                Payload {
+                    aad: &aad,
+                    msg: &ciphertext,
+                },
+            )
+            .map_err(|_| "Error decrypting key backup")?,
+    );
+    let contents: BackupContents = serde_json::from_slice(&plaintext)
+        .map_err(|err| ConversionError::from_msg_err("Invalid key backup contents", err))?;
+
+    let mut backup = KeyBackup {
+        entries: Vec::with_capacity(contents.keys.len()),
+        metadata: contents.metadata,
+    };
+    for entry in contents.keys.iter() {
+        let key = Zeroizing::new(base64::decode_urlsafe(&entry.key)?);
+        backup.entries.push(BackupEntry {
+            id: entry.id.clone(),
+            key: PrivateKey::new(&key[..], Some(KeyType::from(entry.alg.as_str()))),
+            metadata: entry.metadata.clone(),
+        });
+    }
+    Ok(backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::test_fixtures::TEST3;
+
+    const TEST_PARAMS: Argon2Params = Argon2Params {
+        mem_cost: 64,
+        time_cost: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn backup_round_trip() {
+        let sk = TEST3.private_key();
+        let mut backup = KeyBackup::new();
+        let mut key_meta = BTreeMap::new();
+        key_meta.insert("did".to_string(), "VsKV7grR1BUE29mG2Fm2kX".to_string());
+        backup.add_key("signing", sk.clone(), Some(key_meta));
+        backup.add_key("other", PrivateKey::generate(None).unwrap(), None);
+        backup
+            .metadata
+            .insert("wallet".to_string(), "test".to_string());
+
+        let exported = export_backup(&backup, b"passphrase", Some(TEST_PARAMS)).unwrap();
+        assert!(!exported.contains("VsKV7grR1BUE29mG2Fm2kX"));
+        let imported = import_backup(&exported, b"passphrase").unwrap();
+        assert_eq!(imported, backup);
+        assert_eq!(imported.get("signing"), Some(&sk));
+        assert!(import_backup(&exported, b"wrong").is_err());
+
+        // tampering with the embedded KDF parameters is detected
+        let mut json: serde_json::Value = serde_json::from_str(&exported).unwrap();
+        json["kdf"]["t"] = 2.into();
+        assert!(import_backup(&json.to_string(), b"passphrase").is_err());
+    }
+
+    #[test]
+    fn import_rejects_excessive_kdf_params() {
+        let mut backup = KeyBackup::new();
+        backup.add_key("signing", TEST3.private_key(), None);
+        let exported = export_backup(&backup, b"passphrase", Some(TEST_PARAMS)).unwrap();
+        let mut json: serde_json::Value = serde_json::from_str(&exported).unwrap();
+        json["kdf"]["m"] = u32::MAX.into();
+        assert!(import_backup(&json.to_string(), b"passphrase").is_err());
+    }
+}
@@ -0,0 +1,270 @@
+use std::fmt::{self, Debug, Formatter};
+
+use amcl_wrapper::{field_elem::FieldElement, group_elem::GroupElement};
+use ursa::signatures::bls::normal::{
+    AggregatedSignature, Generator, ProofOfPossession, PublicKey, Signature,
+};
+
+use crate::error::ConversionError;
+
+/// The generator point shared by a set of BLS keys
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlsGenerator(Generator);
+
+impl BlsGenerator {
+    /// Load a generator point from its serialized form
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ConversionError> {
+        Generator::from_bytes(bytes)
+            .map(Self)
+            .map_err(|_| "Invalid BLS generator".into())
+    }
+
+    /// Serialize the generator point
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_bytes(false)
+    }
+}
+
+impl Default for BlsGenerator {
+    /// The standard BLS12-381 G1 generator
+    fn default() -> Self {
+        Self(Generator::generator())
+    }
+}
+
+/// A BLS12-381 signing key pair
+#[derive(Clone)]
+pub struct BlsKeyPair {
+    secret: FieldElement,
+    public: BlsPublicKey,
+}
+
+impl BlsKeyPair {
+    /// Generate a new random key pair
+    pub fn generate(gen: Option<&BlsGenerator>) -> Self {
+        Self::from_secret(FieldElement::random(), gen)
+    }
+
+    /// Deterministically derive a key pair from a seed value
+    pub fn from_seed(seed: &[u8], gen: Option<&BlsGenerator>) -> Result<Self, ConversionError> {
+        if seed.len() < 32 {
+            return Err("Invalid length for BLS seed".into());
+        }
+        Ok(Self::from_secret(FieldElement::from_msg_hash(seed), gen))
+    }
+
+    /// Load a key pair from a serialized secret key
+    pub fn from_secret_bytes(
+        secret: &[u8],
+        gen: Option<&BlsGenerator>,
+    ) -> Result<Self, ConversionError> {
+        let secret = FieldElement::from_bytes(secret).map_err(|_| "Invalid BLS secret key")?;
+        Ok(Self::from_secret(secret, gen))
+    }
+
+    fn from_secret(secret: FieldElement, gen: Option<&BlsGenerator>) -> Self {
+        let public = BlsPublicKey(with_generator(gen, |gen| PublicKey::new(&secret, gen)));
+        Self { secret, public }
+    }
+
+    /// Serialize the secret key
+    pub fn secret_bytes(&self) -> Vec<u8> {
+        self.secret.to_bytes()
+    }
+
+    /// Access the public key
+    pub fn public_key(&self) -> &BlsPublicKey {
+        &self.public
+    }
+
+    /// Sign a message
+    pub fn sign<M: AsRef<[u8]>>(&self, message: M) -> BlsSignature {
+        BlsSignature(Signature::new(message, None, &self.secret))
+    }
+
+    /// Create a proof of possession of the secret key, which is published
+    /// alongside the public key to prevent rogue key attacks on
+    /// aggregated signatures
+    pub fn proof_of_possession(&self) -> BlsProofOfPossession {
+        BlsProofOfPossession(ProofOfPossession::new(&self.public.0, None, &self.secret))
+    }
+}
+
+impl Debug for BlsKeyPair {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BlsKeyPair")
+            .field("public", &self.public)
+            .finish()
+    }
+}
+
+/// A BLS12-381 public key
+#[derive(Clone, Debug)]
+pub struct BlsPublicKey(PublicKey);
+
+impl BlsPublicKey {
+    /// Load a public key from its serialized form
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ConversionError> {
+        PublicKey::from_bytes(bytes)
+            .map(Self)
+            .map_err(|_| "Invalid BLS public key".into())
+    }
+
+    /// Serialize the public key
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_bytes()
+    }
+
+    /// Verify a signature produced by the matching secret key
+    pub fn verify<M: AsRef<[u8]>>(
+        &self,
+        message: M,
+        signature: &BlsSignature,
+        gen: Option<&BlsGenerator>,
+    ) -> bool {
+        with_generator(gen, |gen| signature.0.verify(message, None, &self.0, gen))
+    }
+
+    /// Verify a proof of possession for this public key
+    pub fn verify_proof_of_possession(
+        &self,
+        pop: &BlsProofOfPossession,
+        gen: Option<&BlsGenerator>,
+    ) -> bool {
+        with_generator(gen, |gen| pop.0.verify(None, &self.0, gen))
+    }
+}
+
+impl PartialEq for BlsPublicKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.to_bytes() == other.to_bytes()
+    }
+}
+
+impl Eq for BlsPublicKey {}
+
+/// A BLS12-381 signature, or a multi-signature aggregated from several
+/// signatures over the same message
+#[derive(Clone, Debug)]
+pub struct BlsSignature(Signature);
+
+impl BlsSignature {
+    /// Load a signature from its serialized form
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ConversionError> {
+        Signature::from_bytes(bytes)
+            .map(Self)
+            .map_err(|_| "Invalid BLS signature".into())
+    }
+
+    /// Serialize the signature
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_bytes()
+    }
+
+    /// Combine signatures over the same message into a multi-signature
+    pub fn aggregate(signatures: &[BlsSignature]) -> Result<Self, ConversionError> {
+        let (first, rest) = signatures
+            .split_first()
+            .ok_or("No signatures to aggregate")?;
+        let mut result = first.0.clone();
+        result.combine(&rest.iter().map(|sig| sig.0.clone()).collect::<Vec<_>>());
+        Ok(Self(result))
+    }
+
+    /// Verify a multi-signature over a message against the public keys of
+    /// the signers. The proof of possession for each public key must be
+    /// verified separately before the key is accepted.
+    pub fn verify_multi<M: AsRef<[u8]>>(
+        &self,
+        message: M,
+        signers: &[BlsPublicKey],
+        gen: Option<&BlsGenerator>,
+    ) -> Result<bool, ConversionError> {
+        if signers.is_empty() {
+            return Err("No signers for BLS multi-signature".into());
+        }
+        let multi_sig = AggregatedSignature::from_bytes(&self.to_bytes())
+            .map_err(|_| "Invalid BLS multi-signature")?;
+        let signers = signers.iter().map(|pk| pk.0.clone()).collect::<Vec<_>>();
+        Ok(with_generator(gen, |gen| {
+            multi_sig.verify_no_rk(message, None, &signers, gen)
+        }))
+    }
+}
+
+/// A BLS12-381 proof of possession of a secret key
+#[derive(Clone, Debug)]
+pub struct BlsProofOfPossession(ProofOfPossession);
+
+impl BlsProofOfPossession {
+    /// Load a proof of possession from its serialized form
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ConversionError> {
+        ProofOfPossession::from_bytes(bytes)
+            .map(Self)
+            .map_err(|_| "Invalid BLS proof of possession".into())
+    }
+
+    /// Serialize the proof of possession
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.0.to_bytes()
+    }
+}
+
+fn with_generator<R>(gen: Option<&BlsGenerator>, f: impl FnOnce(&Generator) -> R) -> R {
+    match gen {
+        Some(gen) => f(&gen.0),
+        None => f(&BlsGenerator::default().0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bls_sign_verify() {
+        let kp = BlsKeyPair::from_seed(b"000000000000000000000000000Test3", None).unwrap();
+        let sig = kp.sign(b"hello");
+        assert!(kp.public_key().verify(b"hello", &sig, None));
+        assert!(!kp.public_key().verify(b"goodbye", &sig, None));
+
+        let sig = BlsSignature::from_bytes(&sig.to_bytes()).unwrap();
+        let pk = BlsPublicKey::from_bytes(&kp.public_key().to_bytes()).unwrap();
+        assert!(pk.verify(b"hello", &sig, None));
+
+        let restored = BlsKeyPair::from_secret_bytes(&kp.secret_bytes(), None).unwrap();
+        assert_eq!(restored.public_key(), kp.public_key());
+    }
+
+    #[test]
+    fn bls_multi_sig_and_pop() {
+        let gen = BlsGenerator::default();
+        let signers = (0..3)
+            .map(|_| BlsKeyPair::generate(Some(&gen)))
+            .collect::<Vec<_>>();
+        for kp in signers.iter() {
+            let pop =
+                BlsProofOfPossession::from_bytes(&kp.proof_of_possession().to_bytes()).unwrap();
+            assert!(kp.public_key().verify_proof_of_possession(&pop, Some(&gen)));
+        }
+        assert!(!signers[0]
+            .public_key()
+            .verify_proof_of_possession(&signers[1].proof_of_possession(), Some(&gen)));
+
+        let message = b"state root";
+        let sigs = signers
+            .iter()
+            .map(|kp| kp.sign(message))
+            .collect::<Vec<_>>();
+        let multi_sig = BlsSignature::aggregate(&sigs).unwrap();
+        let pks = signers
+            .iter()
+            .map(|kp| kp.public_key().clone())
+            .collect::<Vec<_>>();
+        assert!(multi_sig.verify_multi(message, &pks, Some(&gen)).unwrap());
+        assert!(!multi_sig
+            .verify_multi(message, &pks[..2], Some(&gen))
+            .unwrap());
+        assert!(BlsSignature::aggregate(&[]).is_err());
+    }
+}
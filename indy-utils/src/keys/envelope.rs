@@ -0,0 +1,125 @@
+use aead::generic_array::typenum::U32;
+use serde::{Deserialize, Serialize};
+
+use super::passphrase::{stretch_passphrase, Argon2Params};
+use super::ArrayKey;
+use crate::base64;
+use crate::error::ConversionError;
+use crate::random::random_vec;
+
+/// The envelope format version shared by encrypted key exports and backups
+pub(super) const ENVELOPE_VERSION: u32 = 1;
+pub(super) const NONCE_SIZE: usize = 12;
+const ENVELOPE_KDF: &str = "argon2id";
+const SALT_SIZE: usize = 16;
+
+/// The passphrase KDF parameters embedded in an encrypted envelope
+#[derive(Serialize, Deserialize)]
+pub(super) struct KdfParams {
+    name: String,
+    salt: String,
+    m: u32,
+    t: u32,
+    p: u32,
+}
+
+impl KdfParams {
+    /// Generate KDF parameters with a random salt
+    pub(super) fn generate(params: Argon2Params) -> Result<Self, ConversionError> {
+        params.check_max()?;
+        Ok(Self {
+            name: ENVELOPE_KDF.to_string(),
+            salt: base64::encode_urlsafe(random_vec(SALT_SIZE)),
+            m: params.mem_cost,
+            t: params.time_cost,
+            p: params.parallelism,
+        })
+    }
+
+    /// Stretch a passphrase according to the parameters. Parameters read
+    /// from an untrusted envelope are rejected before any work is done if
+    /// they exceed `Argon2Params::MAX`.
+    pub(super) fn stretch(&self, passphrase: &[u8]) -> Result<ArrayKey<U32>, ConversionError> {
+        if self.name != ENVELOPE_KDF {
+            return Err(format!("Unsupported key derivation function: {}", self.name).into());
+        }
+        let params = Argon2Params {
+            mem_cost: self.m,
+            time_cost: self.t,
+            parallelism: self.p,
+        };
+        params.check_max()?;
+        let salt = base64::decode_urlsafe(&self.salt)?;
+        stretch_passphrase(passphrase, &salt, params)
+    }
+}
+
+/// Check the format version of an envelope
+pub(super) fn check_version(version: u32) -> Result<(), ConversionError> {
+    if version != ENVELOPE_VERSION {
+        return Err(format!("Unsupported envelope version: {}", version).into());
+    }
+    Ok(())
+}
+
+/// The associated data binds the envelope version, algorithm identifiers
+/// and KDF parameters to the ciphertext
+pub(super) fn envelope_aad(
+    version: u32,
+    algs: &[&str],
+    kdf: &KdfParams,
+) -> Result<Vec<u8>, ConversionError> {
+    let kdf = serde_json::to_string(kdf)
+        .map_err(|err| ConversionError::from_msg_err("Error encoding KDF parameters", err))?;
+    Ok(format!("{}:{}:{}", version, algs.join(":"), kdf).into_bytes())
+}
+
+/// Decode the nonce of an envelope
+pub(super) fn decode_nonce(nonce: &str) -> Result<Vec<u8>, ConversionError> {
+    let nonce = base64::decode_urlsafe(nonce)?;
+    if nonce.len() != NONCE_SIZE {
+        return Err("Invalid nonce length for envelope".into());
+    }
+    Ok(nonce)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_PARAMS: Argon2Params = Argon2Params {
+        mem_cost: 64,
+        time_cost: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn kdf_params_round_trip() {
+        let kdf = KdfParams::generate(TEST_PARAMS).unwrap();
+        let json = serde_json::to_string(&kdf).unwrap();
+        let parsed: KdfParams = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            kdf.stretch(b"passphrase").unwrap(),
+            parsed.stretch(b"passphrase").unwrap()
+        );
+        assert_eq!(
+            envelope_aad(ENVELOPE_VERSION, &["a", "b"], &kdf).unwrap(),
+            format!("1:a:b:{}", json).into_bytes()
+        );
+    }
+
+    #[test]
+    fn kdf_params_reject_excessive_cost() {
+        let mut kdf = KdfParams::generate(TEST_PARAMS).unwrap();
+        kdf.m = u32::MAX;
+        assert!(kdf.stretch(b"passphrase").is_err());
+        kdf.m = TEST_PARAMS.mem_cost;
+        kdf.name = "scrypt".to_string();
+        assert!(kdf.stretch(b"passphrase").is_err());
+        assert!(KdfParams::generate(Argon2Params {
+            time_cost: Argon2Params::MAX.time_cost + 1,
+            ..TEST_PARAMS
+        })
+        .is_err());
+    }
+}
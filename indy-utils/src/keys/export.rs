@@ -0,0 +1,151 @@
+use chacha20poly1305::{
+    aead::{generic_array::GenericArray, Aead, NewAead, Payload},
+    ChaCha20Poly1305,
+};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use super::envelope::{
+    check_version, decode_nonce, envelope_aad, KdfParams, ENVELOPE_VERSION, NONCE_SIZE,
+};
+use super::passphrase::Argon2Params;
+use super::{KeyType, PrivateKey};
+use crate::base64;
+use crate::error::ConversionError;
+use crate::random::random_vec;
+
+const EXPORT_ENC: &str = "C20P";
+
+/// The JSON envelope for an encrypted private key
+#[derive(Serialize, Deserialize)]
+struct EncryptedKey {
+    version: u32,
+    alg: String,
+    kdf: KdfParams,
+    enc: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+impl EncryptedKey {
+    /// The associated data binds the key type and KDF parameters to the ciphertext
+    fn aad(&self) -> Result<Vec<u8>, ConversionError> {
+        envelope_aad(self.version, &[&self.alg, &self.enc], &self.kdf)
+    }
+}
+
+/// Encrypt a private key with a wrapping key derived from a wallet key
+pub(super) fn export_encrypted(
+    sk: &PrivateKey,
+    wallet_key: &[u8],
+    params: Argon2Params,
+) -> Result<String, ConversionError> {
+    let nonce = random_vec(NONCE_SIZE);
+    let mut envelope = EncryptedKey {
+        version: ENVELOPE_VERSION,
+        alg: sk.alg.to_string(),
+        kdf: KdfParams::generate(params)?,
+        enc: EXPORT_ENC.to_string(),
+        nonce: base64::encode_urlsafe(&nonce),
+        ciphertext: String::new(),
+    };
+    let wrap_key = envelope.kdf.stretch(wallet_key)?;
+    let aad = envelope.aad()?;
+    let ciphertext = ChaCha20Poly1305::new(GenericArray::from_slice(&wrap_key[..]))
+        .encrypt(
+            GenericArray::from_slice(&nonce),
+            Payload {
+                aad: &aad,
+                msg: &sk.key,
+            },
+        )
+        .map_err(|_| "Error encrypting private key")?;
+    envelope.ciphertext = base64::encode_urlsafe(ciphertext);
+    serde_json::to_string(&envelope)
+        .map_err(|err| ConversionError::from_msg_err("Error encoding encrypted key", err))
+}
+
+/// Decrypt a private key exported by `export_encrypted`, rejecting envelopes
+/// whose KDF parameters exceed `Argon2Params::MAX`
+pub(super) fn import_encrypted(
+    exported: &str,
+    wallet_key: &[u8],
+) -> Result<PrivateKey, ConversionError> {
+    let envelope: EncryptedKey = serde_json::from_str(exported)
+        .map_err(|err| ConversionError::from_msg_err("Invalid encrypted key format", err))?;
+    check_version(envelope.version)?;
+    if envelope.enc != EXPORT_ENC {
+        return Err("Unsupported encrypted key algorithm".into());
+    }
+    let nonce = decode_nonce(&envelope.nonce)?;
+    let ciphertext = base64::decode_urlsafe(&envelope.ciphertext)?;
+    let wrap_key = envelope.kdf.stretch(wallet_key)?;
+    let aad = envelope.aad()?;
+    let key = Zeroizing::new(
+        ChaCha20Poly1305::new(GenericArray::from_slice(&wrap_key[..]))
+            .decrypt(
+                GenericArray::from_slice(&nonce),
+                Payload {
+                    aad: &aad,
+                    msg: &ciphertext,
+                },
+            )
+            .map_err(|_| "Error decrypting private key: invalid wallet key or envelope")?,
+    );
+    Ok(PrivateKey::new(
+        &key[..],
+        Some(KeyType::from(envelope.alg.as_str())),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::test_fixtures::TEST3;
+
+    const TEST_PARAMS: Argon2Params = Argon2Params {
+        mem_cost: 64,
+        time_cost: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn export_import_round_trip() {
+        let sk = TEST3.private_key();
+        let exported = sk
+            .export_encrypted_with_params(b"wallet key", TEST_PARAMS)
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_str(&exported).unwrap();
+        assert_eq!(json["alg"], "ed25519");
+        assert_eq!(json["kdf"]["name"], "argon2id");
+
+        assert_eq!(
+            PrivateKey::import_encrypted(&exported, b"wallet key").unwrap(),
+            sk
+        );
+        assert!(PrivateKey::import_encrypted(&exported, b"other key").is_err());
+
+        // tampering with the key type is detected
+        let tampered = exported.replace("\"ed25519\"", "\"x25519\"");
+        assert!(PrivateKey::import_encrypted(&tampered, b"wallet key").is_err());
+    }
+
+    #[test]
+    fn import_rejects_excessive_kdf_params() {
+        let sk = TEST3.private_key();
+        let exported = sk
+            .export_encrypted_with_params(b"wallet key", TEST_PARAMS)
+            .unwrap();
+        let mut json: serde_json::Value = serde_json::from_str(&exported).unwrap();
+        json["kdf"]["m"] = u32::MAX.into();
+        assert!(PrivateKey::import_encrypted(&json.to_string(), b"wallet key").is_err());
+
+        let params = Argon2Params {
+            parallelism: Argon2Params::MAX.parallelism + 1,
+            ..TEST_PARAMS
+        };
+        assert!(sk
+            .export_encrypted_with_params(b"wallet key", params)
+            .is_err());
+    }
+}
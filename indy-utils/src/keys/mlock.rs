@@ -0,0 +1,130 @@
+//! Best-effort locking of secret key buffers into memory, to prevent them
+//! from being swapped to disk. Locking is only performed on Unix platforms
+//! when the `mlock` feature is enabled, and failures are ignored as the
+//! process may lack the privileges or resource limits to lock pages.
+//!
+//! Memory is locked in whole pages, and separate allocations may share a
+//! page. A count of the live buffers on each page is maintained so that a
+//! page is only unlocked once no locked buffer remains on it.
+
+#[cfg(all(unix, feature = "mlock"))]
+use std::collections::HashMap;
+#[cfg(all(unix, feature = "mlock"))]
+use std::ops::Range;
+#[cfg(all(unix, feature = "mlock"))]
+use std::sync::Mutex;
+
+#[cfg(all(unix, feature = "mlock"))]
+use once_cell::sync::Lazy;
+
+/// The number of locked buffers on each locked page, by page address
+#[cfg(all(unix, feature = "mlock"))]
+static LOCKED_PAGES: Lazy<Mutex<PageCounts>> = Lazy::new(|| Mutex::new(PageCounts::default()));
+
+/// The size of a memory page, the unit in which memory is locked
+#[cfg(all(unix, feature = "mlock"))]
+static PAGE_SIZE: Lazy<usize> = Lazy::new(|| match unsafe { libc::sysconf(libc::_SC_PAGESIZE) } {
+    size if size > 0 => size as usize,
+    _ => 4096,
+});
+
+/// Lock the allocation backing a secret key buffer
+#[inline]
+#[allow(clippy::ptr_arg)]
+pub(crate) fn lock(buf: &Vec<u8>) {
+    #[cfg(all(unix, feature = "mlock"))]
+    if buf.capacity() > 0 {
+        let pages = page_range(buf.as_ptr() as usize, buf.capacity(), *PAGE_SIZE);
+        let mut counts = LOCKED_PAGES.lock().unwrap_or_else(|err| err.into_inner());
+        for page in counts.acquire(pages, *PAGE_SIZE) {
+            unsafe {
+                libc::mlock(page as *const libc::c_void, *PAGE_SIZE);
+            }
+        }
+    }
+    #[cfg(not(all(unix, feature = "mlock")))]
+    let _ = buf;
+}
+
+/// Unlock the allocation backing a secret key buffer. This should be called
+/// after the buffer has been zeroized. Pages shared with other locked buffers
+/// remain locked.
+#[inline]
+#[allow(clippy::ptr_arg)]
+pub(crate) fn unlock(buf: &Vec<u8>) {
+    #[cfg(all(unix, feature = "mlock"))]
+    if buf.capacity() > 0 {
+        let pages = page_range(buf.as_ptr() as usize, buf.capacity(), *PAGE_SIZE);
+        let mut counts = LOCKED_PAGES.lock().unwrap_or_else(|err| err.into_inner());
+        for page in counts.release(pages, *PAGE_SIZE) {
+            unsafe {
+                libc::munlock(page as *const libc::c_void, *PAGE_SIZE);
+            }
+        }
+    }
+    #[cfg(not(all(unix, feature = "mlock")))]
+    let _ = buf;
+}
+
+/// The range of page addresses covering a buffer
+#[cfg(all(unix, feature = "mlock"))]
+fn page_range(addr: usize, len: usize, page_size: usize) -> Range<usize> {
+    let start = addr - addr % page_size;
+    let end = addr + len;
+    start..end
+}
+
+#[cfg(all(unix, feature = "mlock"))]
+#[derive(Debug, Default)]
+struct PageCounts(HashMap<usize, usize>);
+
+#[cfg(all(unix, feature = "mlock"))]
+impl PageCounts {
+    /// Register a locked buffer, returning the pages which must be newly locked
+    fn acquire(&mut self, pages: Range<usize>, page_size: usize) -> Vec<usize> {
+        let mut added = Vec::new();
+        for page in pages.step_by(page_size) {
+            let count = self.0.entry(page).or_insert(0);
+            if *count == 0 {
+                added.push(page);
+            }
+            *count += 1;
+        }
+        added
+    }
+
+    /// Release a locked buffer, returning the pages which may now be unlocked
+    fn release(&mut self, pages: Range<usize>, page_size: usize) -> Vec<usize> {
+        let mut removed = Vec::new();
+        for page in pages.step_by(page_size) {
+            if let Some(count) = self.0.get_mut(&page) {
+                *count -= 1;
+                if *count == 0 {
+                    self.0.remove(&page);
+                    removed.push(page);
+                }
+            }
+        }
+        removed
+    }
+}
+
+#[cfg(all(test, unix, feature = "mlock"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_pages_stay_locked() {
+        let mut counts = PageCounts::default();
+        // two buffers sharing the page at 4096, the first spanning two pages
+        let first = page_range(4000, 200, 4096);
+        let second = page_range(4200, 32, 4096);
+        assert_eq!(first, 0..4200);
+        assert_eq!(counts.acquire(first.clone(), 4096), vec![0, 4096]);
+        assert_eq!(counts.acquire(second.clone(), 4096), Vec::<usize>::new());
+        assert_eq!(counts.release(first, 4096), vec![0]);
+        assert_eq!(counts.release(second, 4096), vec![4096]);
+        // releasing an unknown buffer is ignored
+        assert_eq!(counts.release(8192..8200, 4096), Vec::<usize>::new());
+    }
+}
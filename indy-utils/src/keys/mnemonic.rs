@@ -0,0 +1,93 @@
+//! BIP-39 recovery phrases using the English wordlist
+
+use bip39::{Language, Mnemonic, MnemonicType};
+use zeroize::Zeroizing;
+
+use super::PrivateKey;
+use crate::error::ConversionError;
+
+/// The default number of words in a generated recovery phrase
+pub const DEFAULT_WORD_COUNT: usize = 24;
+
+/// A 64 byte BIP-39 wallet seed derived from a recovery phrase
+pub struct Seed(Zeroizing<Vec<u8>>);
+
+impl Seed {
+    /// Derive the wallet seed for a recovery phrase and optional passphrase.
+    /// The phrase checksum is validated against the English wordlist.
+    pub fn from_mnemonic(phrase: &str, passphrase: Option<&str>) -> Result<Self, ConversionError> {
+        let mnemonic = Mnemonic::from_phrase(phrase, Language::English)
+            .map_err(|err| format!("Invalid recovery phrase: {}", err))?;
+        let seed = bip39::Seed::new(&mnemonic, passphrase.unwrap_or_default());
+        Ok(Self(Zeroizing::new(seed.as_bytes().to_vec())))
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0[..]
+    }
+
+    /// Derive an Ed25519 signing key from the seed using SLIP-0010, for a
+    /// path such as `m/44'/0'/0'`. The master key is used by default.
+    pub fn to_private_key(&self, path: Option<&str>) -> Result<PrivateKey, ConversionError> {
+        PrivateKey::derive_path(&self.0[..], path.unwrap_or("m"))
+    }
+}
+
+impl std::fmt::Debug for Seed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Seed").field(&"<secret>").finish()
+    }
+}
+
+/// Generate a new random recovery phrase of 12, 15, 18, 21 or 24 words
+pub fn generate_mnemonic(word_count: Option<usize>) -> Result<Zeroizing<String>, ConversionError> {
+    let mtype = MnemonicType::for_word_count(word_count.unwrap_or(DEFAULT_WORD_COUNT))
+        .map_err(|err| format!("Invalid word count: {}", err))?;
+    let mnemonic = Mnemonic::new(mtype, Language::English);
+    Ok(Zeroizing::new(mnemonic.phrase().to_string()))
+}
+
+/// Check that a recovery phrase uses the English wordlist and has a valid checksum
+pub fn validate_mnemonic(phrase: &str) -> Result<(), ConversionError> {
+    Mnemonic::validate(phrase, Language::English)
+        .map_err(|err| format!("Invalid recovery phrase: {}", err).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_PHRASE: &str = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+
+    #[test]
+    fn seed_from_mnemonic() {
+        // BIP-39 test vector with the passphrase "TREZOR"
+        let seed = Seed::from_mnemonic(TEST_PHRASE, Some("TREZOR")).unwrap();
+        assert_eq!(
+            hex::encode(seed.as_bytes()),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+        let sk = seed.to_private_key(Some("m/44'/0'")).unwrap();
+        assert_eq!(sk, seed.to_private_key(Some("m/44'/0'")).unwrap());
+        assert_ne!(sk, seed.to_private_key(None).unwrap());
+
+        let invalid = TEST_PHRASE.replace("about", "abandon");
+        assert!(validate_mnemonic(&invalid).is_err());
+        assert!(Seed::from_mnemonic(&invalid, None).is_err());
+    }
+
+    #[test]
+    fn generate_valid_mnemonic() {
+        let phrase = generate_mnemonic(None).unwrap();
+        assert_eq!(phrase.split_whitespace().count(), DEFAULT_WORD_COUNT);
+        validate_mnemonic(&phrase).unwrap();
+        assert_eq!(
+            generate_mnemonic(Some(12))
+                .unwrap()
+                .split_whitespace()
+                .count(),
+            12
+        );
+        assert!(generate_mnemonic(Some(13)).is_err());
+    }
+}
@@ -0,0 +1,75 @@
+use p256::{
+    ecdh::diffie_hellman,
+    ecdsa::{
+        signature::{Signature as _, Signer, Verifier},
+        Signature, SigningKey, VerifyingKey,
+    },
+    elliptic_curve::sec1::ToEncodedPoint,
+    PublicKey, SecretKey,
+};
+use rand::RngCore;
+use zeroize::Zeroizing;
+
+use crate::error::ConversionError;
+
+/// The size of a P-256 secret key or field element
+pub const P256_SECRET_KEY_SIZE: usize = 32;
+
+/// Generate a new P-256 secret key from a random number generator
+pub fn random_secret<G: RngCore>(rng: &mut G) -> SecretKey {
+    loop {
+        let mut bytes = Zeroizing::new([0u8; P256_SECRET_KEY_SIZE]);
+        rng.fill_bytes(&mut bytes[..]);
+        // a random value is outside of the scalar field with negligible probability
+        if let Ok(sk) = SecretKey::from_bytes(&bytes[..]) {
+            return sk;
+        }
+    }
+}
+
+/// Load a P-256 secret key from its raw scalar
+pub fn secret_key(key: &[u8]) -> Result<SecretKey, ConversionError> {
+    SecretKey::from_bytes(key).map_err(|_| "Invalid P-256 secret key".into())
+}
+
+/// Load a P-256 public key from its SEC1 encoding
+pub fn public_key(key: &[u8]) -> Result<PublicKey, ConversionError> {
+    PublicKey::from_sec1_bytes(key).map_err(|_| "Invalid P-256 public key".into())
+}
+
+/// Get the compressed SEC1 encoding of the public key for a secret key
+pub fn public_key_bytes(sk: &SecretKey) -> Vec<u8> {
+    sk.public_key().to_encoded_point(true).as_bytes().to_vec()
+}
+
+/// Get the affine coordinates of a public key
+pub fn public_key_coordinates(pk: &PublicKey) -> (Vec<u8>, Vec<u8>) {
+    let point = pk.to_encoded_point(false);
+    // the uncompressed encoding is 0x04 || x || y
+    let (x, y) = point.as_bytes()[1..].split_at(P256_SECRET_KEY_SIZE);
+    (x.to_vec(), y.to_vec())
+}
+
+/// Perform an ECDH key agreement, returning the raw shared secret (the
+/// x-coordinate of the shared point)
+pub fn key_agreement(sk: &SecretKey, pk: &PublicKey) -> Zeroizing<Vec<u8>> {
+    let shared = diffie_hellman(sk.secret_scalar(), pk.as_affine());
+    Zeroizing::new(shared.as_bytes().to_vec())
+}
+
+/// Produce an ES256 signature, in the fixed-size `r || s` format
+pub fn sign(sk: &[u8], message: &[u8]) -> Result<Vec<u8>, ConversionError> {
+    let signing_key = SigningKey::from_bytes(sk).map_err(|_| "Invalid P-256 signing key")?;
+    let signature: Signature = signing_key.sign(message);
+    Ok(signature.as_ref().to_vec())
+}
+
+/// Verify an ES256 signature
+pub fn verify(pk: &[u8], message: &[u8], signature: &[u8]) -> Result<bool, ConversionError> {
+    let verifying_key = VerifyingKey::from_sec1_bytes(pk).map_err(|_| "Invalid P-256 verkey")?;
+    let signature = match Signature::from_bytes(signature) {
+        Ok(sig) => sig,
+        Err(_) => return Ok(false),
+    };
+    Ok(verifying_key.verify(message, &signature).is_ok())
+}
@@ -0,0 +1,124 @@
+use aead::generic_array::typenum::U32;
+use argon2::{Algorithm, Argon2, Version};
+
+use super::ArrayKey;
+use crate::error::ConversionError;
+
+/// The minimum salt length accepted for passphrase stretching
+pub const MIN_SALT_LENGTH: usize = 16;
+
+/// Argon2id cost parameters for deriving keys from passphrases
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Argon2Params {
+    /// Memory cost in KiB
+    pub mem_cost: u32,
+    /// Number of iterations
+    pub time_cost: u32,
+    /// Degree of parallelism
+    pub parallelism: u32,
+}
+
+impl Argon2Params {
+    /// Reduced parameters suitable for interactive use on constrained devices
+    pub const INTERACTIVE: Self = Self {
+        mem_cost: 19 * 1024,
+        time_cost: 2,
+        parallelism: 1,
+    };
+
+    /// Stronger parameters for keys protecting long-lived identities
+    pub const MODERATE: Self = Self {
+        mem_cost: 64 * 1024,
+        time_cost: 3,
+        parallelism: 1,
+    };
+
+    /// The maximum parameters accepted from untrusted input, bounding the
+    /// memory and time spent deriving a key
+    pub const MAX: Self = Self {
+        mem_cost: 256 * 1024,
+        time_cost: 16,
+        parallelism: 16,
+    };
+
+    /// Check that the parameters do not exceed `Argon2Params::MAX`
+    pub fn check_max(&self) -> Result<(), ConversionError> {
+        if self.mem_cost > Self::MAX.mem_cost
+            || self.time_cost > Self::MAX.time_cost
+            || self.parallelism > Self::MAX.parallelism
+        {
+            return Err("Argon2 parameters exceed the maximum supported cost".into());
+        }
+        Ok(())
+    }
+}
+
+impl Default for Argon2Params {
+    fn default() -> Self {
+        Self::MODERATE
+    }
+}
+
+/// Stretch a passphrase into a 32 byte seed using Argon2id
+pub fn stretch_passphrase(
+    passphrase: &[u8],
+    salt: &[u8],
+    params: Argon2Params,
+) -> Result<ArrayKey<U32>, ConversionError> {
+    if salt.len() < MIN_SALT_LENGTH {
+        return Err(format!("Salt must be at least {} bytes", MIN_SALT_LENGTH).into());
+    }
+    let argon = Argon2::new(
+        None,
+        params.time_cost,
+        params.mem_cost,
+        params.parallelism,
+        Version::V0x13,
+    )
+    .map_err(|err| format!("Invalid Argon2 parameters: {}", err))?;
+    let mut seed = ArrayKey::default();
+    argon
+        .hash_password_into(Algorithm::Argon2id, passphrase, salt, &[], &mut seed[..])
+        .map_err(|err| format!("Error stretching passphrase: {}", err))?;
+    Ok(seed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_PARAMS: Argon2Params = Argon2Params {
+        mem_cost: 64,
+        time_cost: 1,
+        parallelism: 1,
+    };
+
+    #[test]
+    fn stretch_deterministic() {
+        let salt = b"0123456789abcdef";
+        let seed1 = stretch_passphrase(b"correct horse", salt, TEST_PARAMS).unwrap();
+        let seed2 = stretch_passphrase(b"correct horse", salt, TEST_PARAMS).unwrap();
+        assert_eq!(seed1, seed2);
+        let seed3 = stretch_passphrase(b"battery staple", salt, TEST_PARAMS).unwrap();
+        assert_ne!(seed1, seed3);
+        let seed4 = stretch_passphrase(b"correct horse", b"fedcba9876543210", TEST_PARAMS).unwrap();
+        assert_ne!(seed1, seed4);
+        assert!(stretch_passphrase(b"correct horse", b"short", TEST_PARAMS).is_err());
+    }
+
+    #[test]
+    fn check_max_params() {
+        assert!(Argon2Params::default().check_max().is_ok());
+        assert!(Argon2Params::MAX.check_max().is_ok());
+        let params = Argon2Params {
+            mem_cost: u32::MAX,
+            ..TEST_PARAMS
+        };
+        assert!(params.check_max().is_err());
+        let params = Argon2Params {
+            time_cost: Argon2Params::MAX.time_cost + 1,
+            ..TEST_PARAMS
+        };
+        assert!(params.check_max().is_err());
+    }
+}
@@ -0,0 +1,70 @@
+use super::{EncodedVerKey, PrivateKey, Signature};
+use crate::error::ConversionError;
+
+/// The domain separation tag for proof-of-possession signatures, so that a
+/// proof cannot be replayed as a signature over any other message type
+const POSSESSION_DOMAIN: &[u8] = b"indy-key-possession-v1";
+
+/// The minimum accepted challenge length
+pub const MIN_CHALLENGE_LENGTH: usize = 16;
+
+/// Prove control of a signing key by signing a challenge issued by the verifier
+pub fn prove_possession(key: &PrivateKey, challenge: &[u8]) -> Result<Signature, ConversionError> {
+    let verkey = key.public_key()?.as_base58()?;
+    let message = possession_message(&verkey, challenge)?;
+    Signature::sign(key, message)
+}
+
+/// Verify a proof produced by `prove_possession` for a verkey and challenge
+pub fn verify_possession(
+    verkey: &EncodedVerKey,
+    challenge: &[u8],
+    proof: &Signature,
+) -> Result<bool, ConversionError> {
+    let message = possession_message(verkey, challenge)?;
+    proof.verify(message, &verkey.decode()?)
+}
+
+/// Encode the signed structure: the domain tag followed by the
+/// length-prefixed key type, public key and challenge
+fn possession_message(
+    verkey: &EncodedVerKey,
+    challenge: &[u8],
+) -> Result<Vec<u8>, ConversionError> {
+    if challenge.len() < MIN_CHALLENGE_LENGTH {
+        return Err(format!("Challenge must be at least {} bytes", MIN_CHALLENGE_LENGTH).into());
+    }
+    let key = verkey.key_bytes()?;
+    let mut message = POSSESSION_DOMAIN.to_vec();
+    for part in &[verkey.alg.as_str().as_bytes(), &key[..], challenge] {
+        message.extend_from_slice(&(part.len() as u32).to_be_bytes());
+        message.extend_from_slice(part);
+    }
+    Ok(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::test_fixtures::TEST3;
+
+    #[test]
+    fn possession_round_trip() {
+        let sk = TEST3.private_key();
+        let vk = sk.public_key().unwrap().as_base58().unwrap();
+        let challenge = b"0123456789abcdef0123";
+
+        let proof = prove_possession(&sk, challenge).unwrap();
+        assert!(verify_possession(&vk, challenge, &proof).unwrap());
+        assert!(!verify_possession(&vk, b"fedcba9876543210", &proof).unwrap());
+
+        // a plain signature over the challenge is not a valid proof
+        let plain = Signature::sign(&sk, challenge).unwrap();
+        assert!(!verify_possession(&vk, challenge, &plain).unwrap());
+
+        let other = PrivateKey::generate(None).unwrap();
+        let other_vk = other.public_key().unwrap().as_base58().unwrap();
+        assert!(!verify_possession(&other_vk, challenge, &proof).unwrap());
+        assert!(prove_possession(&sk, b"short").is_err());
+    }
+}
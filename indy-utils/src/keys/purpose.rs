@@ -0,0 +1,176 @@
+//! Private keys bound to a single purpose, so that the type system prevents
+//! a key agreement key from being used to sign, or an issuer signing key
+//! from being used for message encryption
+
+use std::marker::PhantomData;
+
+use super::{EncodedVerKey, KeyType, PrivateKey, SharedSecret, VerKey};
+use crate::error::ConversionError;
+
+/// A permitted use of a private key
+pub trait KeyPurpose {
+    /// The name of the purpose, used in error messages
+    const NAME: &'static str;
+
+    /// Check whether a key type may be used for this purpose
+    fn allows(alg: &KeyType) -> bool;
+}
+
+/// Keys used to sign credentials, transactions and other payloads
+#[derive(Debug)]
+pub enum Signing {}
+
+/// Keys used for Diffie-Hellman key agreement in message encryption
+#[derive(Debug)]
+pub enum KeyAgreement {}
+
+/// Keys used to sign authentication challenges for a DID
+#[derive(Debug)]
+pub enum Authentication {}
+
+impl KeyPurpose for Signing {
+    const NAME: &'static str = "signing";
+
+    fn allows(alg: &KeyType) -> bool {
+        match alg {
+            KeyType::ED25519 | KeyType::Secp256k1 | KeyType::P256 => true,
+            KeyType::Bls12381G1 | KeyType::Bls12381G2 => true,
+            _ => false,
+        }
+    }
+}
+
+impl KeyPurpose for KeyAgreement {
+    const NAME: &'static str = "key agreement";
+
+    fn allows(alg: &KeyType) -> bool {
+        match alg {
+            KeyType::X25519 | KeyType::Secp256k1 | KeyType::P256 => true,
+            _ => false,
+        }
+    }
+}
+
+impl KeyPurpose for Authentication {
+    const NAME: &'static str = "authentication";
+
+    fn allows(alg: &KeyType) -> bool {
+        match alg {
+            KeyType::ED25519 | KeyType::Secp256k1 | KeyType::P256 => true,
+            _ => false,
+        }
+    }
+}
+
+/// A private key restricted to a single purpose
+pub struct TypedKey<P: KeyPurpose> {
+    key: PrivateKey,
+    _purpose: PhantomData<fn() -> P>,
+}
+
+impl<P: KeyPurpose> TypedKey<P> {
+    /// Bind a private key to a purpose, checking that the key type is suitable
+    pub fn new(key: PrivateKey) -> Result<Self, ConversionError> {
+        if !P::allows(&key.alg) {
+            return Err(format!(
+                "Key type {} cannot be used for {}",
+                key.alg.as_str(),
+                P::NAME
+            )
+            .into());
+        }
+        Ok(Self {
+            key,
+            _purpose: PhantomData,
+        })
+    }
+
+    pub fn alg(&self) -> &KeyType {
+        &self.key.alg
+    }
+
+    pub fn public_key(&self) -> Result<VerKey, ConversionError> {
+        self.key.public_key()
+    }
+
+    /// Rebind the same key material to another purpose. Reusing a key across
+    /// purposes should be limited to audited call sites.
+    pub fn repurpose<Q: KeyPurpose>(self) -> Result<TypedKey<Q>, ConversionError> {
+        TypedKey::new(self.key)
+    }
+
+    /// Release the underlying private key. This bypasses the purpose
+    /// restriction and should be limited to audited call sites.
+    pub fn into_untyped(self) -> PrivateKey {
+        self.key
+    }
+}
+
+impl TypedKey<Signing> {
+    pub fn sign<M: AsRef<[u8]>>(&self, message: M) -> Result<Vec<u8>, ConversionError> {
+        self.key.sign(message)
+    }
+}
+
+impl TypedKey<Authentication> {
+    /// Sign an authentication challenge
+    pub fn sign_challenge<M: AsRef<[u8]>>(&self, challenge: M) -> Result<Vec<u8>, ConversionError> {
+        self.key.sign(challenge)
+    }
+
+    /// Derive the X25519 key agreement key for an Ed25519 authentication
+    /// key, as used by legacy DIDComm envelopes
+    pub fn to_key_agreement(&self) -> Result<TypedKey<KeyAgreement>, ConversionError> {
+        TypedKey::new(self.key.key_exchange()?)
+    }
+}
+
+impl TypedKey<KeyAgreement> {
+    /// Perform a raw X25519 Diffie-Hellman exchange
+    pub fn dh(&self, their_vk: &EncodedVerKey) -> Result<SharedSecret, ConversionError> {
+        self.key.dh(their_vk)
+    }
+
+    /// Perform a hashed ECDH key agreement
+    pub fn key_agreement(&self, their_vk: &VerKey) -> Result<Vec<u8>, ConversionError> {
+        self.key.key_agreement(their_vk)
+    }
+}
+
+impl<P: KeyPurpose> std::fmt::Debug for TypedKey<P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TypedKey")
+            .field("purpose", &P::NAME)
+            .field("alg", &self.key.alg)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::test_fixtures::TEST3;
+
+    #[test]
+    fn purpose_restrictions() {
+        let sk = TEST3.private_key();
+        let signing = TypedKey::<Signing>::new(sk.clone()).unwrap();
+        let vk = signing.public_key().unwrap();
+        let sig = signing.sign(b"message").unwrap();
+        assert!(vk.verify_signature(b"message", &sig).unwrap());
+        assert!(TypedKey::<KeyAgreement>::new(sk.clone()).is_err());
+
+        let auth = TypedKey::<Authentication>::new(sk).unwrap();
+        let agreement = auth.to_key_agreement().unwrap();
+        assert_eq!(agreement.alg(), &KeyType::X25519);
+        assert!(agreement.repurpose::<Signing>().is_err());
+
+        let other = PrivateKey::generate(None).unwrap();
+        let other_vk = other.public_key().unwrap().as_base58().unwrap();
+        let agreement = auth.to_key_agreement().unwrap();
+        assert_eq!(
+            agreement.dh(&other_vk).unwrap(),
+            other.dh(&vk.as_base58().unwrap()).unwrap()
+        );
+    }
+}
@@ -0,0 +1,236 @@
+//! Key event logs with pre-rotation. Each event reveals the current verkey
+//! and commits to the digest of the next one, and is signed by the current
+//! key, so that a compromised key cannot be used to rotate to a key of the
+//! attacker's choosing.
+
+use ursa::hash::sha2::{Digest, Sha256};
+
+use super::{EncodedVerKey, PrivateKey, Signature};
+use crate::base58;
+use crate::error::ConversionError;
+
+/// The domain separation tag for key event signatures
+const ROTATION_DOMAIN: &[u8] = b"indy-key-rotation-v1";
+
+/// Compute the commitment to a future verkey: the base58 SHA-256 digest of
+/// the key type and raw key bytes
+pub fn key_commitment(verkey: &EncodedVerKey) -> Result<String, ConversionError> {
+    let key = verkey.key_bytes()?;
+    let mut hasher = Sha256::new();
+    for part in &[verkey.alg.as_str().as_bytes(), &key[..]] {
+        hasher.input(&(part.len() as u32).to_be_bytes());
+        hasher.input(part);
+    }
+    Ok(base58::encode(hasher.result()))
+}
+
+/// A single inception or rotation event in a key event log
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RotationEvent {
+    /// The position of the event in the log, starting at zero for inception
+    pub sequence: u64,
+    /// The digest of the previous event, absent for inception
+    pub prior: Option<String>,
+    /// The verkey established by this event
+    pub verkey: EncodedVerKey,
+    /// The commitment to the verkey of the next rotation
+    pub next_commitment: String,
+    /// The signature over the event by `verkey`
+    pub signature: Signature,
+}
+
+impl RotationEvent {
+    /// Create the inception event for a new key event log
+    pub fn incept(current: &PrivateKey, next: &EncodedVerKey) -> Result<Self, ConversionError> {
+        Self::signed(0, None, current, key_commitment(next)?)
+    }
+
+    /// Create the event which rotates to the key committed to by this event,
+    /// committing in turn to the next verkey
+    pub fn rotate(
+        &self,
+        new_current: &PrivateKey,
+        next: &EncodedVerKey,
+    ) -> Result<Self, ConversionError> {
+        let sequence = self
+            .sequence
+            .checked_add(1)
+            .ok_or("Key event sequence exhausted")?;
+        Self::signed(
+            sequence,
+            Some(self.digest()?),
+            new_current,
+            key_commitment(next)?,
+        )
+    }
+
+    fn signed(
+        sequence: u64,
+        prior: Option<String>,
+        current: &PrivateKey,
+        next_commitment: String,
+    ) -> Result<Self, ConversionError> {
+        let verkey = current.public_key()?.as_base58()?;
+        let message = event_message(sequence, prior.as_deref(), &verkey, &next_commitment)?;
+        let signature = Signature::sign(current, message)?;
+        Ok(Self {
+            sequence,
+            prior,
+            verkey,
+            next_commitment,
+            signature,
+        })
+    }
+
+    /// Check that the event is signed by the verkey it establishes
+    pub fn verify_signature(&self) -> Result<bool, ConversionError> {
+        let message = self.message()?;
+        self.signature.verify(message, &self.verkey.decode()?)
+    }
+
+    /// The base58 SHA-256 digest of the signed event, referenced by the
+    /// following event
+    pub fn digest(&self) -> Result<String, ConversionError> {
+        let mut hasher = Sha256::new();
+        hasher.input(self.message()?);
+        hasher.input(&self.signature.bytes);
+        Ok(base58::encode(hasher.result()))
+    }
+
+    fn message(&self) -> Result<Vec<u8>, ConversionError> {
+        event_message(
+            self.sequence,
+            self.prior.as_deref(),
+            &self.verkey,
+            &self.next_commitment,
+        )
+    }
+}
+
+/// Encode the signed structure: the domain tag and sequence number followed
+/// by the length-prefixed prior digest, key type, verkey and next commitment
+fn event_message(
+    sequence: u64,
+    prior: Option<&str>,
+    verkey: &EncodedVerKey,
+    next_commitment: &str,
+) -> Result<Vec<u8>, ConversionError> {
+    let key = verkey.key_bytes()?;
+    let mut message = ROTATION_DOMAIN.to_vec();
+    message.extend_from_slice(&sequence.to_be_bytes());
+    for part in &[
+        prior.unwrap_or_default().as_bytes(),
+        verkey.alg.as_str().as_bytes(),
+        &key[..],
+        next_commitment.as_bytes(),
+    ] {
+        message.extend_from_slice(&(part.len() as u32).to_be_bytes());
+        message.extend_from_slice(part);
+    }
+    Ok(message)
+}
+
+/// A validated sequence of key events, starting from an inception event
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyEventLog {
+    events: Vec<RotationEvent>,
+}
+
+impl KeyEventLog {
+    /// Start a log from a signed inception event
+    pub fn new(inception: RotationEvent) -> Result<Self, ConversionError> {
+        if inception.sequence != 0 || inception.prior.is_some() {
+            return Err("Key event log must start with an inception event".into());
+        }
+        if !inception.verify_signature()? {
+            return Err("Invalid signature on inception event".into());
+        }
+        Ok(Self {
+            events: vec![inception],
+        })
+    }
+
+    /// Validate a complete history of key events
+    pub fn from_events<I: IntoIterator<Item = RotationEvent>>(
+        events: I,
+    ) -> Result<Self, ConversionError> {
+        let mut events = events.into_iter();
+        let mut log = Self::new(events.next().ok_or("Key event log is empty")?)?;
+        for event in events {
+            log.append(event)?;
+        }
+        Ok(log)
+    }
+
+    /// Append a rotation event, checking that it follows the latest event,
+    /// reveals the committed verkey and is signed by that verkey
+    pub fn append(&mut self, event: RotationEvent) -> Result<(), ConversionError> {
+        let last = self.latest();
+        if Some(event.sequence) != last.sequence.checked_add(1) {
+            return Err("Key event is out of sequence".into());
+        }
+        if event.prior.as_deref() != Some(last.digest()?.as_str()) {
+            return Err("Key event does not reference the previous event".into());
+        }
+        if key_commitment(&event.verkey)? != last.next_commitment {
+            return Err("Rotated verkey does not match the prior commitment".into());
+        }
+        if !event.verify_signature()? {
+            return Err("Invalid signature on key event".into());
+        }
+        self.events.push(event);
+        Ok(())
+    }
+
+    /// The most recent event in the log
+    pub fn latest(&self) -> &RotationEvent {
+        // the log always contains the inception event
+        &self.events[self.events.len() - 1]
+    }
+
+    /// The verkey established by the most recent event
+    pub fn current_verkey(&self) -> &EncodedVerKey {
+        &self.latest().verkey
+    }
+
+    pub fn events(&self) -> &[RotationEvent] {
+        &self.events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pre_rotation_log() {
+        let keys = (0..3)
+            .map(|_| PrivateKey::generate(None).unwrap())
+            .collect::<Vec<_>>();
+        let verkeys = keys
+            .iter()
+            .map(|sk| sk.public_key().unwrap().as_base58().unwrap())
+            .collect::<Vec<_>>();
+
+        let inception = RotationEvent::incept(&keys[0], &verkeys[1]).unwrap();
+        let mut log = KeyEventLog::new(inception.clone()).unwrap();
+        let rotation = inception.rotate(&keys[1], &verkeys[2]).unwrap();
+        log.append(rotation.clone()).unwrap();
+        assert_eq!(log.current_verkey(), &verkeys[1]);
+        assert_eq!(
+            KeyEventLog::from_events(log.events().to_vec()).unwrap(),
+            log
+        );
+
+        // rotating to an uncommitted key is rejected
+        let other = PrivateKey::generate(None).unwrap();
+        let forged = rotation.rotate(&other, &verkeys[0]).unwrap();
+        assert!(log.append(forged).is_err());
+
+        // tampered events are rejected
+        let mut tampered = rotation.rotate(&keys[2], &verkeys[0]).unwrap();
+        tampered.next_commitment = key_commitment(&verkeys[1]).unwrap();
+        assert!(log.append(tampered).is_err());
+        assert!(KeyEventLog::from_events(vec![rotation]).is_err());
+    }
+}
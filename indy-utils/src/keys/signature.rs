@@ -0,0 +1,214 @@
+use std::collections::BTreeMap;
+
+#[cfg(feature = "ed25519")]
+use super::PrivateKey;
+use super::{KeyType, VerKey};
+use crate::base58;
+use crate::error::ConversionError;
+
+/// A signature tagged with the type of the key that produced it
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Signature {
+    pub alg: KeyType,
+    pub bytes: Vec<u8>,
+}
+
+impl Signature {
+    pub fn new<S: AsRef<[u8]>>(bytes: S, alg: Option<KeyType>) -> Self {
+        Self {
+            alg: alg.unwrap_or_default(),
+            bytes: bytes.as_ref().to_vec(),
+        }
+    }
+
+    /// Sign a message, tagging the signature with the type of the signing key
+    #[cfg(feature = "ed25519")]
+    pub fn sign<M: AsRef<[u8]>>(sk: &PrivateKey, message: M) -> Result<Self, ConversionError> {
+        Ok(Self::new(sk.sign(message)?, Some(sk.alg.clone())))
+    }
+
+    /// Parse a base58 signature, optionally suffixed with `:alg` for
+    /// key types other than ed25519
+    pub fn from_str(sig: &str) -> Result<Self, ConversionError> {
+        let (sig, alg) = match sig.find(':') {
+            Some(pos) => (&sig[..pos], Some(KeyType::from(&sig[pos + 1..]))),
+            None => (sig, None),
+        };
+        Ok(Self::new(base58::decode(sig)?, alg))
+    }
+
+    /// Verify the signature with a verkey of the same type
+    #[cfg(feature = "ed25519")]
+    pub fn verify<M: AsRef<[u8]>>(
+        &self,
+        message: M,
+        verkey: &VerKey,
+    ) -> Result<bool, ConversionError> {
+        if verkey.alg != self.alg {
+            return Err("Signature type does not match the verkey".into());
+        }
+        verkey.verify_signature(message, &self.bytes)
+    }
+}
+
+impl AsRef<[u8]> for Signature {
+    fn as_ref(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl std::fmt::Display for Signature {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sig = base58::encode(&self.bytes);
+        if self.alg == KeyType::default() {
+            f.write_str(&sig)
+        } else {
+            write!(f, "{}:{}", sig, self.alg.as_str())
+        }
+    }
+}
+
+/// A set of signatures over the same message, ordered by signer key
+/// identifier so that the serialized form is canonical
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MultiSignature {
+    signatures: BTreeMap<String, Signature>,
+}
+
+impl MultiSignature {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the signature of a signer, returning any previous signature
+    pub fn insert<K: Into<String>>(&mut self, kid: K, sig: Signature) -> Option<Signature> {
+        self.signatures.insert(kid.into(), sig)
+    }
+
+    pub fn get(&self, kid: &str) -> Option<&Signature> {
+        self.signatures.get(kid)
+    }
+
+    pub fn remove(&mut self, kid: &str) -> Option<Signature> {
+        self.signatures.remove(kid)
+    }
+
+    pub fn len(&self) -> usize {
+        self.signatures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.signatures.is_empty()
+    }
+
+    /// Iterate the signatures in signer key identifier order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Signature)> {
+        self.signatures.iter().map(|(kid, sig)| (kid.as_str(), sig))
+    }
+
+    /// Check that at least `threshold` signers with a known verkey have
+    /// produced a valid signature over the message. Signers for which
+    /// `lookup` returns no verkey are ignored.
+    #[cfg(feature = "ed25519")]
+    pub fn verify_threshold<M, F>(
+        &self,
+        message: M,
+        threshold: usize,
+        lookup: F,
+    ) -> Result<bool, ConversionError>
+    where
+        M: AsRef<[u8]>,
+        F: Fn(&str) -> Option<VerKey>,
+    {
+        let message = message.as_ref();
+        let mut valid = 0;
+        for (kid, sig) in self.iter() {
+            if let Some(verkey) = lookup(kid) {
+                if sig.alg == verkey.alg && sig.verify(message, &verkey)? {
+                    valid += 1;
+                }
+            }
+        }
+        Ok(valid >= threshold)
+    }
+
+    /// The canonical JSON serialization: an object mapping each signer key
+    /// identifier to its signature, with sorted keys
+    #[cfg(feature = "serde_support")]
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::Value::Object(
+            self.iter()
+                .map(|(kid, sig)| (kid.to_string(), sig.to_string().into()))
+                .collect(),
+        )
+    }
+
+    /// Parse the canonical JSON serialization
+    #[cfg(feature = "serde_support")]
+    pub fn from_json(value: &serde_json::Value) -> Result<Self, ConversionError> {
+        let obj = value
+            .as_object()
+            .ok_or("Multi-signature must be a JSON object")?;
+        let mut result = Self::new();
+        for (kid, sig) in obj {
+            let sig = sig.as_str().ok_or("Signature must be a string")?;
+            result.insert(kid.as_str(), Signature::from_str(sig)?);
+        }
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_string_round_trip() {
+        let sig = Signature::new([1u8; 64], None);
+        assert!(!sig.to_string().contains(':'));
+        assert_eq!(Signature::from_str(&sig.to_string()).unwrap(), sig);
+
+        let sig = Signature::new([2u8; 64], Some(KeyType::P256));
+        assert!(sig.to_string().ends_with(":p256"));
+        assert_eq!(Signature::from_str(&sig.to_string()).unwrap(), sig);
+    }
+
+    #[cfg(all(feature = "ed25519", feature = "serde_support"))]
+    #[test]
+    fn multi_signature_threshold() {
+        let message = b"multi-signed request";
+        let keys = (0..3)
+            .map(|_| PrivateKey::generate(None).unwrap())
+            .collect::<Vec<_>>();
+        let kids = keys
+            .iter()
+            .map(|sk| sk.public_key().unwrap().as_base58().unwrap().key)
+            .collect::<Vec<_>>();
+        let lookup = |kid: &str| {
+            kids.iter()
+                .position(|k| k == kid)
+                .map(|idx| keys[idx].public_key().unwrap())
+        };
+
+        let mut multi = MultiSignature::new();
+        for (sk, kid) in keys.iter().zip(kids.iter()).take(2) {
+            multi.insert(kid.as_str(), Signature::sign(sk, message).unwrap());
+        }
+        assert!(multi.verify_threshold(message, 2, &lookup).unwrap());
+        assert!(!multi.verify_threshold(message, 3, &lookup).unwrap());
+        assert!(!multi.verify_threshold(b"other", 1, &lookup).unwrap());
+
+        let json = multi.to_json();
+        let mut sorted = kids[..2].to_vec();
+        sorted.sort();
+        assert_eq!(
+            json.as_object()
+                .unwrap()
+                .keys()
+                .cloned()
+                .collect::<Vec<_>>(),
+            sorted
+        );
+        assert_eq!(MultiSignature::from_json(&json).unwrap(), multi);
+    }
+}
@@ -0,0 +1,33 @@
+use std::future::Future;
+use std::pin::Pin;
+
+use super::EncodedVerKey;
+#[cfg(feature = "ed25519")]
+use super::PrivateKey;
+use crate::error::ConversionError;
+
+/// A boxed future returned by `AsyncSigner` operations
+pub type SignFuture<'a, T> = Pin<Box<dyn Future<Output = Result<T, ConversionError>> + Send + 'a>>;
+
+/// A trait for delegating signatures to an external key store, such as a
+/// KMS or HSM, so that the signing key never needs to be held in process
+/// memory
+pub trait AsyncSigner: Sync {
+    /// Sign a message, returning the raw signature bytes
+    fn sign<'a>(&'a self, message: &'a [u8]) -> SignFuture<'a, Vec<u8>>;
+
+    /// Get the verkey corresponding to the signing key
+    fn verkey(&self) -> Result<EncodedVerKey, ConversionError>;
+}
+
+/// The software implementation, using a private key held in memory
+#[cfg(feature = "ed25519")]
+impl AsyncSigner for PrivateKey {
+    fn sign<'a>(&'a self, message: &'a [u8]) -> SignFuture<'a, Vec<u8>> {
+        Box::pin(async move { PrivateKey::sign(self, message) })
+    }
+
+    fn verkey(&self) -> Result<EncodedVerKey, ConversionError> {
+        self.public_key()?.as_base58()
+    }
+}
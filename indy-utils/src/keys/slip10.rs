@@ -0,0 +1,134 @@
+use hmac::{Hmac, Mac};
+use ursa::hash::sha2::Sha512;
+use zeroize::Zeroizing;
+
+use crate::error::ConversionError;
+
+type HmacSha512 = Hmac<Sha512>;
+
+const ED25519_CURVE_SEED: &[u8] = b"ed25519 seed";
+const HARDENED_OFFSET: u32 = 0x8000_0000;
+
+/// A SLIP-0010 extended private key: the 32 byte Ed25519 seed and the chain code
+pub struct ExtendedKey {
+    pub key: Zeroizing<[u8; 32]>,
+    pub chain_code: Zeroizing<[u8; 32]>,
+}
+
+impl ExtendedKey {
+    /// Derive the master key from a wallet seed
+    pub fn master(seed: &[u8]) -> Result<Self, ConversionError> {
+        if seed.len() < 16 || seed.len() > 64 {
+            return Err("Invalid length for SLIP-0010 seed".into());
+        }
+        Ok(Self::from_hmac(ED25519_CURVE_SEED, &[seed]))
+    }
+
+    /// Derive a hardened child key. Ed25519 only supports hardened derivation,
+    /// so the index is always treated as hardened.
+    pub fn derive_child(&self, index: u32) -> Self {
+        let index = (index | HARDENED_OFFSET).to_be_bytes();
+        Self::from_hmac(&self.chain_code[..], &[&[0u8], &self.key[..], &index])
+    }
+
+    /// Derive the key at a path such as `m/44'/0'/0'`
+    pub fn derive_path(seed: &[u8], path: &str) -> Result<Self, ConversionError> {
+        let indexes = parse_path(path)?;
+        let mut result = Self::master(seed)?;
+        for index in indexes {
+            result = result.derive_child(index);
+        }
+        Ok(result)
+    }
+
+    fn from_hmac(key: &[u8], data: &[&[u8]]) -> Self {
+        let mut mac = HmacSha512::new_varkey(key).expect("HMAC accepts keys of any length");
+        for part in data {
+            mac.input(part);
+        }
+        let output = Zeroizing::new(mac.result().code());
+        let mut key = Zeroizing::new([0u8; 32]);
+        let mut chain_code = Zeroizing::new([0u8; 32]);
+        key.copy_from_slice(&output[..32]);
+        chain_code.copy_from_slice(&output[32..]);
+        Self { key, chain_code }
+    }
+}
+
+/// Parse a derivation path. Every segment must be hardened, marked with a
+/// trailing `'` or `H`.
+fn parse_path(path: &str) -> Result<Vec<u32>, ConversionError> {
+    let mut segments = path.split('/');
+    if segments.next() != Some("m") {
+        return Err("Derivation path must start with 'm'".into());
+    }
+    segments
+        .map(|segment| {
+            let index = segment
+                .strip_suffix('\'')
+                .or_else(|| segment.strip_suffix('H'))
+                .or_else(|| segment.strip_suffix('h'))
+                .ok_or_else(|| {
+                    ConversionError::from_msg(format!(
+                        "Ed25519 derivation requires hardened path segments: {}",
+                        segment
+                    ))
+                })?;
+            match index.parse::<u32>() {
+                Ok(index) if index < HARDENED_OFFSET => Ok(index),
+                _ => Err(ConversionError::from_msg(format!(
+                    "Invalid derivation path segment: {}",
+                    segment
+                ))),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slip10_ed25519_vector_1() {
+        // test vector 1 for ed25519 from the SLIP-0010 specification
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+        let cases = [
+            (
+                "m",
+                "90046a93de5380a72b5e45010748567d5ea02bbf6522f979e05c0d8d8ca9fffb",
+                "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7",
+            ),
+            (
+                "m/0'",
+                "8b59aa11380b624e81507a27fedda59fea6d0b779a778918a2fd3590e16e9c69",
+                "68e0fe46dfb67e368c75379acec591dad19df3cde26e63b93a8e704f1dade7a3",
+            ),
+            (
+                "m/0'/1'",
+                "a320425f77d1b5c2505a6b1b27382b37368ee640e3557c315416801243552f14",
+                "b1d0bad404bf35da785a64ca1ac54b2617211d2777696fbffaf208f746ae84f2",
+            ),
+            (
+                "m/0H/1H/2H/2H/1000000000H",
+                "68789923a0cac2cd5a29172a475fe9e0fb14cd6adb5ad98a3fa70333e7afa230",
+                "8f94d394a8e8fd6b1bc2f3f49f5c47e385281d5c17e65324b0f62483e37e8793",
+            ),
+        ];
+        for (path, chain_code, key) in cases.iter() {
+            let derived = ExtendedKey::derive_path(&seed, path).unwrap();
+            assert_eq!(hex::encode(&derived.chain_code[..]), *chain_code);
+            assert_eq!(hex::encode(&derived.key[..]), *key);
+        }
+    }
+
+    #[test]
+    fn slip10_invalid_paths() {
+        let seed = [0u8; 32];
+        assert!(ExtendedKey::derive_path(&seed, "0'/1'").is_err());
+        assert!(ExtendedKey::derive_path(&seed, "m/0").is_err());
+        assert!(ExtendedKey::derive_path(&seed, "m/2147483648'").is_err());
+        assert!(ExtendedKey::derive_path(&seed, "m/x'").is_err());
+        assert!(ExtendedKey::derive_path(&seed[..8], "m").is_err());
+    }
+}
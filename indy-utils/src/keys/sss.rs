@@ -0,0 +1,168 @@
+//! Shamir secret sharing over GF(256), for splitting a wallet seed into
+//! recovery shares
+
+use rand::{rngs::OsRng, CryptoRng, RngCore};
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::error::ConversionError;
+
+/// A single share of a split secret
+#[derive(Clone, Debug, Zeroize)]
+#[zeroize(drop)]
+pub struct Share {
+    /// The x-coordinate of the share, which must be non-zero
+    pub index: u8,
+    /// The evaluation of each secret byte's polynomial at `index`
+    pub value: Vec<u8>,
+}
+
+impl ConstantTimeEq for Share {
+    fn ct_eq(&self, other: &Self) -> subtle::Choice {
+        self.index.ct_eq(&other.index) & self.value.ct_eq(&other.value)
+    }
+}
+
+impl PartialEq for Share {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for Share {}
+
+/// Split a secret into `shares` shares, any `threshold` of which recover it
+pub fn split(secret: &[u8], threshold: u8, shares: u8) -> Result<Vec<Share>, ConversionError> {
+    split_with_rng(secret, threshold, shares, &mut OsRng)
+}
+
+/// Split a secret using a specific random number generator
+pub fn split_with_rng<G: CryptoRng + RngCore>(
+    secret: &[u8],
+    threshold: u8,
+    shares: u8,
+    rng: &mut G,
+) -> Result<Vec<Share>, ConversionError> {
+    if secret.is_empty() {
+        return Err("Cannot split an empty secret".into());
+    }
+    if threshold == 0 || threshold > shares {
+        return Err("Threshold must be between 1 and the number of shares".into());
+    }
+
+    let mut result = (1..=shares)
+        .map(|index| Share {
+            index,
+            value: Vec::with_capacity(secret.len()),
+        })
+        .collect::<Vec<_>>();
+    let mut coeffs = Zeroizing::new(vec![0u8; threshold as usize]);
+    for byte in secret {
+        coeffs[0] = *byte;
+        rng.fill_bytes(&mut coeffs[1..]);
+        for share in result.iter_mut() {
+            share.value.push(eval_poly(&coeffs, share.index));
+        }
+    }
+    Ok(result)
+}
+
+/// Recover a secret from at least `threshold` distinct shares. Supplying
+/// fewer shares than the threshold produces an unrelated value.
+pub fn combine(shares: &[Share]) -> Result<Zeroizing<Vec<u8>>, ConversionError> {
+    let first = shares.first().ok_or("No shares provided")?;
+    let len = first.value.len();
+    for (idx, share) in shares.iter().enumerate() {
+        if share.index == 0 {
+            return Err("Invalid share index".into());
+        }
+        if share.value.len() != len {
+            return Err("Inconsistent share lengths".into());
+        }
+        if shares[..idx].iter().any(|s| s.index == share.index) {
+            return Err("Duplicate share index".into());
+        }
+    }
+
+    // Lagrange interpolation at x = 0
+    let mut secret = Zeroizing::new(vec![0u8; len]);
+    for (i, share) in shares.iter().enumerate() {
+        let mut basis = 1u8;
+        for (j, other) in shares.iter().enumerate() {
+            if i != j {
+                // x_j / (x_j - x_i), where subtraction is xor
+                basis = gf_mul(basis, gf_div(other.index, other.index ^ share.index));
+            }
+        }
+        for (out, value) in secret.iter_mut().zip(share.value.iter()) {
+            *out ^= gf_mul(*value, basis);
+        }
+    }
+    Ok(secret)
+}
+
+fn eval_poly(coeffs: &[u8], x: u8) -> u8 {
+    coeffs
+        .iter()
+        .rev()
+        .fold(0u8, |acc, coeff| gf_mul(acc, x) ^ coeff)
+}
+
+/// Multiply in GF(256) with the AES reduction polynomial, without branching
+/// on secret values
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut result = 0u8;
+    for _ in 0..8 {
+        result ^= a & (b & 1).wrapping_neg();
+        let carry = (a >> 7).wrapping_neg();
+        a = (a << 1) ^ (0x1b & carry);
+        b >>= 1;
+    }
+    result
+}
+
+fn gf_div(a: u8, b: u8) -> u8 {
+    // b^254 is the multiplicative inverse of b
+    let mut inv = 1u8;
+    let mut base = b;
+    let mut exp = 254u8;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            inv = gf_mul(inv, base);
+        }
+        base = gf_mul(base, base);
+        exp >>= 1;
+    }
+    gf_mul(a, inv)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gf_arithmetic() {
+        assert_eq!(gf_mul(0x53, 0xca), 0x01);
+        assert_eq!(gf_div(0x01, 0x53), 0xca);
+        for x in 1..=255u8 {
+            assert_eq!(gf_mul(gf_div(1, x), x), 1);
+        }
+    }
+
+    #[test]
+    fn split_and_combine() {
+        let seed = b"000000000000000000000000000Test3";
+        let shares = split(seed, 3, 5).unwrap();
+        assert_eq!(shares.len(), 5);
+        assert_eq!(&combine(&shares[..3]).unwrap()[..], &seed[..]);
+        assert_eq!(&combine(&shares[2..]).unwrap()[..], &seed[..]);
+        let subset = vec![shares[4].clone(), shares[0].clone(), shares[2].clone()];
+        assert_eq!(&combine(&subset).unwrap()[..], &seed[..]);
+        assert_ne!(&combine(&shares[..2]).unwrap()[..], &seed[..]);
+
+        let duplicate = vec![shares[0].clone(), shares[0].clone(), shares[1].clone()];
+        assert!(combine(&duplicate).is_err());
+        assert!(split(seed, 4, 3).is_err());
+        assert!(split(seed, 0, 3).is_err());
+    }
+}
@@ -0,0 +1,103 @@
+//! Deterministic keys and DIDs for tests, available to the crate's own tests
+//! and to downstream integration tests with the `test_utils` feature. The
+//! seeds are public, so these keys must never be used outside of tests.
+
+use super::{EncodedVerKey, PrivateKey, VerKey};
+use crate::did::{generate_did, ShortDidValue};
+
+pub const TEST_SEED_0: &[u8; 32] = b"000000000000000000000000000Test0";
+pub const TEST_SEED_1: &[u8; 32] = b"000000000000000000000000000Test1";
+pub const TEST_SEED_2: &[u8; 32] = b"000000000000000000000000000Test2";
+pub const TEST_SEED_3: &[u8; 32] = b"000000000000000000000000000Test3";
+pub const TEST_SEED_4: &[u8; 32] = b"000000000000000000000000000Test4";
+
+/// A named Ed25519 identity derived from a fixed seed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TestIdentity {
+    pub name: &'static str,
+    pub seed: &'static [u8; 32],
+}
+
+impl TestIdentity {
+    pub fn private_key(&self) -> PrivateKey {
+        PrivateKey::from_seed(self.seed).expect("Error creating test signing key")
+    }
+
+    pub fn verkey(&self) -> VerKey {
+        self.private_key()
+            .public_key()
+            .expect("Error deriving test verkey")
+    }
+
+    /// The base58-encoded verkey
+    pub fn encoded_verkey(&self) -> EncodedVerKey {
+        self.verkey()
+            .as_base58()
+            .expect("Error encoding test verkey")
+    }
+
+    /// The unqualified DID derived from the verkey
+    pub fn did(&self) -> ShortDidValue {
+        let (did, _sk, _vk) = generate_did(Some(self.seed)).expect("Error creating test DID");
+        did
+    }
+}
+
+pub const TEST0: TestIdentity = TestIdentity {
+    name: "Test0",
+    seed: TEST_SEED_0,
+};
+pub const TEST1: TestIdentity = TestIdentity {
+    name: "Test1",
+    seed: TEST_SEED_1,
+};
+pub const TEST2: TestIdentity = TestIdentity {
+    name: "Test2",
+    seed: TEST_SEED_2,
+};
+pub const TEST3: TestIdentity = TestIdentity {
+    name: "Test3",
+    seed: TEST_SEED_3,
+};
+pub const TEST4: TestIdentity = TestIdentity {
+    name: "Test4",
+    seed: TEST_SEED_4,
+};
+
+/// All of the test identities, in order
+pub const TEST_IDENTITIES: [TestIdentity; 5] = [TEST0, TEST1, TEST2, TEST3, TEST4];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fixtures_are_stable() {
+        let expected = [
+            (
+                "GAnfrJJm6fJeh2mFcANEpuW4X7EVWJ5B2F7D7iC8ihpt",
+                "UqBuxfK7Qv8eNBwYgCFDap",
+            ),
+            (
+                "Dp23y8cjwURrxEwdBPDRA3RpLpNtjvzRSAVKnG6wxeDX",
+                "QWFZKRPHTmvZCdJrC69vbZ",
+            ),
+            (
+                "Dat8DtXYZfYNZkrt7FF3K9DSoSWbJwCCd4W8ho67Kgkk",
+                "Q69cbx673aiYfsYryxYA7t",
+            ),
+            (
+                "BxDuzHpkHQv7bECmhC8dU74KysaoF98PjMcLidiyGkec",
+                "M6UTiBxATPkmhvLayhkXYA",
+            ),
+            (
+                "Eo3NJ6hQhfpecaJvB9qJgQK3a9roUXv5Vj6q8eyKkeZV",
+                "SJsecPfEhkCkNBWZDVtE8k",
+            ),
+        ];
+        for (ident, (verkey, did)) in TEST_IDENTITIES.iter().zip(expected.iter()) {
+            assert_eq!(ident.encoded_verkey().key, *verkey);
+            assert_eq!(ident.did().0, *did);
+        }
+    }
+}
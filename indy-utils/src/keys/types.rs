@@ -1,17 +1,49 @@
 use aead::generic_array::{ArrayLength, GenericArray};
-
+use once_cell::sync::OnceCell;
+use rand::{CryptoRng, RngCore};
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, Zeroizing};
+
+#[cfg(feature = "base64")]
+use crate::base64;
+use crate::error::ConversionError;
 use crate::random::random_array;
 
 pub const KEY_ENC_BASE58: &'static str = "base58";
+pub const KEY_ENC_MULTIBASE: &'static str = "multibase";
+
+/// The multibase prefix for base58btc encoding
+pub const MULTIBASE_BASE58_PREFIX: char = 'z';
 
 pub const KEY_TYPE_ED25519: &'static str = "ed25519";
 pub const KEY_TYPE_X25519: &'static str = "x25519";
+pub const KEY_TYPE_SECP256K1: &'static str = "secp256k1";
+pub const KEY_TYPE_P256: &'static str = "p256";
+pub const KEY_TYPE_BLS12381G1: &'static str = "bls12381g1";
+pub const KEY_TYPE_BLS12381G2: &'static str = "bls12381g2";
+
+/// The alternative name accepted for secp256k1 keys
+const KEY_TYPE_K256: &'static str = "k256";
+
+const MULTICODEC_ED25519_PUB: &'static [u8] = &[0xed, 0x01];
+const MULTICODEC_X25519_PUB: &'static [u8] = &[0xec, 0x01];
+const MULTICODEC_SECP256K1_PUB: &'static [u8] = &[0xe7, 0x01];
+const MULTICODEC_P256_PUB: &'static [u8] = &[0x80, 0x24];
+const MULTICODEC_BLS12381G1_PUB: &'static [u8] = &[0xea, 0x01];
+const MULTICODEC_BLS12381G2_PUB: &'static [u8] = &[0xeb, 0x01];
 
 /// Enum of known and unknown key types
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum KeyType {
     ED25519,
     X25519,
+    /// secp256k1, also known as K-256
+    Secp256k1,
+    P256,
+    /// BLS12-381 keys in the G1 group
+    Bls12381G1,
+    /// BLS12-381 keys in the G2 group
+    Bls12381G2,
     Other(String),
 }
 
@@ -20,10 +52,36 @@ impl KeyType {
         match keytype.to_ascii_lowercase().as_str() {
             KEY_TYPE_ED25519 => KeyType::ED25519,
             KEY_TYPE_X25519 => KeyType::X25519,
+            KEY_TYPE_SECP256K1 | KEY_TYPE_K256 => KeyType::Secp256k1,
+            KEY_TYPE_P256 => KeyType::P256,
+            KEY_TYPE_BLS12381G1 => KeyType::Bls12381G1,
+            KEY_TYPE_BLS12381G2 => KeyType::Bls12381G2,
             _ => KeyType::Other(keytype.to_owned()),
         }
     }
 
+    /// Look up the key type for a multicodec-prefixed public key, returning
+    /// the key type and the remaining key bytes
+    pub fn from_multicodec(key: &[u8]) -> Option<(KeyType, &[u8])> {
+        [
+            KeyType::ED25519,
+            KeyType::X25519,
+            KeyType::Secp256k1,
+            KeyType::P256,
+            KeyType::Bls12381G1,
+            KeyType::Bls12381G2,
+        ]
+        .iter()
+        .find_map(|alg| {
+            let prefix = alg.multicodec_prefix()?;
+            if key.starts_with(prefix) {
+                Some((alg.clone(), &key[prefix.len()..]))
+            } else {
+                None
+            }
+        })
+    }
+
     pub fn is_known(&self) -> bool {
         match self {
             Self::Other(_) => false,
@@ -35,9 +93,26 @@ impl KeyType {
         match self {
             Self::ED25519 => KEY_TYPE_ED25519,
             Self::X25519 => KEY_TYPE_X25519,
+            Self::Secp256k1 => KEY_TYPE_SECP256K1,
+            Self::P256 => KEY_TYPE_P256,
+            Self::Bls12381G1 => KEY_TYPE_BLS12381G1,
+            Self::Bls12381G2 => KEY_TYPE_BLS12381G2,
             Self::Other(t) => t.as_str(),
         }
     }
+
+    /// Get the multicodec prefix for public keys of this type
+    pub fn multicodec_prefix(&self) -> Option<&'static [u8]> {
+        match self {
+            Self::ED25519 => Some(MULTICODEC_ED25519_PUB),
+            Self::X25519 => Some(MULTICODEC_X25519_PUB),
+            Self::Secp256k1 => Some(MULTICODEC_SECP256K1_PUB),
+            Self::P256 => Some(MULTICODEC_P256_PUB),
+            Self::Bls12381G1 => Some(MULTICODEC_BLS12381G1_PUB),
+            Self::Bls12381G2 => Some(MULTICODEC_BLS12381G2_PUB),
+            Self::Other(_) => None,
+        }
+    }
 }
 
 impl std::string::ToString for KeyType {
@@ -75,6 +150,8 @@ impl From<String> for KeyType {
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum KeyEncoding {
     BASE58,
+    /// Multibase (base58btc) encoding of a multicodec-prefixed key
+    MULTIBASE,
     Other(String),
 }
 
@@ -82,6 +159,7 @@ impl KeyEncoding {
     pub fn from_str(keyenc: &str) -> KeyEncoding {
         match keyenc.to_ascii_lowercase().as_str() {
             KEY_ENC_BASE58 => KeyEncoding::BASE58,
+            KEY_ENC_MULTIBASE => KeyEncoding::MULTIBASE,
             _ => KeyEncoding::Other(keyenc.to_owned()),
         }
     }
@@ -89,6 +167,7 @@ impl KeyEncoding {
     pub fn as_str(&self) -> &str {
         match self {
             Self::BASE58 => KEY_ENC_BASE58,
+            Self::MULTIBASE => KEY_ENC_MULTIBASE,
             Self::Other(e) => e.as_str(),
         }
     }
@@ -125,8 +204,27 @@ impl From<String> for KeyEncoding {
     }
 }
 
+/// The format of a key identifier derived from a public key
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum KidFormat {
+    /// The base58 encoding of the raw key
+    Base58,
+    /// The multibase, multicodec-prefixed key fingerprint used by did:key
+    Fingerprint,
+    /// The base64url-encoded JWK thumbprint defined by RFC 7638
+    JwkThumbprint,
+    /// A did:key DID URL, as used in DIDComm v2 protected headers
+    DidKey,
+}
+
+impl Default for KidFormat {
+    fn default() -> Self {
+        Self::Base58
+    }
+}
+
 /// A secure key representation for fixed-length keys
-#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Zeroize)]
+#[derive(Clone, Debug, Zeroize)]
 pub struct ArrayKey<L: ArrayLength<u8>>(GenericArray<u8, L>);
 
 impl<L: ArrayLength<u8>> ArrayKey<L> {
@@ -135,15 +233,81 @@ impl<L: ArrayLength<u8>> ArrayKey<L> {
         Self(GenericArray::from_slice(data.as_ref()).clone())
     }
 
+    /// Load a key from a slice, checking that the length matches
+    pub fn try_from_slice<D: AsRef<[u8]>>(data: D) -> Result<Self, ConversionError> {
+        let data = data.as_ref();
+        if data.len() != L::USIZE {
+            return Err(format!("Invalid key length, expected {} bytes", L::USIZE).into());
+        }
+        Ok(Self::from_slice(data))
+    }
+
+    /// Load a key from its hex encoding
+    pub fn from_hex(key: &str) -> Result<Self, ConversionError> {
+        let bytes =
+            Zeroizing::new(hex::decode(key).map_err(|err| ("Error decoding hex key", err))?);
+        Self::try_from_slice(&bytes[..])
+    }
+
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.0.as_slice())
+    }
+
+    /// Load a key from its base64-URL encoding, with or without padding
+    #[cfg(feature = "base64")]
+    pub fn from_b64(key: &str) -> Result<Self, ConversionError> {
+        let bytes = Zeroizing::new(base64::decode_urlsafe(key)?);
+        Self::try_from_slice(&bytes[..])
+    }
+
+    /// Encode the key as unpadded base64-URL
+    #[cfg(feature = "base64")]
+    pub fn to_b64(&self) -> String {
+        base64::encode_urlsafe_nopad(self.0.as_slice())
+    }
+
     #[inline]
     pub fn extract(self) -> GenericArray<u8, L> {
-        self.0
+        self.0.clone()
     }
 
     #[inline]
     pub fn random() -> Self {
         Self(random_array())
     }
+
+    #[inline]
+    pub fn random_with_rng<R: CryptoRng + RngCore>(rng: &mut R) -> Self {
+        let mut key = GenericArray::default();
+        rng.fill_bytes(key.as_mut_slice());
+        Self(key)
+    }
+}
+
+impl<L: ArrayLength<u8>> ConstantTimeEq for ArrayKey<L> {
+    fn ct_eq(&self, other: &Self) -> subtle::Choice {
+        self.0.as_slice().ct_eq(other.0.as_slice())
+    }
+}
+
+impl<L: ArrayLength<u8>> PartialEq for ArrayKey<L> {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl<L: ArrayLength<u8>> Eq for ArrayKey<L> {}
+
+impl<L: ArrayLength<u8>> std::hash::Hash for ArrayKey<L> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.0.as_slice().hash(state)
+    }
+}
+
+impl<L: ArrayLength<u8>> Drop for ArrayKey<L> {
+    fn drop(&mut self) {
+        self.0.as_mut_slice().zeroize()
+    }
 }
 
 impl<L: ArrayLength<u8>> Default for ArrayKey<L> {
@@ -172,11 +336,117 @@ impl<L: ArrayLength<u8>> std::ops::DerefMut for ArrayKey<L> {
     }
 }
 
+/// The raw output of an X25519 Diffie-Hellman exchange, zeroized on drop
+#[derive(Clone, Zeroize)]
+#[zeroize(drop)]
+pub struct SharedSecret([u8; 32]);
+
+impl SharedSecret {
+    #[inline]
+    pub(crate) fn new(secret: [u8; 32]) -> Self {
+        Self(secret)
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for SharedSecret {
+    fn as_ref(&self) -> &[u8] {
+        &self.0[..]
+    }
+}
+
+impl std::fmt::Debug for SharedSecret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SharedSecret").field(&"<secret>").finish()
+    }
+}
+
+impl ConstantTimeEq for SharedSecret {
+    fn ct_eq(&self, other: &Self) -> subtle::Choice {
+        self.0.ct_eq(&other.0)
+    }
+}
+
+impl PartialEq for SharedSecret {
+    fn eq(&self, other: &Self) -> bool {
+        self.ct_eq(other).into()
+    }
+}
+
+impl Eq for SharedSecret {}
+
+/// A lazily computed value derived from key material, such as the X25519
+/// form of an Ed25519 key. The cache is ignored when comparing keys, and the
+/// value is recomputed if the source key bytes have changed since it was filled.
+pub(crate) struct KeyCache<T>(OnceCell<(Zeroizing<Vec<u8>>, T)>);
+
+impl<T: Clone> KeyCache<T> {
+    pub fn new() -> Self {
+        Self(OnceCell::new())
+    }
+
+    /// Fetch the cached value for the source bytes, or compute and store it
+    pub fn get_or_try_init<E, F>(&self, source: &[u8], init: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Result<T, E>,
+    {
+        match self.0.get() {
+            Some((cached, value)) if bool::from(cached[..].ct_eq(source)) => Ok(value.clone()),
+            Some(_) => init(),
+            None => {
+                let value = init()?;
+                let _ = self.0.set((Zeroizing::new(source.to_vec()), value.clone()));
+                Ok(value)
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.0.take();
+    }
+}
+
+impl<T: Clone> Clone for KeyCache<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> std::fmt::Debug for KeyCache<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("KeyCache")
+    }
+}
+
+impl<T> PartialEq for KeyCache<T> {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl<T> Eq for KeyCache<T> {}
+
+impl<T> PartialOrd for KeyCache<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for KeyCache<T> {
+    fn cmp(&self, _other: &Self) -> std::cmp::Ordering {
+        std::cmp::Ordering::Equal
+    }
+}
+
 #[cfg(feature = "serde")]
 mod serde {
     use serde::{de::Visitor, Deserialize, Deserializer, Serialize, Serializer};
 
-    use super::{ArrayKey, ArrayLength, GenericArray};
+    use super::{ArrayKey, ArrayLength};
 
     impl<L: ArrayLength<u8>> Serialize for ArrayKey<L> {
         fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -213,8 +483,7 @@ mod serde {
         where
             E: serde::de::Error,
         {
-            let key = hex::decode(value).map_err(E::custom)?;
-            Ok(ArrayKey(GenericArray::clone_from_slice(key.as_slice())))
+            ArrayKey::from_hex(value).map_err(E::custom)
         }
     }
 }
@@ -26,6 +26,10 @@ pub use qualifiable::Qualifiable;
 mod validation;
 pub use validation::Validatable;
 
+/// Pluggable cryptographic backends
+#[cfg(feature = "ed25519")]
+pub mod backend;
+
 /// base58 encoding and decoding
 pub mod base58;
 
@@ -35,6 +39,9 @@ pub mod did;
 /// Indy signing keys and verification keys
 pub mod keys;
 
+/// Nonce generation for AEAD ciphers
+pub mod nonce;
+
 /// Random number generation
 pub mod random;
 
@@ -42,18 +49,46 @@ pub mod random;
 #[cfg(feature = "base64")]
 pub mod base64;
 
+/// Public-key authenticated encryption and sealed boxes
+#[cfg(feature = "pack")]
+pub mod crypto;
+
 /// Hash algorithms
 #[cfg(feature = "hash")]
 pub mod hash;
 
+/// Compact JWS signing and verification with Ed25519 keys
+#[cfg(feature = "pack")]
+pub mod jose;
+
+/// Conversion of JSON-LD documents to RDF datasets
+#[cfg(all(feature = "hash", feature = "serde_support"))]
+pub mod jsonld;
+
+/// Key derivation functions
+#[cfg(feature = "hash")]
+pub mod kdf;
+
+/// Out-of-band invitations
+#[cfg(feature = "pack")]
+pub mod oob;
+
 /// Message packing and unpacking
 #[cfg(feature = "pack")]
 pub mod pack;
 
+/// RDF dataset canonicalization (URDNA2015)
+#[cfg(feature = "hash")]
+pub mod rdfc;
+
 /// Generation of normalized ledger transaction for signing
 #[cfg(feature = "txn_signature")]
 pub mod txn_signature;
 
+/// W3C Verifiable Credentials and Presentations with Ed25519Signature2020 proofs
+#[cfg(all(feature = "ed25519", feature = "hash", feature = "serde_support"))]
+pub mod vc;
+
 /// Wallet query language
 #[cfg(feature = "wql")]
 pub mod wql;
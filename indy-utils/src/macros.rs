@@ -7,6 +7,20 @@ macro_rules! unwrap_opt_or_return {
     };
 }
 
+/// Enter a debug-level `tracing` span for the remainder of the enclosing
+/// scope. This is a no-op unless the `tracing` feature is enabled.
+#[cfg(feature = "tracing")]
+macro_rules! trace_span {
+    ($($args:tt)+) => {
+        let _span = tracing::debug_span!($($args)+).entered();
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! trace_span {
+    ($($args:tt)+) => {};
+}
+
 /// Used to optionally add Serialize and Deserialize traits to Qualifiable types
 #[cfg(feature = "serde")]
 #[macro_export]
@@ -0,0 +1,175 @@
+use aead::generic_array::{
+    typenum::{U12, U24},
+    ArrayLength, GenericArray,
+};
+use rand::{rngs::OsRng, CryptoRng, RngCore};
+
+use crate::error::ConversionError;
+
+/// The number of trailing bytes of a `CounterNonce` used for the counter
+/// and final-chunk flag
+pub const COUNTER_NONCE_SUFFIX_SIZE: usize = 5;
+
+/// A single-use random nonce. The nonce is neither `Copy` nor `Clone`, so
+/// it must be consumed by the encryption operation it was generated for.
+#[derive(Debug, PartialEq, Eq)]
+pub struct RandomNonce<L: ArrayLength<u8>>(GenericArray<u8, L>);
+
+impl<L: ArrayLength<u8>> RandomNonce<L> {
+    /// Generate a nonce using the system random number generator
+    pub fn generate() -> Self {
+        let nonce = Self::generate_with_rng(&mut OsRng);
+        debug_check_fresh(&nonce.0);
+        nonce
+    }
+
+    /// Generate a nonce using a specific random number generator
+    pub fn generate_with_rng<R: CryptoRng + RngCore>(rng: &mut R) -> Self {
+        let mut nonce = GenericArray::default();
+        rng.fill_bytes(nonce.as_mut_slice());
+        Self(nonce)
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+
+    pub fn into_inner(self) -> GenericArray<u8, L> {
+        self.0
+    }
+}
+
+impl<L: ArrayLength<u8>> AsRef<[u8]> for RandomNonce<L> {
+    fn as_ref(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+}
+
+/// A 96-bit nonce for ChaCha20-Poly1305 and AES-GCM
+pub type ChaChaNonce = RandomNonce<U12>;
+
+/// A 192-bit extended nonce for XChaCha20-Poly1305, which is large enough
+/// to be generated randomly for every message under the same key
+pub type XChaChaNonce = RandomNonce<U24>;
+
+/// A sequence of nonces for streaming encryption, formed from a random
+/// prefix, a 32-bit big-endian chunk counter and a final-chunk flag. Each
+/// nonce is issued only once, and no nonces are issued after the final one.
+#[derive(Debug)]
+pub struct CounterNonce<L: ArrayLength<u8>> {
+    nonce: GenericArray<u8, L>,
+    counter: Option<u32>,
+    finished: bool,
+}
+
+impl<L: ArrayLength<u8>> CounterNonce<L> {
+    /// Start a sequence with a random prefix from the system random number generator
+    pub fn random() -> Self {
+        Self::random_with_rng(&mut OsRng)
+    }
+
+    /// Start a sequence with a random prefix from a specific random number generator
+    pub fn random_with_rng<R: CryptoRng + RngCore>(rng: &mut R) -> Self {
+        let mut nonce = GenericArray::<u8, L>::default();
+        let prefix_len = Self::prefix_size();
+        rng.fill_bytes(&mut nonce[..prefix_len]);
+        Self {
+            nonce,
+            counter: Some(0),
+            finished: false,
+        }
+    }
+
+    /// Resume a sequence from a prefix received from the sender
+    pub fn from_prefix(prefix: &[u8]) -> Result<Self, ConversionError> {
+        if prefix.len() != Self::prefix_size() {
+            return Err("Invalid size for nonce prefix".into());
+        }
+        let mut nonce = GenericArray::<u8, L>::default();
+        nonce[..prefix.len()].copy_from_slice(prefix);
+        Ok(Self {
+            nonce,
+            counter: Some(0),
+            finished: false,
+        })
+    }
+
+    /// The size of the random prefix
+    pub fn prefix_size() -> usize {
+        L::USIZE - COUNTER_NONCE_SUFFIX_SIZE
+    }
+
+    pub fn prefix(&self) -> &[u8] {
+        &self.nonce[..Self::prefix_size()]
+    }
+
+    /// Issue the next nonce in the sequence, flagging whether it is for
+    /// the final chunk
+    pub fn next(&mut self, last: bool) -> Result<GenericArray<u8, L>, ConversionError> {
+        if self.finished {
+            return Err("Nonce sequence has already been finalized".into());
+        }
+        let counter = self.counter.ok_or("Nonce sequence has been exhausted")?;
+        let prefix_len = Self::prefix_size();
+        let mut nonce = self.nonce.clone();
+        nonce[prefix_len..L::USIZE - 1].copy_from_slice(&counter.to_be_bytes());
+        nonce[L::USIZE - 1] = last as u8;
+        self.counter = counter.checked_add(1);
+        self.finished = last;
+        Ok(nonce)
+    }
+}
+
+#[cfg(debug_assertions)]
+thread_local! {
+    static LAST_NONCE: std::cell::RefCell<Vec<u8>> = std::cell::RefCell::new(Vec::new());
+}
+
+/// In debug builds, assert that the system random number generator has not
+/// produced the same nonce twice in a row
+#[inline]
+fn debug_check_fresh(nonce: &[u8]) {
+    #[cfg(debug_assertions)]
+    LAST_NONCE.with(|last| {
+        let mut last = last.borrow_mut();
+        debug_assert!(last.as_slice() != nonce, "Random nonce reused");
+        last.clear();
+        last.extend_from_slice(nonce);
+    });
+    #[cfg(not(debug_assertions))]
+    let _ = nonce;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn random_nonces() {
+        let nonce1 = XChaChaNonce::generate();
+        let nonce2 = XChaChaNonce::generate();
+        assert_eq!(nonce1.as_slice().len(), 24);
+        assert_ne!(nonce1, nonce2);
+        assert_eq!(ChaChaNonce::generate().into_inner().len(), 12);
+    }
+
+    #[test]
+    fn counter_nonces() {
+        let mut seq = CounterNonce::<U12>::from_prefix(&[1u8; 7]).unwrap();
+        assert_eq!(
+            seq.next(false).unwrap().as_slice(),
+            &[1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0]
+        );
+        assert_eq!(
+            seq.next(true).unwrap().as_slice(),
+            &[1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 1, 1]
+        );
+        assert!(seq.next(false).is_err());
+
+        let mut seq = CounterNonce::<U12>::random();
+        seq.counter = Some(u32::MAX);
+        seq.next(false).unwrap();
+        assert!(seq.next(true).is_err());
+        assert!(CounterNonce::<U24>::from_prefix(&[0u8; 7]).is_err());
+    }
+}
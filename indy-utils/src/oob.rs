@@ -0,0 +1,601 @@
+//! Out-of-band invitations as defined by Aries RFC 0434
+
+use std::convert::TryFrom;
+
+use crate::did::key::resolve_verkey;
+use crate::did::peer::{create_peer2, PeerPurpose};
+use crate::did::service::{DidCommService, Service, DIDCOMM_MESSAGING};
+use crate::did::{ShortDidValue, DID};
+use crate::error::ConversionError;
+use crate::keys::{KeyType, VerKey};
+use crate::pack::{random_message_id, Attachment, AttachmentData};
+use crate::{base58, base64};
+use crate::{Validatable, ValidationError};
+
+/// Message type of an out-of-band invitation
+pub const OOB_INVITATION_TYPE: &'static str = "https://didcomm.org/out-of-band/1.1/invitation";
+/// Message type prefix accepted for incoming invitations of any 1.x version
+const OOB_INVITATION_PREFIX: &'static str = "https://didcomm.org/out-of-band/1.";
+/// Legacy message type of an out-of-band invitation
+pub const OOB_INVITATION_TYPE_LEGACY: &'static str =
+    "did:sov:BzCbsNYhMrjHiqZDTUASHg;spec/out-of-band/1.0/invitation";
+
+/// The DID Exchange handshake protocol (RFC 0023)
+pub const DIDEXCHANGE_PROTOCOL: &'static str = "https://didcomm.org/didexchange/1.0";
+/// The Connections handshake protocol (RFC 0160)
+pub const CONNECTIONS_PROTOCOL: &'static str = "https://didcomm.org/connections/1.0";
+
+/// Message type of a legacy connection invitation
+pub const CONNECTION_INVITATION_TYPE: &'static str =
+    "https://didcomm.org/connections/1.0/invitation";
+/// Legacy message type of a connection invitation
+pub const CONNECTION_INVITATION_TYPE_LEGACY: &'static str =
+    "did:sov:BzCbsNYhMrjHiqZDTUASHg;spec/connections/1.0/invitation";
+
+/// The query parameter holding an encoded invitation
+const OOB_QUERY_PARAM: &'static str = "oob";
+/// The query parameter holding an encoded connection invitation
+const CONNECTION_QUERY_PARAM: &'static str = "c_i";
+
+/// An out-of-band invitation message
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Invitation {
+    #[serde(rename = "@type")]
+    pub msg_type: String,
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub goal_code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub goal: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub accept: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub handshake_protocols: Vec<String>,
+    #[serde(
+        rename = "requests~attach",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub requests_attach: Vec<Attachment>,
+    pub services: Vec<OobService>,
+}
+
+/// A service listed in an invitation, either as a resolvable DID or inline
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum OobService {
+    Did(DID),
+    Inline(DidCommService),
+}
+
+impl Invitation {
+    /// Parse and validate a JSON invitation
+    pub fn from_json(json: &[u8]) -> Result<Self, ConversionError> {
+        let invitation: Self = serde_json::from_slice(json)
+            .map_err(|err| ConversionError::from_msg_err("Invalid out-of-band invitation", err))?;
+        invitation.validate()?;
+        Ok(invitation)
+    }
+
+    /// Parse and validate an invitation URL carrying an `oob` query parameter
+    pub fn from_url(url: &str) -> Result<Self, ConversionError> {
+        let encoded = query_param(url, OOB_QUERY_PARAM)?;
+        Self::from_json(&base64::decode_urlsafe(encoded)?)
+    }
+
+    /// Encode the invitation as an `oob` query parameter of a base URL
+    pub fn to_url(&self, base_url: &str) -> Result<String, ConversionError> {
+        let json = serde_json::to_vec(self)
+            .map_err(|err| ConversionError::from_msg_err("Error encoding invitation", err))?;
+        Ok(append_query_param(
+            base_url,
+            OOB_QUERY_PARAM,
+            &base64::encode_urlsafe_nopad(json),
+        ))
+    }
+}
+
+impl Validatable for Invitation {
+    fn validate(&self) -> Result<(), ValidationError> {
+        if !self.msg_type.starts_with(OOB_INVITATION_PREFIX)
+            && self.msg_type != OOB_INVITATION_TYPE_LEGACY
+        {
+            return Err(invalid!("Unsupported invitation type: {}", self.msg_type));
+        }
+        if self.id.is_empty() {
+            return Err(invalid!("Invitation identifier must not be empty"));
+        }
+        if self.handshake_protocols.is_empty() && self.requests_attach.is_empty() {
+            return Err(invalid!(
+                "Invitation must include handshake protocols or attached requests"
+            ));
+        }
+        if self.services.is_empty() {
+            return Err(invalid!("Invitation must list at least one service"));
+        }
+        for service in &self.services {
+            match service {
+                OobService::Did(did) => did.validate()?,
+                OobService::Inline(service) => service.validate()?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A builder for out-of-band invitations
+#[derive(Clone, Debug)]
+pub struct InvitationBuilder {
+    invitation: Invitation,
+}
+
+impl InvitationBuilder {
+    pub fn new() -> Self {
+        Self {
+            invitation: Invitation {
+                msg_type: OOB_INVITATION_TYPE.to_owned(),
+                id: random_message_id(),
+                label: None,
+                goal_code: None,
+                goal: None,
+                accept: Vec::new(),
+                handshake_protocols: Vec::new(),
+                requests_attach: Vec::new(),
+                services: Vec::new(),
+            },
+        }
+    }
+
+    pub fn label<S: Into<String>>(mut self, label: S) -> Self {
+        self.invitation.label = Some(label.into());
+        self
+    }
+
+    pub fn goal<C: Into<String>, G: Into<String>>(mut self, goal_code: C, goal: G) -> Self {
+        self.invitation.goal_code = Some(goal_code.into());
+        self.invitation.goal = Some(goal.into());
+        self
+    }
+
+    /// Add an accepted media type profile, such as `didcomm/aip2;env=rfc19`
+    pub fn accept<S: Into<String>>(mut self, accept: S) -> Self {
+        self.invitation.accept.push(accept.into());
+        self
+    }
+
+    /// Add a handshake protocol, in order of preference
+    pub fn handshake_protocol<S: Into<String>>(mut self, protocol: S) -> Self {
+        self.invitation.handshake_protocols.push(protocol.into());
+        self
+    }
+
+    /// Attach a request message, such as a credential offer, as inline JSON
+    pub fn request(mut self, message: serde_json::Value) -> Self {
+        let id = format!("request-{}", self.invitation.requests_attach.len());
+        self.invitation.requests_attach.push(Attachment {
+            id,
+            mime_type: Some("application/json".to_owned()),
+            enc: None,
+            data: AttachmentData {
+                json: Some(message),
+                ..Default::default()
+            },
+        });
+        self
+    }
+
+    /// Add a service by a resolvable DID
+    pub fn service_did(mut self, did: &DID) -> Self {
+        self.invitation.services.push(OobService::Did(did.clone()));
+        self
+    }
+
+    /// Add an inline DIDComm service block
+    pub fn inline_service(mut self, service: DidCommService) -> Self {
+        self.invitation.services.push(OobService::Inline(service));
+        self
+    }
+
+    /// Add a did:peer:2 service for an Ed25519 verkey, which is listed for
+    /// authentication and, in X25519 form, for key agreement
+    pub fn peer_service(
+        mut self,
+        verkey: &VerKey,
+        endpoint: &str,
+        routing_keys: Option<Vec<String>>,
+    ) -> Result<Self, ConversionError> {
+        if verkey.alg != KeyType::ED25519 {
+            return Err("Peer DID services require an ed25519 verkey".into());
+        }
+        let mut service = Service::new("#didcomm", DIDCOMM_MESSAGING, endpoint);
+        service.routing_keys = routing_keys.unwrap_or_default();
+        let did = create_peer2(
+            &[
+                (PeerPurpose::Authentication, verkey.clone()),
+                (PeerPurpose::KeyAgreement, verkey.key_exchange()?),
+            ],
+            &[service],
+        )?;
+        self.invitation.services.push(OobService::Did(did));
+        Ok(self)
+    }
+
+    /// Validate and return the invitation
+    pub fn build(self) -> Result<Invitation, ConversionError> {
+        self.invitation.validate()?;
+        Ok(self.invitation)
+    }
+}
+
+impl Default for InvitationBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A legacy connection invitation as defined by Aries RFC 0160, listing
+/// either a public DID or inline keys and an endpoint
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionInvitation {
+    #[serde(rename = "@type")]
+    pub msg_type: String,
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// The public DID of the inviter, which legacy agents send unqualified
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub did: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub recipient_keys: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub routing_keys: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_endpoint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_url: Option<String>,
+}
+
+impl ConnectionInvitation {
+    /// Create an invitation with inline base58 recipient keys and an endpoint
+    pub fn new(
+        label: Option<&str>,
+        recipient_keys: Vec<String>,
+        service_endpoint: &str,
+        routing_keys: Option<Vec<String>>,
+    ) -> Result<Self, ConversionError> {
+        let invitation = Self {
+            msg_type: CONNECTION_INVITATION_TYPE.to_owned(),
+            id: random_message_id(),
+            label: label.map(str::to_owned),
+            did: None,
+            recipient_keys,
+            routing_keys: routing_keys.unwrap_or_default(),
+            service_endpoint: Some(service_endpoint.to_owned()),
+            image_url: None,
+        };
+        invitation.validate()?;
+        Ok(invitation)
+    }
+
+    /// Create an invitation to connect with a public DID
+    pub fn with_did(label: Option<&str>, did: &DID) -> Self {
+        Self {
+            msg_type: CONNECTION_INVITATION_TYPE.to_owned(),
+            id: random_message_id(),
+            label: label.map(str::to_owned),
+            did: Some(did.to_string()),
+            recipient_keys: Vec::new(),
+            routing_keys: Vec::new(),
+            service_endpoint: None,
+            image_url: None,
+        }
+    }
+
+    /// The public DID of the inviter, qualifying a legacy unqualified DID
+    /// as `did:sov`
+    pub fn qualified_did(&self) -> Result<Option<DID>, ValidationError> {
+        match self.did.as_deref() {
+            Some(did) if did.starts_with("did:") => DID::parse(did).map(Some),
+            Some(did) => DID::from_unqualified(&ShortDidValue(did.to_owned()), None).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Parse and validate a JSON invitation
+    pub fn from_json(json: &[u8]) -> Result<Self, ConversionError> {
+        let invitation: Self = serde_json::from_slice(json)
+            .map_err(|err| ConversionError::from_msg_err("Invalid connection invitation", err))?;
+        invitation.validate()?;
+        Ok(invitation)
+    }
+
+    /// Parse and validate an invitation URL carrying a `c_i` query parameter.
+    /// Both URL-safe and standard base64 encodings are accepted.
+    pub fn from_url(url: &str) -> Result<Self, ConversionError> {
+        let encoded = query_param(url, CONNECTION_QUERY_PARAM)?;
+        let json = base64::decode_urlsafe(encoded).or_else(|_| base64::decode(encoded))?;
+        Self::from_json(&json)
+    }
+
+    /// Encode the invitation as a `c_i` query parameter of a base URL
+    pub fn to_url(&self, base_url: &str) -> Result<String, ConversionError> {
+        let json = serde_json::to_vec(self)
+            .map_err(|err| ConversionError::from_msg_err("Error encoding invitation", err))?;
+        Ok(append_query_param(
+            base_url,
+            CONNECTION_QUERY_PARAM,
+            &base64::encode_urlsafe_nopad(json),
+        ))
+    }
+}
+
+impl Validatable for ConnectionInvitation {
+    fn validate(&self) -> Result<(), ValidationError> {
+        if self.msg_type != CONNECTION_INVITATION_TYPE
+            && self.msg_type != CONNECTION_INVITATION_TYPE_LEGACY
+        {
+            return Err(invalid!("Unsupported invitation type: {}", self.msg_type));
+        }
+        match (&self.did, &self.service_endpoint) {
+            (Some(_), None) if self.recipient_keys.is_empty() => {
+                self.qualified_did()?;
+            }
+            (None, Some(endpoint)) => {
+                for key in self.recipient_keys.iter().chain(self.routing_keys.iter()) {
+                    let decoded = base58::decode(key).map_err(ValidationError::from_msg)?;
+                    if decoded.len() != 32 {
+                        return Err(invalid!("Invalid invitation key: {}", key));
+                    }
+                }
+                let mut service = DidCommService::new("#inline", endpoint);
+                service.recipient_keys = self.recipient_keys.clone();
+                service.validate()?;
+            }
+            _ => {
+                return Err(invalid!(
+                "Connection invitation must include either a DID or recipient keys and an endpoint"
+            ))
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TryFrom<&ConnectionInvitation> for Invitation {
+    type Error = ConversionError;
+
+    fn try_from(invitation: &ConnectionInvitation) -> Result<Self, Self::Error> {
+        invitation.validate()?;
+        let service = match (invitation.qualified_did()?, &invitation.service_endpoint) {
+            (Some(did), _) => OobService::Did(did),
+            (None, Some(endpoint)) => {
+                let mut service = DidCommService::new("#inline", endpoint);
+                service.recipient_keys = invitation.recipient_keys.clone();
+                service.routing_keys = invitation.routing_keys.clone();
+                OobService::Inline(service)
+            }
+            (None, None) => return Err("Connection invitation has no service".into()),
+        };
+        let converted = Invitation {
+            msg_type: OOB_INVITATION_TYPE.to_owned(),
+            id: invitation.id.clone(),
+            label: invitation.label.clone(),
+            goal_code: None,
+            goal: None,
+            accept: Vec::new(),
+            handshake_protocols: vec![CONNECTIONS_PROTOCOL.to_owned()],
+            requests_attach: Vec::new(),
+            services: vec![service],
+        };
+        converted.validate()?;
+        Ok(converted)
+    }
+}
+
+impl TryFrom<&Invitation> for ConnectionInvitation {
+    type Error = ConversionError;
+
+    /// Convert an out-of-band invitation offering the connections protocol
+    /// and a single service. did:key recipient and routing keys are
+    /// converted to base58 verkeys.
+    fn try_from(invitation: &Invitation) -> Result<Self, Self::Error> {
+        if !invitation
+            .handshake_protocols
+            .iter()
+            .any(|protocol| protocol == CONNECTIONS_PROTOCOL)
+        {
+            return Err("Invitation does not offer the connections protocol".into());
+        }
+        if !invitation.requests_attach.is_empty() {
+            return Err("Connection invitations cannot carry attached requests".into());
+        }
+        let mut converted = match invitation.services.as_slice() {
+            [OobService::Did(did)] => ConnectionInvitation::with_did(None, did),
+            [OobService::Inline(service)] => {
+                let keys = |keys: &[String]| {
+                    keys.iter()
+                        .map(|key| legacy_key(key))
+                        .collect::<Result<Vec<_>, _>>()
+                };
+                ConnectionInvitation::new(
+                    None,
+                    keys(&service.recipient_keys)?,
+                    &service.service_endpoint,
+                    Some(keys(&service.routing_keys)?),
+                )?
+            }
+            _ => return Err("Connection invitations must list exactly one service".into()),
+        };
+        converted.id = invitation.id.clone();
+        converted.label = invitation.label.clone();
+        Ok(converted)
+    }
+}
+
+/// Convert a did:key reference to a base58 verkey, as used by legacy agents
+fn legacy_key(key: &str) -> Result<String, ConversionError> {
+    if key.starts_with("did:key:") {
+        let did = DID::parse(key.split('#').next().unwrap_or_default())?;
+        Ok(resolve_verkey(&did)?.as_base58()?.key)
+    } else {
+        Ok(key.to_owned())
+    }
+}
+
+fn query_param<'a>(url: &'a str, name: &str) -> Result<&'a str, ConversionError> {
+    let query = url
+        .split('#')
+        .next()
+        .and_then(|url| url.splitn(2, '?').nth(1))
+        .ok_or("Invitation URL has no query string")?;
+    let value = query
+        .split('&')
+        .find_map(|param| param.strip_prefix(name)?.strip_prefix('='))
+        .ok_or_else(|| format!("Invitation URL has no {} parameter", name))?;
+    // padding may be present, and percent-encoded
+    Ok(value.trim_end_matches("%3D").trim_end_matches('='))
+}
+
+fn append_query_param(base_url: &str, name: &str, value: &str) -> String {
+    let separator = if base_url.contains('?') { '&' } else { '?' };
+    format!("{}{}{}={}", base_url, separator, name, value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::did::peer::resolve_peer;
+    use crate::keys::test_fixtures::TEST1;
+
+    #[test]
+    fn invitation_url_round_trip() {
+        let invitation = InvitationBuilder::new()
+            .label("Faber College")
+            .goal("issue-vc", "To issue a Faber College Graduate credential")
+            .accept("didcomm/v2")
+            .handshake_protocol(DIDEXCHANGE_PROTOCOL)
+            .request(serde_json::json!({"@type": "https://didcomm.org/issue-credential/2.0/offer-credential"}))
+            .peer_service(&TEST1.verkey(), "https://example.com/endpoint", None)
+            .unwrap()
+            .inline_service(
+                DidCommService::new("#inline", "https://example.com/endpoint")
+                    .recipient_key(TEST1.encoded_verkey().key),
+            )
+            .build()
+            .unwrap();
+        assert_eq!(invitation.id.len(), 36);
+
+        let url = invitation.to_url("https://example.com/invite").unwrap();
+        assert!(url.starts_with("https://example.com/invite?oob="));
+        let parsed = Invitation::from_url(&url).unwrap();
+        assert_eq!(parsed, invitation);
+        assert_eq!(
+            parsed.requests_attach[0].data.json.as_ref().unwrap()["@type"],
+            "https://didcomm.org/issue-credential/2.0/offer-credential"
+        );
+
+        match &parsed.services[0] {
+            OobService::Did(did) => {
+                let doc = resolve_peer(did).unwrap();
+                assert_eq!(
+                    doc.service[0].service_endpoint,
+                    "https://example.com/endpoint"
+                );
+            }
+            other => panic!("Unexpected service: {:?}", other),
+        }
+
+        let url = invitation
+            .to_url("https://example.com/invite?lang=en")
+            .unwrap();
+        assert_eq!(Invitation::from_url(&url).unwrap(), invitation);
+    }
+
+    #[test]
+    fn invalid_invitations() {
+        assert!(InvitationBuilder::new()
+            .handshake_protocol(DIDEXCHANGE_PROTOCOL)
+            .build()
+            .is_err());
+        assert!(InvitationBuilder::new()
+            .service_did(&DID::parse("did:sov:LjgpST2rjsoxYegQDRm7EL").unwrap())
+            .build()
+            .is_err());
+        assert!(Invitation::from_url("https://example.com/invite").is_err());
+        assert!(Invitation::from_url("https://example.com/invite?c_i=abc").is_err());
+
+        let json = br#"{
+            "@type": "https://didcomm.org/out-of-band/1.0/invitation",
+            "@id": "69212a3a-d068-4f9d-a2dd-4741bca89af3",
+            "handshake_protocols": ["https://didcomm.org/didexchange/1.0"],
+            "services": ["did:sov:LjgpST2rjsoxYegQDRm7EL"]
+        }"#;
+        Invitation::from_json(json).unwrap();
+        let json = String::from_utf8_lossy(json).replace("did:sov:", "sov:");
+        assert!(Invitation::from_json(json.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn connection_invitation() {
+        let url = "https://example.com/?c_i=eyJAdHlwZSI6ImRpZDpzb3Y6QnpDYnNOWWhNcmpIaXFaRFRVQVNIZztzcGVjL2Nvbm5lY3Rpb25zLzEuMC9pbnZpdGF0aW9uIiwiQGlkIjoiMTIzNDU2Nzg5MDA5ODc2NTQzMjEiLCJsYWJlbCI6IkFsaWNlIiwicmVjaXBpZW50S2V5cyI6WyI4SEg1Z1lFZU5jM3o3UFlYbWQ1NGQ0eDZxQWZDTnJxUXFFQjNuUzdaZnU3SyJdLCJzZXJ2aWNlRW5kcG9pbnQiOiJodHRwczovL2V4YW1wbGUuY29tL2VuZHBvaW50Iiwicm91dGluZ0tleXMiOlsiOEhINWdZRWVOYzN6N1BZWG1kNTRkNHg2cUFmQ05ycVFxRUIzblM3WmZ1N0siXX0=";
+        let invitation = ConnectionInvitation::from_url(url).unwrap();
+        assert_eq!(invitation.label.as_deref(), Some("Alice"));
+        assert_eq!(
+            invitation.recipient_keys,
+            vec!["8HH5gYEeNc3z7PYXmd54d4x6qAfCNrqQqEB3nS7Zfu7K"]
+        );
+        let url = invitation.to_url("https://example.com/").unwrap();
+        assert_eq!(ConnectionInvitation::from_url(&url).unwrap(), invitation);
+
+        let oob = Invitation::try_from(&invitation).unwrap();
+        assert_eq!(oob.handshake_protocols, vec![CONNECTIONS_PROTOCOL]);
+        let mut back = ConnectionInvitation::try_from(&oob).unwrap();
+        back.msg_type = invitation.msg_type.clone();
+        assert_eq!(back, invitation);
+
+        let did = DID::parse("did:sov:LjgpST2rjsoxYegQDRm7EL").unwrap();
+        let public = ConnectionInvitation::with_did(Some("Faber"), &did);
+        public.validate().unwrap();
+        let oob = Invitation::try_from(&public).unwrap();
+        assert_eq!(oob.services, vec![OobService::Did(did.clone())]);
+
+        // legacy agents send the DID unqualified
+        let mut legacy = public;
+        legacy.did = Some("LjgpST2rjsoxYegQDRm7EL".to_owned());
+        assert_eq!(legacy.qualified_did().unwrap(), Some(did));
+        assert_eq!(
+            Invitation::try_from(&legacy).unwrap().services,
+            oob.services
+        );
+
+        // did:key references are converted to base58 verkeys
+        let oob = InvitationBuilder::new()
+            .handshake_protocol(CONNECTIONS_PROTOCOL)
+            .inline_service(
+                DidCommService::new("#inline", "https://example.com/endpoint").recipient_key(
+                    "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp#z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp",
+                ),
+            )
+            .build()
+            .unwrap();
+        let legacy = ConnectionInvitation::try_from(&oob).unwrap();
+        assert_eq!(
+            legacy.recipient_keys,
+            vec!["4zvwRjXUKGfvwnParsHAS3HuSVzV5cA4McphgmoCtajS"]
+        );
+
+        assert!(ConnectionInvitation::new(None, vec![], "https://example.com", None).is_err());
+        assert!(ConnectionInvitation::new(
+            None,
+            vec!["abc".to_owned()],
+            "https://example.com",
+            None
+        )
+        .is_err());
+    }
+}
@@ -3,238 +3,1122 @@ use chacha20poly1305::{
     ChaCha20Poly1305, Key as ChaChaKey,
 };
 
+use std::collections::{HashMap, HashSet};
+use std::io::{ErrorKind, Read, Write};
 use std::string::ToString;
 
+use rand::{rngs::OsRng, CryptoRng, RngCore};
+use zeroize::Zeroizing;
+
+use super::builder::check_extra_headers;
+use super::cipher::{
+    decrypt_content, enc_header_value, encrypt_content, parse_enc_header,
+    PROTECTED_HEADER_ENC_CHACHA,
+};
+use super::error::{PackError, UnpackError};
+use super::jws::{sign_message, verify_message, JWS};
 use super::nacl_box::*;
 use super::types::*;
+use super::v2::{
+    pack_jwe_v2, pack_message_v2, unpack_jwe_v2, unpack_jwe_v2_detached, unpack_jwe_v2_sync,
+};
 use crate::base64;
-use crate::error::ConversionError;
-use crate::keys::{ArrayKey, EncodedVerKey, PrivateKey};
-use crate::random::random_array;
+use crate::keys::{ArrayKey, EncodedVerKey, PrivateKey, VerKey};
+use crate::nonce::CounterNonce;
 
-pub const PROTECTED_HEADER_ENC: &'static str = "xchacha20poly1305_ietf";
+pub const PROTECTED_HEADER_ENC: &'static str = PROTECTED_HEADER_ENC_CHACHA;
 pub const PROTECTED_HEADER_TYP: &'static str = "JWM/1.0";
 pub const PROTECTED_HEADER_ALG_AUTH: &'static str = "Authcrypt";
 pub const PROTECTED_HEADER_ALG_ANON: &'static str = "Anoncrypt";
+pub const PROTECTED_HEADER_SENDER_HINT: &'static str = "sender_hint";
+/// Content type of a payload signed by the sender before encryption
+pub const SIGNED_CONTENT_TYPE: &'static str = "application/didcomm-signed+json";
 
-type KeySize = <ChaCha20Poly1305 as NewAead>::KeySize;
+pub(super) type KeySize = <ChaCha20Poly1305 as NewAead>::KeySize;
 
-const NONCE_SIZE: usize = <ChaCha20Poly1305 as Aead>::NonceSize::USIZE;
 const TAG_SIZE: usize = <ChaCha20Poly1305 as Aead>::TagSize::USIZE;
 
-pub fn pack_message<M: AsRef<[u8]>>(
+/// Payload chunk size used by `pack_message_stream`
+pub const STREAM_CHUNK_SIZE: usize = 1024 * 1024;
+const STREAM_MAX_CHUNK_SIZE: usize = 16 * 1024 * 1024;
+const STREAM_MAX_HEADER_SIZE: usize = 1024 * 1024;
+
+/// Stream nonces are formed from a random prefix, a 32-bit chunk counter and a final-chunk flag
+type StreamNonce = CounterNonce<<ChaCha20Poly1305 as Aead>::NonceSize>;
+
+pub fn pack_message<M: AsRef<[u8]>, R: Into<RecipientKey>>(
+    message: M,
+    receiver_list: Vec<R>,
+    sender_key: Option<PrivateKey>,
+) -> Result<Vec<u8>, PackError> {
+    pack_message_with_rng(message, receiver_list, sender_key, &mut OsRng)
+}
+
+/// Pack a message using the provided random number generator for the content
+/// encryption key, nonces and ephemeral keys. A seeded generator produces
+/// deterministic output for test vectors.
+pub fn pack_message_with_rng<M: AsRef<[u8]>, R: Into<RecipientKey>, G: CryptoRng + RngCore>(
+    message: M,
+    receiver_list: Vec<R>,
+    sender_key: Option<PrivateKey>,
+    rng: &mut G,
+) -> Result<Vec<u8>, PackError> {
+    pack_with_options(
+        message,
+        receiver_list,
+        sender_key,
+        PackFormat::default(),
+        EnvelopeAlg::default(),
+        HeaderMap::new(),
+        None,
+        rng,
+    )
+}
+
+/// Pack a message with the envelope options otherwise set by `PackBuilder`
+pub(super) fn pack_with_options<M: AsRef<[u8]>, R: Into<RecipientKey>, G: CryptoRng + RngCore>(
     message: M,
-    receiver_list: Vec<EncodedVerKey>,
+    receiver_list: Vec<R>,
     sender_key: Option<PrivateKey>,
-) -> Result<Vec<u8>, ConversionError> {
+    format: PackFormat,
+    enc_alg: EnvelopeAlg,
+    mut extra_headers: HeaderMap,
+    sender_hint: Option<String>,
+    rng: &mut G,
+) -> Result<Vec<u8>, PackError> {
     // break early and error out if no receivers keys are provided
     if receiver_list.is_empty() {
-        return Err("No message recipients".into());
+        return Err(PackError::NoRecipients);
     }
 
-    // generate content encryption key that will encrypt `message`
-    let cek = ArrayKey::random();
+    let receiver_list = recipient_keys(receiver_list)?;
+    check_extra_headers(format, &extra_headers)?;
+    insert_sender_hint(&mut extra_headers, sender_hint);
+    match format {
+        PackFormat::Legacy => pack_message_legacy(
+            message,
+            receiver_list,
+            sender_key,
+            enc_alg,
+            &extra_headers,
+            rng,
+        ),
+        PackFormat::DIDCommV2 => pack_message_v2(
+            message,
+            receiver_list,
+            sender_key,
+            enc_alg,
+            &extra_headers,
+            rng,
+        ),
+    }
+}
 
-    let base64_protected = if let Some(sender_key) = sender_key {
-        // returns authcrypted pack_message format. See Wire message format HIPE for details
-        prepare_protected_authcrypt(&cek, receiver_list, &sender_key)?
-    } else {
-        // returns anoncrypted pack_message format. See Wire message format HIPE for details
-        prepare_protected_anoncrypt(&cek, receiver_list)?
+/// Pack a message, returning the envelope with an empty `ciphertext` field
+/// along with the raw encrypted payload to be transmitted separately
+pub fn pack_message_detached<M: AsRef<[u8]>, R: Into<RecipientKey>>(
+    message: M,
+    receiver_list: Vec<R>,
+    sender_key: Option<PrivateKey>,
+    format: Option<PackFormat>,
+    enc_alg: Option<EnvelopeAlg>,
+    extra_headers: Option<HeaderMap>,
+) -> Result<(Vec<u8>, Vec<u8>), PackError> {
+    if receiver_list.is_empty() {
+        return Err(PackError::NoRecipients);
+    }
+
+    let receiver_list = recipient_keys(receiver_list)?;
+    let format = format.unwrap_or_default();
+    let enc_alg = enc_alg.unwrap_or_default();
+    let extra_headers = extra_headers.unwrap_or_default();
+    check_extra_headers(format, &extra_headers)?;
+    match format {
+        PackFormat::Legacy => {
+            let (jwe, ciphertext) = pack_jwe_legacy(
+                message,
+                receiver_list,
+                sender_key,
+                enc_alg,
+                &extra_headers,
+                JweSerialization::Json,
+                &mut OsRng,
+            )?;
+            Ok((serde_json::to_vec(&jwe)?, ciphertext))
+        }
+        PackFormat::DIDCommV2 => {
+            let (jwe, ciphertext) = pack_jwe_v2(
+                message,
+                receiver_list,
+                sender_key,
+                enc_alg,
+                &extra_headers,
+                &mut OsRng,
+            )?;
+            Ok((serde_json::to_vec(&jwe)?, ciphertext))
+        }
+    }
+}
+
+/// Sign a message with the sender key before packing it in an authcrypt
+/// envelope, providing recipients with a non-repudiable signature of origin
+pub fn pack_message_signed<M: AsRef<[u8]>, R: Into<RecipientKey>>(
+    message: M,
+    receiver_list: Vec<R>,
+    sender_key: PrivateKey,
+    format: Option<PackFormat>,
+    enc_alg: Option<EnvelopeAlg>,
+    extra_headers: Option<HeaderMap>,
+) -> Result<Vec<u8>, PackError> {
+    let mut extra_headers = extra_headers.unwrap_or_default();
+    if extra_headers.contains_key("cty") {
+        return Err(PackError::ReservedHeader("cty".to_string()));
+    }
+    extra_headers.insert("cty".to_string(), SIGNED_CONTENT_TYPE.into());
+    let jws = sign_message(message, &sender_key)?;
+    pack_with_options(
+        serde_json::to_vec(&jws)?,
+        receiver_list,
+        Some(sender_key),
+        format.unwrap_or_default(),
+        enc_alg.unwrap_or_default(),
+        extra_headers,
+        None,
+        &mut OsRng,
+    )
+}
+
+/// Pack a message for a single recipient using the compact JWE serialization
+pub fn pack_message_compact<M: AsRef<[u8]>, R: Into<RecipientKey>>(
+    message: M,
+    receiver: R,
+    sender_key: Option<PrivateKey>,
+    format: Option<PackFormat>,
+    enc_alg: Option<EnvelopeAlg>,
+) -> Result<Vec<u8>, PackError> {
+    let receiver = receiver.into();
+    check_recipient_key(0, &receiver)?;
+    let enc_alg = enc_alg.unwrap_or_default();
+    let (protected, encrypted_key, iv, ciphertext, tag) = match format.unwrap_or_default() {
+        PackFormat::Legacy => {
+            let (jwe, ciphertext) = pack_jwe_legacy(
+                message,
+                vec![receiver],
+                sender_key,
+                enc_alg,
+                &HeaderMap::new(),
+                JweSerialization::Compact,
+                &mut OsRng,
+            )?;
+            // the encrypted key is carried in the protected header
+            (jwe.protected, String::new(), jwe.iv, ciphertext, jwe.tag)
+        }
+        PackFormat::DIDCommV2 => {
+            // the recipient key identifier must be moved to the protected header
+            let mut extra_headers = HeaderMap::new();
+            extra_headers.insert(
+                "kid".to_string(),
+                receiver.kid().map_err(PackError::InvalidKey)?.into(),
+            );
+            let (mut jwe, ciphertext) = pack_jwe_v2(
+                message,
+                vec![receiver],
+                sender_key,
+                enc_alg,
+                &extra_headers,
+                &mut OsRng,
+            )?;
+            let recipient = jwe.recipients.remove(0);
+            (
+                jwe.protected,
+                recipient.encrypted_key,
+                jwe.iv,
+                ciphertext,
+                jwe.tag,
+            )
+        }
     };
+    Ok(format!(
+        "{}.{}.{}.{}.{}",
+        protected,
+        encrypted_key.trim_end_matches('='),
+        iv.trim_end_matches('='),
+        base64::encode_urlsafe_nopad(ciphertext),
+        tag.trim_end_matches('=')
+    )
+    .into_bytes())
+}
+
+/// Pack a legacy envelope in which each recipient entry is authcrypted with
+/// its own (for example pairwise) sender key, or anoncrypted when no sender
+/// key is given
+pub fn pack_message_pairwise<M: AsRef<[u8]>, R: Into<RecipientKey>>(
+    message: M,
+    receiver_list: Vec<(R, Option<PrivateKey>)>,
+    enc_alg: Option<EnvelopeAlg>,
+    extra_headers: Option<HeaderMap>,
+) -> Result<Vec<u8>, PackError> {
+    if receiver_list.is_empty() {
+        return Err(PackError::NoRecipients);
+    }
+
+    let enc_alg = enc_alg.unwrap_or_default();
+    let extra_headers = extra_headers.unwrap_or_default();
+    check_extra_headers(PackFormat::Legacy, &extra_headers)?;
+
+    let (receivers, senders): (Vec<RecipientKey>, Vec<Option<PrivateKey>>) = receiver_list
+        .into_iter()
+        .map(|(recip, sender)| (recip.into(), sender))
+        .unzip();
+    for (idx, recipient) in receivers.iter().enumerate() {
+        check_recipient_key(idx, recipient)?;
+    }
+    let recipients = derive_recipients(&receivers)?;
+    let senders = senders
+        .iter()
+        .map(|sender| sender.as_ref().map(derive_sender).transpose())
+        .collect::<Result<Vec<_>, _>>()?;
+    let entries = recipients
+        .iter()
+        .zip(senders.iter())
+        .map(|(recip, sender)| (recip, sender.as_ref()))
+        .collect::<Vec<_>>();
+
+    let (mut jwe, ciphertext) = pack_jwe_entries(
+        message,
+        &entries,
+        enc_alg,
+        &extra_headers,
+        JweSerialization::Json,
+        &mut OsRng,
+    )?;
+    jwe.ciphertext = base64::encode_urlsafe(&ciphertext);
+    Ok(serde_json::to_vec(&jwe)?)
+}
+
+pub(super) fn pack_message_legacy<M: AsRef<[u8]>, G: CryptoRng + RngCore>(
+    message: M,
+    receiver_list: Vec<RecipientKey>,
+    sender_key: Option<PrivateKey>,
+    enc_alg: EnvelopeAlg,
+    extra_headers: &HeaderMap,
+    rng: &mut G,
+) -> Result<Vec<u8>, PackError> {
+    let (mut jwe, ciphertext) = pack_jwe_legacy(
+        message,
+        receiver_list,
+        sender_key,
+        enc_alg,
+        extra_headers,
+        JweSerialization::Json,
+        rng,
+    )?;
+    jwe.ciphertext = base64::encode_urlsafe(&ciphertext);
+    Ok(serde_json::to_vec(&jwe)?)
+}
+
+/// Produce a legacy envelope with an empty `ciphertext` field, along
+/// with the raw encrypted payload
+fn pack_jwe_legacy<M: AsRef<[u8]>, G: CryptoRng + RngCore>(
+    message: M,
+    receiver_list: Vec<RecipientKey>,
+    sender_key: Option<PrivateKey>,
+    enc_alg: EnvelopeAlg,
+    extra_headers: &HeaderMap,
+    serialization: JweSerialization,
+    rng: &mut G,
+) -> Result<(JWE, Vec<u8>), PackError> {
+    let recipients = derive_recipients(&receiver_list)?;
+    let sender = sender_key.as_ref().map(derive_sender).transpose()?;
+    pack_jwe_derived(
+        message,
+        &recipients,
+        sender.as_ref(),
+        enc_alg,
+        extra_headers,
+        serialization,
+        rng,
+    )
+}
+
+/// Produce a legacy envelope using previously derived key exchange keys
+pub(super) fn pack_jwe_derived<M: AsRef<[u8]>, G: CryptoRng + RngCore>(
+    message: M,
+    recipients: &[DerivedRecipient],
+    sender: Option<&DerivedSender>,
+    enc_alg: EnvelopeAlg,
+    extra_headers: &HeaderMap,
+    serialization: JweSerialization,
+    rng: &mut G,
+) -> Result<(JWE, Vec<u8>), PackError> {
+    let entries = recipients
+        .iter()
+        .map(|recip| (recip, sender))
+        .collect::<Vec<_>>();
+    pack_jwe_entries(
+        message,
+        &entries,
+        enc_alg,
+        extra_headers,
+        serialization,
+        rng,
+    )
+}
+
+/// Produce a legacy envelope where each recipient entry carries its own
+/// optional sender key
+fn pack_jwe_entries<M: AsRef<[u8]>, G: CryptoRng + RngCore>(
+    message: M,
+    entries: &[(&DerivedRecipient, Option<&DerivedSender>)],
+    enc_alg: EnvelopeAlg,
+    extra_headers: &HeaderMap,
+    serialization: JweSerialization,
+    rng: &mut G,
+) -> Result<(JWE, Vec<u8>), PackError> {
+    // generate content encryption key that will encrypt `message`
+    let cek = ArrayKey::random_with_rng(rng);
+
+    let base64_protected =
+        prepare_protected_entries(&cek, entries, enc_alg, extra_headers, serialization, rng)?;
 
     // Use AEAD to encrypt `message` with "protected" data as "associated data"
-    let chacha = ChaCha20Poly1305::new(ChaChaKey::from_slice(&cek));
-    let nonce = random_array();
-    let payload = Payload {
-        aad: base64_protected.as_bytes(),
-        msg: message.as_ref(),
-    };
-    let ciphertext = chacha
-        .encrypt(&nonce, payload)
-        .map_err(|_| "Error encrypting payload")?;
-    let iv = base64::encode_urlsafe(nonce);
-    let clen = ciphertext.len() - TAG_SIZE;
-    let tag = base64::encode_urlsafe(&ciphertext[clen..]);
-    let ciphertext = base64::encode_urlsafe(&ciphertext[..clen]);
+    let content = encrypt_content(
+        enc_alg,
+        PackFormat::Legacy,
+        &cek,
+        base64_protected.as_bytes(),
+        message.as_ref(),
+        rng,
+    )?;
 
-    format_pack_message(&base64_protected, &ciphertext, &iv, &tag)
+    let jwe = JWE {
+        protected: base64_protected,
+        iv: base64::encode_urlsafe(&content.iv),
+        ciphertext: String::new(),
+        tag: base64::encode_urlsafe(&content.tag),
+    };
+    Ok((jwe, content.ciphertext))
 }
 
-fn prepare_protected_anoncrypt(
+fn prepare_protected<G: CryptoRng + RngCore>(
     cek: &ArrayKey<KeySize>,
-    receiver_list: Vec<EncodedVerKey>,
-) -> Result<String, ConversionError> {
-    let mut encrypted_recipients_struct: Vec<Recipient> = Vec::with_capacity(receiver_list.len());
-
-    for their_vk in receiver_list {
-        // encrypt cek for recipient
-        let their_vk_x = their_vk.key_exchange()?;
-        let enc_cek = crypto_box_seal(their_vk_x.as_ref(), cek.as_ref())?;
-
-        // create recipient struct and push to encrypted list
-        encrypted_recipients_struct.push(Recipient {
-            encrypted_key: base64::encode_urlsafe(enc_cek.as_slice()),
-            header: Header {
-                kid: their_vk.to_string(),
-                sender: None,
-                iv: None,
-            },
-        });
+    receiver_list: Vec<RecipientKey>,
+    sender_key: Option<&PrivateKey>,
+    enc_alg: EnvelopeAlg,
+    extra_headers: &HeaderMap,
+    serialization: JweSerialization,
+    rng: &mut G,
+) -> Result<String, PackError> {
+    let recipients = derive_recipients(&receiver_list)?;
+    let sender = sender_key.map(derive_sender).transpose()?;
+    prepare_protected_derived(
+        cek,
+        &recipients,
+        sender.as_ref(),
+        enc_alg,
+        extra_headers,
+        serialization,
+        rng,
+    )
+}
+
+/// A recipient key along with its derived X25519 public key
+#[derive(Debug, Clone)]
+pub(super) struct DerivedRecipient {
+    key: RecipientKey,
+    key_x: VerKey,
+}
+
+/// A sender key along with its derived X25519 private key and encoded verkey
+#[derive(Debug, Clone)]
+pub(super) struct DerivedSender {
+    key_x: PrivateKey,
+    verkey: EncodedVerKey,
+}
+
+impl DerivedRecipient {
+    pub(super) fn derive(key: RecipientKey) -> Result<Self, PackError> {
+        if let RecipientKey::P256(_) = key {
+            return Err(PackError::InvalidKey(ConversionError::from_msg(
+                "P-256 recipient keys require the DIDComm v2 envelope format",
+            )));
+        }
+        Ok(Self {
+            key_x: key.key_exchange().map_err(PackError::InvalidKey)?,
+            key,
+        })
+    }
+
+    /// The X25519 public key of the recipient
+    pub(super) fn key_exchange(&self) -> &VerKey {
+        &self.key_x
     }
+}
 
-    base64_encode_protected(encrypted_recipients_struct, false)
+pub(super) fn derive_recipients(
+    receiver_list: &[RecipientKey],
+) -> Result<Vec<DerivedRecipient>, PackError> {
+    map_recipients(receiver_list, |key| DerivedRecipient::derive(key.clone()))
 }
 
-fn prepare_protected_authcrypt(
-    cek: &ArrayKey<KeySize>,
-    receiver_list: Vec<EncodedVerKey>,
-    sender_key: &PrivateKey,
-) -> Result<String, ConversionError> {
-    let mut encrypted_recipients_struct: Vec<Recipient> = vec![];
-
-    let sender_key_x = sender_key.key_exchange()?;
-    let sender_pk = sender_key.public_key()?.as_base58()?;
-
-    for their_vk in receiver_list {
-        let their_vk_x = their_vk.key_exchange()?;
-
-        // encrypt cek for recipient
-        let (enc_cek, iv) = crypto_box(their_vk_x.as_ref(), sender_key_x.as_ref(), &cek[..], None)?;
-
-        // encrypt sender key for recipient
-        let enc_sender = crypto_box_seal(their_vk_x.as_ref(), sender_pk.encoded_key_bytes())?;
-
-        // create recipient struct and push to encrypted list
-        encrypted_recipients_struct.push(Recipient {
-            encrypted_key: base64::encode_urlsafe(enc_cek.as_slice()),
-            header: Header {
-                kid: their_vk.to_string(),
-                sender: Some(base64::encode_urlsafe(enc_sender.as_slice())),
-                iv: Some(base64::encode_urlsafe(iv.as_slice())),
-            },
-        });
+pub(super) fn derive_sender(sender_key: &PrivateKey) -> Result<DerivedSender, PackError> {
+    Ok(DerivedSender {
+        key_x: sender_key.key_exchange().map_err(PackError::InvalidKey)?,
+        verkey: sender_key
+            .public_key()
+            .and_then(|pk| pk.as_base58())
+            .map_err(PackError::InvalidKey)?,
+    })
+}
+
+/// Random values used when encrypting the content encryption key for a recipient
+pub(super) struct RecipientEntropy {
+    seal_key: Zeroizing<Vec<u8>>,
+    box_nonce: Vec<u8>,
+}
+
+impl RecipientEntropy {
+    pub(super) fn random<G: CryptoRng + RngCore>(rng: &mut G) -> Self {
+        let mut seal_key = Zeroizing::new(vec![0u8; CBOX_KEY_SIZE]);
+        rng.fill_bytes(&mut seal_key);
+        let box_nonce = CboxNonce::generate_with_rng(rng).as_slice().to_vec();
+        Self {
+            seal_key,
+            box_nonce,
+        }
     }
+}
+
+fn prepare_protected_derived<G: CryptoRng + RngCore>(
+    cek: &ArrayKey<KeySize>,
+    recipients: &[DerivedRecipient],
+    sender: Option<&DerivedSender>,
+    enc_alg: EnvelopeAlg,
+    extra_headers: &HeaderMap,
+    serialization: JweSerialization,
+    rng: &mut G,
+) -> Result<String, PackError> {
+    let entries = recipients
+        .iter()
+        .map(|recip| (recip, sender))
+        .collect::<Vec<_>>();
+    prepare_protected_entries(cek, &entries, enc_alg, extra_headers, serialization, rng)
+}
 
-    base64_encode_protected(encrypted_recipients_struct, true)
+/// Build the protected header for a list of recipients, each of which may
+/// be authcrypted with its own sender key or anoncrypted
+fn prepare_protected_entries<G: CryptoRng + RngCore>(
+    cek: &ArrayKey<KeySize>,
+    entries: &[(&DerivedRecipient, Option<&DerivedSender>)],
+    enc_alg: EnvelopeAlg,
+    extra_headers: &HeaderMap,
+    serialization: JweSerialization,
+    rng: &mut G,
+) -> Result<String, PackError> {
+    trace_span!(
+        "prepare_recipients",
+        format = "legacy",
+        recipients = entries.len(),
+        authcrypt = entries.iter().any(|(_, sender)| sender.is_some()),
+    );
+    // draw all random values up front so that the output does not depend
+    // on the order in which recipients are processed
+    let entries = entries
+        .iter()
+        .map(|(recip, sender)| (*recip, *sender, RecipientEntropy::random(rng)))
+        .collect::<Vec<_>>();
+    let encrypted_recipients_struct =
+        map_recipients(&entries, |(their_vk, sender, entropy)| match sender {
+            // authcrypted pack_message format. See Wire message format HIPE for details
+            Some(sender) => wrap_cek_authcrypt(cek, their_vk, sender, entropy),
+            // anoncrypted pack_message format. See Wire message format HIPE for details
+            None => wrap_cek_anoncrypt(cek, their_vk, entropy),
+        })?;
+    let alg_is_authcrypt = entries.iter().any(|(_, sender, _)| sender.is_some());
+    let protected = build_protected(
+        encrypted_recipients_struct,
+        alg_is_authcrypt,
+        enc_alg,
+        extra_headers,
+    );
+    let protected_encoded = serde_json::to_vec(&protected)?;
+    Ok(serialization.encode_protected(&protected_encoded))
+}
+
+pub(super) fn wrap_cek_anoncrypt(
+    cek: &ArrayKey<KeySize>,
+    their_vk: &DerivedRecipient,
+    entropy: &RecipientEntropy,
+) -> Result<Recipient, PackError> {
+    // encrypt cek for recipient
+    let enc_cek =
+        crypto_box_seal_ephemeral(their_vk.key_x.as_ref(), cek.as_ref(), &entropy.seal_key)
+            .map_err(|_| PackError::KeyWrapFailure)?;
+
+    // create recipient struct
+    Ok(Recipient {
+        encrypted_key: base64::encode_urlsafe(enc_cek.as_slice()),
+        header: Header {
+            kid: their_vk.key.to_string(),
+            sender: None,
+            iv: None,
+        },
+    })
+}
+
+pub(super) fn wrap_cek_authcrypt(
+    cek: &ArrayKey<KeySize>,
+    their_vk: &DerivedRecipient,
+    sender: &DerivedSender,
+    entropy: &RecipientEntropy,
+) -> Result<Recipient, PackError> {
+    let shared = crypto_box_key_exchange(sender.key_x.as_ref(), their_vk.key_x.as_ref())
+        .map_err(|_| PackError::KeyWrapFailure)?;
+    wrap_cek_authcrypt_shared(cek, their_vk, &sender.verkey, &shared[..], entropy)
 }
 
-fn base64_encode_protected(
+/// Authcrypt the content encryption key for a recipient given the X25519
+/// shared secret between the sender and recipient keys
+pub(super) fn wrap_cek_authcrypt_shared(
+    cek: &ArrayKey<KeySize>,
+    their_vk: &DerivedRecipient,
+    sender_vk: &EncodedVerKey,
+    shared_secret: &[u8],
+    entropy: &RecipientEntropy,
+) -> Result<Recipient, PackError> {
+    // encrypt cek for recipient
+    let (enc_cek, iv) = crypto_box_shared(shared_secret, &cek[..], Some(entropy.box_nonce.clone()))
+        .map_err(|_| PackError::KeyWrapFailure)?;
+
+    // encrypt sender key for recipient
+    let enc_sender = crypto_box_seal_ephemeral(
+        their_vk.key_x.as_ref(),
+        sender_vk.encoded_key_bytes(),
+        &entropy.seal_key,
+    )
+    .map_err(|_| PackError::KeyWrapFailure)?;
+
+    // create recipient struct
+    Ok(Recipient {
+        encrypted_key: base64::encode_urlsafe(enc_cek.as_slice()),
+        header: Header {
+            kid: their_vk.key.to_string(),
+            sender: Some(base64::encode_urlsafe(enc_sender.as_slice())),
+            iv: Some(base64::encode_urlsafe(iv.as_slice())),
+        },
+    })
+}
+
+/// Process the recipient entries in order, in parallel when the `rayon`
+/// feature is enabled (except on wasm32 targets)
+#[cfg(all(feature = "rayon", not(target_arch = "wasm32")))]
+fn map_recipients<T, R, F>(recipients: &[T], f: F) -> Result<Vec<R>, PackError>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> Result<R, PackError> + Send + Sync,
+{
+    use rayon::prelude::*;
+    recipients.par_iter().map(f).collect()
+}
+
+/// Process the recipient entries in order
+#[cfg(any(not(feature = "rayon"), target_arch = "wasm32"))]
+fn map_recipients<T, R, F>(recipients: &[T], f: F) -> Result<Vec<R>, PackError>
+where
+    F: Fn(&T) -> Result<R, PackError>,
+{
+    recipients.iter().map(f).collect()
+}
+
+pub(super) fn build_protected(
     encrypted_recipients_struct: Vec<Recipient>,
     alg_is_authcrypt: bool,
-) -> Result<String, ConversionError> {
+    enc_alg: EnvelopeAlg,
+    extra_headers: &HeaderMap,
+) -> Protected {
     let alg_val = if alg_is_authcrypt {
         String::from(PROTECTED_HEADER_ALG_AUTH)
     } else {
         String::from(PROTECTED_HEADER_ALG_ANON)
     };
 
-    // structure protected and base64URL encode it
-    let protected_struct = Protected {
-        enc: PROTECTED_HEADER_ENC.to_string(),
+    Protected {
+        enc: enc_header_value(enc_alg, PackFormat::Legacy).to_string(),
         typ: PROTECTED_HEADER_TYP.to_string(),
         alg: alg_val,
         recipients: encrypted_recipients_struct,
-    };
-    let protected_encoded = serde_json::to_string(&protected_struct)
-        .map_err(|err| format!("Failed to serialize protected field {}", err))?;
+        extra: extra_headers.clone(),
+    }
+}
+
+pub async fn unpack_message<'f>(
+    message: impl AsRef<[u8]>,
+    lookup: impl KeyLookup<'f>,
+) -> Result<(Vec<u8>, EncodedVerKey, Option<EncodedVerKey>), UnpackError> {
+    unpack_message_with_meta(message, lookup, None)
+        .await
+        .map(split_unpack_meta)
+}
 
-    Ok(base64::encode_urlsafe(protected_encoded.as_bytes()))
+/// Unpack a message, returning the plaintext along with the protected
+/// header metadata of the envelope
+pub async fn unpack_message_with_meta<'f>(
+    message: impl AsRef<[u8]>,
+    lookup: impl KeyLookup<'f>,
+    options: Option<UnpackOptions>,
+) -> Result<(Vec<u8>, UnpackMeta), UnpackError> {
+    let options = options.unwrap_or_default();
+    match parse_envelope(message.as_ref(), &options.limits)? {
+        Envelope::DIDCommV2(jwe) => {
+            let ciphertext = decode_b64(&jwe.ciphertext, "ciphertext")?;
+            unpack_jwe_v2(&jwe, ciphertext, lookup, &options).await
+        }
+        Envelope::Legacy(jwe) => {
+            let parsed = parse_jwe(&jwe, &options)?;
+            let ciphertext = decode_b64_compat(&jwe.ciphertext, "ciphertext", &options)?;
+            unpack_parsed_jwe(&jwe, parsed, ciphertext, lookup).await
+        }
+    }
 }
 
-fn format_pack_message(
-    base64_protected: &str,
-    ciphertext: &str,
-    iv: &str,
-    tag: &str,
-) -> Result<Vec<u8>, ConversionError> {
-    // serialize pack message and return as vector of bytes
-    let jwe_struct = JWE {
-        protected: base64_protected.to_string(),
-        iv: iv.to_string(),
-        ciphertext: ciphertext.to_string(),
-        tag: tag.to_string(),
+/// Unpack a message produced by `pack_message_detached`, given the
+/// encrypted payload received separately
+pub async fn unpack_message_detached<'f>(
+    message: impl AsRef<[u8]>,
+    ciphertext: &[u8],
+    lookup: impl KeyLookup<'f>,
+) -> Result<(Vec<u8>, EncodedVerKey, Option<EncodedVerKey>), UnpackError> {
+    let options = UnpackOptions::default();
+    let result = match parse_envelope(message.as_ref(), &options.limits)? {
+        Envelope::DIDCommV2(jwe) => {
+            unpack_jwe_v2_detached(&jwe, ciphertext, lookup, &options).await
+        }
+        Envelope::Legacy(jwe) => unpack_jwe_detached(&jwe, ciphertext, lookup, &options).await,
     };
+    result.map(split_unpack_meta)
+}
 
-    Ok(serde_json::to_vec(&jwe_struct)?)
+/// Unpack a message using a map of recipient verkeys to private keys,
+/// without requiring an async executor
+pub fn unpack_message_sync(
+    message: impl AsRef<[u8]>,
+    keys: &HashMap<EncodedVerKey, PrivateKey>,
+) -> Result<(Vec<u8>, EncodedVerKey, Option<EncodedVerKey>), UnpackError> {
+    unpack_message_sync_with_meta(message, keys, None).map(split_unpack_meta)
 }
 
-pub async fn unpack_message<'f>(
+/// Unpack a message using a map of recipient verkeys to private keys,
+/// returning the protected header metadata of the envelope
+pub fn unpack_message_sync_with_meta(
     message: impl AsRef<[u8]>,
+    keys: &HashMap<EncodedVerKey, PrivateKey>,
+    options: Option<UnpackOptions>,
+) -> Result<(Vec<u8>, UnpackMeta), UnpackError> {
+    let options = options.unwrap_or_default();
+    let lookup = |find_pks: &Vec<EncodedVerKey>| {
+        find_pks
+            .iter()
+            .enumerate()
+            .find_map(|(idx, pk)| keys.get(pk).map(|sk| (idx, sk.clone())))
+    };
+    match parse_envelope(message.as_ref(), &options.limits)? {
+        Envelope::DIDCommV2(jwe) => unpack_jwe_v2_sync(&jwe, lookup, &options),
+        Envelope::Legacy(jwe) => unpack_jwe_sync(&jwe, lookup, &options),
+    }
+}
+
+/// Unpack a message using a map of recipient verkeys to private keys,
+/// selecting among the matching recipients according to `policy`. A result
+/// is returned for each selected recipient.
+pub fn unpack_message_sync_with_policy(
+    message: impl AsRef<[u8]>,
+    keys: &HashMap<EncodedVerKey, PrivateKey>,
+    policy: RecipientPolicy,
+    options: Option<UnpackOptions>,
+) -> Result<Vec<(Vec<u8>, UnpackMeta)>, UnpackError> {
+    let options = options.unwrap_or_default();
+    let envelope = parse_envelope(message.as_ref(), &options.limits)?;
+    let recip_vks = match &envelope {
+        Envelope::DIDCommV2(jwe) => {
+            check_recipient_count(jwe.recipients.len(), &options.limits)?;
+            recipient_verkeys(jwe.recipients.iter().map(|r| r.header.kid.as_str()))?
+        }
+        Envelope::Legacy(jwe) => {
+            let (protected, _) = decode_protected(&jwe.protected, &options)?;
+            recipient_verkeys(protected.recipients.iter().map(|r| r.header.kid.as_str()))?
+        }
+    };
+
+    let matches = recip_vks
+        .iter()
+        .enumerate()
+        .filter(|(_, vk)| keys.contains_key(vk))
+        .map(|(idx, _)| idx)
+        .collect::<Vec<usize>>();
+    let selected = match policy {
+        RecipientPolicy::First => matches.into_iter().take(1).collect(),
+        RecipientPolicy::Prefer(kid) => match matches.iter().find(|idx| recip_vks[**idx] == kid) {
+            Some(idx) => vec![*idx],
+            None => matches.into_iter().take(1).collect(),
+        },
+        RecipientPolicy::All => matches,
+    };
+    if selected.is_empty() {
+        return Err(UnpackError::NoMatchingRecipient);
+    }
+
+    selected
+        .into_iter()
+        .map(|idx| {
+            let lookup =
+                |_: &Vec<EncodedVerKey>| keys.get(&recip_vks[idx]).map(|sk| (idx, sk.clone()));
+            match &envelope {
+                Envelope::DIDCommV2(jwe) => unpack_jwe_v2_sync(jwe, lookup, &options),
+                Envelope::Legacy(jwe) => unpack_jwe_sync(jwe, lookup, &options),
+            }
+        })
+        .collect()
+}
+
+/// Unpack an envelope having a detached payload
+pub async fn unpack_jwe_detached<'f>(
+    jwe_struct: &JWE,
+    ciphertext: &[u8],
     lookup: impl KeyLookup<'f>,
-) -> Result<(Vec<u8>, EncodedVerKey, Option<EncodedVerKey>), ConversionError> {
-    let jwe = serde_json::from_slice(message.as_ref())?;
-    unpack_jwe(&jwe, lookup).await
+    options: &UnpackOptions,
+) -> Result<(Vec<u8>, UnpackMeta), UnpackError> {
+    if !jwe_struct.ciphertext.is_empty() {
+        return Err(UnpackError::InvalidEnvelope(
+            "Envelope does not have a detached payload".to_string(),
+        ));
+    }
+    if ciphertext.len() > options.limits.max_message {
+        return Err(UnpackError::LimitExceeded("ciphertext"));
+    }
+    unpack_jwe(jwe_struct, ciphertext.to_vec(), lookup, options).await
 }
 
 pub async fn unpack_jwe<'f>(
     jwe_struct: &JWE,
+    ciphertext: Vec<u8>,
     lookup: impl KeyLookup<'f>,
-) -> Result<(Vec<u8>, EncodedVerKey, Option<EncodedVerKey>), ConversionError> {
-    // decode protected data
-    let protected_decoded = base64::decode_urlsafe(&jwe_struct.protected)?;
-    let protected: Protected = serde_json::from_slice(&protected_decoded)?;
+    options: &UnpackOptions,
+) -> Result<(Vec<u8>, UnpackMeta), UnpackError> {
+    let parsed = parse_jwe(jwe_struct, options)?;
+    unpack_parsed_jwe(jwe_struct, parsed, ciphertext, lookup).await
+}
 
+pub fn unpack_jwe_sync<F>(
+    jwe_struct: &JWE,
+    lookup: F,
+    options: &UnpackOptions,
+) -> Result<(Vec<u8>, UnpackMeta), UnpackError>
+where
+    F: Fn(&Vec<EncodedVerKey>) -> Option<(usize, PrivateKey)>,
+{
+    let parsed = parse_jwe(jwe_struct, options)?;
+
+    // extract recipient that matches a known key
+    let found = lookup(&parsed.recip_vks);
+
+    let ciphertext = decode_b64_compat(&jwe_struct.ciphertext, "ciphertext", options)?;
+    decrypt_jwe(jwe_struct, parsed, ciphertext, found)
+}
+
+async fn unpack_parsed_jwe<'f>(
+    jwe_struct: &JWE,
+    parsed: ParsedJwe,
+    ciphertext: Vec<u8>,
+    lookup: impl KeyLookup<'f>,
+) -> Result<(Vec<u8>, UnpackMeta), UnpackError> {
     // extract recipient that matches a key in the wallet
-    let (recipient, recip_pk, recip_sk) = unwrap_opt_or_return!(
-        find_unpack_recipient(protected, lookup).await?,
-        Err("No matching recipient found".into())
-    );
-    let is_auth_recipient = recipient.header.sender.is_some() && recipient.header.iv.is_some();
+    let found = find_recipient_key(lookup, &parsed.recip_vks).await?;
+    decrypt_jwe(jwe_struct, parsed, ciphertext, found)
+}
 
-    // get cek and sender data
-    let (sender_verkey_option, cek) = if is_auth_recipient {
-        let (send, cek) = unpack_cek_authcrypt(&recipient, &recip_sk)?;
-        (Some(send), cek)
-    } else {
-        let cek = unpack_cek_anoncrypt(&recipient, &recip_sk)?;
-        (None, cek)
+/// The decoded fields of a legacy envelope, other than the ciphertext
+struct ParsedJwe {
+    protected: Protected,
+    protected_json: String,
+    recip_vks: Vec<EncodedVerKey>,
+    iv: Vec<u8>,
+    tag: Vec<u8>,
+}
+
+/// Decode the protected header, recipients, nonce and tag of a legacy
+/// envelope according to the compatibility mode. The protected header is
+/// retained as transmitted for use as the AEAD associated data, as libindy
+/// used it directly.
+fn parse_jwe(jwe_struct: &JWE, options: &UnpackOptions) -> Result<ParsedJwe, UnpackError> {
+    let (protected, protected_json) = decode_protected(&jwe_struct.protected, options)?;
+    let recip_vks = recipient_verkeys(protected.recipients.iter().map(|r| r.header.kid.as_str()))?;
+    Ok(ParsedJwe {
+        protected,
+        protected_json,
+        recip_vks,
+        iv: decode_b64_compat(&jwe_struct.iv, "iv", options)?,
+        tag: decode_b64_compat(&jwe_struct.tag, "tag", options)?,
+    })
+}
+
+fn decode_protected(
+    protected: &str,
+    options: &UnpackOptions,
+) -> Result<(Protected, String), UnpackError> {
+    check_header_size(protected, &options.limits)?;
+    let protected_decoded = decode_b64_compat(protected, "protected", options)?;
+    let mut protected: Protected = serde_json::from_slice(&protected_decoded)
+        .map_err(|err| UnpackError::InvalidProtectedHeader(err.to_string()))?;
+    check_recipient_count(protected.recipients.len(), &options.limits)?;
+    if options.compat == Compat::IndySdk {
+        for recipient in protected.recipients.iter_mut() {
+            recipient.encrypted_key = normalize_b64(&recipient.encrypted_key, "encrypted_key")?;
+            if let Some(sender) = recipient.header.sender.as_mut() {
+                *sender = normalize_b64(sender, "sender")?;
+            }
+            if let Some(iv) = recipient.header.iv.as_mut() {
+                *iv = normalize_b64(iv, "iv")?;
+            }
+        }
+    }
+    if !options.lenient_alg {
+        check_alg_structure(&protected)?;
+    }
+    let protected_json = protected_json_string(protected_decoded)?;
+    Ok((protected, protected_json))
+}
+
+/// Decode a base64 envelope field according to the compatibility mode
+fn decode_b64_compat(
+    value: &str,
+    field: &'static str,
+    options: &UnpackOptions,
+) -> Result<Vec<u8>, UnpackError> {
+    match options.compat {
+        Compat::Strict => decode_b64(value, field),
+        Compat::IndySdk => decode_b64(&normalize_b64(value, field)?, field),
+    }
+}
+
+/// Re-encode a base64 value which may use either alphabet, with or without
+/// padding, in the padded URL-safe form
+fn normalize_b64(value: &str, field: &'static str) -> Result<String, UnpackError> {
+    let value = value.replace('+', "-").replace('/', "_");
+    decode_b64(&value, field).map(base64::encode_urlsafe)
+}
+
+/// Ensure that the declared `alg` is consistent with the recipient headers.
+/// An authcrypt envelope may include anoncrypted entries for some recipients,
+/// as produced by `pack_message_pairwise`.
+fn check_alg_structure(protected: &Protected) -> Result<(), UnpackError> {
+    let authcrypt = match protected.alg.as_str() {
+        PROTECTED_HEADER_ALG_AUTH => true,
+        PROTECTED_HEADER_ALG_ANON => false,
+        _ => return Err(UnpackError::UnsupportedAlg(protected.alg.clone())),
     };
+    let mut auth_count = 0;
+    for recipient in &protected.recipients {
+        match (&recipient.header.sender, &recipient.header.iv) {
+            (None, None) => (),
+            (Some(_), Some(_)) if authcrypt => auth_count += 1,
+            _ => {
+                return Err(UnpackError::InvalidProtectedHeader(format!(
+                    "Recipient header does not match alg: {}",
+                    protected.alg
+                )))
+            }
+        }
+    }
+    if authcrypt && auth_count == 0 {
+        return Err(UnpackError::InvalidProtectedHeader(format!(
+            "No authenticated recipients for alg: {}",
+            protected.alg
+        )));
+    }
+    Ok(())
+}
+
+fn decrypt_jwe(
+    jwe_struct: &JWE,
+    parsed: ParsedJwe,
+    ciphertext: Vec<u8>,
+    found: Option<(usize, PrivateKey)>,
+) -> Result<(Vec<u8>, UnpackMeta), UnpackError> {
+    let (recip_idx, recip_sk) = select_recipient(&parsed.recip_vks, found)?;
+
+    // get cek and sender data
+    let (sender_verkey_option, cek) =
+        unpack_cek(&parsed.protected.recipients[recip_idx], &recip_sk)?;
+
+    decrypt_jwe_content(
+        jwe_struct,
+        parsed,
+        ciphertext,
+        recip_idx,
+        sender_verkey_option,
+        cek,
+    )
+}
+
+fn decrypt_jwe_content(
+    jwe_struct: &JWE,
+    parsed: ParsedJwe,
+    ciphertext: Vec<u8>,
+    recip_idx: usize,
+    sender_verkey_option: Option<EncodedVerKey>,
+    cek: Zeroizing<Vec<u8>>,
+) -> Result<(Vec<u8>, UnpackMeta), UnpackError> {
+    let protected = parsed.protected;
+    let enc_alg = parse_enc_header(&protected.enc, PackFormat::Legacy)?;
 
     // decrypt message
-    let chacha = ChaCha20Poly1305::new_varkey(&cek)
-        .map_err(|_| "Error creating unpack decryptor for cek")?;
-    let nonce = base64::decode_urlsafe(&jwe_struct.iv)?;
-    if nonce.len() != NONCE_SIZE {
-        return Err("Invalid size for message nonce".into());
-    }
-    let mut ciphertext = base64::decode_urlsafe(&jwe_struct.ciphertext)?;
-    ciphertext.append(base64::decode_urlsafe(&jwe_struct.tag)?.as_mut());
-    let payload = Payload {
-        aad: jwe_struct.protected.as_bytes(),
-        msg: ciphertext.as_slice(),
+    let message = decrypt_content(
+        enc_alg,
+        PackFormat::Legacy,
+        &cek,
+        jwe_struct.protected.as_bytes(),
+        &parsed.iv,
+        ciphertext,
+        &parsed.tag,
+    )?;
+
+    let mut extra = protected.extra;
+    let sender_hint = take_sender_hint(&mut extra);
+    let meta = UnpackMeta {
+        format: PackFormat::Legacy,
+        alg: protected.alg,
+        enc: protected.enc,
+        typ: protected.typ,
+        recipients: parsed.recip_vks,
+        recipient_index: recip_idx,
+        sender: sender_verkey_option,
+        sender_hint,
+        signature_verified: false,
+        protected: parsed.protected_json,
+        extra,
+    };
+    verify_signed_content(message, meta)
+}
+
+/// Unpack a legacy envelope, delegating recipient key operations to an
+/// external key store
+pub async fn unpack_message_with_unwrapper(
+    message: impl AsRef<[u8]>,
+    unwrapper: &impl CekUnwrapper,
+    options: Option<UnpackOptions>,
+) -> Result<(Vec<u8>, UnpackMeta), UnpackError> {
+    let options = options.unwrap_or_default();
+    let jwe_struct = match parse_envelope(message.as_ref(), &options.limits)? {
+        Envelope::Legacy(jwe) => jwe,
+        Envelope::DIDCommV2(_) => {
+            return Err(UnpackError::InvalidEnvelope(
+                "External key unwrapping is only supported for legacy envelopes".to_string(),
+            ))
+        }
+    };
+    let parsed = parse_jwe(&jwe_struct, &options)?;
+
+    let recip_vks = &parsed.recip_vks;
+    let recip_idx = match unwrapper
+        .find_recipient(recip_vks)
+        .await
+        .map_err(UnpackError::KeyLookup)?
+    {
+        Some(idx) if idx < recip_vks.len() => idx,
+        _ => return Err(UnpackError::NoMatchingRecipient),
+    };
+
+    let recipient = &parsed.protected.recipients[recip_idx];
+    let recip_vk = &recip_vks[recip_idx];
+    let encrypted_key = decode_b64(&recipient.encrypted_key, "encrypted_key")?;
+    let (sender_verkey_option, cek) = match (&recipient.header.sender, &recipient.header.iv) {
+        (Some(sender), Some(iv)) => {
+            let enc_sender_vk = decode_b64(sender, "sender")?;
+            let iv = decode_b64(iv, "iv")?;
+            let sender_vk_vec = Zeroizing::new(
+                unwrapper
+                    .seal_open(recip_vk, &enc_sender_vk)
+                    .await
+                    .map_err(|_| UnpackError::KeyUnwrapFailure)?,
+            );
+            let sender_vk = EncodedVerKey::from_slice(&sender_vk_vec)
+                .map_err(|_| UnpackError::MalformedField("sender".to_string()))?;
+            let cek = Zeroizing::new(
+                unwrapper
+                    .box_open(recip_vk, &sender_vk, &encrypted_key, &iv)
+                    .await
+                    .map_err(|_| UnpackError::KeyUnwrapFailure)?,
+            );
+            (Some(sender_vk), cek)
+        }
+        _ => {
+            let cek = Zeroizing::new(
+                unwrapper
+                    .seal_open(recip_vk, &encrypted_key)
+                    .await
+                    .map_err(|_| UnpackError::KeyUnwrapFailure)?,
+            );
+            (None, cek)
+        }
     };
-    let message = chacha
-        .decrypt(nonce.as_slice().into(), payload)
-        .map_err(|_| "Error decrypting message payload")?;
 
-    Ok((message, recip_pk, sender_verkey_option))
+    let ciphertext = decode_b64_compat(&jwe_struct.ciphertext, "ciphertext", &options)?;
+    decrypt_jwe_content(
+        &jwe_struct,
+        parsed,
+        ciphertext,
+        recip_idx,
+        sender_verkey_option,
+        cek,
+    )
+}
+
+fn unpack_cek(
+    recipient: &Recipient,
+    recip_sk: &PrivateKey,
+) -> Result<(Option<EncodedVerKey>, Zeroizing<Vec<u8>>), UnpackError> {
+    let is_auth_recipient = recipient.header.sender.is_some() && recipient.header.iv.is_some();
+    trace_span!("unwrap_cek", authcrypt = is_auth_recipient);
+    if is_auth_recipient {
+        let (send, cek) = unpack_cek_authcrypt(recipient, recip_sk)?;
+        Ok((Some(send), cek))
+    } else {
+        let cek = unpack_cek_anoncrypt(recipient, recip_sk)?;
+        Ok((None, cek))
+    }
 }
 
 fn unpack_cek_authcrypt(
     recipient: &Recipient,
     recip_sk: &PrivateKey,
-) -> Result<(EncodedVerKey, Vec<u8>), ConversionError> {
-    let encrypted_key_vec = base64::decode_urlsafe(&recipient.encrypted_key)?;
-    let iv = base64::decode_urlsafe(&recipient.header.iv.as_ref().unwrap())?;
-    let enc_sender_vk = base64::decode_urlsafe(&recipient.header.sender.as_ref().unwrap())?;
+) -> Result<(EncodedVerKey, Zeroizing<Vec<u8>>), UnpackError> {
+    let encrypted_key_vec = decode_b64(&recipient.encrypted_key, "encrypted_key")?;
+    let iv = decode_b64(recipient.header.iv.as_ref().unwrap(), "iv")?;
+    let enc_sender_vk = decode_b64(recipient.header.sender.as_ref().unwrap(), "sender")?;
 
     // decrypt sender_vk
-    let recip_pk = recip_sk.public_key()?;
-    let sender_vk_vec = crypto_box_seal_open(
-        recip_pk.key_exchange()?.as_ref(),
-        recip_sk.key_exchange()?.as_ref(),
-        &enc_sender_vk,
-    )?;
-    let sender_vk = EncodedVerKey::from_slice(&sender_vk_vec)?;
+    let (recip_pk_x, recip_sk_x) = recipient_key_exchange(recip_sk)?;
+    let sender_vk_vec = Zeroizing::new(
+        crypto_box_seal_open(recip_pk_x.as_ref(), recip_sk_x.as_ref(), &enc_sender_vk)
+            .map_err(|_| UnpackError::KeyUnwrapFailure)?,
+    );
+    let sender_vk = EncodedVerKey::from_slice(&sender_vk_vec)
+        .map_err(|_| UnpackError::MalformedField("sender".to_string()))?;
+    let sender_pk_x = sender_vk
+        .key_exchange()
+        .map_err(|_| UnpackError::MalformedField("sender".to_string()))?;
 
     // decrypt cek
-    let cek = crypto_box_open(
-        recip_sk.key_exchange()?.as_ref(),
-        sender_vk.key_exchange()?.as_ref(),
-        encrypted_key_vec.as_slice(),
-        iv.as_slice(),
-    )?;
+    let cek = Zeroizing::new(
+        crypto_box_open(
+            recip_sk_x.as_ref(),
+            sender_pk_x.as_ref(),
+            encrypted_key_vec.as_slice(),
+            iv.as_slice(),
+        )
+        .map_err(|_| UnpackError::KeyUnwrapFailure)?,
+    );
 
     Ok((sender_vk, cek))
 }
@@ -242,35 +1126,434 @@ fn unpack_cek_authcrypt(
 fn unpack_cek_anoncrypt(
     recipient: &Recipient,
     recip_sk: &PrivateKey,
-) -> Result<Vec<u8>, ConversionError> {
-    let encrypted_key = base64::decode_urlsafe(&recipient.encrypted_key)?;
+) -> Result<Zeroizing<Vec<u8>>, UnpackError> {
+    let encrypted_key = decode_b64(&recipient.encrypted_key, "encrypted_key")?;
 
     // decrypt cek
-    let recip_pk = recip_sk.public_key()?;
-    let cek = crypto_box_seal_open(
-        recip_pk.key_exchange()?.as_ref(),
-        recip_sk.key_exchange()?.as_ref(),
-        &encrypted_key,
-    )?;
+    let (recip_pk_x, recip_sk_x) = recipient_key_exchange(recip_sk)?;
+    let cek = Zeroizing::new(
+        crypto_box_seal_open(recip_pk_x.as_ref(), recip_sk_x.as_ref(), &encrypted_key)
+            .map_err(|_| UnpackError::KeyUnwrapFailure)?,
+    );
 
     Ok(cek)
 }
 
-async fn find_unpack_recipient<'f>(
-    protected: Protected,
+/// Derive the X25519 public and private keys of the recipient
+fn recipient_key_exchange(recip_sk: &PrivateKey) -> Result<(VerKey, PrivateKey), UnpackError> {
+    let recip_pk = recip_sk.public_key().map_err(UnpackError::InvalidKey)?;
+    Ok((
+        recip_pk.key_exchange().map_err(UnpackError::InvalidKey)?,
+        recip_sk.key_exchange().map_err(UnpackError::InvalidKey)?,
+    ))
+}
+
+/// Pack a message read from `message` into `output`, encrypting the payload
+/// in fixed-size chunks so that memory use remains bounded
+pub fn pack_message_stream<R: Read, W: Write, K: Into<RecipientKey>>(
+    message: R,
+    output: W,
+    receiver_list: Vec<K>,
+    sender_key: Option<PrivateKey>,
+) -> Result<(), PackError> {
+    pack_message_stream_with_rng(message, output, receiver_list, sender_key, &mut OsRng)
+}
+
+/// Pack a message stream using the provided random number generator for the
+/// content encryption key, nonces and ephemeral keys
+pub fn pack_message_stream_with_rng<
+    R: Read,
+    W: Write,
+    K: Into<RecipientKey>,
+    G: CryptoRng + RngCore,
+>(
+    message: R,
+    output: W,
+    receiver_list: Vec<K>,
+    sender_key: Option<PrivateKey>,
+    rng: &mut G,
+) -> Result<(), PackError> {
+    pack_stream_chunked(
+        message,
+        output,
+        recipient_keys(receiver_list)?,
+        sender_key,
+        STREAM_CHUNK_SIZE,
+        rng,
+    )
+}
+
+fn pack_stream_chunked<R: Read, W: Write, G: CryptoRng + RngCore>(
+    mut message: R,
+    mut output: W,
+    receiver_list: Vec<RecipientKey>,
+    sender_key: Option<PrivateKey>,
+    chunk_size: usize,
+    rng: &mut G,
+) -> Result<(), PackError> {
+    if receiver_list.is_empty() {
+        return Err(PackError::NoRecipients);
+    }
+
+    // generate content encryption key and prepare the stream header
+    let cek = ArrayKey::random_with_rng(rng);
+    let base64_protected = prepare_protected(
+        &cek,
+        receiver_list,
+        sender_key.as_ref(),
+        EnvelopeAlg::XChaCha20Poly1305,
+        &HeaderMap::new(),
+        JweSerialization::Json,
+        rng,
+    )?;
+    let mut nonces = StreamNonce::random_with_rng(rng);
+    let header = StreamHeader {
+        protected: base64_protected,
+        iv: base64::encode_urlsafe(nonces.prefix()),
+        chunk_size,
+    };
+    let header_json = serde_json::to_vec(&header)?;
+    output
+        .write_all(&(header_json.len() as u32).to_be_bytes())
+        .and_then(|_| output.write_all(&header_json))
+        .map_err(PackError::Io)?;
+
+    // encrypt each chunk, flagging the final chunk, which is always shorter
+    // than the chunk size (and may be empty). The serialized stream header is
+    // authenticated with every chunk so that the chunk size cannot be altered
+    let chacha = ChaCha20Poly1305::new(ChaChaKey::from_slice(&cek));
+    let mut buf = Zeroizing::new(vec![0u8; chunk_size]);
+    loop {
+        let len = read_full(&mut message, &mut buf).map_err(PackError::Io)?;
+        let last = len < chunk_size;
+        let nonce = nonces.next(last).map_err(|_| PackError::StreamTooLong)?;
+        let payload = Payload {
+            aad: &header_json,
+            msg: &buf[..len],
+        };
+        let ciphertext = chacha
+            .encrypt(&nonce, payload)
+            .map_err(|_| PackError::AeadFailure)?;
+        output.write_all(&ciphertext).map_err(PackError::Io)?;
+        if last {
+            break;
+        }
+    }
+    output.flush().map_err(PackError::Io)?;
+    Ok(())
+}
+
+/// Unpack a message produced by `pack_message_stream`, writing the decrypted
+/// payload to `output`
+pub async fn unpack_message_stream<'f, R: Read, W: Write>(
+    mut input: R,
+    mut output: W,
     lookup: impl KeyLookup<'f>,
-) -> Result<Option<(Recipient, EncodedVerKey, PrivateKey)>, ConversionError> {
-    let mut recip_vks = Vec::<EncodedVerKey>::with_capacity(protected.recipients.len());
-    for recipient in &protected.recipients {
-        let vk = EncodedVerKey::from_str(&recipient.header.kid)?;
+) -> Result<(EncodedVerKey, Option<EncodedVerKey>), UnpackError> {
+    // read and decode the stream header
+    let mut header_len = [0u8; 4];
+    input.read_exact(&mut header_len).map_err(UnpackError::Io)?;
+    let header_len = u32::from_be_bytes(header_len) as usize;
+    if header_len > STREAM_MAX_HEADER_SIZE {
+        return Err(UnpackError::InvalidEnvelope(
+            "Message stream header exceeds maximum size".to_string(),
+        ));
+    }
+    let mut header_json = vec![0u8; header_len];
+    input
+        .read_exact(&mut header_json)
+        .map_err(UnpackError::Io)?;
+    let header: StreamHeader = serde_json::from_slice(&header_json)
+        .map_err(|err| UnpackError::InvalidEnvelope(err.to_string()))?;
+    if header.chunk_size == 0 || header.chunk_size > STREAM_MAX_CHUNK_SIZE {
+        return Err(UnpackError::InvalidEnvelope(
+            "Invalid chunk size for message stream".to_string(),
+        ));
+    }
+    let mut nonces = StreamNonce::from_prefix(&decode_b64(&header.iv, "iv")?)
+        .map_err(|_| UnpackError::InvalidEnvelope("Invalid size for message nonce".to_string()))?;
+    let (protected, _) = decode_protected(&header.protected, &UnpackOptions::default())?;
+    if protected.enc != PROTECTED_HEADER_ENC {
+        return Err(UnpackError::UnsupportedAlg(protected.enc));
+    }
+
+    // extract recipient that matches a key in the wallet
+    let recip_vks = recipient_verkeys(protected.recipients.iter().map(|r| r.header.kid.as_str()))?;
+    let found = find_recipient_key(lookup, &recip_vks).await?;
+    let (idx, recip_sk) = select_recipient(&recip_vks, found)?;
+    let (sender_verkey_option, cek) = unpack_cek(&protected.recipients[idx], &recip_sk)?;
+
+    // decrypt each chunk in turn
+    let chacha = ChaCha20Poly1305::new_varkey(&cek).map_err(|_| UnpackError::KeyUnwrapFailure)?;
+    let mut buf = vec![0u8; header.chunk_size + TAG_SIZE];
+    loop {
+        let len = read_full(&mut input, &mut buf).map_err(UnpackError::Io)?;
+        if len < TAG_SIZE {
+            return Err(UnpackError::TruncatedStream);
+        }
+        let last = len < buf.len();
+        let nonce = nonces.next(last).map_err(|_| {
+            UnpackError::InvalidEnvelope("Maximum message stream length exceeded".to_string())
+        })?;
+        let payload = Payload {
+            aad: &header_json,
+            msg: &buf[..len],
+        };
+        let message = Zeroizing::new(
+            chacha
+                .decrypt(&nonce, payload)
+                .map_err(|_| UnpackError::AeadFailure)?,
+        );
+        output.write_all(&message).map_err(UnpackError::Io)?;
+        if last {
+            break;
+        }
+    }
+    output.flush().map_err(UnpackError::Io)?;
+
+    Ok((recip_vks[idx].clone(), sender_verkey_option))
+}
+
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut pos = 0;
+    while pos < buf.len() {
+        match reader.read(&mut buf[pos..]) {
+            Ok(0) => break,
+            Ok(len) => pos += len,
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(pos)
+}
+
+/// Convert a list of recipient keys, skipping the X25519 conversion for
+/// keys which are already key agreement keys
+pub(super) fn recipient_keys<R: Into<RecipientKey>>(
+    receiver_list: Vec<R>,
+) -> Result<Vec<RecipientKey>, PackError> {
+    validate_recipients(receiver_list.into_iter().map(Into::into).collect())
+}
+
+/// Validate the encoding and length of each recipient key, removing
+/// duplicate recipients
+pub(super) fn validate_recipients(
+    receiver_list: Vec<RecipientKey>,
+) -> Result<Vec<RecipientKey>, PackError> {
+    let mut seen = HashSet::new();
+    let mut result = Vec::with_capacity(receiver_list.len());
+    for (idx, recipient) in receiver_list.into_iter().enumerate() {
+        let key = check_recipient_key(idx, &recipient)?;
+        let is_x25519 = matches!(recipient, RecipientKey::X25519(_));
+        if seen.insert((is_x25519, key)) {
+            result.push(recipient);
+        }
+    }
+    Ok(result)
+}
+
+/// Decode a recipient key, ensuring that it is a valid public key: 32 bytes
+/// for Ed25519 and X25519 keys, or a 33 byte compressed P-256 key
+pub(super) fn check_recipient_key(
+    idx: usize,
+    recipient: &RecipientKey,
+) -> Result<Vec<u8>, PackError> {
+    let key = recipient
+        .verkey()
+        .key_bytes()
+        .map_err(|err| PackError::InvalidRecipient(idx, err.to_string()))?;
+    let expected_len = match recipient {
+        RecipientKey::P256(_) => 33,
+        _ => 32,
+    };
+    if key.len() != expected_len {
+        return Err(PackError::InvalidRecipient(
+            idx,
+            format!(
+                "Expected a {} byte key, found {} bytes",
+                expected_len,
+                key.len()
+            ),
+        ));
+    }
+    Ok(key)
+}
+
+/// Parse the envelope structure of a packed message
+pub(super) fn parse_envelope(
+    message: &[u8],
+    limits: &UnpackLimits,
+) -> Result<Envelope, UnpackError> {
+    if message.len() > limits.max_message {
+        return Err(UnpackError::LimitExceeded("message"));
+    }
+    if message.iter().find(|b| !b.is_ascii_whitespace()) == Some(&b'{') {
+        serde_json::from_slice(message).map_err(|err| UnpackError::InvalidEnvelope(err.to_string()))
+    } else {
+        parse_compact_envelope(message)
+    }
+}
+
+/// Parse an envelope in the compact JWE serialization
+fn parse_compact_envelope(message: &[u8]) -> Result<Envelope, UnpackError> {
+    let message = std::str::from_utf8(message)
+        .map_err(|_| UnpackError::InvalidEnvelope("Invalid UTF-8 in envelope".to_string()))?;
+    let parts = message.trim().split('.').collect::<Vec<&str>>();
+    if parts.len() != 5 {
+        return Err(UnpackError::InvalidEnvelope(
+            "Invalid compact serialization".to_string(),
+        ));
+    }
+    let (protected, encrypted_key, iv, ciphertext, tag) =
+        (parts[0], parts[1], parts[2], parts[3], parts[4]);
+    if encrypted_key.is_empty() {
+        return Ok(Envelope::Legacy(JWE {
+            protected: protected.to_string(),
+            iv: iv.to_string(),
+            ciphertext: ciphertext.to_string(),
+            tag: tag.to_string(),
+        }));
+    }
+    let header: HeaderV2 = serde_json::from_slice(&decode_b64(protected, "protected")?)
+        .map_err(|err| UnpackError::InvalidProtectedHeader(err.to_string()))?;
+    Ok(Envelope::DIDCommV2(JWEv2 {
+        protected: protected.to_string(),
+        recipients: vec![RecipientV2 {
+            encrypted_key: encrypted_key.to_string(),
+            header,
+        }],
+        iv: iv.to_string(),
+        ciphertext: ciphertext.to_string(),
+        tag: tag.to_string(),
+    }))
+}
+
+pub(super) fn check_header_size(protected: &str, limits: &UnpackLimits) -> Result<(), UnpackError> {
+    if protected.len() > limits.max_header {
+        Err(UnpackError::LimitExceeded("protected"))
+    } else {
+        Ok(())
+    }
+}
+
+pub(super) fn check_recipient_count(
+    count: usize,
+    limits: &UnpackLimits,
+) -> Result<(), UnpackError> {
+    if count > limits.max_recipients {
+        Err(UnpackError::LimitExceeded("recipients"))
+    } else {
+        Ok(())
+    }
+}
+
+/// Add an unauthenticated sender hint to the protected header fields
+pub(super) fn insert_sender_hint(extra_headers: &mut HeaderMap, sender_hint: Option<String>) {
+    if let Some(hint) = sender_hint {
+        extra_headers.insert(PROTECTED_HEADER_SENDER_HINT.to_string(), hint.into());
+    }
+}
+
+/// Remove the unauthenticated sender hint from the protected header fields
+pub(super) fn take_sender_hint(extra_headers: &mut HeaderMap) -> Option<String> {
+    match extra_headers.remove(PROTECTED_HEADER_SENDER_HINT) {
+        Some(serde_json::Value::String(hint)) => Some(hint),
+        _ => None,
+    }
+}
+
+/// Verify and unwrap a payload produced by `pack_message_signed`, which must
+/// be signed by the authenticated sender of the envelope
+pub(super) fn verify_signed_content(
+    message: Vec<u8>,
+    mut meta: UnpackMeta,
+) -> Result<(Vec<u8>, UnpackMeta), UnpackError> {
+    if meta.cty() != Some(SIGNED_CONTENT_TYPE) {
+        return Ok((message, meta));
+    }
+    let jws: JWS = serde_json::from_slice(&message)
+        .map_err(|err| UnpackError::InvalidEnvelope(err.to_string()))?;
+    let (payload, signer) = verify_message(&jws)?;
+    match meta.sender.as_ref() {
+        Some(sender) if same_key(sender, &signer) => (),
+        _ => return Err(UnpackError::InvalidSignature),
+    }
+    meta.signature_verified = true;
+    Ok((payload, meta))
+}
+
+/// Compare the decoded key bytes of two verkeys
+fn same_key(left: &EncodedVerKey, right: &EncodedVerKey) -> bool {
+    match (left.key_bytes(), right.key_bytes()) {
+        (Ok(left), Ok(right)) => left == right,
+        _ => false,
+    }
+}
+
+/// Decode a base64 envelope field
+pub(super) fn decode_b64(value: &str, field: &'static str) -> Result<Vec<u8>, UnpackError> {
+    base64::decode_urlsafe(value).map_err(|_| UnpackError::MalformedBase64(field))
+}
+
+pub(super) fn protected_json_string(protected: Vec<u8>) -> Result<String, UnpackError> {
+    String::from_utf8(protected).map_err(|_| {
+        UnpackError::InvalidProtectedHeader("Invalid UTF-8 in protected header".to_string())
+    })
+}
+
+pub(super) fn recipient_verkeys<'k>(
+    kids: impl Iterator<Item = &'k str>,
+) -> Result<Vec<EncodedVerKey>, UnpackError> {
+    let mut recip_vks = Vec::<EncodedVerKey>::new();
+    for kid in kids {
+        let vk = parse_kid(kid).map_err(|_| {
+            UnpackError::InvalidProtectedHeader(format!("Invalid recipient kid: {}", kid))
+        })?;
         recip_vks.push(vk);
     }
-    if let Some((idx, sk)) = lookup.find(&recip_vks).await {
-        let recip = protected.recipients.into_iter().nth(idx).unwrap();
-        let vk = recip_vks.into_iter().nth(idx).unwrap();
-        Ok(Some((recip, vk, sk)))
+    Ok(recip_vks)
+}
+
+/// Parse a recipient or sender key identifier, given either as a base58
+/// verkey or as a did:key DID URL
+pub(super) fn parse_kid(kid: &str) -> Result<EncodedVerKey, ConversionError> {
+    if kid.starts_with("did:key:") {
+        crate::did::key::kid_to_verkey(kid)
     } else {
-        Ok(None)
+        EncodedVerKey::from_str(kid)
+    }
+}
+
+/// Reduce unpack results to the plaintext, recipient and sender verkeys
+pub(super) fn split_unpack_meta(
+    (message, meta): (Vec<u8>, UnpackMeta),
+) -> (Vec<u8>, EncodedVerKey, Option<EncodedVerKey>) {
+    let recipient = meta.recipient().clone();
+    (message, recipient, meta.sender)
+}
+
+/// Look up the private key for one of the envelope recipients
+pub(super) async fn find_recipient_key<'f>(
+    lookup: impl KeyLookup<'f>,
+    recip_vks: &Vec<EncodedVerKey>,
+) -> Result<Option<(usize, PrivateKey)>, UnpackError> {
+    let find = lookup.find(recip_vks);
+    #[cfg(feature = "tracing")]
+    let find = tracing::Instrument::instrument(
+        find,
+        tracing::debug_span!("key_lookup", recipients = recip_vks.len()),
+    );
+    find.await.map_err(UnpackError::KeyLookup)
+}
+
+/// Validate the result of a recipient key lookup
+pub(super) fn select_recipient(
+    recip_vks: &[EncodedVerKey],
+    found: Option<(usize, PrivateKey)>,
+) -> Result<(usize, PrivateKey), UnpackError> {
+    match found {
+        Some((idx, sk)) if idx < recip_vks.len() => Ok((idx, sk)),
+        _ => Err(UnpackError::NoMatchingRecipient),
     }
 }
 
@@ -278,12 +1561,32 @@ async fn find_unpack_recipient<'f>(
 mod tests {
     use async_global_executor::block_on;
 
+    use super::super::PackBuilder;
     use super::*;
+    use crate::keys::test_fixtures::{TEST1, TEST2, TEST3, TEST4};
+
+    #[test]
+    fn test_pack_recipient_validation() {
+        let sk = TEST4.private_key();
+        let pk = sk.public_key().unwrap().as_base58().unwrap();
+
+        let packed = pack_message(b"hello there", vec![pk.clone(), pk.clone()], None).unwrap();
+        let jwe: JWE = serde_json::from_slice(&packed).unwrap();
+        let protected: Protected =
+            serde_json::from_slice(&base64::decode_urlsafe(&jwe.protected).unwrap()).unwrap();
+        assert_eq!(protected.recipients.len(), 1);
+
+        let bad_key = EncodedVerKey::new("3QhAfrWxN", None, None);
+        assert!(matches!(
+            pack_message(b"hello there", vec![pk, bad_key], None),
+            Err(PackError::InvalidRecipient(1, _))
+        ));
+    }
 
     #[test]
     fn test_anon_pack() {
-        let pk = PrivateKey::from_seed(b"000000000000000000000000000Test2")
-            .unwrap()
+        let pk = TEST2
+            .private_key()
             .public_key()
             .unwrap()
             .as_base58()
@@ -295,9 +1598,9 @@ mod tests {
 
     #[test]
     fn test_auth_pack() {
-        let sk = PrivateKey::from_seed(b"000000000000000000000000000Test1").unwrap();
-        let pk = PrivateKey::from_seed(b"000000000000000000000000000Test2")
-            .unwrap()
+        let sk = TEST1.private_key();
+        let pk = TEST2
+            .private_key()
             .public_key()
             .unwrap()
             .as_base58()
@@ -309,7 +1612,7 @@ mod tests {
 
     #[test]
     fn test_anon_pack_round_trip() {
-        let sk1 = PrivateKey::from_seed(b"000000000000000000000000000Test3").unwrap();
+        let sk1 = TEST3.private_key();
         let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
 
         let input_msg = b"hello there";
@@ -332,11 +1635,26 @@ mod tests {
         assert_eq!(p_send, None);
     }
 
+    #[test]
+    fn test_unpack_lookup_error() {
+        let pk1 = TEST3
+            .private_key()
+            .public_key()
+            .unwrap()
+            .as_base58()
+            .unwrap();
+        let packed = pack_message(b"hello there", vec![pk1], None).unwrap();
+
+        let lookup_fn = try_key_lookup_fn(|_| Err("Wallet unavailable".into()));
+        let result = block_on(unpack_message(&packed, &lookup_fn));
+        assert!(matches!(result, Err(UnpackError::KeyLookup(_))));
+    }
+
     #[test]
     fn test_auth_pack_round_trip() {
-        let sk1 = PrivateKey::from_seed(b"000000000000000000000000000Test3").unwrap();
+        let sk1 = TEST3.private_key();
         let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
-        let sk2 = PrivateKey::from_seed(b"000000000000000000000000000Test4").unwrap();
+        let sk2 = TEST4.private_key();
         let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
 
         let input_msg = b"hello there";
@@ -358,4 +1676,701 @@ mod tests {
         assert_eq!(p_recip, pk2);
         assert_eq!(p_send, Some(pk1));
     }
+
+    #[test]
+    fn test_signed_round_trip() {
+        let sk1 = TEST3.private_key();
+        let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
+        let sk2 = TEST4.private_key();
+        let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
+        let mut keys = HashMap::new();
+        keys.insert(pk2.clone(), sk2);
+
+        for format in &[PackFormat::Legacy, PackFormat::DIDCommV2] {
+            let packed = pack_message_signed(
+                b"hello there",
+                vec![pk2.clone()],
+                sk1.clone(),
+                Some(*format),
+                None,
+                None,
+            )
+            .unwrap();
+            let (msg, meta) = unpack_message_sync_with_meta(&packed, &keys, None).unwrap();
+            assert_eq!(msg, b"hello there");
+            assert_eq!(meta.sender, Some(pk1.clone()));
+            assert!(meta.signature_verified);
+            assert_eq!(meta.cty(), Some(SIGNED_CONTENT_TYPE));
+
+            let packed = PackBuilder::new()
+                .recipient(pk2.clone())
+                .sender(sk1.clone())
+                .format(*format)
+                .build()
+                .unwrap()
+                .pack(b"hello there")
+                .unwrap();
+            let (_, meta) = unpack_message_sync_with_meta(&packed, &keys, None).unwrap();
+            assert!(!meta.signature_verified);
+        }
+
+        // a payload signed by a key other than the sender is rejected
+        let other = TEST1.private_key();
+        let jws = sign_message(b"hello there", &other).unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert("cty".to_string(), SIGNED_CONTENT_TYPE.into());
+        let packed = PackBuilder::new()
+            .recipient(pk2)
+            .sender(sk1)
+            .extra_headers(headers)
+            .build()
+            .unwrap()
+            .pack(serde_json::to_vec(&jws).unwrap())
+            .unwrap();
+        assert!(matches!(
+            unpack_message_sync_with_meta(&packed, &keys, None),
+            Err(UnpackError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn test_sender_hint() {
+        let sk = TEST4.private_key();
+        let pk = sk.public_key().unwrap().as_base58().unwrap();
+        let mut keys = HashMap::new();
+        keys.insert(pk.clone(), sk);
+
+        for format in &[PackFormat::Legacy, PackFormat::DIDCommV2] {
+            let packed = PackBuilder::new()
+                .recipient(pk.clone())
+                .format(*format)
+                .sender_hint("did:sov:LjgpST2rjsoxYegQDRm7EL")
+                .build()
+                .unwrap()
+                .pack(b"hello there")
+                .unwrap();
+            let (msg, meta) = unpack_message_sync_with_meta(&packed, &keys, None).unwrap();
+            assert_eq!(msg, b"hello there");
+            assert_eq!(meta.sender, None);
+            assert_eq!(
+                meta.sender_hint.as_deref(),
+                Some("did:sov:LjgpST2rjsoxYegQDRm7EL")
+            );
+            assert!(meta.extra.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_pairwise_pack_round_trip() {
+        let sk1 = TEST1.private_key();
+        let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
+        let sk2 = TEST2.private_key();
+        let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
+        let sk3 = TEST3.private_key();
+        let pk3 = sk3.public_key().unwrap().as_base58().unwrap();
+        let sk4 = TEST4.private_key();
+        let pk4 = sk4.public_key().unwrap().as_base58().unwrap();
+
+        // pk3 and pk4 receive from distinct pairwise senders, pk1 anonymously
+        let packed = pack_message_pairwise(
+            b"hello there",
+            vec![
+                (pk3.clone(), Some(sk1.clone())),
+                (pk4.clone(), Some(sk2)),
+                (pk1.clone(), None),
+            ],
+            None,
+            None,
+        )
+        .unwrap();
+
+        for (recip_pk, recip_sk, sender) in vec![
+            (pk3, sk3, Some(pk1.clone())),
+            (pk4, sk4, Some(pk2)),
+            (pk1, sk1, None),
+        ] {
+            let mut keys = HashMap::new();
+            keys.insert(recip_pk.clone(), recip_sk);
+            let (msg, recip, send) = unpack_message_sync(&packed, &keys).unwrap();
+            assert_eq!(msg, b"hello there");
+            assert_eq!(recip, recip_pk);
+            assert_eq!(send, sender);
+        }
+    }
+
+    #[test]
+    fn test_unpack_with_unwrapper() {
+        let sk1 = TEST3.private_key();
+        let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
+        let sk2 = TEST4.private_key();
+        let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
+
+        for sender in vec![None, Some(sk1.clone())] {
+            let authcrypt = sender.is_some();
+            let packed = pack_message(b"hello there", vec![pk2.clone()], sender).unwrap();
+            let (msg, meta) = block_on(unpack_message_with_unwrapper(&packed, &sk2, None)).unwrap();
+            assert_eq!(msg, b"hello there");
+            assert_eq!(meta.recipient(), &pk2);
+            assert_eq!(
+                meta.sender,
+                if authcrypt { Some(pk1.clone()) } else { None }
+            );
+        }
+
+        let packed = pack_message(b"hello there", vec![pk2], None).unwrap();
+        assert!(matches!(
+            block_on(unpack_message_with_unwrapper(&packed, &sk1, None)),
+            Err(UnpackError::NoMatchingRecipient)
+        ));
+    }
+
+    #[test]
+    fn test_v2_pack_format_round_trip() {
+        let sk1 = TEST3.private_key();
+        let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
+        let sk2 = TEST4.private_key();
+        let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
+
+        let input_msg = b"hello there";
+        let packed = PackBuilder::new()
+            .recipient(pk2.clone())
+            .sender(sk1.clone())
+            .format(PackFormat::DIDCommV2)
+            .build()
+            .unwrap()
+            .pack(&input_msg)
+            .unwrap();
+
+        let lookup = |find_pks: &Vec<EncodedVerKey>| {
+            for (idx, pk) in find_pks.into_iter().enumerate() {
+                if pk == &pk2 {
+                    return Some((idx, sk2.clone()));
+                }
+            }
+            None
+        };
+
+        let lookup_fn = key_lookup_fn(lookup);
+        let result = unpack_message(&packed, &lookup_fn);
+        let (msg, p_recip, p_send) = block_on(result).unwrap();
+        assert_eq!(msg, input_msg);
+        assert_eq!(p_recip, pk2);
+        assert_eq!(p_send, Some(pk1));
+    }
+
+    #[test]
+    fn test_unpack_sync() {
+        let sk1 = TEST3.private_key();
+        let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
+        let sk2 = TEST4.private_key();
+        let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
+
+        let mut keys = HashMap::new();
+        keys.insert(pk2.clone(), sk2.clone());
+
+        let input_msg = b"hello there";
+        for format in &[PackFormat::Legacy, PackFormat::DIDCommV2] {
+            let packed = PackBuilder::new()
+                .recipients(vec![pk1.clone(), pk2.clone()])
+                .sender(sk1.clone())
+                .format(*format)
+                .build()
+                .unwrap()
+                .pack(&input_msg)
+                .unwrap();
+            let (msg, p_recip, p_send) = unpack_message_sync(&packed, &keys).unwrap();
+            assert_eq!(msg, input_msg);
+            assert_eq!(p_recip, pk2);
+            assert_eq!(p_send, Some(pk1.clone()));
+        }
+
+        let packed = pack_message(&input_msg, vec![pk1.clone()], None).unwrap();
+        assert!(matches!(
+            unpack_message_sync(&packed, &keys),
+            Err(UnpackError::NoMatchingRecipient)
+        ));
+    }
+
+    #[test]
+    fn test_pack_deterministic() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let sk1 = TEST3.private_key();
+        let sk2 = TEST4.private_key();
+        let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
+
+        let mut keys = HashMap::new();
+        keys.insert(pk2.clone(), sk2.clone());
+
+        for format in &[PackFormat::Legacy, PackFormat::DIDCommV2] {
+            let pack = |seed: u64| {
+                PackBuilder::new()
+                    .recipient(pk2.clone())
+                    .sender(sk1.clone())
+                    .format(*format)
+                    .build()
+                    .unwrap()
+                    .pack_with_rng(b"hello there", &mut StdRng::seed_from_u64(seed))
+                    .unwrap()
+            };
+            let packed = pack(1);
+            assert_eq!(packed, pack(1));
+            assert_ne!(packed, pack(2));
+            let (msg, _, _) = unpack_message_sync(&packed, &keys).unwrap();
+            assert_eq!(msg, b"hello there");
+        }
+    }
+
+    #[test]
+    fn test_compact_round_trip() {
+        let sk1 = TEST3.private_key();
+        let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
+        let sk2 = TEST4.private_key();
+        let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
+
+        let mut keys = HashMap::new();
+        keys.insert(pk2.clone(), sk2.clone());
+
+        let input_msg = b"hello there";
+        for format in &[PackFormat::Legacy, PackFormat::DIDCommV2] {
+            for sender in &[None, Some(sk1.clone())] {
+                let packed = pack_message_compact(
+                    &input_msg,
+                    pk2.clone(),
+                    sender.clone(),
+                    Some(*format),
+                    None,
+                )
+                .unwrap();
+                let compact = std::str::from_utf8(&packed).unwrap();
+                assert_eq!(compact.split('.').count(), 5);
+                assert!(!compact.contains('='));
+
+                let (msg, meta) = unpack_message_sync_with_meta(&packed, &keys, None).unwrap();
+                assert_eq!(msg, input_msg);
+                assert_eq!(meta.format, *format);
+                assert_eq!(meta.recipient(), &pk2);
+                assert_eq!(meta.sender, sender.as_ref().map(|_| pk1.clone()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_unpack_limits() {
+        let sk1 = TEST3.private_key();
+        let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
+        let sk2 = TEST4.private_key();
+        let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
+
+        let mut keys = HashMap::new();
+        keys.insert(pk2.clone(), sk2.clone());
+
+        for format in &[PackFormat::Legacy, PackFormat::DIDCommV2] {
+            let packed = PackBuilder::new()
+                .recipients(vec![pk1.clone(), pk2.clone()])
+                .format(*format)
+                .build()
+                .unwrap()
+                .pack(b"hello there")
+                .unwrap();
+            let limits = UnpackLimits {
+                max_recipients: 1,
+                ..Default::default()
+            };
+            assert!(matches!(
+                unpack_message_sync_with_meta(&packed, &keys, Some(limits.into())),
+                Err(UnpackError::LimitExceeded("recipients"))
+            ));
+            let limits = UnpackLimits {
+                max_message: 16,
+                ..Default::default()
+            };
+            assert!(matches!(
+                unpack_message_sync_with_meta(&packed, &keys, Some(limits.into())),
+                Err(UnpackError::LimitExceeded("message"))
+            ));
+            assert!(unpack_message_sync_with_meta(&packed, &keys, None).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_unpack_indy_sdk_compat() {
+        let sk = TEST4.private_key();
+        let pk = sk.public_key().unwrap().as_base58().unwrap();
+        let mut keys = HashMap::new();
+        keys.insert(pk.clone(), sk.clone());
+        let std_b64 = |val: &[u8]| base64::encode(val).trim_end_matches('=').to_string();
+
+        // construct an anoncrypt envelope using the unpadded standard base64
+        // alphabet, with the protected header as transmitted used as the AAD
+        let cek = [7u8; 32];
+        let enc_cek = crypto_box_seal(pk.key_exchange().unwrap().as_ref(), &cek).unwrap();
+        let protected = build_protected(
+            vec![Recipient {
+                encrypted_key: std_b64(&enc_cek),
+                header: Header {
+                    kid: pk.to_string(),
+                    sender: None,
+                    iv: None,
+                },
+            }],
+            false,
+            EnvelopeAlg::XChaCha20Poly1305,
+            &HeaderMap::new(),
+        );
+        let protected = std_b64(&serde_json::to_vec(&protected).unwrap());
+        let message = vec![0xfbu8; 512];
+        let content = encrypt_content(
+            EnvelopeAlg::XChaCha20Poly1305,
+            PackFormat::Legacy,
+            &cek,
+            protected.as_bytes(),
+            &message,
+            &mut OsRng,
+        )
+        .unwrap();
+        let packed = serde_json::to_vec(&JWE {
+            protected,
+            iv: std_b64(&content.iv),
+            ciphertext: std_b64(&content.ciphertext),
+            tag: std_b64(&content.tag),
+        })
+        .unwrap();
+
+        assert!(unpack_message_sync_with_meta(&packed, &keys, None).is_err());
+        let options = || {
+            Some(UnpackOptions {
+                compat: Compat::IndySdk,
+                ..Default::default()
+            })
+        };
+        let (msg, meta) = unpack_message_sync_with_meta(&packed, &keys, options()).unwrap();
+        assert_eq!(msg, message);
+        assert_eq!(meta.recipient(), &pk);
+
+        // the async and external key unwrapping entry points apply the same
+        // compatibility handling
+        let lookup_fn = key_lookup_fn(|find_pks: &Vec<EncodedVerKey>| {
+            find_pks
+                .iter()
+                .position(|find_pk| find_pk == &pk)
+                .map(|idx| (idx, sk.clone()))
+        });
+        let (msg, _) = block_on(unpack_message_with_meta(&packed, &lookup_fn, options())).unwrap();
+        assert_eq!(msg, message);
+        let (msg, meta) = block_on(unpack_message_with_unwrapper(&packed, &sk, options())).unwrap();
+        assert_eq!(msg, message);
+        assert_eq!(meta.recipient(), &pk);
+    }
+
+    #[test]
+    fn test_unpack_recipient_policy() {
+        let sk1 = TEST1.private_key();
+        let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
+        let sk2 = TEST2.private_key();
+        let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
+        let pk3 = TEST3
+            .private_key()
+            .public_key()
+            .unwrap()
+            .as_base58()
+            .unwrap();
+        let mut keys = HashMap::new();
+        keys.insert(pk1.clone(), sk1);
+        keys.insert(pk2.clone(), sk2);
+
+        for format in &[PackFormat::Legacy, PackFormat::DIDCommV2] {
+            let packed = PackBuilder::new()
+                .recipients(vec![pk3.clone(), pk1.clone(), pk2.clone()])
+                .format(*format)
+                .build()
+                .unwrap()
+                .pack(b"hello there")
+                .unwrap();
+
+            let results =
+                unpack_message_sync_with_policy(&packed, &keys, RecipientPolicy::First, None)
+                    .unwrap();
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].1.recipient(), &pk1);
+
+            let results = unpack_message_sync_with_policy(
+                &packed,
+                &keys,
+                RecipientPolicy::Prefer(pk2.clone()),
+                None,
+            )
+            .unwrap();
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].1.recipient(), &pk2);
+
+            let results =
+                unpack_message_sync_with_policy(&packed, &keys, RecipientPolicy::All, None)
+                    .unwrap();
+            assert_eq!(
+                results
+                    .iter()
+                    .map(|(msg, meta)| (msg.as_slice(), meta.recipient_index))
+                    .collect::<Vec<_>>(),
+                vec![(&b"hello there"[..], 1), (&b"hello there"[..], 2)]
+            );
+        }
+    }
+
+    #[test]
+    fn test_unpack_alg_mismatch() {
+        let sk1 = TEST3.private_key();
+        let sk2 = TEST4.private_key();
+        let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
+        let mut keys = HashMap::new();
+        keys.insert(pk2.clone(), sk2);
+
+        let packed = pack_message(b"hello there", vec![pk2], Some(sk1)).unwrap();
+        let mut jwe: JWE = serde_json::from_slice(&packed).unwrap();
+        let mut protected: Protected =
+            serde_json::from_slice(&base64::decode_urlsafe(&jwe.protected).unwrap()).unwrap();
+        protected.alg = PROTECTED_HEADER_ALG_ANON.to_string();
+        jwe.protected = base64::encode_urlsafe(serde_json::to_vec(&protected).unwrap());
+        let packed = serde_json::to_vec(&jwe).unwrap();
+
+        assert!(matches!(
+            unpack_message_sync_with_meta(&packed, &keys, None),
+            Err(UnpackError::InvalidProtectedHeader(_))
+        ));
+        // the modified protected header no longer matches the AAD
+        let options = UnpackOptions {
+            lenient_alg: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            unpack_message_sync_with_meta(&packed, &keys, Some(options)),
+            Err(UnpackError::AeadFailure)
+        ));
+    }
+
+    #[test]
+    fn test_unpack_meta() {
+        let sk1 = TEST3.private_key();
+        let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
+        let sk2 = TEST4.private_key();
+        let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
+
+        let lookup = |find_pks: &Vec<EncodedVerKey>| {
+            find_pks
+                .iter()
+                .position(|pk| pk == &pk2)
+                .map(|idx| (idx, sk2.clone()))
+        };
+        let lookup_fn = key_lookup_fn(lookup);
+
+        let input_msg = b"hello there";
+        let mut extra_headers = HeaderMap::new();
+        extra_headers.insert("cty".to_string(), "application/json".into());
+        extra_headers.insert("x-vendor".to_string(), 1.into());
+        for format in &[PackFormat::Legacy, PackFormat::DIDCommV2] {
+            let packed = PackBuilder::new()
+                .recipients(vec![pk1.clone(), pk2.clone()])
+                .format(*format)
+                .extra_headers(extra_headers.clone())
+                .build()
+                .unwrap()
+                .pack(&input_msg)
+                .unwrap();
+            let (msg, meta) =
+                block_on(unpack_message_with_meta(&packed, &lookup_fn, None)).unwrap();
+            assert_eq!(meta.cty(), Some("application/json"));
+            assert_eq!(meta.extra, extra_headers);
+            assert_eq!(msg, input_msg);
+            assert_eq!(meta.format, *format);
+            assert_eq!(meta.recipients, vec![pk1.clone(), pk2.clone()]);
+            assert_eq!(meta.recipient_index, 1);
+            assert_eq!(meta.recipient(), &pk2);
+            assert!(!meta.is_authcrypt());
+            let protected: serde_json::Value = serde_json::from_str(&meta.protected).unwrap();
+            assert_eq!(protected["alg"], meta.alg.as_str());
+            assert_eq!(protected["enc"], meta.enc.as_str());
+        }
+
+        let packed = pack_message(&input_msg, vec![pk2.clone()], Some(sk1)).unwrap();
+        let (_, meta) = block_on(unpack_message_with_meta(&packed, &lookup_fn, None)).unwrap();
+        assert_eq!(meta.alg, PROTECTED_HEADER_ALG_AUTH);
+        assert_eq!(meta.sender, Some(pk1));
+    }
+
+    #[test]
+    fn test_stream_round_trip() {
+        let sk1 = TEST3.private_key();
+        let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
+        let sk2 = TEST4.private_key();
+        let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
+
+        let lookup = |find_pks: &Vec<EncodedVerKey>| {
+            find_pks
+                .iter()
+                .position(|pk| pk == &pk2)
+                .map(|idx| (idx, sk2.clone()))
+        };
+        let lookup_fn = key_lookup_fn(lookup);
+
+        // include lengths falling on a chunk boundary
+        for msg_len in &[0usize, 5, 16, 48, 50] {
+            let input_msg = vec![7u8; *msg_len];
+            let mut packed = Vec::new();
+            pack_stream_chunked(
+                input_msg.as_slice(),
+                &mut packed,
+                vec![pk2.clone().into()],
+                Some(sk1.clone()),
+                16,
+                &mut OsRng,
+            )
+            .unwrap();
+
+            let mut output = Vec::new();
+            let result = unpack_message_stream(packed.as_slice(), &mut output, &lookup_fn);
+            let (p_recip, p_send) = block_on(result).unwrap();
+            assert_eq!(output, input_msg);
+            assert_eq!(p_recip, pk2);
+            assert_eq!(p_send, Some(pk1.clone()));
+
+            // truncating the stream must be detected
+            let mut output = Vec::new();
+            let truncated = &packed[..packed.len() - 1];
+            let result = unpack_message_stream(truncated, &mut output, &lookup_fn);
+            assert!(block_on(result).is_err());
+        }
+    }
+
+    #[test]
+    fn test_stream_deterministic() {
+        use rand::{rngs::StdRng, SeedableRng};
+
+        let sk1 = TEST3.private_key();
+        let pk2 = TEST4.encoded_verkey();
+        let pack = |seed: u64| {
+            let mut packed = Vec::new();
+            pack_message_stream_with_rng(
+                &b"hello there"[..],
+                &mut packed,
+                vec![pk2.clone()],
+                Some(sk1.clone()),
+                &mut StdRng::seed_from_u64(seed),
+            )
+            .unwrap();
+            packed
+        };
+        let packed = pack(1);
+        assert_eq!(packed, pack(1));
+        assert_ne!(packed, pack(2));
+    }
+
+    #[test]
+    fn test_stream_header_tampered() {
+        let sk = TEST4.private_key();
+        let pk = sk.public_key().unwrap().as_base58().unwrap();
+        let lookup = |find_pks: &Vec<EncodedVerKey>| {
+            find_pks
+                .iter()
+                .position(|find_pk| find_pk == &pk)
+                .map(|idx| (idx, sk.clone()))
+        };
+        let lookup_fn = key_lookup_fn(lookup);
+
+        let mut packed = Vec::new();
+        pack_stream_chunked(
+            &b"hello"[..],
+            &mut packed,
+            vec![pk.clone().into()],
+            None,
+            16,
+            &mut OsRng,
+        )
+        .unwrap();
+
+        // alter the chunk size without changing the header length
+        let field = b"\"chunk_size\":16";
+        let pos = packed
+            .windows(field.len())
+            .position(|window| window == field)
+            .unwrap();
+        packed[pos + field.len() - 1] = b'7';
+        let mut output = Vec::new();
+        let result = unpack_message_stream(packed.as_slice(), &mut output, &lookup_fn);
+        assert!(matches!(block_on(result), Err(UnpackError::AeadFailure)));
+        assert!(output.is_empty());
+    }
+
+    #[test]
+    fn test_aes_gcm_round_trip() {
+        let sk1 = TEST3.private_key();
+        let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
+        let sk2 = TEST4.private_key();
+        let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
+
+        let mut keys = HashMap::new();
+        keys.insert(pk2.clone(), sk2.clone());
+
+        let input_msg = b"hello there";
+        for format in &[PackFormat::Legacy, PackFormat::DIDCommV2] {
+            let packed = PackBuilder::new()
+                .recipient(pk2.clone())
+                .sender(sk1.clone())
+                .format(*format)
+                .encryption_alg(EnvelopeAlg::Aes256Gcm)
+                .build()
+                .unwrap()
+                .pack(&input_msg)
+                .unwrap();
+            let jwe: serde_json::Value = serde_json::from_slice(&packed).unwrap();
+            let protected: serde_json::Value = serde_json::from_slice(
+                &base64::decode_urlsafe(jwe["protected"].as_str().unwrap()).unwrap(),
+            )
+            .unwrap();
+            assert_eq!(protected["enc"], "A256GCM");
+
+            let (msg, p_recip, p_send) = unpack_message_sync(&packed, &keys).unwrap();
+            assert_eq!(msg, input_msg);
+            assert_eq!(p_recip, pk2);
+            assert_eq!(p_send, Some(pk1.clone()));
+        }
+    }
+
+    #[test]
+    fn test_detached_round_trip() {
+        let sk1 = TEST3.private_key();
+        let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
+
+        let lookup = |find_pks: &Vec<EncodedVerKey>| {
+            find_pks
+                .iter()
+                .position(|pk| pk == &pk1)
+                .map(|idx| (idx, sk1.clone()))
+        };
+        let lookup_fn = key_lookup_fn(lookup);
+
+        let input_msg = b"hello there";
+        for format in &[PackFormat::Legacy, PackFormat::DIDCommV2] {
+            let (envelope, ciphertext) = pack_message_detached(
+                &input_msg,
+                vec![pk1.clone()],
+                None,
+                Some(*format),
+                None,
+                None,
+            )
+            .unwrap();
+            let jwe: serde_json::Value = serde_json::from_slice(&envelope).unwrap();
+            assert_eq!(jwe["ciphertext"], "");
+            assert_eq!(ciphertext.len(), input_msg.len());
+
+            let result = unpack_message_detached(&envelope, &ciphertext, &lookup_fn);
+            let (msg, p_recip, p_send) = block_on(result).unwrap();
+            assert_eq!(msg, input_msg);
+            assert_eq!(p_recip, pk1);
+            assert_eq!(p_send, None);
+
+            let result = unpack_message_detached(&envelope, b"hello where", &lookup_fn);
+            assert!(matches!(block_on(result), Err(UnpackError::AeadFailure)));
+        }
+    }
 }
@@ -0,0 +1,338 @@
+use aead::generic_array::typenum::U32;
+use rand::{rngs::OsRng, CryptoRng, RngCore};
+use ursa::hash::sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use super::cipher::{decrypt_content, enc_header_value, encrypt_content, parse_enc_header};
+use super::error::{PackError, UnpackError};
+use super::jws::{sign_message, verify_message, JWS};
+use super::types::{EnvelopeAlg, PackFormat};
+use crate::base64;
+use crate::keys::{ArrayKey, EncodedVerKey, PrivateKey};
+
+const ATTACHMENT_KEY_LABEL: &'static [u8] = b"DIDComm-attachment";
+
+/// The root key for encrypting the attachments of a message.
+///
+/// A random attachment key is generated by the sender and included in the
+/// message body, so that it is protected by the envelope CEK. Each attachment
+/// is then encrypted with a key derived from the attachment key and its `@id`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct AttachmentKey(ArrayKey<U32>);
+
+impl AttachmentKey {
+    /// Generate a new random attachment key
+    pub fn random() -> Self {
+        Self::random_with_rng(&mut OsRng)
+    }
+
+    /// Generate a new attachment key from a random number generator
+    pub fn random_with_rng<G: CryptoRng + RngCore>(rng: &mut G) -> Self {
+        Self(ArrayKey::random_with_rng(rng))
+    }
+
+    /// Load an attachment key from its base64-URL encoding
+    pub fn from_base64(key: &str) -> Result<Self, UnpackError> {
+        let bytes = Zeroizing::new(
+            base64::decode_urlsafe(key).map_err(|_| UnpackError::MalformedBase64("key"))?,
+        );
+        Ok(Self(
+            ArrayKey::try_from_slice(&bytes[..]).map_err(UnpackError::InvalidKey)?,
+        ))
+    }
+
+    /// Encode the attachment key for inclusion in a message body
+    pub fn to_base64(&self) -> String {
+        self.0.to_b64()
+    }
+
+    /// Derive the content encryption key for a single attachment
+    fn derive(&self, id: &str) -> Zeroizing<Vec<u8>> {
+        let mut hasher = Sha256::new();
+        hasher.input(ATTACHMENT_KEY_LABEL);
+        hasher.input(&self.0[..]);
+        hasher.input(id.as_bytes());
+        Zeroizing::new(hasher.result().to_vec())
+    }
+}
+
+impl std::fmt::Debug for AttachmentKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("AttachmentKey").field(&"<secret>").finish()
+    }
+}
+
+/// An attachment decorator as defined by Aries RFC 0017
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Attachment {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(rename = "mime-type", skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    /// The content encryption algorithm, when the attachment data is encrypted
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enc: Option<String>,
+    pub data: AttachmentData,
+}
+
+/// The data of an attachment decorator, either inline or linked
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq)]
+pub struct AttachmentData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base64: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub json: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub links: Vec<String>,
+    /// The hex-encoded SHA-256 hash of the (encrypted) attachment content
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jws: Option<JWS>,
+}
+
+/// Encrypt an attachment payload, embedding the result as base64 data
+pub fn encrypt_attachment(
+    key: &AttachmentKey,
+    id: &str,
+    mime_type: Option<String>,
+    payload: impl AsRef<[u8]>,
+    enc_alg: Option<EnvelopeAlg>,
+) -> Result<Attachment, PackError> {
+    let (mut attachment, content) = encrypt_attachment_content(
+        key,
+        id,
+        mime_type,
+        payload.as_ref(),
+        enc_alg.unwrap_or_default(),
+        &mut OsRng,
+    )?;
+    attachment.data.base64 = Some(base64::encode_urlsafe_nopad(content));
+    Ok(attachment)
+}
+
+/// Encrypt an attachment payload to be hosted externally, returning the
+/// attachment decorator and the encrypted content to be published at `links`
+pub fn encrypt_attachment_linked(
+    key: &AttachmentKey,
+    id: &str,
+    mime_type: Option<String>,
+    payload: impl AsRef<[u8]>,
+    links: Vec<String>,
+    enc_alg: Option<EnvelopeAlg>,
+) -> Result<(Attachment, Vec<u8>), PackError> {
+    if links.is_empty() {
+        return Err(PackError::InvalidPayload(
+            "At least one attachment link is required".to_string(),
+        ));
+    }
+    let (mut attachment, content) = encrypt_attachment_content(
+        key,
+        id,
+        mime_type,
+        payload.as_ref(),
+        enc_alg.unwrap_or_default(),
+        &mut OsRng,
+    )?;
+    attachment.data.links = links;
+    Ok((attachment, content))
+}
+
+fn encrypt_attachment_content<G: CryptoRng + RngCore>(
+    key: &AttachmentKey,
+    id: &str,
+    mime_type: Option<String>,
+    payload: &[u8],
+    enc_alg: EnvelopeAlg,
+    rng: &mut G,
+) -> Result<(Attachment, Vec<u8>), PackError> {
+    let cek = key.derive(id);
+    let encrypted = encrypt_content(
+        enc_alg,
+        PackFormat::DIDCommV2,
+        &cek,
+        id.as_bytes(),
+        payload,
+        rng,
+    )?;
+    let mut content = encrypted.iv;
+    content.extend_from_slice(&encrypted.ciphertext);
+    content.extend_from_slice(&encrypted.tag);
+    let attachment = Attachment {
+        id: id.to_string(),
+        mime_type,
+        enc: Some(enc_header_value(enc_alg, PackFormat::DIDCommV2).to_string()),
+        data: AttachmentData {
+            sha256: Some(hex::encode(Sha256::digest(&content))),
+            ..Default::default()
+        },
+    };
+    Ok((attachment, content))
+}
+
+/// Decrypt an attachment payload. For linked attachments, `fetched` must
+/// contain the content retrieved from one of the attachment links.
+pub fn decrypt_attachment(
+    key: &AttachmentKey,
+    attachment: &Attachment,
+    fetched: Option<&[u8]>,
+) -> Result<Vec<u8>, UnpackError> {
+    let enc = attachment
+        .enc
+        .as_ref()
+        .ok_or_else(|| UnpackError::MalformedField("enc".to_string()))?;
+    let enc_alg = parse_enc_header(enc, PackFormat::DIDCommV2)?;
+    let content = match (&attachment.data.base64, fetched) {
+        (Some(data), _) => {
+            base64::decode_urlsafe(data).map_err(|_| UnpackError::MalformedBase64("base64"))?
+        }
+        (None, Some(fetched)) if !attachment.data.links.is_empty() => fetched.to_vec(),
+        _ => return Err(UnpackError::MalformedField("data".to_string())),
+    };
+    if let Some(sha256) = &attachment.data.sha256 {
+        check_sha256(sha256, &content)?;
+    }
+
+    let (iv_len, tag_len) = match enc_alg {
+        EnvelopeAlg::XChaCha20Poly1305 => (24, 16),
+        EnvelopeAlg::Aes256Gcm => (12, 16),
+    };
+    if content.len() < iv_len + tag_len {
+        return Err(UnpackError::InvalidEnvelope(
+            "Attachment content is too short".to_string(),
+        ));
+    }
+    let (iv, rest) = content.split_at(iv_len);
+    let (ciphertext, tag) = rest.split_at(rest.len() - tag_len);
+    let cek = key.derive(&attachment.id);
+    decrypt_content(
+        enc_alg,
+        PackFormat::DIDCommV2,
+        &cek,
+        attachment.id.as_bytes(),
+        iv,
+        ciphertext.to_vec(),
+        tag,
+    )
+}
+
+/// Sign the SHA-256 hash of an attachment's content, adding a JWS to the
+/// attachment data
+pub fn sign_attachment(attachment: &mut Attachment, key: &PrivateKey) -> Result<(), PackError> {
+    let digest = match (&attachment.data.sha256, &attachment.data.base64) {
+        (Some(sha256), _) => hex::decode(sha256)
+            .map_err(|_| PackError::InvalidPayload("Invalid sha256".to_string()))?,
+        (None, Some(data)) => {
+            let content = base64::decode_urlsafe(data)
+                .map_err(|_| PackError::InvalidPayload("Invalid attachment data".to_string()))?;
+            let digest = Sha256::digest(&content).to_vec();
+            attachment.data.sha256 = Some(hex::encode(&digest));
+            digest
+        }
+        (None, None) => {
+            return Err(PackError::InvalidPayload(
+                "Attachment has no content hash".to_string(),
+            ))
+        }
+    };
+    attachment.data.jws = Some(sign_message(digest, key)?);
+    Ok(())
+}
+
+/// Verify the JWS of an attachment against its content hash, returning the
+/// signer's verkey
+pub fn verify_attachment(attachment: &Attachment) -> Result<EncodedVerKey, UnpackError> {
+    let jws = attachment
+        .data
+        .jws
+        .as_ref()
+        .ok_or_else(|| UnpackError::MalformedField("jws".to_string()))?;
+    let sha256 = attachment
+        .data
+        .sha256
+        .as_ref()
+        .ok_or_else(|| UnpackError::MalformedField("sha256".to_string()))?;
+    let (digest, signer) = verify_message(jws)?;
+    if hex::decode(sha256).ok().as_deref() != Some(&digest[..]) {
+        return Err(UnpackError::InvalidSignature);
+    }
+    if let Some(data) = &attachment.data.base64 {
+        let content =
+            base64::decode_urlsafe(data).map_err(|_| UnpackError::MalformedBase64("base64"))?;
+        check_sha256(sha256, &content)?;
+    }
+    Ok(signer)
+}
+
+fn check_sha256(sha256: &str, content: &[u8]) -> Result<(), UnpackError> {
+    if hex::decode(sha256).ok().as_deref() != Some(&Sha256::digest(content)[..]) {
+        return Err(UnpackError::MalformedField("sha256".to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::test_fixtures::TEST3;
+
+    #[test]
+    fn test_attachment_round_trip() {
+        let key = AttachmentKey::random();
+        let key_b64 = key.to_base64();
+        let attachment = encrypt_attachment(
+            &key,
+            "attach-1",
+            Some("text/plain".to_string()),
+            b"attached data",
+            None,
+        )
+        .unwrap();
+
+        let key = AttachmentKey::from_base64(&key_b64).unwrap();
+        assert_eq!(
+            decrypt_attachment(&key, &attachment, None).unwrap(),
+            b"attached data"
+        );
+
+        // the derived key is bound to the attachment id
+        let mut moved = attachment.clone();
+        moved.id = "attach-2".to_string();
+        assert!(decrypt_attachment(&key, &moved, None).is_err());
+
+        let (linked, content) = encrypt_attachment_linked(
+            &key,
+            "attach-3",
+            None,
+            b"linked data",
+            vec!["https://example.com/attach-3".to_string()],
+            Some(EnvelopeAlg::Aes256Gcm),
+        )
+        .unwrap();
+        assert!(decrypt_attachment(&key, &linked, None).is_err());
+        assert_eq!(
+            decrypt_attachment(&key, &linked, Some(&content)).unwrap(),
+            b"linked data"
+        );
+        let mut tampered = content.clone();
+        tampered[0] ^= 1;
+        match decrypt_attachment(&key, &linked, Some(&tampered)) {
+            Err(UnpackError::MalformedField(field)) => assert_eq!(field, "sha256"),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_attachment_signature() {
+        let sk = TEST3.private_key();
+        let pk = sk.public_key().unwrap().as_base58().unwrap();
+        let key = AttachmentKey::random();
+        let mut attachment = encrypt_attachment(&key, "attach-1", None, b"data", None).unwrap();
+
+        sign_attachment(&mut attachment, &sk).unwrap();
+        assert_eq!(verify_attachment(&attachment).unwrap(), pk);
+
+        attachment.data.sha256 = Some(hex::encode(Sha256::digest(b"other")));
+        assert!(verify_attachment(&attachment).is_err());
+    }
+}
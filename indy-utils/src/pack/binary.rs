@@ -0,0 +1,253 @@
+use std::convert::TryFrom;
+
+use rand::rngs::OsRng;
+
+use super::alg::{pack_with_options, parse_envelope, unpack_message};
+use super::error::{PackError, UnpackError};
+use super::types::{
+    Envelope, EnvelopeAlg, HeaderMap, HeaderV2, JWEv2, KeyLookup, PackFormat, RecipientKey,
+    RecipientV2, UnpackLimits, JWE,
+};
+use crate::base64;
+use crate::keys::{EncodedVerKey, PrivateKey};
+
+const BINARY_TAG_LEGACY: u8 = 0x01;
+const BINARY_TAG_V2: u8 = 0x02;
+
+/// Pack a message, producing the length-prefixed binary serialization of
+/// the envelope
+pub fn pack_message_binary<M: AsRef<[u8]>, R: Into<RecipientKey>>(
+    message: M,
+    receiver_list: Vec<R>,
+    sender_key: Option<PrivateKey>,
+    format: Option<PackFormat>,
+    enc_alg: Option<EnvelopeAlg>,
+    extra_headers: Option<HeaderMap>,
+) -> Result<Vec<u8>, PackError> {
+    let envelope = pack_with_options(
+        message,
+        receiver_list,
+        sender_key,
+        format.unwrap_or_default(),
+        enc_alg.unwrap_or_default(),
+        extra_headers.unwrap_or_default(),
+        None,
+        &mut OsRng,
+    )?;
+    envelope_to_binary(&envelope)
+}
+
+/// Unpack a message in the length-prefixed binary serialization
+pub async fn unpack_message_binary<'f>(
+    message: impl AsRef<[u8]>,
+    lookup: impl KeyLookup<'f>,
+) -> Result<(Vec<u8>, EncodedVerKey, Option<EncodedVerKey>), UnpackError> {
+    let envelope = envelope_from_binary(message.as_ref())?;
+    unpack_message(envelope, lookup).await
+}
+
+/// Convert a JSON envelope to the length-prefixed binary serialization.
+///
+/// The envelope is written as a format tag followed by each field as a
+/// 32-bit big-endian length and the decoded field value. Legacy envelopes
+/// contain the `protected`, `iv`, `ciphertext` and `tag` fields, and DIDComm
+/// v2 envelopes add a recipient count followed by the `encrypted_key` and
+/// `kid` of each recipient.
+pub fn envelope_to_binary(envelope: &[u8]) -> Result<Vec<u8>, PackError> {
+    let envelope = parse_envelope(envelope, &UnpackLimits::default())
+        .map_err(|err| PackError::InvalidEnvelope(err.to_string()))?;
+    let mut out = Vec::new();
+    match envelope {
+        Envelope::Legacy(jwe) => {
+            out.push(BINARY_TAG_LEGACY);
+            for field in &[&jwe.protected, &jwe.iv, &jwe.ciphertext, &jwe.tag] {
+                write_field(&mut out, &decode_canonical(field, true)?)?;
+            }
+        }
+        Envelope::DIDCommV2(jwe) => {
+            out.push(BINARY_TAG_V2);
+            for field in &[&jwe.protected, &jwe.iv, &jwe.ciphertext, &jwe.tag] {
+                write_field(&mut out, &decode_canonical(field, false)?)?;
+            }
+            write_len(&mut out, jwe.recipients.len())?;
+            for recipient in &jwe.recipients {
+                write_field(
+                    &mut out,
+                    &decode_canonical(&recipient.encrypted_key, false)?,
+                )?;
+                write_field(&mut out, recipient.header.kid.as_bytes())?;
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Convert a length-prefixed binary envelope to its JSON serialization
+pub fn envelope_from_binary(message: &[u8]) -> Result<Vec<u8>, UnpackError> {
+    let limits = UnpackLimits::default();
+    if message.len() > limits.max_message {
+        return Err(UnpackError::LimitExceeded("message"));
+    }
+    let (format_tag, mut reader) = message
+        .split_first()
+        .ok_or_else(|| UnpackError::InvalidEnvelope("Empty binary envelope".to_string()))?;
+    // legacy envelopes use padded base64url, DIDComm v2 envelopes do not
+    let padded = *format_tag == BINARY_TAG_LEGACY;
+    let protected = encode_field(read_field(&mut reader)?, padded);
+    let iv = encode_field(read_field(&mut reader)?, padded);
+    let ciphertext = encode_field(read_field(&mut reader)?, padded);
+    let tag = encode_field(read_field(&mut reader)?, padded);
+    let result = match *format_tag {
+        BINARY_TAG_LEGACY => serde_json::to_vec(&JWE {
+            protected,
+            iv,
+            ciphertext,
+            tag,
+        }),
+        BINARY_TAG_V2 => {
+            let count = read_len(&mut reader)?;
+            if count > limits.max_recipients {
+                return Err(UnpackError::LimitExceeded("recipients"));
+            }
+            let mut recipients = Vec::with_capacity(count);
+            for _ in 0..count {
+                let encrypted_key = encode_field(read_field(&mut reader)?, false);
+                let kid = String::from_utf8(read_field(&mut reader)?.to_vec()).map_err(|_| {
+                    UnpackError::InvalidEnvelope("Invalid recipient kid".to_string())
+                })?;
+                recipients.push(RecipientV2 {
+                    encrypted_key,
+                    header: HeaderV2 { kid },
+                });
+            }
+            serde_json::to_vec(&JWEv2 {
+                protected,
+                recipients,
+                iv,
+                ciphertext,
+                tag,
+            })
+        }
+        _ => {
+            return Err(UnpackError::InvalidEnvelope(format!(
+                "Unknown binary envelope format: {}",
+                format_tag
+            )))
+        }
+    };
+    if !reader.is_empty() {
+        return Err(UnpackError::InvalidEnvelope(
+            "Trailing data in binary envelope".to_string(),
+        ));
+    }
+    result.map_err(|err| UnpackError::InvalidEnvelope(err.to_string()))
+}
+
+/// Decode a base64 envelope field, ensuring that it can be reproduced exactly
+/// when converting back to JSON
+fn encode_field(value: &[u8], padded: bool) -> String {
+    if padded {
+        base64::encode_urlsafe(value)
+    } else {
+        base64::encode_urlsafe_nopad(value)
+    }
+}
+
+fn decode_canonical(value: &str, padded: bool) -> Result<Vec<u8>, PackError> {
+    let decoded = base64::decode_urlsafe(value)
+        .map_err(|_| PackError::InvalidEnvelope("Invalid base64 field".to_string()))?;
+    if encode_field(&decoded, padded) != value {
+        return Err(PackError::InvalidEnvelope(
+            "Non-canonical base64 field".to_string(),
+        ));
+    }
+    Ok(decoded)
+}
+
+fn write_len(out: &mut Vec<u8>, len: usize) -> Result<(), PackError> {
+    let len =
+        u32::try_from(len).map_err(|_| PackError::InvalidEnvelope("Field too long".to_string()))?;
+    out.extend_from_slice(&len.to_be_bytes());
+    Ok(())
+}
+
+fn write_field(out: &mut Vec<u8>, value: &[u8]) -> Result<(), PackError> {
+    write_len(out, value.len())?;
+    out.extend_from_slice(value);
+    Ok(())
+}
+
+fn read_len(reader: &mut &[u8]) -> Result<usize, UnpackError> {
+    if reader.len() < 4 {
+        return Err(UnpackError::InvalidEnvelope(
+            "Truncated binary envelope".to_string(),
+        ));
+    }
+    let (len, rest) = reader.split_at(4);
+    *reader = rest;
+    Ok(u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize)
+}
+
+fn read_field<'r>(reader: &mut &'r [u8]) -> Result<&'r [u8], UnpackError> {
+    let len = read_len(reader)?;
+    if reader.len() < len {
+        return Err(UnpackError::InvalidEnvelope(
+            "Truncated binary envelope".to_string(),
+        ));
+    }
+    let (field, rest) = reader.split_at(len);
+    *reader = rest;
+    Ok(field)
+}
+
+#[cfg(test)]
+mod tests {
+    use async_global_executor::block_on;
+
+    use super::super::{key_lookup_fn, PackBuilder};
+    use super::*;
+    use crate::keys::test_fixtures::{TEST3, TEST4};
+
+    #[test]
+    fn test_binary_round_trip() {
+        let sk1 = TEST3.private_key();
+        let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
+        let sk2 = TEST4.private_key();
+        let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
+        let lookup = key_lookup_fn(|pks: &Vec<EncodedVerKey>| {
+            pks.iter()
+                .position(|pk| pk == &pk2)
+                .map(|idx| (idx, sk2.clone()))
+        });
+
+        for format in &[PackFormat::Legacy, PackFormat::DIDCommV2] {
+            let packed = PackBuilder::new()
+                .recipient(pk2.clone())
+                .sender(sk1.clone())
+                .format(*format)
+                .build()
+                .unwrap()
+                .pack(b"hello there")
+                .unwrap();
+            let binary = envelope_to_binary(&packed).unwrap();
+            assert!(binary.len() < packed.len());
+            assert_eq!(envelope_from_binary(&binary).unwrap(), packed);
+
+            let binary = pack_message_binary(
+                b"hello there",
+                vec![pk2.clone()],
+                Some(sk1.clone()),
+                Some(*format),
+                None,
+                None,
+            )
+            .unwrap();
+            let (msg, recip, sender) = block_on(unpack_message_binary(&binary, &lookup)).unwrap();
+            assert_eq!(msg, b"hello there");
+            assert_eq!(recip, pk2);
+            assert_eq!(sender, Some(pk1.clone()));
+
+            assert!(envelope_from_binary(&binary[..binary.len() - 1]).is_err());
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use rand::{rngs::OsRng, CryptoRng, RngCore};
+
+use super::alg::{insert_sender_hint, pack_message_legacy, validate_recipients};
+use super::error::PackError;
+use super::nonblocking::pack_async_with_options;
+use super::types::{EnvelopeAlg, HeaderMap, PackFormat, RecipientKey};
+use super::v2::pack_message_v2;
+use crate::keys::PrivateKey;
+
+const RESERVED_HEADERS_LEGACY: &[&str] = &["alg", "enc", "recipients", "sender_hint", "typ"];
+const RESERVED_HEADERS_V2: &[&str] = &[
+    "alg",
+    "apu",
+    "apv",
+    "enc",
+    "epk",
+    "sender_hint",
+    "skid",
+    "typ",
+];
+
+/// Builder for the options used when packing a message
+#[derive(Debug, Default)]
+pub struct PackBuilder {
+    recipients: Vec<RecipientKey>,
+    sender: Option<PrivateKey>,
+    format: PackFormat,
+    enc_alg: EnvelopeAlg,
+    extra_headers: HeaderMap,
+    sender_hint: Option<String>,
+}
+
+impl PackBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a recipient key
+    pub fn recipient<K: Into<RecipientKey>>(mut self, recipient: K) -> Self {
+        self.recipients.push(recipient.into());
+        self
+    }
+
+    /// Add multiple recipient keys
+    pub fn recipients<I>(mut self, recipients: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<RecipientKey>,
+    {
+        self.recipients
+            .extend(recipients.into_iter().map(Into::into));
+        self
+    }
+
+    /// Set the sender key, producing an authcrypt envelope
+    pub fn sender(mut self, sender: PrivateKey) -> Self {
+        self.sender = Some(sender);
+        self
+    }
+
+    /// Set the envelope format
+    pub fn format(mut self, format: PackFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Set the content encryption algorithm
+    pub fn encryption_alg(mut self, enc_alg: EnvelopeAlg) -> Self {
+        self.enc_alg = enc_alg;
+        self
+    }
+
+    /// Add an additional field to the protected header
+    pub fn extra_header<N, V>(mut self, name: N, value: V) -> Self
+    where
+        N: Into<String>,
+        V: Into<serde_json::Value>,
+    {
+        self.extra_headers.insert(name.into(), value.into());
+        self
+    }
+
+    /// Add a set of additional fields to the protected header
+    pub fn extra_headers(mut self, headers: HeaderMap) -> Self {
+        self.extra_headers.extend(headers);
+        self
+    }
+
+    /// Set an unauthenticated sender hint, such as the sender DID
+    pub fn sender_hint<S: Into<String>>(mut self, hint: S) -> Self {
+        self.sender_hint = Some(hint.into());
+        self
+    }
+
+    /// Validate the options and produce a `Packer`
+    pub fn build(self) -> Result<Packer, PackError> {
+        if self.recipients.is_empty() {
+            return Err(PackError::NoRecipients);
+        }
+        check_extra_headers(self.format, &self.extra_headers)?;
+        let mut extra_headers = self.extra_headers;
+        insert_sender_hint(&mut extra_headers, self.sender_hint);
+        Ok(Packer {
+            recipients: validate_recipients(self.recipients)?,
+            sender: self.sender,
+            format: self.format,
+            enc_alg: self.enc_alg,
+            extra_headers,
+        })
+    }
+}
+
+/// Ensure that additional protected header fields do not override reserved fields
+pub(super) fn check_extra_headers(
+    format: PackFormat,
+    extra_headers: &HeaderMap,
+) -> Result<(), PackError> {
+    let reserved = match format {
+        PackFormat::Legacy => RESERVED_HEADERS_LEGACY,
+        PackFormat::DIDCommV2 => RESERVED_HEADERS_V2,
+    };
+    for name in extra_headers.keys() {
+        if reserved.contains(&name.as_str()) {
+            return Err(PackError::ReservedHeader(name.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// A validated set of packing options
+#[derive(Debug, Clone)]
+pub struct Packer {
+    recipients: Vec<RecipientKey>,
+    sender: Option<PrivateKey>,
+    format: PackFormat,
+    enc_alg: EnvelopeAlg,
+    extra_headers: HeaderMap,
+}
+
+impl Packer {
+    pub fn format(&self) -> PackFormat {
+        self.format
+    }
+
+    pub fn encryption_alg(&self) -> EnvelopeAlg {
+        self.enc_alg
+    }
+
+    /// Pack a message for the configured recipients
+    pub fn pack<M: AsRef<[u8]>>(&self, message: M) -> Result<Vec<u8>, PackError> {
+        self.pack_with_rng(message, &mut OsRng)
+    }
+
+    /// Pack a message using the provided random number generator for the
+    /// content encryption key, nonces and ephemeral keys
+    pub fn pack_with_rng<M: AsRef<[u8]>, G: CryptoRng + RngCore>(
+        &self,
+        message: M,
+        rng: &mut G,
+    ) -> Result<Vec<u8>, PackError> {
+        match self.format {
+            PackFormat::Legacy => pack_message_legacy(
+                message,
+                self.recipients.clone(),
+                self.sender.clone(),
+                self.enc_alg,
+                &self.extra_headers,
+                rng,
+            ),
+            PackFormat::DIDCommV2 => pack_message_v2(
+                message,
+                self.recipients.clone(),
+                self.sender.clone(),
+                self.enc_alg,
+                &self.extra_headers,
+                rng,
+            ),
+        }
+    }
+
+    /// Pack a message, yielding to the executor after the content encryption
+    /// key is wrapped for each recipient
+    pub async fn pack_async<M: AsRef<[u8]>>(&self, message: M) -> Result<Vec<u8>, PackError> {
+        self.pack_async_with_rng(message, &mut OsRng).await
+    }
+
+    /// Pack a message asynchronously using the provided random number generator
+    pub async fn pack_async_with_rng<M: AsRef<[u8]>, G: CryptoRng + RngCore>(
+        &self,
+        message: M,
+        rng: &mut G,
+    ) -> Result<Vec<u8>, PackError> {
+        pack_async_with_options(
+            message,
+            self.recipients.clone(),
+            self.sender.clone(),
+            self.format,
+            self.enc_alg,
+            &self.extra_headers,
+            rng,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_global_executor::block_on;
+
+    use super::super::{key_lookup_fn, unpack_message};
+    use super::*;
+    use crate::base64;
+    use crate::keys::test_fixtures::{TEST2, TEST3, TEST4};
+    use crate::keys::EncodedVerKey;
+
+    #[test]
+    fn test_builder_requires_recipient() {
+        assert!(matches!(
+            PackBuilder::new().build(),
+            Err(PackError::NoRecipients)
+        ));
+    }
+
+    #[test]
+    fn test_builder_reserved_header() {
+        let pk = TEST2
+            .private_key()
+            .public_key()
+            .unwrap()
+            .as_base58()
+            .unwrap();
+        let result = PackBuilder::new()
+            .recipient(pk)
+            .extra_header("alg", "none")
+            .build();
+        assert!(matches!(result, Err(PackError::ReservedHeader(_))));
+    }
+
+    #[test]
+    fn test_builder_round_trip() {
+        let sk1 = TEST3.private_key();
+        let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
+        let sk2 = TEST4.private_key();
+        let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
+
+        let input_msg = b"hello there";
+        for format in &[PackFormat::Legacy, PackFormat::DIDCommV2] {
+            let packed = PackBuilder::new()
+                .recipient(pk2.clone())
+                .sender(sk1.clone())
+                .format(*format)
+                .extra_header("ext", "value")
+                .build()
+                .unwrap()
+                .pack(&input_msg)
+                .unwrap();
+
+            let jwe: serde_json::Value = serde_json::from_slice(&packed).unwrap();
+            let protected: serde_json::Value = serde_json::from_slice(
+                &base64::decode_urlsafe(jwe["protected"].as_str().unwrap()).unwrap(),
+            )
+            .unwrap();
+            assert_eq!(protected["ext"], "value");
+
+            let lookup = |find_pks: &Vec<EncodedVerKey>| {
+                find_pks
+                    .iter()
+                    .position(|pk| pk == &pk2)
+                    .map(|idx| (idx, sk2.clone()))
+            };
+            let lookup_fn = key_lookup_fn(lookup);
+            let (msg, p_recip, p_send) = block_on(unpack_message(&packed, &lookup_fn)).unwrap();
+            assert_eq!(msg, input_msg);
+            assert_eq!(p_recip, pk2);
+            assert_eq!(p_send, Some(pk1.clone()));
+        }
+    }
+}
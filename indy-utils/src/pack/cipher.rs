@@ -0,0 +1,327 @@
+use aead::{
+    generic_array::{typenum::Unsigned, GenericArray},
+    Aead, NewAead, Payload,
+};
+use aes::{Aes256, BlockCipher, NewBlockCipher};
+use aes_gcm::Aes256Gcm;
+use chacha20poly1305::{ChaCha20Poly1305, XChaCha20Poly1305};
+use hmac::{Hmac, Mac};
+use rand::{CryptoRng, RngCore};
+use subtle::ConstantTimeEq;
+use ursa::hash::sha2::Sha512;
+use zeroize::Zeroize;
+
+use super::error::{PackError, UnpackError};
+use super::types::{EnvelopeAlg, PackFormat};
+use crate::nonce::RandomNonce;
+
+pub const PROTECTED_HEADER_ENC_CHACHA: &'static str = "xchacha20poly1305_ietf";
+pub const PROTECTED_HEADER_ENC_XC20P: &'static str = "XC20P";
+pub const PROTECTED_HEADER_ENC_A256GCM: &'static str = "A256GCM";
+pub const PROTECTED_HEADER_ENC_A256CBC_HS512: &'static str = "A256CBC-HS512";
+
+/// Content encryption key length for A256CBC-HS512 in bytes
+pub(super) const A256CBC_HS512_KEY_SIZE: usize = 64;
+const CBC_BLOCK_SIZE: usize = 16;
+const CBC_HS512_TAG_SIZE: usize = 32;
+
+/// The result of encrypting an envelope payload
+pub(super) struct EncryptedContent {
+    pub iv: Vec<u8>,
+    pub ciphertext: Vec<u8>,
+    pub tag: Vec<u8>,
+}
+
+/// Get the `enc` protected header value for a content encryption algorithm
+pub(super) fn enc_header_value(enc_alg: EnvelopeAlg, format: PackFormat) -> &'static str {
+    match (enc_alg, format) {
+        (EnvelopeAlg::XChaCha20Poly1305, PackFormat::Legacy) => PROTECTED_HEADER_ENC_CHACHA,
+        (EnvelopeAlg::XChaCha20Poly1305, PackFormat::DIDCommV2) => PROTECTED_HEADER_ENC_XC20P,
+        (EnvelopeAlg::Aes256Gcm, _) => PROTECTED_HEADER_ENC_A256GCM,
+    }
+}
+
+/// Parse the `enc` protected header value of an envelope
+pub(super) fn parse_enc_header(enc: &str, format: PackFormat) -> Result<EnvelopeAlg, UnpackError> {
+    match (enc, format) {
+        (PROTECTED_HEADER_ENC_CHACHA, PackFormat::Legacy) => Ok(EnvelopeAlg::XChaCha20Poly1305),
+        (PROTECTED_HEADER_ENC_XC20P, PackFormat::DIDCommV2) => Ok(EnvelopeAlg::XChaCha20Poly1305),
+        (PROTECTED_HEADER_ENC_A256GCM, _) => Ok(EnvelopeAlg::Aes256Gcm),
+        _ => Err(UnpackError::UnsupportedAlg(enc.to_string())),
+    }
+}
+
+pub(super) fn encrypt_content<R: CryptoRng + RngCore>(
+    enc_alg: EnvelopeAlg,
+    format: PackFormat,
+    cek: &[u8],
+    aad: &[u8],
+    message: &[u8],
+    rng: &mut R,
+) -> Result<EncryptedContent, PackError> {
+    trace_span!("encrypt_content", enc = ?enc_alg, size = message.len());
+    match (enc_alg, format) {
+        // the legacy envelope format uses the IETF variant with a 96-bit nonce
+        (EnvelopeAlg::XChaCha20Poly1305, PackFormat::Legacy) => {
+            aead_encrypt::<ChaCha20Poly1305, R>(cek, aad, message, rng)
+        }
+        (EnvelopeAlg::XChaCha20Poly1305, PackFormat::DIDCommV2) => {
+            aead_encrypt::<XChaCha20Poly1305, R>(cek, aad, message, rng)
+        }
+        (EnvelopeAlg::Aes256Gcm, _) => aead_encrypt::<Aes256Gcm, R>(cek, aad, message, rng),
+    }
+}
+
+pub(super) fn decrypt_content(
+    enc_alg: EnvelopeAlg,
+    format: PackFormat,
+    cek: &[u8],
+    aad: &[u8],
+    iv: &[u8],
+    ciphertext: Vec<u8>,
+    tag: &[u8],
+) -> Result<Vec<u8>, UnpackError> {
+    trace_span!("decrypt_content", enc = ?enc_alg, size = ciphertext.len());
+    match (enc_alg, format) {
+        (EnvelopeAlg::XChaCha20Poly1305, PackFormat::Legacy) => {
+            aead_decrypt::<ChaCha20Poly1305>(cek, aad, iv, ciphertext, tag)
+        }
+        (EnvelopeAlg::XChaCha20Poly1305, PackFormat::DIDCommV2) => {
+            aead_decrypt::<XChaCha20Poly1305>(cek, aad, iv, ciphertext, tag)
+        }
+        (EnvelopeAlg::Aes256Gcm, _) => aead_decrypt::<Aes256Gcm>(cek, aad, iv, ciphertext, tag),
+    }
+}
+
+/// Encrypt content with AES_256_CBC_HMAC_SHA_512 (RFC 7518 section 5.2.5),
+/// the content encryption algorithm required for DIDComm v2 authcrypt
+pub(super) fn encrypt_cbc_hs512<R: CryptoRng + RngCore>(
+    cek: &[u8],
+    aad: &[u8],
+    message: &[u8],
+    rng: &mut R,
+) -> Result<EncryptedContent, PackError> {
+    if cek.len() != A256CBC_HS512_KEY_SIZE {
+        return Err(PackError::AeadFailure);
+    }
+    let mut iv = vec![0u8; CBC_BLOCK_SIZE];
+    rng.fill_bytes(&mut iv);
+    let ciphertext = cbc_encrypt(&cek[32..], &iv, message);
+    let tag = cbc_hs512_tag(&cek[..32], aad, &iv, &ciphertext);
+    Ok(EncryptedContent {
+        iv,
+        ciphertext,
+        tag,
+    })
+}
+
+/// Decrypt content encrypted with AES_256_CBC_HMAC_SHA_512
+pub(super) fn decrypt_cbc_hs512(
+    cek: &[u8],
+    aad: &[u8],
+    iv: &[u8],
+    ciphertext: &[u8],
+    tag: &[u8],
+) -> Result<Vec<u8>, UnpackError> {
+    if cek.len() != A256CBC_HS512_KEY_SIZE {
+        return Err(UnpackError::KeyUnwrapFailure);
+    }
+    if iv.len() != CBC_BLOCK_SIZE {
+        return Err(UnpackError::InvalidEnvelope(
+            "Invalid size for message nonce".to_string(),
+        ));
+    }
+    if tag.len() != CBC_HS512_TAG_SIZE {
+        return Err(UnpackError::InvalidEnvelope(
+            "Invalid size for message tag".to_string(),
+        ));
+    }
+    let expected = cbc_hs512_tag(&cek[..32], aad, iv, ciphertext);
+    if !bool::from(expected.ct_eq(tag)) {
+        return Err(UnpackError::AeadFailure);
+    }
+    cbc_decrypt(&cek[32..], iv, ciphertext).ok_or(UnpackError::AeadFailure)
+}
+
+/// The truncated HMAC-SHA-512 of the AAD, IV, ciphertext and AAD bit length
+fn cbc_hs512_tag(mac_key: &[u8], aad: &[u8], iv: &[u8], ciphertext: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha512>::new_varkey(mac_key).expect("HMAC accepts keys of any length");
+    mac.input(aad);
+    mac.input(iv);
+    mac.input(ciphertext);
+    mac.input(&((aad.len() as u64) * 8).to_be_bytes());
+    mac.result().code()[..CBC_HS512_TAG_SIZE].to_vec()
+}
+
+/// AES-256-CBC encryption with PKCS#7 padding
+fn cbc_encrypt(key: &[u8], iv: &[u8], message: &[u8]) -> Vec<u8> {
+    let cipher = Aes256::new(GenericArray::from_slice(key));
+    let pad = CBC_BLOCK_SIZE - message.len() % CBC_BLOCK_SIZE;
+    let mut buf = Vec::with_capacity(message.len() + pad);
+    buf.extend_from_slice(message);
+    buf.resize(message.len() + pad, pad as u8);
+    let mut prev = GenericArray::clone_from_slice(iv);
+    for chunk in buf.chunks_mut(CBC_BLOCK_SIZE) {
+        let mut block = GenericArray::clone_from_slice(chunk);
+        for (b, p) in block.iter_mut().zip(prev.iter()) {
+            *b ^= p;
+        }
+        cipher.encrypt_block(&mut block);
+        chunk.copy_from_slice(&block);
+        prev = block;
+    }
+    buf
+}
+
+/// AES-256-CBC decryption, removing the PKCS#7 padding
+fn cbc_decrypt(key: &[u8], iv: &[u8], ciphertext: &[u8]) -> Option<Vec<u8>> {
+    if ciphertext.is_empty() || ciphertext.len() % CBC_BLOCK_SIZE != 0 {
+        return None;
+    }
+    let cipher = Aes256::new(GenericArray::from_slice(key));
+    let mut buf = ciphertext.to_vec();
+    let mut prev = GenericArray::clone_from_slice(iv);
+    for chunk in buf.chunks_mut(CBC_BLOCK_SIZE) {
+        let encrypted = GenericArray::clone_from_slice(chunk);
+        let mut block = encrypted.clone();
+        cipher.decrypt_block(&mut block);
+        for (b, p) in block.iter_mut().zip(prev.iter()) {
+            *b ^= p;
+        }
+        chunk.copy_from_slice(&block);
+        block.as_mut_slice().zeroize();
+        prev = encrypted;
+    }
+    let pad = *buf.last()? as usize;
+    if pad == 0
+        || pad > CBC_BLOCK_SIZE
+        || !buf[buf.len() - pad..].iter().all(|b| *b as usize == pad)
+    {
+        buf.zeroize();
+        return None;
+    }
+    buf.truncate(buf.len() - pad);
+    Some(buf)
+}
+
+fn aead_encrypt<A: Aead + NewAead, R: CryptoRng + RngCore>(
+    cek: &[u8],
+    aad: &[u8],
+    message: &[u8],
+    rng: &mut R,
+) -> Result<EncryptedContent, PackError> {
+    let cipher = A::new_varkey(cek).map_err(|_| PackError::AeadFailure)?;
+    let iv = RandomNonce::<A::NonceSize>::generate_with_rng(rng)
+        .as_slice()
+        .to_vec();
+    let mut ciphertext = cipher
+        .encrypt(GenericArray::from_slice(&iv), Payload { aad, msg: message })
+        .map_err(|_| PackError::AeadFailure)?;
+    let tag = ciphertext.split_off(ciphertext.len() - A::TagSize::USIZE);
+    Ok(EncryptedContent {
+        iv,
+        ciphertext,
+        tag,
+    })
+}
+
+fn aead_decrypt<A: Aead + NewAead>(
+    cek: &[u8],
+    aad: &[u8],
+    iv: &[u8],
+    mut ciphertext: Vec<u8>,
+    tag: &[u8],
+) -> Result<Vec<u8>, UnpackError> {
+    let cipher = A::new_varkey(cek).map_err(|_| UnpackError::KeyUnwrapFailure)?;
+    if iv.len() != A::NonceSize::USIZE {
+        return Err(UnpackError::InvalidEnvelope(
+            "Invalid size for message nonce".to_string(),
+        ));
+    }
+    if tag.len() != A::TagSize::USIZE {
+        return Err(UnpackError::InvalidEnvelope(
+            "Invalid size for message tag".to_string(),
+        ));
+    }
+    ciphertext.extend_from_slice(tag);
+    let payload = Payload {
+        aad,
+        msg: ciphertext.as_slice(),
+    };
+    cipher
+        .decrypt(GenericArray::from_slice(iv), payload)
+        .map_err(|_| UnpackError::AeadFailure)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::OsRng;
+
+    #[test]
+    fn test_content_round_trip() {
+        let cek = [1u8; 32];
+        for format in &[PackFormat::Legacy, PackFormat::DIDCommV2] {
+            for enc_alg in &[EnvelopeAlg::XChaCha20Poly1305, EnvelopeAlg::Aes256Gcm] {
+                let enc = enc_header_value(*enc_alg, *format);
+                assert_eq!(parse_enc_header(enc, *format).unwrap(), *enc_alg);
+                let content =
+                    encrypt_content(*enc_alg, *format, &cek, b"aad", b"hello", &mut OsRng).unwrap();
+                let message = decrypt_content(
+                    *enc_alg,
+                    *format,
+                    &cek,
+                    b"aad",
+                    &content.iv,
+                    content.ciphertext.clone(),
+                    &content.tag,
+                )
+                .unwrap();
+                assert_eq!(message, b"hello");
+                assert!(matches!(
+                    decrypt_content(
+                        *enc_alg,
+                        *format,
+                        &cek,
+                        b"other",
+                        &content.iv,
+                        content.ciphertext,
+                        &content.tag,
+                    ),
+                    Err(UnpackError::AeadFailure)
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn test_cbc_hs512_rfc7518() {
+        // test vector from RFC 7518 appendix B.3
+        let key = (0u8..64).collect::<Vec<u8>>();
+        let message = b"A cipher system must not be required to be secret, and it must be able to fall into the hands of the enemy without inconvenience";
+        let iv = hex::decode("1af38c2dc2b96ffdd86694092341bc04").unwrap();
+        let aad = b"The second principle of Auguste Kerckhoffs";
+        let ciphertext = cbc_encrypt(&key[32..], &iv, message);
+        assert_eq!(
+            hex::encode(&ciphertext),
+            "4affaaadb78c31c5da4b1b590d10ffbd3dd8d5d302423526912da037ecbcc7bd\
+             822c301dd67c373bccb584ad3e9279c2e6d12a1374b77f077553df829410446b\
+             36ebd97066296ae6427ea75c2e0846a11a09ccf5370dc80bfecbad28c73f09b3\
+             a3b75e662a2594410ae496b2e2e6609e31e6e02cc837f053d21f37ff4f51950b\
+             be2638d09dd7a4930930806d0703b1f6"
+        );
+        let tag = cbc_hs512_tag(&key[..32], aad, &iv, &ciphertext);
+        assert_eq!(
+            hex::encode(&tag),
+            "4dd3b4c088a7f45c216839645b2012bf2e6269a8c56a816dbc1b267761955bc5"
+        );
+        assert_eq!(
+            decrypt_cbc_hs512(&key, aad, &iv, &ciphertext, &tag).unwrap(),
+            message.to_vec()
+        );
+        assert!(matches!(
+            decrypt_cbc_hs512(&key, b"other", &iv, &ciphertext, &tag),
+            Err(UnpackError::AeadFailure)
+        ));
+    }
+}
@@ -0,0 +1,85 @@
+use thiserror::Error;
+
+use crate::error::ConversionError;
+
+/// Error type for failures when packing a message
+#[derive(Debug, Error)]
+pub enum PackError {
+    #[error("No message recipients")]
+    NoRecipients,
+    #[error("Invalid envelope: {0}")]
+    InvalidEnvelope(String),
+    #[error("Invalid message payload: {0}")]
+    InvalidPayload(String),
+    #[error("Reserved protected header field: {0}")]
+    ReservedHeader(String),
+    #[error("Invalid key")]
+    InvalidKey(#[source] ConversionError),
+    #[error("Invalid recipient key at index {0}: {1}")]
+    InvalidRecipient(usize, String),
+    #[error("Error encrypting content encryption key")]
+    KeyWrapFailure,
+    #[error("Error encrypting payload")]
+    AeadFailure,
+    #[error("Error signing message")]
+    SigningFailure(#[source] ConversionError),
+    #[error("Error serializing envelope")]
+    Serialization(#[source] serde_json::Error),
+    #[error("Maximum message stream length exceeded")]
+    StreamTooLong,
+    #[error("Error processing message stream")]
+    Io(#[source] std::io::Error),
+    #[error("Error unpacking message")]
+    Unpack(#[source] UnpackError),
+}
+
+/// Error type for failures when unpacking a message
+#[derive(Debug, Error)]
+pub enum UnpackError {
+    #[error("Invalid envelope: {0}")]
+    InvalidEnvelope(String),
+    #[error("Invalid protected header: {0}")]
+    InvalidProtectedHeader(String),
+    #[error("Size limit exceeded: {0}")]
+    LimitExceeded(&'static str),
+    #[error("Malformed envelope field: {0}")]
+    MalformedField(String),
+    #[error("Malformed base64 in field: {0}")]
+    MalformedBase64(&'static str),
+    #[error("Unsupported algorithm: {0}")]
+    UnsupportedAlg(String),
+    #[error("No matching recipient found")]
+    NoMatchingRecipient,
+    #[error("Error looking up recipient keys")]
+    KeyLookup(#[source] ConversionError),
+    #[error("Invalid key")]
+    InvalidKey(#[source] ConversionError),
+    #[error("Error decrypting content encryption key")]
+    KeyUnwrapFailure,
+    #[error("Error decrypting message payload")]
+    AeadFailure,
+    #[error("Invalid message signature")]
+    InvalidSignature,
+    #[error("Truncated message stream")]
+    TruncatedStream,
+    #[error("Error processing message stream")]
+    Io(#[source] std::io::Error),
+}
+
+impl From<serde_json::Error> for PackError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Serialization(err)
+    }
+}
+
+impl From<PackError> for ConversionError {
+    fn from(err: PackError) -> Self {
+        Self::from_msg_err(err.to_string(), err)
+    }
+}
+
+impl From<UnpackError> for ConversionError {
+    fn from(err: UnpackError) -> Self {
+        Self::from_msg_err(err.to_string(), err)
+    }
+}
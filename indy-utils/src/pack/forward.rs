@@ -0,0 +1,149 @@
+use super::alg::{pack_message_legacy, parse_envelope, unpack_message};
+use super::error::{PackError, UnpackError};
+use super::peek::list_recipients;
+use super::types::{EnvelopeAlg, HeaderMap, KeyLookup, UnpackLimits};
+use crate::keys::EncodedVerKey;
+use crate::random::random_array;
+
+use aead::generic_array::typenum::U16;
+use rand::rngs::OsRng;
+
+/// Message type of an Aries routing forward message (RFC 0094)
+pub const FORWARD_MSG_TYPE: &'static str = "https://didcomm.org/routing/1.0/forward";
+/// Legacy message type of a forward message
+pub const FORWARD_MSG_TYPE_LEGACY: &'static str =
+    "did:sov:BzCbsNYhMrjHiqZDTUASHg;spec/routing/1.0/forward";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Forward {
+    #[serde(rename = "@type")]
+    msg_type: String,
+    #[serde(rename = "@id")]
+    id: String,
+    to: String,
+    msg: serde_json::Value,
+}
+
+/// Wrap a packed message in nested anoncrypt forward messages, one for each
+/// routing key. The first routing key is the mediator closest to the recipient.
+pub fn pack_forward(
+    message: impl AsRef<[u8]>,
+    route: &[EncodedVerKey],
+) -> Result<Vec<u8>, PackError> {
+    let mut message = message.as_ref().to_vec();
+    for routing_key in route {
+        let to = unwrap_opt_or_return!(
+            list_recipients(&message)
+                .map_err(|err| PackError::InvalidEnvelope(err.to_string()))?
+                .into_iter()
+                .next(),
+            Err(PackError::InvalidEnvelope(
+                "Envelope has no recipients".to_string()
+            ))
+        );
+        let forward = Forward {
+            msg_type: FORWARD_MSG_TYPE.to_string(),
+            id: random_message_id(),
+            to: to.to_string(),
+            msg: serde_json::from_slice(&message)
+                .map_err(|err| PackError::InvalidEnvelope(err.to_string()))?,
+        };
+        message = pack_message_legacy(
+            serde_json::to_vec(&forward)?,
+            vec![routing_key.clone().into()],
+            None,
+            EnvelopeAlg::default(),
+            &HeaderMap::new(),
+            &mut OsRng,
+        )?;
+    }
+    Ok(message)
+}
+
+/// Unpack a forward message received by a mediator, returning the key of
+/// the next recipient and the inner packed message
+pub async fn unwrap_forward<'f>(
+    message: impl AsRef<[u8]>,
+    lookup: impl KeyLookup<'f>,
+) -> Result<(EncodedVerKey, Vec<u8>), UnpackError> {
+    let (plaintext, _, _) = unpack_message(message, lookup).await?;
+    let forward: Forward = serde_json::from_slice(&plaintext)
+        .map_err(|err| UnpackError::InvalidEnvelope(err.to_string()))?;
+    if forward.msg_type != FORWARD_MSG_TYPE && forward.msg_type != FORWARD_MSG_TYPE_LEGACY {
+        return Err(UnpackError::InvalidEnvelope(format!(
+            "Unexpected message type: {}",
+            forward.msg_type
+        )));
+    }
+    let to = EncodedVerKey::from_str(&forward.to)
+        .map_err(|_| UnpackError::InvalidEnvelope("Invalid forward recipient key".to_string()))?;
+    let inner = serde_json::to_vec(&forward.msg)
+        .map_err(|err| UnpackError::InvalidEnvelope(err.to_string()))?;
+    parse_envelope(&inner, &UnpackLimits::default())?;
+    Ok((to, inner))
+}
+
+/// Generate a random UUID v4 message identifier
+pub(crate) fn random_message_id() -> String {
+    let mut bytes = random_array::<U16>();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex = hex::encode(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use async_global_executor::block_on;
+
+    use super::super::{key_lookup_fn, pack_message};
+    use super::*;
+    use crate::keys::test_fixtures::{TEST1, TEST2, TEST3};
+    use crate::keys::PrivateKey;
+
+    #[test]
+    fn test_forward_round_trip() {
+        let recip_sk = TEST1.private_key();
+        let recip_pk = recip_sk.public_key().unwrap().as_base58().unwrap();
+        let med1_sk = TEST2.private_key();
+        let med1_pk = med1_sk.public_key().unwrap().as_base58().unwrap();
+        let med2_sk = TEST3.private_key();
+        let med2_pk = med2_sk.public_key().unwrap().as_base58().unwrap();
+
+        let packed = pack_message(b"hello there", vec![recip_pk.clone()], None).unwrap();
+        let routed = pack_forward(&packed, &[med1_pk.clone(), med2_pk.clone()]).unwrap();
+        assert_eq!(list_recipients(&routed).unwrap(), vec![med2_pk.clone()]);
+
+        let lookup = |sk: PrivateKey, pk: EncodedVerKey| {
+            key_lookup_fn(move |find_pks: &Vec<EncodedVerKey>| {
+                find_pks
+                    .iter()
+                    .position(|k| k == &pk)
+                    .map(|idx| (idx, sk.clone()))
+            })
+        };
+
+        let lookup2 = lookup(med2_sk, med2_pk);
+        let (to, inner) = block_on(unwrap_forward(&routed, &lookup2)).unwrap();
+        assert_eq!(to, med1_pk);
+
+        let lookup1 = lookup(med1_sk, med1_pk);
+        let (to, inner) = block_on(unwrap_forward(&inner, &lookup1)).unwrap();
+        assert_eq!(to, recip_pk);
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&inner).unwrap(),
+            serde_json::from_slice::<serde_json::Value>(&packed).unwrap()
+        );
+
+        let lookup0 = lookup(recip_sk, recip_pk);
+        let result = block_on(unwrap_forward(&inner, &lookup0));
+        assert!(matches!(result, Err(UnpackError::InvalidEnvelope(_))));
+    }
+}
@@ -0,0 +1,251 @@
+use super::error::{PackError, UnpackError};
+use crate::base64;
+use crate::keys::{AsyncSigner, EncodedVerKey, KeyType, PrivateKey, VerKey};
+
+pub const JWS_ALG_EDDSA: &'static str = "EdDSA";
+
+/// A JWS in the flattened JSON serialization used for Aries attachment signatures
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct JWS {
+    pub header: JwsHeader,
+    pub protected: String,
+    pub payload: String,
+    pub signature: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct JwsHeader {
+    pub kid: String,
+}
+
+/// The protected header of a JWS, shared by the flattened JSON serialization
+/// and the compact serialization
+#[derive(Serialize, Deserialize, Debug, Clone, Default, Eq, PartialEq)]
+pub struct JwsProtectedHeader {
+    pub alg: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub jwk: Option<JwsKey>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub typ: Option<String>,
+    /// Whether the payload is base64-URL encoded, absent for the default of `true`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub b64: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crit: Option<Vec<String>>,
+}
+
+/// An Ed25519 public key in JWK format
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct JwsKey {
+    pub kty: String,
+    pub crv: String,
+    pub x: String,
+    pub kid: String,
+}
+
+/// Sign a message with an ed25519 key, producing a JWS
+pub fn sign_message(message: impl AsRef<[u8]>, key: &PrivateKey) -> Result<JWS, PackError> {
+    let verkey = key
+        .public_key()
+        .and_then(|pk| pk.as_base58())
+        .map_err(PackError::InvalidKey)?;
+    let (protected, payload) = encode_unsigned(message.as_ref(), &verkey)?;
+    let signature = create_signature(key, &protected, payload.as_bytes())?;
+    Ok(finish_jws(verkey, protected, payload, signature))
+}
+
+/// Sign a message with an ed25519 key held by an external signer, producing a JWS
+pub async fn sign_message_with_signer<S>(
+    message: impl AsRef<[u8]>,
+    signer: &S,
+) -> Result<JWS, PackError>
+where
+    S: AsyncSigner + ?Sized,
+{
+    let verkey = signer.verkey().map_err(PackError::InvalidKey)?;
+    let (protected, payload) = encode_unsigned(message.as_ref(), &verkey)?;
+    let signature = signer
+        .sign(&signing_input(&protected, payload.as_bytes()))
+        .await
+        .map_err(PackError::SigningFailure)?;
+    Ok(finish_jws(
+        verkey,
+        protected,
+        payload,
+        base64::encode_urlsafe_nopad(signature),
+    ))
+}
+
+/// Encode the protected header and payload of a JWS
+fn encode_unsigned(message: &[u8], verkey: &EncodedVerKey) -> Result<(String, String), PackError> {
+    if verkey.alg != KeyType::ED25519 {
+        return Err(PackError::InvalidKey(
+            "JWS signing requires an ed25519 key".into(),
+        ));
+    }
+    let kid = verkey.to_string();
+    let protected = JwsProtectedHeader {
+        alg: JWS_ALG_EDDSA.to_string(),
+        kid: Some(kid.clone()),
+        jwk: Some(JwsKey {
+            kty: "OKP".to_string(),
+            crv: "Ed25519".to_string(),
+            x: base64::encode_urlsafe_nopad(verkey.key_bytes().map_err(PackError::InvalidKey)?),
+            kid,
+        }),
+        ..Default::default()
+    };
+    let protected = encode_protected(&protected)?;
+    let payload = base64::encode_urlsafe_nopad(message);
+    Ok((protected, payload))
+}
+
+fn finish_jws(verkey: EncodedVerKey, protected: String, payload: String, signature: String) -> JWS {
+    JWS {
+        header: JwsHeader {
+            kid: verkey.to_string(),
+        },
+        protected,
+        payload,
+        signature,
+    }
+}
+
+/// Verify a JWS, returning the decoded payload and the signer's verkey
+pub fn verify_message(jws: &JWS) -> Result<(Vec<u8>, EncodedVerKey), UnpackError> {
+    let protected = decode_protected(&jws.protected)?;
+    let jwk = protected
+        .jwk
+        .ok_or_else(|| UnpackError::MalformedField("jwk".to_string()))?;
+    if jwk.kty != "OKP" || jwk.crv != "Ed25519" {
+        return Err(UnpackError::UnsupportedAlg(format!(
+            "{}/{}",
+            jwk.kty, jwk.crv
+        )));
+    }
+    let verkey = VerKey::new(
+        base64::decode_urlsafe(&jwk.x).map_err(|_| UnpackError::MalformedBase64("x"))?,
+        Some(KeyType::ED25519),
+    );
+    verify_signature(
+        &verkey,
+        &jws.protected,
+        jws.payload.as_bytes(),
+        &jws.signature,
+    )?;
+    let payload = base64::decode_urlsafe(&jws.payload)
+        .map_err(|_| UnpackError::MalformedBase64("payload"))?;
+    let verkey = verkey
+        .as_base58()
+        .map_err(|_| UnpackError::MalformedField("jwk".to_string()))?;
+    Ok((payload, verkey))
+}
+
+/// Encode a JWS protected header
+pub(crate) fn encode_protected(header: &JwsProtectedHeader) -> Result<String, serde_json::Error> {
+    Ok(base64::encode_urlsafe_nopad(serde_json::to_vec(header)?))
+}
+
+/// Decode a JWS protected header, checking that it uses the EdDSA algorithm
+pub(crate) fn decode_protected(protected: &str) -> Result<JwsProtectedHeader, UnpackError> {
+    let header: JwsProtectedHeader = serde_json::from_slice(
+        &base64::decode_urlsafe(protected)
+            .map_err(|_| UnpackError::MalformedBase64("protected"))?,
+    )
+    .map_err(|err| UnpackError::InvalidProtectedHeader(err.to_string()))?;
+    if header.alg != JWS_ALG_EDDSA {
+        return Err(UnpackError::UnsupportedAlg(header.alg));
+    }
+    Ok(header)
+}
+
+/// Produce the base64-URL encoded Ed25519 signature over an encoded protected
+/// header and payload
+pub(crate) fn create_signature(
+    key: &PrivateKey,
+    protected: &str,
+    payload: &[u8],
+) -> Result<String, PackError> {
+    if key.alg != KeyType::ED25519 {
+        return Err(PackError::InvalidKey(
+            "JWS signing requires an ed25519 key".into(),
+        ));
+    }
+    let signature = key
+        .sign(signing_input(protected, payload))
+        .map_err(PackError::SigningFailure)?;
+    Ok(base64::encode_urlsafe_nopad(signature))
+}
+
+/// Verify a base64-URL encoded Ed25519 signature over an encoded protected
+/// header and payload
+pub(crate) fn verify_signature(
+    verkey: &VerKey,
+    protected: &str,
+    payload: &[u8],
+    signature: &str,
+) -> Result<(), UnpackError> {
+    if verkey.alg != KeyType::ED25519 {
+        return Err(UnpackError::InvalidKey(
+            "JWS verification requires an ed25519 key".into(),
+        ));
+    }
+    let signature =
+        base64::decode_urlsafe(signature).map_err(|_| UnpackError::MalformedBase64("signature"))?;
+    if verkey
+        .verify_signature(signing_input(protected, payload), signature)
+        .map_err(|_| UnpackError::InvalidSignature)?
+    {
+        Ok(())
+    } else {
+        Err(UnpackError::InvalidSignature)
+    }
+}
+
+fn signing_input(protected: &str, payload: &[u8]) -> Vec<u8> {
+    let mut input = Vec::with_capacity(protected.len() + payload.len() + 1);
+    input.extend_from_slice(protected.as_bytes());
+    input.push(b'.');
+    input.extend_from_slice(payload);
+    input
+}
+
+#[cfg(test)]
+mod tests {
+    use async_global_executor::block_on;
+
+    use super::*;
+    use crate::keys::test_fixtures::TEST1;
+
+    #[test]
+    fn test_jws_async_signer() {
+        let sk = TEST1.private_key();
+        let jws = block_on(sign_message_with_signer(b"hello there", &sk)).unwrap();
+        assert_eq!(jws, sign_message(b"hello there", &sk).unwrap());
+        let (payload, _) = verify_message(&jws).unwrap();
+        assert_eq!(payload, b"hello there");
+    }
+
+    #[test]
+    fn test_jws_round_trip() {
+        let sk = TEST1.private_key();
+        let pk = sk.public_key().unwrap().as_base58().unwrap();
+
+        let jws = sign_message(b"hello there", &sk).unwrap();
+        assert_eq!(jws.header.kid, pk.to_string());
+        assert!(!jws.signature.contains('='));
+
+        let (payload, signer) = verify_message(&jws).unwrap();
+        assert_eq!(payload, b"hello there");
+        assert_eq!(signer, pk);
+
+        let mut tampered = jws.clone();
+        tampered.payload = base64::encode_urlsafe_nopad(b"hello where");
+        assert!(matches!(
+            verify_message(&tampered),
+            Err(UnpackError::InvalidSignature)
+        ));
+    }
+}
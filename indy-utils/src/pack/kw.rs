@@ -0,0 +1,108 @@
+use aes::{
+    cipher::generic_array::{typenum::Unsigned, GenericArray},
+    Aes256, BlockCipher, NewBlockCipher,
+};
+
+use zeroize::Zeroize;
+
+use crate::error::ConversionError;
+
+/// Key length for A256KW in bytes
+pub const KW_KEY_SIZE: usize = <Aes256 as NewBlockCipher>::KeySize::USIZE;
+
+const KW_IV: [u8; 8] = [0xA6; 8];
+
+/// Wrap a content encryption key using AES-256 key wrap (RFC 3394)
+pub fn aes_key_wrap(kek: &[u8], key: &[u8]) -> Result<Vec<u8>, ConversionError> {
+    if kek.len() != KW_KEY_SIZE {
+        return Err("Invalid key wrapping key length".into());
+    }
+    if key.len() % 8 != 0 || key.len() < 16 {
+        return Err("Invalid length for wrapped key".into());
+    }
+    let cipher = Aes256::new(GenericArray::from_slice(kek));
+    let n = key.len() / 8;
+    let mut a = KW_IV;
+    let mut r = key.to_vec();
+    let mut block = GenericArray::default();
+    for j in 0..6 {
+        for i in 0..n {
+            block[..8].copy_from_slice(&a);
+            block[8..].copy_from_slice(&r[i * 8..(i + 1) * 8]);
+            cipher.encrypt_block(&mut block);
+            let t = ((n * j + i + 1) as u64).to_be_bytes();
+            for (a_k, (b_k, t_k)) in a.iter_mut().zip(block.iter().zip(t.iter())) {
+                *a_k = b_k ^ t_k;
+            }
+            r[i * 8..(i + 1) * 8].copy_from_slice(&block[8..]);
+        }
+    }
+    block.as_mut_slice().zeroize();
+    let mut result = Vec::with_capacity(key.len() + 8);
+    result.extend_from_slice(&a);
+    result.append(&mut r);
+    Ok(result)
+}
+
+/// Unwrap a content encryption key using AES-256 key wrap (RFC 3394)
+pub fn aes_key_unwrap(kek: &[u8], wrapped: &[u8]) -> Result<Vec<u8>, ConversionError> {
+    if kek.len() != KW_KEY_SIZE {
+        return Err("Invalid key wrapping key length".into());
+    }
+    if wrapped.len() % 8 != 0 || wrapped.len() < 24 {
+        return Err("Invalid length for wrapped key".into());
+    }
+    let cipher = Aes256::new(GenericArray::from_slice(kek));
+    let n = wrapped.len() / 8 - 1;
+    let mut a = [0u8; 8];
+    a.copy_from_slice(&wrapped[..8]);
+    let mut r = wrapped[8..].to_vec();
+    let mut block = GenericArray::default();
+    for j in (0..6).rev() {
+        for i in (0..n).rev() {
+            let t = ((n * j + i + 1) as u64).to_be_bytes();
+            for (b_k, (a_k, t_k)) in block.iter_mut().zip(a.iter().zip(t.iter())) {
+                *b_k = a_k ^ t_k;
+            }
+            block[8..].copy_from_slice(&r[i * 8..(i + 1) * 8]);
+            cipher.decrypt_block(&mut block);
+            a.copy_from_slice(&block[..8]);
+            r[i * 8..(i + 1) * 8].copy_from_slice(&block[8..]);
+        }
+    }
+    block.as_mut_slice().zeroize();
+    if a != KW_IV {
+        r.zeroize();
+        return Err("Error unwrapping key: integrity check failed".into());
+    }
+    Ok(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rfc3394_vector() {
+        // RFC 3394 section 4.6: wrap 256 bits of key data with a 256-bit KEK
+        let kek = hex::decode("000102030405060708090A0B0C0D0E0F101112131415161718191A1B1C1D1E1F")
+            .unwrap();
+        let key = hex::decode("00112233445566778899AABBCCDDEEFF000102030405060708090A0B0C0D0E0F")
+            .unwrap();
+        let expected = hex::decode(
+            "28C9F404C4B810F4CBCCB35CFB87F8263F5786E2D80ED326CBC7F0E71A99F43BFB988B9B7A02DD21",
+        )
+        .unwrap();
+        let wrapped = aes_key_wrap(&kek, &key).unwrap();
+        assert_eq!(wrapped, expected);
+        assert_eq!(aes_key_unwrap(&kek, &wrapped).unwrap(), key);
+    }
+
+    #[test]
+    fn test_unwrap_tampered() {
+        let kek = [1u8; KW_KEY_SIZE];
+        let mut wrapped = aes_key_wrap(&kek, &[2u8; 32]).unwrap();
+        wrapped[3] ^= 1;
+        assert!(aes_key_unwrap(&kek, &wrapped).is_err());
+    }
+}
@@ -1,6 +1,60 @@
 mod alg;
-mod nacl_box;
+mod attachments;
+mod binary;
+mod builder;
+mod cipher;
+mod error;
+mod forward;
+pub(crate) mod jws;
+mod kw;
+pub(crate) mod nacl_box;
+mod nonblocking;
+mod pairwise;
+mod peek;
+mod plaintext;
+mod rekey;
+mod resolve;
+mod session;
 mod types;
+mod v2;
+mod vectors;
+mod version;
 
-pub use alg::{pack_message, unpack_message};
-pub use types::{key_lookup_fn, KeyLookup};
+pub use alg::{
+    pack_message, pack_message_compact, pack_message_detached, pack_message_pairwise,
+    pack_message_signed, pack_message_stream, pack_message_stream_with_rng, pack_message_with_rng,
+    unpack_message, unpack_message_detached, unpack_message_stream, unpack_message_sync,
+    unpack_message_sync_with_meta, unpack_message_sync_with_policy, unpack_message_with_meta,
+    unpack_message_with_unwrapper, SIGNED_CONTENT_TYPE, STREAM_CHUNK_SIZE,
+};
+pub use attachments::{
+    decrypt_attachment, encrypt_attachment, encrypt_attachment_linked, sign_attachment,
+    verify_attachment, Attachment, AttachmentData, AttachmentKey,
+};
+pub use binary::{
+    envelope_from_binary, envelope_to_binary, pack_message_binary, unpack_message_binary,
+};
+pub use builder::{PackBuilder, Packer};
+pub use error::{PackError, UnpackError};
+pub(crate) use forward::random_message_id;
+pub use forward::{pack_forward, unwrap_forward, FORWARD_MSG_TYPE};
+pub use jws::{
+    sign_message, sign_message_with_signer, verify_message, JwsHeader, JwsKey, JwsProtectedHeader,
+    JWS, JWS_ALG_EDDSA,
+};
+pub use nonblocking::{pack_message_async, pack_message_async_with_rng, pack_message_with_sender};
+pub use pairwise::{
+    unpack_message_with_store, InMemoryPairwiseStore, PairwiseRecord, PairwiseStore,
+};
+pub use peek::{list_recipients, peek_envelope, EnvelopeInfo};
+pub use plaintext::{pack_plaintext, unpack_plaintext};
+pub use rekey::reencrypt;
+pub use resolve::{pack_message_for_dids, unpack_message_with_resolver};
+pub use session::PackSession;
+pub use types::{
+    key_lookup_fn, try_key_lookup_fn, CekUnwrapper, Compat, EnvelopeAlg, HeaderMap, KeyLookup,
+    KeyLookupResult, PackFormat, RecipientKey, RecipientPolicy, SenderCrypto, UnpackLimits,
+    UnpackMeta, UnpackOptions, UnwrapFuture,
+};
+pub use vectors::{load_vectors, verify_envelope_format, PackVector};
+pub use version::{EnvelopeCapabilities, EnvelopeVersion};
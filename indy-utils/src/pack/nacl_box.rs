@@ -1,14 +1,30 @@
+use chacha20poly1305::{
+    aead::{
+        generic_array::{typenum::U24, GenericArray},
+        Aead as _, NewAead,
+    },
+    XChaCha20Poly1305,
+};
 use crypto_box::{
     self as cbox,
     aead::{generic_array::typenum::Unsigned, Aead},
 };
+use rand::{rngs::OsRng, RngCore};
+use salsa20::hsalsa20;
 use ursa::blake2::{digest::Input, digest::VariableOutput, VarBlake2b};
+use x25519_dalek::{PublicKey as XPublicKey, StaticSecret};
+use xsalsa20poly1305::XSalsa20Poly1305;
+use zeroize::{Zeroize, Zeroizing};
 
 use crate::error::{ConversionError, UnexpectedError, ValidationError};
-use crate::keys::{KeyType, PrivateKey};
+use crate::nonce::RandomNonce;
 use crate::random::random_vec;
 
-const CBOX_NONCE_SIZE: usize = <cbox::Box as Aead>::NonceSize::USIZE;
+pub const CBOX_KEY_SIZE: usize = cbox::KEY_SIZE;
+pub const CBOX_NONCE_SIZE: usize = <cbox::Box as Aead>::NonceSize::USIZE;
+
+/// A random nonce for `crypto_box`
+pub(super) type CboxNonce = RandomNonce<U24>;
 
 fn crypto_box_key<F, T>(key: F) -> Result<T, ValidationError>
 where
@@ -21,10 +37,13 @@ where
     } else {
         let mut key_bytes = [0u8; cbox::KEY_SIZE];
         key_bytes.copy_from_slice(key);
-        Ok(T::from(key_bytes))
+        let result = T::from(key_bytes);
+        key_bytes.zeroize();
+        Ok(result)
     }
 }
 
+/// Derive the nonce for a sealed box from the ephemeral and recipient public keys
 pub fn crypto_box_nonce(
     ephemeral_pk: &[u8],
     recip_pk: &[u8],
@@ -37,6 +56,9 @@ pub fn crypto_box_nonce(
     Ok(nonce)
 }
 
+/// Encrypt a message from the sender to the recipient using X25519 and
+/// XSalsa20-Poly1305, returning the ciphertext and nonce. A random nonce is
+/// generated if none is provided.
 pub fn crypto_box(
     recip_pk: &[u8],
     sender_sk: &[u8],
@@ -50,7 +72,7 @@ pub fn crypto_box(
     let nonce = if let Some(nonce) = nonce {
         nonce.as_slice().into()
     } else {
-        random_vec(CBOX_NONCE_SIZE)
+        CboxNonce::generate().as_slice().to_vec()
     };
 
     let ciphertext = box_inst
@@ -59,6 +81,7 @@ pub fn crypto_box(
     Ok((ciphertext, nonce))
 }
 
+/// Decrypt a message produced by `crypto_box`
 pub fn crypto_box_open(
     recip_sk: &[u8],
     sender_pk: &[u8],
@@ -75,38 +98,159 @@ pub fn crypto_box_open(
     Ok(plaintext)
 }
 
+/// Perform an X25519 key exchange, returning the raw shared secret
+pub fn crypto_box_key_exchange(
+    sk: &[u8],
+    pk: &[u8],
+) -> Result<Zeroizing<[u8; cbox::KEY_SIZE]>, ConversionError> {
+    let sk_x = StaticSecret::from(crypto_box_key::<_, [u8; cbox::KEY_SIZE]>(sk)?);
+    let pk_x = XPublicKey::from(crypto_box_key::<_, [u8; cbox::KEY_SIZE]>(pk)?);
+    Ok(Zeroizing::new(*sk_x.diffie_hellman(&pk_x).as_bytes()))
+}
+
+/// Encrypt a message using a precomputed X25519 shared secret, producing the
+/// same output as `crypto_box` with the corresponding key pair. A random
+/// nonce is generated if none is provided.
+pub fn crypto_box_shared(
+    shared_secret: &[u8],
+    message: &[u8],
+    nonce: Option<Vec<u8>>,
+) -> Result<(Vec<u8>, Vec<u8>), ConversionError> {
+    let shared_secret: Zeroizing<[u8; cbox::KEY_SIZE]> =
+        Zeroizing::new(crypto_box_key(shared_secret)?);
+    let nonce = nonce.unwrap_or_else(|| CboxNonce::generate().as_slice().to_vec());
+    if nonce.len() != CBOX_NONCE_SIZE {
+        return Err(ConversionError::from_msg("Invalid crypto box nonce length"));
+    }
+
+    // derive the box key from the shared secret as in `crypto_box_beforenm`
+    let mut key = hsalsa20(
+        GenericArray::from_slice(&shared_secret[..]),
+        &GenericArray::default(),
+    );
+    let cipher = XSalsa20Poly1305::new(&key);
+    key.as_mut_slice().zeroize();
+
+    let ciphertext = cipher
+        .encrypt(GenericArray::from_slice(&nonce), message)
+        .map_err(|_| "Error encrypting box")?;
+    Ok((ciphertext, nonce))
+}
+
+/// Anonymously encrypt a message for the recipient using an ephemeral key,
+/// compatible with libsodium `crypto_box_seal`
 pub fn crypto_box_seal(recip_pk: &[u8], message: &[u8]) -> Result<Vec<u8>, ConversionError> {
-    let sk = PrivateKey::generate(Some(KeyType::ED25519))?;
-    let ephem_sk = sk.key_exchange()?;
-    let ephem_sk_x: cbox::SecretKey = crypto_box_key(&ephem_sk)?;
-    assert_eq!(ephem_sk_x.to_bytes(), ephem_sk.as_ref());
+    let mut ephem_sk = random_vec(CBOX_KEY_SIZE);
+    let result = crypto_box_seal_ephemeral(recip_pk, message, &ephem_sk);
+    ephem_sk.zeroize();
+    result
+}
+
+/// Seal a message using the provided ephemeral X25519 secret key
+pub fn crypto_box_seal_ephemeral(
+    recip_pk: &[u8],
+    message: &[u8],
+    ephem_sk: &[u8],
+) -> Result<Vec<u8>, ConversionError> {
+    let ephem_sk_x: cbox::SecretKey = crypto_box_key(ephem_sk)?;
     let ephem_pk_x = ephem_sk_x.public_key();
 
     let nonce = crypto_box_nonce(ephem_pk_x.as_bytes(), &recip_pk)?.to_vec();
-    let (mut boxed, _) = crypto_box(recip_pk, ephem_sk.as_ref(), message, Some(nonce))?;
+    let (mut boxed, _) = crypto_box(recip_pk, ephem_sk, message, Some(nonce))?;
 
-    let mut result = Vec::<u8>::with_capacity(cbox::KEY_SIZE); // FIXME
+    let mut result = Vec::<u8>::with_capacity(cbox::KEY_SIZE + boxed.len());
     result.extend_from_slice(ephem_pk_x.as_bytes());
     result.append(&mut boxed);
     Ok(result)
 }
 
+/// Decrypt a message produced by `crypto_box_seal`
 pub fn crypto_box_seal_open(
     recip_pk: &[u8],
     recip_sk: &[u8],
     ciphertext: &[u8],
 ) -> Result<Vec<u8>, ConversionError> {
-    let ephem_pk = &ciphertext[..32];
-    let boxed = &ciphertext[32..];
+    if ciphertext.len() < cbox::KEY_SIZE {
+        return Err(ConversionError::from_msg("Invalid sealed box length"));
+    }
+    let (ephem_pk, boxed) = ciphertext.split_at(cbox::KEY_SIZE);
 
     let nonce = crypto_box_nonce(&ephem_pk, &recip_pk)?;
     let decode = crypto_box_open(recip_sk, ephem_pk, boxed, &nonce)?;
     Ok(decode)
 }
 
+/// Anonymously encrypt a message for the recipient using an ephemeral X25519
+/// key and XChaCha20-Poly1305. The result is the ephemeral public key followed
+/// by the ciphertext and tag. The encryption key is derived as
+/// BLAKE2b-256(shared secret || ephemeral pk || recipient pk) and the nonce
+/// as in `crypto_box_nonce`.
+pub fn crypto_box_seal_xchacha(
+    recip_pk: &[u8],
+    message: &[u8],
+) -> Result<Vec<u8>, ConversionError> {
+    let mut ephem_sk = [0u8; cbox::KEY_SIZE];
+    OsRng.fill_bytes(&mut ephem_sk);
+    let ephem_sk_x = StaticSecret::from(ephem_sk);
+    ephem_sk.zeroize();
+    let ephem_pk_x = XPublicKey::from(&ephem_sk_x);
+
+    let recip_pk_x = XPublicKey::from(crypto_box_key::<_, [u8; cbox::KEY_SIZE]>(recip_pk)?);
+    let shared = ephem_sk_x.diffie_hellman(&recip_pk_x);
+    let key = xchacha_seal_key(shared.as_bytes(), ephem_pk_x.as_bytes(), recip_pk)?;
+    let nonce = crypto_box_nonce(ephem_pk_x.as_bytes(), recip_pk)?;
+
+    let mut boxed = XChaCha20Poly1305::new(GenericArray::from_slice(&key[..]))
+        .encrypt(GenericArray::from_slice(&nonce), message)
+        .map_err(|_| "Error encrypting box")?;
+    let mut result = Vec::<u8>::with_capacity(cbox::KEY_SIZE + boxed.len());
+    result.extend_from_slice(ephem_pk_x.as_bytes());
+    result.append(&mut boxed);
+    Ok(result)
+}
+
+/// Decrypt a message produced by `crypto_box_seal_xchacha`
+pub fn crypto_box_seal_xchacha_open(
+    recip_pk: &[u8],
+    recip_sk: &[u8],
+    ciphertext: &[u8],
+) -> Result<Vec<u8>, ConversionError> {
+    if ciphertext.len() < cbox::KEY_SIZE {
+        return Err(ConversionError::from_msg("Invalid sealed box length"));
+    }
+    let (ephem_pk, boxed) = ciphertext.split_at(cbox::KEY_SIZE);
+
+    let recip_sk_x = StaticSecret::from(crypto_box_key::<_, [u8; cbox::KEY_SIZE]>(recip_sk)?);
+    let ephem_pk_x = XPublicKey::from(crypto_box_key::<_, [u8; cbox::KEY_SIZE]>(ephem_pk)?);
+    let shared = recip_sk_x.diffie_hellman(&ephem_pk_x);
+    let key = xchacha_seal_key(shared.as_bytes(), ephem_pk, recip_pk)?;
+    let nonce = crypto_box_nonce(ephem_pk, recip_pk)?;
+
+    let plaintext = XChaCha20Poly1305::new(GenericArray::from_slice(&key[..]))
+        .decrypt(GenericArray::from_slice(&nonce), boxed)
+        .map_err(|_| "Error decrypting box")?;
+    Ok(plaintext)
+}
+
+fn xchacha_seal_key(
+    shared: &[u8],
+    ephem_pk: &[u8],
+    recip_pk: &[u8],
+) -> Result<Zeroizing<[u8; cbox::KEY_SIZE]>, UnexpectedError> {
+    let mut key_hash = VarBlake2b::new(cbox::KEY_SIZE).map_err(|_| "Error creating hasher")?;
+    key_hash.input(shared);
+    key_hash.input(ephem_pk);
+    key_hash.input(recip_pk);
+    let mut key = Zeroizing::new([0u8; cbox::KEY_SIZE]);
+    key_hash.variable_result(|hash| key.copy_from_slice(hash));
+    Ok(key)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::keys::test_fixtures::{TEST0, TEST1};
+    use crate::keys::PrivateKey;
 
     #[test]
     fn test_box() {
@@ -122,10 +266,26 @@ mod tests {
         assert_eq!(open, message);
     }
 
+    #[test]
+    fn test_box_shared() {
+        let sk1 = TEST0.private_key();
+        let sk2 = TEST1.private_key();
+        let sk1_x = sk1.key_exchange().unwrap();
+        let pk2_x = sk2.public_key().unwrap().key_exchange().unwrap();
+        let nonce = vec![1u8; CBOX_NONCE_SIZE];
+
+        let shared = crypto_box_key_exchange(sk1_x.as_ref(), pk2_x.as_ref()).unwrap();
+        let (boxed, _) =
+            crypto_box_shared(&shared[..], b"hello there", Some(nonce.clone())).unwrap();
+        let (expected, _) =
+            crypto_box(pk2_x.as_ref(), sk1_x.as_ref(), b"hello there", Some(nonce)).unwrap();
+        assert_eq!(boxed, expected);
+    }
+
     #[test]
     fn test_box_seal() {
         // let sk = SignKey::generate(Some(KeyType::ED25519)).unwrap();
-        let sk = PrivateKey::from_seed(b"000000000000000000000000000Test0").unwrap();
+        let sk = TEST0.private_key();
         let pk_x = sk.public_key().unwrap().key_exchange().unwrap();
         let sk_x = sk.key_exchange().unwrap();
 
@@ -134,5 +294,25 @@ mod tests {
 
         let open = crypto_box_seal_open(&pk_x.as_ref(), &sk_x.as_ref(), &sealed).unwrap();
         assert_eq!(open, message);
+
+        assert!(crypto_box_seal_open(&pk_x.as_ref(), &sk_x.as_ref(), &sealed[..16]).is_err());
+    }
+
+    #[test]
+    fn test_box_seal_xchacha() {
+        let sk = TEST0.private_key();
+        let pk_x = sk.public_key().unwrap().key_exchange().unwrap();
+        let sk_x = sk.key_exchange().unwrap();
+
+        let message = b"hello there";
+        let sealed = crypto_box_seal_xchacha(&pk_x.as_ref(), message).unwrap();
+        assert_eq!(sealed.len(), CBOX_KEY_SIZE + message.len() + 16);
+
+        let open = crypto_box_seal_xchacha_open(&pk_x.as_ref(), &sk_x.as_ref(), &sealed).unwrap();
+        assert_eq!(open, message);
+
+        let mut tampered = sealed.clone();
+        tampered[40] ^= 1;
+        assert!(crypto_box_seal_xchacha_open(&pk_x.as_ref(), &sk_x.as_ref(), &tampered).is_err());
     }
 }
@@ -0,0 +1,313 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use rand::{rngs::OsRng, CryptoRng, RngCore};
+
+use super::alg::{
+    build_protected, derive_sender, insert_sender_hint, recipient_keys, wrap_cek_anoncrypt,
+    wrap_cek_authcrypt, wrap_cek_authcrypt_shared, DerivedRecipient, KeySize, RecipientEntropy,
+};
+use super::builder::check_extra_headers;
+use super::cipher::encrypt_content;
+use super::error::PackError;
+use super::types::{
+    EnvelopeAlg, HeaderMap, JweSerialization, PackFormat, Protected, RecipientKey, SenderCrypto,
+    JWE,
+};
+use super::v2::KeyAgreementV2;
+use crate::base64;
+use crate::keys::{ArrayKey, PrivateKey};
+
+/// Pack a message, yielding to the executor after the content encryption
+/// key is wrapped for each recipient so that packing for a large number of
+/// recipients does not block a single-threaded runtime. Other envelope
+/// options may be set with `PackBuilder::pack_async`.
+pub async fn pack_message_async<M: AsRef<[u8]>, R: Into<RecipientKey>>(
+    message: M,
+    receiver_list: Vec<R>,
+    sender_key: Option<PrivateKey>,
+) -> Result<Vec<u8>, PackError> {
+    pack_message_async_with_rng(message, receiver_list, sender_key, &mut OsRng).await
+}
+
+/// Pack a message asynchronously using the provided random number generator,
+/// such as one drawing entropy from the embedding runtime
+pub async fn pack_message_async_with_rng<
+    M: AsRef<[u8]>,
+    R: Into<RecipientKey>,
+    G: CryptoRng + RngCore,
+>(
+    message: M,
+    receiver_list: Vec<R>,
+    sender_key: Option<PrivateKey>,
+    rng: &mut G,
+) -> Result<Vec<u8>, PackError> {
+    if receiver_list.is_empty() {
+        return Err(PackError::NoRecipients);
+    }
+
+    pack_async_with_options(
+        message,
+        recipient_keys(receiver_list)?,
+        sender_key,
+        PackFormat::default(),
+        EnvelopeAlg::default(),
+        &HeaderMap::new(),
+        rng,
+    )
+    .await
+}
+
+/// Pack a message for validated recipients with the options set by
+/// `PackBuilder`
+pub(super) async fn pack_async_with_options<M: AsRef<[u8]>, G: CryptoRng + RngCore>(
+    message: M,
+    receiver_list: Vec<RecipientKey>,
+    sender_key: Option<PrivateKey>,
+    format: PackFormat,
+    enc_alg: EnvelopeAlg,
+    extra_headers: &HeaderMap,
+    rng: &mut G,
+) -> Result<Vec<u8>, PackError> {
+    match format {
+        PackFormat::Legacy => {
+            let sender = sender_key.as_ref().map(derive_sender).transpose()?;
+            let cek = ArrayKey::random_with_rng(rng);
+            let mut recipients = Vec::with_capacity(receiver_list.len());
+            for their_vk in receiver_list {
+                let their_vk = DerivedRecipient::derive(their_vk)?;
+                let entropy = RecipientEntropy::random(rng);
+                recipients.push(match sender.as_ref() {
+                    Some(sender) => wrap_cek_authcrypt(&cek, &their_vk, sender, &entropy)?,
+                    None => wrap_cek_anoncrypt(&cek, &their_vk, &entropy)?,
+                });
+                yield_now().await;
+            }
+
+            let protected = build_protected(recipients, sender.is_some(), enc_alg, extra_headers);
+            encrypt_legacy(message, &cek, &protected, enc_alg, rng)
+        }
+        PackFormat::DIDCommV2 => {
+            let agreement = KeyAgreementV2::new(&receiver_list, sender_key.as_ref(), enc_alg, rng)?;
+            let (mut encrypted, ciphertext) = agreement.encrypt(message, extra_headers, rng)?;
+            for their_vk in receiver_list.iter() {
+                encrypted.add_recipient(their_vk)?;
+                yield_now().await;
+            }
+
+            let mut jwe = encrypted.finish();
+            jwe.ciphertext = base64::encode_urlsafe_nopad(&ciphertext);
+            Ok(serde_json::to_vec(&jwe)?)
+        }
+    }
+}
+
+/// Pack an authcrypted legacy envelope using a sender key held by an external
+/// key store, which performs the X25519 key exchange with each recipient.
+/// Randomness is drawn from the provided generator.
+pub async fn pack_message_with_sender<M, R, S, G>(
+    message: M,
+    receiver_list: Vec<R>,
+    sender: &S,
+    enc_alg: Option<EnvelopeAlg>,
+    extra_headers: Option<HeaderMap>,
+    sender_hint: Option<String>,
+    rng: &mut G,
+) -> Result<Vec<u8>, PackError>
+where
+    M: AsRef<[u8]>,
+    R: Into<RecipientKey>,
+    S: SenderCrypto + ?Sized,
+    G: CryptoRng + RngCore,
+{
+    if receiver_list.is_empty() {
+        return Err(PackError::NoRecipients);
+    }
+
+    let receiver_list = recipient_keys(receiver_list)?;
+    let enc_alg = enc_alg.unwrap_or_default();
+    let mut extra_headers = extra_headers.unwrap_or_default();
+    check_extra_headers(PackFormat::Legacy, &extra_headers)?;
+    insert_sender_hint(&mut extra_headers, sender_hint);
+
+    let sender_vk = sender
+        .public_key()
+        .await
+        .and_then(|pk| pk.as_base58())
+        .map_err(PackError::InvalidKey)?;
+    let cek = ArrayKey::random_with_rng(rng);
+    let mut recipients = Vec::with_capacity(receiver_list.len());
+    for their_vk in receiver_list {
+        let their_vk = DerivedRecipient::derive(their_vk)?;
+        let entropy = RecipientEntropy::random(rng);
+        let shared = sender
+            .key_exchange_with(their_vk.key_exchange())
+            .await
+            .map_err(|_| PackError::KeyWrapFailure)?;
+        recipients.push(wrap_cek_authcrypt_shared(
+            &cek, &their_vk, &sender_vk, &shared, &entropy,
+        )?);
+    }
+
+    let protected = build_protected(recipients, true, enc_alg, &extra_headers);
+    encrypt_legacy(message, &cek, &protected, enc_alg, rng)
+}
+
+/// Encrypt the payload of a legacy envelope and serialize the result
+fn encrypt_legacy<M: AsRef<[u8]>, G: CryptoRng + RngCore>(
+    message: M,
+    cek: &ArrayKey<KeySize>,
+    protected: &Protected,
+    enc_alg: EnvelopeAlg,
+    rng: &mut G,
+) -> Result<Vec<u8>, PackError> {
+    let base64_protected = JweSerialization::Json.encode_protected(&serde_json::to_vec(protected)?);
+    let content = encrypt_content(
+        enc_alg,
+        PackFormat::Legacy,
+        cek,
+        base64_protected.as_bytes(),
+        message.as_ref(),
+        rng,
+    )?;
+    Ok(serde_json::to_vec(&JWE {
+        protected: base64_protected,
+        iv: base64::encode_urlsafe(&content.iv),
+        ciphertext: base64::encode_urlsafe(&content.ciphertext),
+        tag: base64::encode_urlsafe(&content.tag),
+    })?)
+}
+
+/// Yield once to the executor
+fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            Poll::Ready(())
+        } else {
+            self.yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use async_global_executor::block_on;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::super::{
+        pack_message, unpack_message_sync, unpack_message_sync_with_meta, PackBuilder,
+    };
+    use super::*;
+    use crate::keys::test_fixtures::{TEST3, TEST4};
+
+    #[test]
+    fn test_pack_async_round_trip() {
+        let sk1 = TEST3.private_key();
+        let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
+        let sk2 = TEST4.private_key();
+        let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
+        let mut keys = HashMap::new();
+        keys.insert(pk2.clone(), sk2);
+
+        for format in &[PackFormat::Legacy, PackFormat::DIDCommV2] {
+            for sender in vec![None, Some(sk1.clone())] {
+                let mut builder = PackBuilder::new()
+                    .recipients(vec![pk1.clone(), pk2.clone()])
+                    .format(*format);
+                if let Some(sk) = sender.clone() {
+                    builder = builder.sender(sk);
+                }
+                let packed = block_on(builder.build().unwrap().pack_async(b"hello there")).unwrap();
+                let (msg, recip, send) = unpack_message_sync(&packed, &keys).unwrap();
+                assert_eq!(msg, b"hello there");
+                assert_eq!(recip, pk2);
+                assert_eq!(send, sender.map(|_| pk1.clone()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_pack_async_with_rng() {
+        let sk1 = TEST3.private_key();
+        let sk2 = TEST4.private_key();
+        let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
+        let mut keys = HashMap::new();
+        keys.insert(pk2.clone(), sk2);
+
+        for format in &[PackFormat::Legacy, PackFormat::DIDCommV2] {
+            let pack = |seed: u64| {
+                block_on(
+                    PackBuilder::new()
+                        .recipient(pk2.clone())
+                        .sender(sk1.clone())
+                        .format(*format)
+                        .sender_hint("did:example:sender")
+                        .build()
+                        .unwrap()
+                        .pack_async_with_rng(b"hello there", &mut StdRng::seed_from_u64(seed)),
+                )
+                .unwrap()
+            };
+            // all randomness is drawn from the caller's generator
+            let packed = pack(1);
+            assert_eq!(packed, pack(1));
+            assert_ne!(packed, pack(2));
+
+            let (msg, meta) = unpack_message_sync_with_meta(&packed, &keys, None).unwrap();
+            assert_eq!(msg, b"hello there");
+            assert_eq!(meta.sender_hint.as_deref(), Some("did:example:sender"));
+        }
+    }
+
+    #[test]
+    fn test_pack_with_sender_crypto() {
+        let sk1 = TEST3.private_key();
+        let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
+        let sk2 = TEST4.private_key();
+        let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
+        let mut keys = HashMap::new();
+        keys.insert(pk2.clone(), sk2);
+
+        let packed = block_on(pack_message_with_sender(
+            b"hello there",
+            vec![pk2.clone()],
+            &sk1,
+            None,
+            None,
+            None,
+            &mut OsRng,
+        ))
+        .unwrap();
+        let (msg, recip, send) = unpack_message_sync(&packed, &keys).unwrap();
+        assert_eq!(msg, b"hello there");
+        assert_eq!(recip, pk2);
+        assert_eq!(send, Some(pk1.clone()));
+
+        // the protected header matches the in-memory sender key implementation
+        let expected = pack_message(b"hello there", vec![pk2.clone()], Some(sk1.clone())).unwrap();
+        let header = |packed: &[u8]| {
+            let jwe: serde_json::Value = serde_json::from_slice(packed).unwrap();
+            let protected: serde_json::Value = serde_json::from_slice(
+                &base64::decode_urlsafe(jwe["protected"].as_str().unwrap()).unwrap(),
+            )
+            .unwrap();
+            (protected["alg"].clone(), protected["enc"].clone())
+        };
+        assert_eq!(header(&packed), header(&expected));
+    }
+}
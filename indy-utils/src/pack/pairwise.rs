@@ -0,0 +1,210 @@
+use std::collections::{BTreeMap, HashMap};
+
+use super::alg::unpack_message_with_meta;
+use super::error::UnpackError;
+use super::types::{try_key_lookup_fn, UnpackMeta, UnpackOptions};
+use crate::did::DID;
+use crate::error::ConversionError;
+use crate::keys::{EncodedVerKey, PrivateKey};
+
+/// A pairwise connection: our key for the connection and the DID and keys
+/// of the other party
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PairwiseRecord {
+    pub connection_id: String,
+    pub their_did: DID,
+    pub their_keys: Vec<EncodedVerKey>,
+    pub my_key: PrivateKey,
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl PairwiseRecord {
+    pub fn new(
+        connection_id: &str,
+        their_did: &DID,
+        their_keys: Vec<EncodedVerKey>,
+        my_key: PrivateKey,
+    ) -> Self {
+        Self {
+            connection_id: connection_id.to_owned(),
+            their_did: their_did.clone(),
+            their_keys,
+            my_key,
+            metadata: BTreeMap::new(),
+        }
+    }
+
+    /// Our verkey for the connection
+    pub fn my_verkey(&self) -> Result<EncodedVerKey, ConversionError> {
+        self.my_key.public_key()?.as_base58()
+    }
+
+    /// Check whether a key belongs to the other party
+    pub fn has_their_key(&self, key: &EncodedVerKey) -> bool {
+        self.their_keys.iter().any(|their_key| their_key == key)
+    }
+}
+
+/// Storage for pairwise connections, indexed by the DID of the other party
+pub trait PairwiseStore: Send + Sync {
+    /// Fetch the connection with another party by their DID
+    fn get(&self, their_did: &DID) -> Result<Option<PairwiseRecord>, ConversionError>;
+
+    /// Find the connection using one of our verkeys
+    fn find_by_my_key(
+        &self,
+        my_key: &EncodedVerKey,
+    ) -> Result<Option<PairwiseRecord>, ConversionError>;
+
+    /// Find the connection using one of the other party's verkeys
+    fn find_by_their_key(
+        &self,
+        their_key: &EncodedVerKey,
+    ) -> Result<Option<PairwiseRecord>, ConversionError>;
+
+    /// Add or replace the connection with another party
+    fn store(&mut self, record: PairwiseRecord) -> Result<(), ConversionError>;
+
+    /// Remove the connection with another party, returning it if present
+    fn remove(&mut self, their_did: &DID) -> Result<Option<PairwiseRecord>, ConversionError>;
+}
+
+/// A `PairwiseStore` held in memory
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryPairwiseStore {
+    records: HashMap<DID, PairwiseRecord>,
+}
+
+impl InMemoryPairwiseStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+}
+
+impl PairwiseStore for InMemoryPairwiseStore {
+    fn get(&self, their_did: &DID) -> Result<Option<PairwiseRecord>, ConversionError> {
+        Ok(self.records.get(their_did).cloned())
+    }
+
+    fn find_by_my_key(
+        &self,
+        my_key: &EncodedVerKey,
+    ) -> Result<Option<PairwiseRecord>, ConversionError> {
+        for record in self.records.values() {
+            if record.my_verkey()? == *my_key {
+                return Ok(Some(record.clone()));
+            }
+        }
+        Ok(None)
+    }
+
+    fn find_by_their_key(
+        &self,
+        their_key: &EncodedVerKey,
+    ) -> Result<Option<PairwiseRecord>, ConversionError> {
+        Ok(self
+            .records
+            .values()
+            .find(|record| record.has_their_key(their_key))
+            .cloned())
+    }
+
+    fn store(&mut self, record: PairwiseRecord) -> Result<(), ConversionError> {
+        self.records.insert(record.their_did.clone(), record);
+        Ok(())
+    }
+
+    fn remove(&mut self, their_did: &DID) -> Result<Option<PairwiseRecord>, ConversionError> {
+        Ok(self.records.remove(their_did))
+    }
+}
+
+/// Unpack a message addressed to one of our pairwise keys, returning the
+/// plaintext, the envelope metadata and the identifier of the connection.
+/// The connection is only identified when the message was authcrypted by a
+/// key of the other party to the connection of the recipient key.
+pub async fn unpack_message_with_store<S: PairwiseStore>(
+    message: impl AsRef<[u8]>,
+    store: &S,
+    options: Option<UnpackOptions>,
+) -> Result<(Vec<u8>, UnpackMeta, Option<String>), UnpackError> {
+    let lookup = try_key_lookup_fn(|keys: &Vec<EncodedVerKey>| {
+        for (idx, key) in keys.iter().enumerate() {
+            if let Some(record) = store.find_by_my_key(key)? {
+                return Ok(Some((idx, record.my_key)));
+            }
+        }
+        Ok(None)
+    });
+    let (message, meta) = unpack_message_with_meta(message, &lookup, options).await?;
+    let connection_id = match meta.sender {
+        Some(ref sender) => store
+            .find_by_my_key(meta.recipient())
+            .map_err(UnpackError::KeyLookup)?
+            .filter(|record| record.has_their_key(sender))
+            .map(|record| record.connection_id),
+        None => None,
+    };
+    Ok((message, meta, connection_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keys::test_fixtures::{TEST1, TEST2, TEST3};
+    use crate::pack::pack_message;
+    use async_global_executor::block_on;
+
+    #[test]
+    fn unpack_with_store() {
+        let mut store = InMemoryPairwiseStore::new();
+        let their_did = DID::from_verkey(&TEST2.encoded_verkey()).unwrap();
+        store
+            .store(PairwiseRecord::new(
+                "conn-1",
+                &their_did,
+                vec![TEST2.encoded_verkey()],
+                TEST1.private_key(),
+            ))
+            .unwrap();
+        assert!(store.get(&their_did).unwrap().is_some());
+
+        let packed = pack_message(
+            b"hello there",
+            vec![TEST1.encoded_verkey()],
+            Some(TEST2.private_key()),
+        )
+        .unwrap();
+        let (message, meta, connection_id) =
+            block_on(unpack_message_with_store(&packed, &store, None)).unwrap();
+        assert_eq!(message, b"hello there");
+        assert_eq!(meta.sender, Some(TEST2.encoded_verkey()));
+        assert_eq!(connection_id.as_deref(), Some("conn-1"));
+
+        // a sender who is not party to the connection is not matched
+        let packed = pack_message(
+            b"hello there",
+            vec![TEST1.encoded_verkey()],
+            Some(TEST3.private_key()),
+        )
+        .unwrap();
+        let (_, _, connection_id) =
+            block_on(unpack_message_with_store(&packed, &store, None)).unwrap();
+        assert_eq!(connection_id, None);
+
+        // messages for unknown keys cannot be unpacked
+        let packed = pack_message(b"hello there", vec![TEST3.encoded_verkey()], None).unwrap();
+        assert!(block_on(unpack_message_with_store(&packed, &store, None)).is_err());
+
+        assert!(store.remove(&their_did).unwrap().is_some());
+        assert!(store.is_empty());
+    }
+}
@@ -0,0 +1,117 @@
+use super::alg::{decode_b64, parse_envelope, recipient_verkeys};
+use super::error::UnpackError;
+use super::types::*;
+use crate::keys::EncodedVerKey;
+
+/// Envelope metadata which can be determined without decrypting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvelopeInfo {
+    pub format: PackFormat,
+    pub alg: String,
+    pub enc: String,
+    pub typ: String,
+    pub recipients: Vec<EncodedVerKey>,
+}
+
+impl EnvelopeInfo {
+    /// Determine whether the envelope was encrypted by an identified sender
+    pub fn is_authcrypt(&self) -> bool {
+        match self.format {
+            PackFormat::Legacy => self.alg == super::alg::PROTECTED_HEADER_ALG_AUTH,
+            PackFormat::DIDCommV2 => self.alg == super::v2::PROTECTED_HEADER_ALG_ECDH_1PU,
+        }
+    }
+}
+
+/// Decode the protected header of a packed message without decrypting it
+pub fn peek_envelope(message: &[u8]) -> Result<EnvelopeInfo, UnpackError> {
+    match parse_envelope(message, &UnpackLimits::default())? {
+        Envelope::DIDCommV2(jwe) => {
+            let protected: ProtectedV2 = decode_protected_header(&jwe.protected)?;
+            let recipients =
+                recipient_verkeys(jwe.recipients.iter().map(|r| r.header.kid.as_str()))?;
+            Ok(EnvelopeInfo {
+                format: PackFormat::DIDCommV2,
+                alg: protected.alg,
+                enc: protected.enc,
+                typ: protected.typ,
+                recipients,
+            })
+        }
+        Envelope::Legacy(jwe) => {
+            let protected: Protected = decode_protected_header(&jwe.protected)?;
+            let recipients =
+                recipient_verkeys(protected.recipients.iter().map(|r| r.header.kid.as_str()))?;
+            Ok(EnvelopeInfo {
+                format: PackFormat::Legacy,
+                alg: protected.alg,
+                enc: protected.enc,
+                typ: protected.typ,
+                recipients,
+            })
+        }
+    }
+}
+
+/// List the recipient verkeys of a packed message without decrypting it
+pub fn list_recipients(message: &[u8]) -> Result<Vec<EncodedVerKey>, UnpackError> {
+    Ok(peek_envelope(message)?.recipients)
+}
+
+fn decode_protected_header<T: serde::de::DeserializeOwned>(
+    protected: &str,
+) -> Result<T, UnpackError> {
+    serde_json::from_slice(&decode_b64(protected, "protected")?)
+        .map_err(|err| UnpackError::InvalidProtectedHeader(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::PackBuilder;
+    use super::*;
+    use crate::keys::test_fixtures::{TEST1, TEST2, TEST3};
+    use crate::keys::PrivateKey;
+
+    #[test]
+    fn test_list_recipients() {
+        let sk = TEST1.private_key();
+        let pk1 = TEST2
+            .private_key()
+            .public_key()
+            .unwrap()
+            .as_base58()
+            .unwrap();
+        let pk2 = TEST3
+            .private_key()
+            .public_key()
+            .unwrap()
+            .as_base58()
+            .unwrap();
+
+        for format in &[PackFormat::Legacy, PackFormat::DIDCommV2] {
+            let packed = PackBuilder::new()
+                .recipients(vec![pk1.clone(), pk2.clone()])
+                .sender(sk.clone())
+                .format(*format)
+                .build()
+                .unwrap()
+                .pack(b"hello there")
+                .unwrap();
+            assert_eq!(
+                list_recipients(&packed).unwrap(),
+                vec![pk1.clone(), pk2.clone()]
+            );
+            let info = peek_envelope(&packed).unwrap();
+            assert_eq!(info.format, *format);
+            assert!(info.is_authcrypt());
+        }
+    }
+
+    #[test]
+    fn test_peek_invalid() {
+        assert!(matches!(
+            peek_envelope(b"{}"),
+            Err(UnpackError::InvalidEnvelope(_))
+        ));
+    }
+}
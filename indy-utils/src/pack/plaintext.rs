@@ -0,0 +1,85 @@
+use super::alg::PROTECTED_HEADER_TYP;
+use super::error::{PackError, UnpackError};
+
+const TYP_FIELD: &'static str = "typ";
+
+/// Produce a plaintext JWM envelope for a JSON message object
+pub fn pack_plaintext(message: impl AsRef<[u8]>) -> Result<Vec<u8>, PackError> {
+    let mut message: serde_json::Value = serde_json::from_slice(message.as_ref())
+        .map_err(|err| PackError::InvalidPayload(err.to_string()))?;
+    let fields = unwrap_opt_or_return!(
+        message.as_object_mut(),
+        Err(PackError::InvalidPayload(
+            "Message must be a JSON object".to_string()
+        ))
+    );
+    match fields.get(TYP_FIELD) {
+        None => {
+            fields.insert(TYP_FIELD.to_string(), PROTECTED_HEADER_TYP.into());
+        }
+        Some(typ) if typ == PROTECTED_HEADER_TYP => (),
+        Some(_) => return Err(PackError::ReservedHeader(TYP_FIELD.to_string())),
+    }
+    Ok(serde_json::to_vec(&message)?)
+}
+
+/// Unpack a plaintext JWM envelope, returning the message without the `typ` field
+pub fn unpack_plaintext(message: impl AsRef<[u8]>) -> Result<Vec<u8>, UnpackError> {
+    let mut message: serde_json::Value = serde_json::from_slice(message.as_ref())
+        .map_err(|err| UnpackError::InvalidEnvelope(err.to_string()))?;
+    let fields = unwrap_opt_or_return!(
+        message.as_object_mut(),
+        Err(UnpackError::InvalidEnvelope(
+            "Plaintext envelope must be a JSON object".to_string()
+        ))
+    );
+    match fields.remove(TYP_FIELD) {
+        Some(typ) if typ == PROTECTED_HEADER_TYP => (),
+        Some(typ) => {
+            return Err(UnpackError::InvalidEnvelope(format!(
+                "Unexpected envelope type: {}",
+                typ
+            )))
+        }
+        None => {
+            return Err(UnpackError::InvalidEnvelope(
+                "Missing envelope type".to_string(),
+            ))
+        }
+    }
+    serde_json::to_vec(&message).map_err(|err| UnpackError::InvalidEnvelope(err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plaintext_round_trip() {
+        let message =
+            br#"{"@type":"https://didcomm.org/out-of-band/1.0/invitation","label":"Alice"}"#;
+        let packed = pack_plaintext(&message[..]).unwrap();
+        let envelope: serde_json::Value = serde_json::from_slice(&packed).unwrap();
+        assert_eq!(envelope["typ"], PROTECTED_HEADER_TYP);
+        assert_eq!(envelope["label"], "Alice");
+
+        let unpacked = unpack_plaintext(&packed).unwrap();
+        assert_eq!(
+            serde_json::from_slice::<serde_json::Value>(&unpacked).unwrap(),
+            serde_json::from_slice::<serde_json::Value>(&message[..]).unwrap()
+        );
+
+        assert!(matches!(
+            pack_plaintext(br#"["not", "an", "object"]"#),
+            Err(PackError::InvalidPayload(_))
+        ));
+        assert!(matches!(
+            pack_plaintext(br#"{"typ":"other"}"#),
+            Err(PackError::ReservedHeader(_))
+        ));
+        assert!(matches!(
+            unpack_plaintext(br#"{"label":"Alice"}"#),
+            Err(UnpackError::InvalidEnvelope(_))
+        ));
+    }
+}
@@ -0,0 +1,132 @@
+use rand::rngs::OsRng;
+use zeroize::Zeroizing;
+
+use super::alg::{pack_with_options, unpack_message_with_meta};
+use super::cipher::parse_enc_header;
+use super::error::PackError;
+use super::types::{KeyLookup, RecipientKey};
+
+/// Re-encrypt a packed message for a new set of recipients, preserving its
+/// format, content encryption algorithm, sender hint and extra protected
+/// headers. The intermediate plaintext is zeroized once repacked. As the
+/// original sender key is not available, the new envelope is always
+/// anoncrypted, and the payload of a signed message is repacked without its
+/// signature.
+pub async fn reencrypt<'f, R: Into<RecipientKey>>(
+    message: impl AsRef<[u8]>,
+    lookup: impl KeyLookup<'f>,
+    new_recipients: Vec<R>,
+) -> Result<Vec<u8>, PackError> {
+    if new_recipients.is_empty() {
+        return Err(PackError::NoRecipients);
+    }
+    let (plaintext, meta) = unpack_message_with_meta(message, lookup, None)
+        .await
+        .map_err(PackError::Unpack)?;
+    let plaintext = Zeroizing::new(plaintext);
+    let mut extra = meta.extra;
+    if meta.signature_verified {
+        // the signature has been removed from the payload
+        extra.remove("cty");
+    }
+    let enc_alg = parse_enc_header(&meta.enc, meta.format).map_err(PackError::Unpack)?;
+    pack_with_options(
+        plaintext.as_slice(),
+        new_recipients,
+        None,
+        meta.format,
+        enc_alg,
+        extra,
+        meta.sender_hint,
+        &mut OsRng,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use async_global_executor::block_on;
+
+    use super::super::{
+        key_lookup_fn, pack_message_signed, unpack_message, EnvelopeAlg, PackBuilder, PackFormat,
+        UnpackError,
+    };
+    use super::*;
+    use crate::keys::test_fixtures::{TEST1, TEST2, TEST3};
+    use crate::keys::EncodedVerKey;
+
+    #[test]
+    fn test_reencrypt() {
+        let old_sk = TEST1.private_key();
+        let old_pk = old_sk.public_key().unwrap().as_base58().unwrap();
+        let new_sk = TEST2.private_key();
+        let new_pk = new_sk.public_key().unwrap().as_base58().unwrap();
+
+        let packed = PackBuilder::new()
+            .recipient(old_pk.clone())
+            .format(PackFormat::DIDCommV2)
+            .encryption_alg(EnvelopeAlg::Aes256Gcm)
+            .build()
+            .unwrap()
+            .pack(b"hello there")
+            .unwrap();
+
+        let old_lookup = key_lookup_fn(|pks: &Vec<EncodedVerKey>| {
+            pks.iter()
+                .position(|pk| pk == &old_pk)
+                .map(|idx| (idx, old_sk.clone()))
+        });
+        let rekeyed = block_on(reencrypt(&packed, &old_lookup, vec![new_pk.clone()])).unwrap();
+
+        let new_lookup = key_lookup_fn(|pks: &Vec<EncodedVerKey>| {
+            pks.iter()
+                .position(|pk| pk == &new_pk)
+                .map(|idx| (idx, new_sk.clone()))
+        });
+        let (msg, meta) = block_on(unpack_message_with_meta(&rekeyed, &new_lookup, None)).unwrap();
+        assert_eq!(msg, b"hello there");
+        assert_eq!(meta.format, PackFormat::DIDCommV2);
+        assert_eq!(meta.recipients, vec![new_pk]);
+        assert!(matches!(
+            block_on(reencrypt(&rekeyed, &old_lookup, vec![new_pk.clone()])),
+            Err(PackError::Unpack(UnpackError::NoMatchingRecipient))
+        ));
+    }
+
+    #[test]
+    fn test_reencrypt_signed() {
+        let sender_sk = TEST3.private_key();
+        let old_sk = TEST1.private_key();
+        let old_pk = old_sk.public_key().unwrap().as_base58().unwrap();
+        let new_sk = TEST2.private_key();
+        let new_pk = new_sk.public_key().unwrap().as_base58().unwrap();
+
+        for format in &[PackFormat::Legacy, PackFormat::DIDCommV2] {
+            let packed = pack_message_signed(
+                b"hello there",
+                vec![old_pk.clone()],
+                sender_sk.clone(),
+                Some(*format),
+                None,
+                None,
+            )
+            .unwrap();
+
+            let old_lookup = key_lookup_fn(|pks: &Vec<EncodedVerKey>| {
+                pks.iter()
+                    .position(|pk| pk == &old_pk)
+                    .map(|idx| (idx, old_sk.clone()))
+            });
+            let rekeyed = block_on(reencrypt(&packed, &old_lookup, vec![new_pk.clone()])).unwrap();
+
+            let new_lookup = key_lookup_fn(|pks: &Vec<EncodedVerKey>| {
+                pks.iter()
+                    .position(|pk| pk == &new_pk)
+                    .map(|idx| (idx, new_sk.clone()))
+            });
+            let (msg, recipient, sender) = block_on(unpack_message(&rekeyed, &new_lookup)).unwrap();
+            assert_eq!(msg, b"hello there");
+            assert_eq!(recipient, new_pk);
+            assert_eq!(sender, None);
+        }
+    }
+}
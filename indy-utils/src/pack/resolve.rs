@@ -0,0 +1,177 @@
+use super::alg::{
+    check_recipient_count, decode_b64, parse_envelope, recipient_verkeys, unpack_message_with_meta,
+};
+use super::builder::PackBuilder;
+use super::error::{PackError, UnpackError};
+use super::types::{Envelope, KeyLookup, PackFormat, RecipientKey, UnpackMeta, UnpackOptions};
+use super::v2::unpack_jwe_v2_for_keys;
+use crate::did::resolver::{key_agreement_keys, DidResolver};
+use crate::did::DID;
+use crate::error::ConversionError;
+use crate::keys::{EncodedVerKey, PrivateKey};
+
+/// Pack a message for the key agreement keys listed in the resolved DID
+/// Document of each recipient DID
+pub async fn pack_message_for_dids<M: AsRef<[u8]>, R: DidResolver>(
+    message: M,
+    recipients: &[DID],
+    sender_key: Option<PrivateKey>,
+    resolver: &R,
+    format: Option<PackFormat>,
+) -> Result<Vec<u8>, PackError> {
+    let mut receiver_list = Vec::new();
+    for (idx, did) in recipients.iter().enumerate() {
+        let doc = resolver
+            .resolve(did)
+            .await
+            .map_err(|err| PackError::InvalidRecipient(idx, err.to_string()))?;
+        let keys = key_agreement_keys(&doc)
+            .map_err(|err| PackError::InvalidRecipient(idx, err.to_string()))?;
+        if keys.is_empty() {
+            return Err(PackError::InvalidRecipient(
+                idx,
+                format!("No key agreement keys found for {}", did),
+            ));
+        }
+        for (_, key) in keys {
+            let key = key
+                .as_base58()
+                .map_err(|err| PackError::InvalidRecipient(idx, err.to_string()))?;
+            receiver_list.push(RecipientKey::from(key));
+        }
+    }
+    let mut builder = PackBuilder::new()
+        .recipients(receiver_list)
+        .format(format.unwrap_or_default());
+    if let Some(sender_key) = sender_key {
+        builder = builder.sender(sender_key);
+    }
+    builder.build()?.pack_async(message).await
+}
+
+/// Unpack a message whose DIDComm v2 recipient key identifiers may be DID
+/// URLs, such as `did:peer:2...#key-2`. These are resolved to the verkeys
+/// passed to the key lookup.
+pub async fn unpack_message_with_resolver<'f, R: DidResolver>(
+    message: impl AsRef<[u8]>,
+    lookup: impl KeyLookup<'f>,
+    resolver: &R,
+    options: Option<UnpackOptions>,
+) -> Result<(Vec<u8>, UnpackMeta), UnpackError> {
+    let options = options.unwrap_or_default();
+    let envelope = parse_envelope(message.as_ref(), &options.limits)?;
+    match envelope {
+        Envelope::DIDCommV2(jwe) => {
+            check_recipient_count(jwe.recipients.len(), &options.limits)?;
+            // the envelope is left unchanged, as `apv` covers the original identifiers
+            let mut recip_vks = Vec::with_capacity(jwe.recipients.len());
+            for recipient in jwe.recipients.iter() {
+                let kid = recipient.header.kid.as_str();
+                if kid.starts_with("did:") && !kid.starts_with("did:key:") {
+                    recip_vks.push(
+                        resolve_kid(kid, resolver)
+                            .await
+                            .map_err(UnpackError::KeyLookup)?,
+                    );
+                } else {
+                    recip_vks.extend(recipient_verkeys(std::iter::once(kid))?);
+                }
+            }
+            let ciphertext = decode_b64(&jwe.ciphertext, "ciphertext")?;
+            unpack_jwe_v2_for_keys(&jwe, recip_vks, ciphertext, lookup, &options).await
+        }
+        Envelope::Legacy(_) => unpack_message_with_meta(message, lookup, Some(options)).await,
+    }
+}
+
+/// Resolve a DID URL to the key agreement key it identifies
+async fn resolve_kid<R: DidResolver>(
+    kid: &str,
+    resolver: &R,
+) -> Result<EncodedVerKey, ConversionError> {
+    let did = DID::parse(kid.split('#').next().unwrap_or_default())?;
+    let doc = resolver.resolve(&did).await?;
+    key_agreement_keys(&doc)?
+        .into_iter()
+        .find(|(id, _)| id == kid)
+        .ok_or_else(|| format!("Unknown key agreement key: {}", kid))?
+        .1
+        .as_base58()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::did::peer::{create_peer2, PeerPurpose};
+    use crate::did::resolver::UniversalResolver;
+    use crate::keys::test_fixtures::{TEST1, TEST2};
+    use crate::pack::key_lookup_fn;
+    use crate::pack::v2::pack_message_v2_with_kids;
+    use async_global_executor::block_on;
+
+    #[test]
+    fn pack_for_resolved_dids() {
+        let resolver = UniversalResolver::new();
+        let vk = TEST1.verkey();
+        let did = create_peer2(
+            &[
+                (PeerPurpose::Authentication, vk.clone()),
+                (PeerPurpose::KeyAgreement, vk.key_exchange().unwrap()),
+            ],
+            &[],
+        )
+        .unwrap();
+        let lookup = || {
+            key_lookup_fn(|keys: &Vec<EncodedVerKey>| {
+                keys.iter()
+                    .position(|key| *key == TEST1.encoded_verkey())
+                    .map(|idx| (idx, TEST1.private_key()))
+            })
+        };
+
+        for format in &[PackFormat::Legacy, PackFormat::DIDCommV2] {
+            let packed = block_on(pack_message_for_dids(
+                b"hello there",
+                &[did.clone()],
+                Some(TEST2.private_key()),
+                &resolver,
+                Some(*format),
+            ))
+            .unwrap();
+            let (message, meta) = block_on(unpack_message_with_resolver(
+                &packed,
+                &lookup(),
+                &resolver,
+                None,
+            ))
+            .unwrap();
+            assert_eq!(message, b"hello there");
+            assert_eq!(meta.recipient(), &TEST1.encoded_verkey());
+        }
+
+        // recipient key identifiers given as DID URLs are resolved
+        let kid = format!("{}#key-2", did);
+        let key = block_on(resolve_kid(&kid, &resolver)).unwrap();
+        let packed = pack_message_v2_with_kids(b"hello there", vec![key.into()], &[kid]);
+        assert!(block_on(unpack_message_with_meta(&packed, &lookup(), None)).is_err());
+        let (message, meta) = block_on(unpack_message_with_resolver(
+            &packed,
+            &lookup(),
+            &resolver,
+            None,
+        ))
+        .unwrap();
+        assert_eq!(message, b"hello there");
+        assert_eq!(meta.recipient(), &TEST1.encoded_verkey());
+
+        let did = DID::parse("did:sov:VsKV7grR1BUE29mG2Fm2kX").unwrap();
+        assert!(block_on(pack_message_for_dids(
+            b"hello there",
+            &[did],
+            None,
+            &resolver,
+            None
+        ))
+        .is_err());
+    }
+}
@@ -0,0 +1,86 @@
+use rand::rngs::OsRng;
+
+use super::alg::{
+    derive_recipients, derive_sender, pack_jwe_derived, validate_recipients, DerivedRecipient,
+    DerivedSender,
+};
+use super::error::PackError;
+use super::types::{EnvelopeAlg, HeaderMap, JweSerialization, RecipientKey};
+use crate::base64;
+use crate::keys::PrivateKey;
+
+/// A reusable legacy packing context for a fixed sender and set of recipients,
+/// which derives the key exchange keys only once
+#[derive(Debug, Clone)]
+pub struct PackSession {
+    recipients: Vec<DerivedRecipient>,
+    sender: Option<DerivedSender>,
+    enc_alg: EnvelopeAlg,
+}
+
+impl PackSession {
+    pub fn new<R: Into<RecipientKey>>(
+        sender: Option<PrivateKey>,
+        recipients: Vec<R>,
+    ) -> Result<Self, PackError> {
+        if recipients.is_empty() {
+            return Err(PackError::NoRecipients);
+        }
+        let recipients = validate_recipients(recipients.into_iter().map(Into::into).collect())?;
+        Ok(Self {
+            recipients: derive_recipients(&recipients)?,
+            sender: sender.as_ref().map(derive_sender).transpose()?,
+            enc_alg: EnvelopeAlg::default(),
+        })
+    }
+
+    /// Set the content encryption algorithm
+    pub fn encryption_alg(mut self, enc_alg: EnvelopeAlg) -> Self {
+        self.enc_alg = enc_alg;
+        self
+    }
+
+    /// Pack a message for the session recipients
+    pub fn pack<M: AsRef<[u8]>>(&self, message: M) -> Result<Vec<u8>, PackError> {
+        let (mut jwe, ciphertext) = pack_jwe_derived(
+            message,
+            &self.recipients,
+            self.sender.as_ref(),
+            self.enc_alg,
+            &HeaderMap::new(),
+            JweSerialization::Json,
+            &mut OsRng,
+        )?;
+        jwe.ciphertext = base64::encode_urlsafe(&ciphertext);
+        Ok(serde_json::to_vec(&jwe)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::unpack_message_sync;
+    use super::*;
+    use crate::keys::test_fixtures::{TEST3, TEST4};
+
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_session_round_trip() {
+        let sk1 = TEST3.private_key();
+        let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
+        let sk2 = TEST4.private_key();
+        let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
+
+        let mut keys = HashMap::new();
+        keys.insert(pk2.clone(), sk2.clone());
+
+        let session = PackSession::new(Some(sk1), vec![pk2.clone()]).unwrap();
+        for input_msg in &[&b"hello there"[..], &b"general kenobi"[..]] {
+            let packed = session.pack(input_msg).unwrap();
+            let (msg, p_recip, p_send) = unpack_message_sync(&packed, &keys).unwrap();
+            assert_eq!(msg, *input_msg);
+            assert_eq!(p_recip, pk2);
+            assert_eq!(p_send, Some(pk1.clone()));
+        }
+    }
+}
@@ -1,7 +1,16 @@
-use crate::keys::{EncodedVerKey, PrivateKey};
+use super::nacl_box::{crypto_box_key_exchange, crypto_box_open, crypto_box_seal_open};
+use crate::base64;
+use crate::error::ConversionError;
+use crate::keys::{EncodedVerKey, KeyType, PrivateKey, VerKey};
 
+use std::collections::BTreeMap;
 use std::future::Future;
 
+use zeroize::Zeroizing;
+
+/// Additional fields to be included in the protected header of an envelope
+pub type HeaderMap = BTreeMap<String, serde_json::Value>;
+
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub struct JWE {
     pub protected: String,
@@ -10,6 +19,14 @@ pub struct JWE {
     pub tag: String,
 }
 
+/// Header for a chunked message stream
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct StreamHeader {
+    pub protected: String,
+    pub iv: String,
+    pub chunk_size: usize,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
 pub struct Recipient {
     pub encrypted_key: String,
@@ -33,20 +50,289 @@ pub struct Protected {
     pub typ: String,
     pub alg: String,
     pub recipients: Vec<Recipient>,
+    #[serde(flatten)]
+    pub extra: HeaderMap,
+}
+
+/// The envelope format produced by `pack_message`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PackFormat {
+    /// Legacy JWM/1.0 envelope (Aries RFC 0019)
+    Legacy,
+    /// DIDComm v2 JWE using ECDH-ES+A256KW or ECDH-1PU+A256KW
+    DIDCommV2,
+}
+
+impl Default for PackFormat {
+    fn default() -> Self {
+        Self::Legacy
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct JWEv2 {
+    pub protected: String,
+    pub recipients: Vec<RecipientV2>,
+    pub iv: String,
+    pub ciphertext: String,
+    pub tag: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct RecipientV2 {
+    pub encrypted_key: String,
+    pub header: HeaderV2,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct HeaderV2 {
+    pub kid: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct ProtectedV2 {
+    pub typ: String,
+    pub alg: String,
+    pub enc: String,
+    pub epk: EphemeralKey,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skid: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub apu: Option<String>,
+    pub apv: String,
+    #[serde(flatten)]
+    pub extra: HeaderMap,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Eq, PartialEq)]
+pub struct EphemeralKey {
+    pub kty: String,
+    pub crv: String,
+    pub x: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y: Option<String>,
+}
+
+/// The serialization of a packed envelope
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum JweSerialization {
+    /// JSON serialization, compatible with existing agents
+    Json,
+    /// Compact serialization for a single recipient (RFC 7516 section 7.1)
+    Compact,
+}
+
+impl JweSerialization {
+    /// Encode the protected header, which is also used as the AEAD associated data
+    pub fn encode_protected(&self, protected: &[u8]) -> String {
+        match self {
+            Self::Json => base64::encode_urlsafe(protected),
+            Self::Compact => base64::encode_urlsafe_nopad(protected),
+        }
+    }
+}
+
+/// The content encryption algorithm used for the envelope payload
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum EnvelopeAlg {
+    XChaCha20Poly1305,
+    Aes256Gcm,
+}
+
+impl Default for EnvelopeAlg {
+    fn default() -> Self {
+        Self::XChaCha20Poly1305
+    }
+}
+
+/// A message recipient key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecipientKey {
+    /// An ed25519 verkey, converted to X25519 for key agreement
+    Ed25519(EncodedVerKey),
+    /// An X25519 key agreement key, used as-is
+    X25519(EncodedVerKey),
+    /// A P-256 key agreement key, only supported by the DIDComm v2 format
+    P256(EncodedVerKey),
+}
+
+impl RecipientKey {
+    /// The encoded key used as the recipient key identifier
+    pub fn verkey(&self) -> &EncodedVerKey {
+        match self {
+            Self::Ed25519(vk) | Self::X25519(vk) | Self::P256(vk) => vk,
+        }
+    }
+
+    /// The did:key DID URL identifying the recipient in DIDComm v2 headers
+    pub fn kid(&self) -> Result<String, ConversionError> {
+        crate::did::key::verkey_to_kid(self.verkey())
+    }
+
+    /// The key agreement public key of the recipient
+    pub fn key_exchange(&self) -> Result<VerKey, ConversionError> {
+        match self {
+            Self::Ed25519(vk) => vk.key_exchange(),
+            Self::X25519(vk) | Self::P256(vk) => vk.decode(),
+        }
+    }
+}
+
+impl From<EncodedVerKey> for RecipientKey {
+    fn from(vk: EncodedVerKey) -> Self {
+        match vk.alg {
+            KeyType::X25519 => Self::X25519(vk),
+            KeyType::P256 => Self::P256(vk),
+            _ => Self::Ed25519(vk),
+        }
+    }
 }
 
+impl std::fmt::Display for RecipientKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.verkey().fmt(f)
+    }
+}
+
+/// Protected header metadata for an unpacked message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnpackMeta {
+    pub format: PackFormat,
+    pub alg: String,
+    pub enc: String,
+    pub typ: String,
+    pub recipients: Vec<EncodedVerKey>,
+    /// Index of the matched recipient in `recipients`
+    pub recipient_index: usize,
+    pub sender: Option<EncodedVerKey>,
+    /// Unauthenticated sender hint, which must not be trusted as the sender identity
+    pub sender_hint: Option<String>,
+    /// Whether the payload was signed by the sender and the signature verified
+    pub signature_verified: bool,
+    /// The decoded protected header JSON
+    pub protected: String,
+    /// Additional protected header fields, such as `cty`
+    pub extra: HeaderMap,
+}
+
+impl UnpackMeta {
+    /// The recipient verkey used to decrypt the message
+    pub fn recipient(&self) -> &EncodedVerKey {
+        &self.recipients[self.recipient_index]
+    }
+
+    /// Determine whether the message was encrypted by an identified sender
+    pub fn is_authcrypt(&self) -> bool {
+        self.sender.is_some()
+    }
+
+    /// The content type of the message payload, if provided
+    pub fn cty(&self) -> Option<&str> {
+        self.extra.get("cty").and_then(serde_json::Value::as_str)
+    }
+}
+
+/// Policy for selecting among several envelope recipients whose keys are held
+/// by the caller
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecipientPolicy {
+    /// Use the first matching recipient in envelope order
+    First,
+    /// Use the given recipient if its key is held, otherwise the first match
+    Prefer(EncodedVerKey),
+    /// Unpack the message for every matching recipient
+    All,
+}
+
+impl Default for RecipientPolicy {
+    fn default() -> Self {
+        Self::First
+    }
+}
+
+/// Compatibility modes for unpacking envelopes produced by other implementations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compat {
+    /// Accept only envelopes produced according to the specification
+    Strict,
+    /// Accept legacy envelopes produced by libindy, which may use the
+    /// standard base64 alphabet with or without padding
+    IndySdk,
+}
+
+impl Default for Compat {
+    fn default() -> Self {
+        Self::Strict
+    }
+}
+
+/// Size limits enforced when unpacking untrusted envelopes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnpackLimits {
+    /// Maximum length of the serialized envelope in bytes
+    pub max_message: usize,
+    /// Maximum number of envelope recipients
+    pub max_recipients: usize,
+    /// Maximum length of the encoded protected header in bytes
+    pub max_header: usize,
+}
+
+impl Default for UnpackLimits {
+    fn default() -> Self {
+        Self {
+            max_message: 32 * 1024 * 1024,
+            max_recipients: 1024,
+            max_header: 1024 * 1024,
+        }
+    }
+}
+
+/// Options for unpacking untrusted envelopes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UnpackOptions {
+    /// Size limits enforced on the envelope
+    pub limits: UnpackLimits,
+    /// Accept envelopes whose declared `alg` does not match the structure of
+    /// the recipient headers, for compatibility with older implementations
+    pub lenient_alg: bool,
+    /// Compatibility mode for envelopes produced by other implementations
+    pub compat: Compat,
+}
+
+impl From<UnpackLimits> for UnpackOptions {
+    fn from(limits: UnpackLimits) -> Self {
+        Self {
+            limits,
+            ..Default::default()
+        }
+    }
+}
+
+/// A packed envelope in any of the supported formats
+#[derive(Deserialize, Debug, Clone, Eq, PartialEq)]
+#[serde(untagged)]
+pub enum Envelope {
+    DIDCommV2(JWEv2),
+    Legacy(JWE),
+}
+
+/// The result of a recipient key lookup: the index and private key of a
+/// matching recipient, if any
+pub type KeyLookupResult = Result<Option<(usize, PrivateKey)>, ConversionError>;
+
 /// A trait for custom key lookup implementations used by unpack
 pub trait KeyLookup<'f> {
     fn find<'a>(
         self,
         key: &'a Vec<EncodedVerKey>,
-    ) -> std::pin::Pin<Box<dyn Future<Output = Option<(usize, PrivateKey)>> + Send + 'a>>
+    ) -> std::pin::Pin<Box<dyn Future<Output = KeyLookupResult> + Send + 'a>>
     where
         'f: 'a;
 }
 
-type KeyLookupCb<'a> =
-    Box<dyn Fn(&Vec<EncodedVerKey>) -> Option<(usize, PrivateKey)> + Send + Sync + 'a>;
+type KeyLookupCb<'a> = Box<dyn Fn(&Vec<EncodedVerKey>) -> KeyLookupResult + Send + Sync + 'a>;
 
 pub struct KeyLookupFn<'a> {
     cb: KeyLookupCb<'a>,
@@ -56,6 +342,16 @@ pub struct KeyLookupFn<'a> {
 pub fn key_lookup_fn<'a, F>(cb: F) -> KeyLookupFn<'a>
 where
     F: Fn(&Vec<EncodedVerKey>) -> Option<(usize, PrivateKey)> + Send + Sync + 'a,
+{
+    KeyLookupFn {
+        cb: Box::new(move |keys| Ok(cb(keys))) as KeyLookupCb,
+    }
+}
+
+/// Create a `KeyLookup` from a fallible callback function
+pub fn try_key_lookup_fn<'a, F>(cb: F) -> KeyLookupFn<'a>
+where
+    F: Fn(&Vec<EncodedVerKey>) -> KeyLookupResult + Send + Sync + 'a,
 {
     KeyLookupFn {
         cb: Box::new(cb) as KeyLookupCb,
@@ -70,10 +366,117 @@ where
     fn find<'f>(
         self,
         keys: &'f Vec<EncodedVerKey>,
-    ) -> std::pin::Pin<Box<dyn Future<Output = Option<(usize, PrivateKey)>> + Send + 'f>>
+    ) -> std::pin::Pin<Box<dyn Future<Output = KeyLookupResult> + Send + 'f>>
     where
         'l: 'f,
     {
         Box::pin(async move { (&self.cb)(keys) })
     }
 }
+
+/// A boxed future returned by `CekUnwrapper` operations
+pub type UnwrapFuture<'a, T> =
+    std::pin::Pin<Box<dyn Future<Output = Result<T, ConversionError>> + Send + 'a>>;
+
+/// A trait for delegating the recipient side of content key unwrapping
+/// to an external key store, such as an HSM or KMS, so that the recipient
+/// private key never needs to be held in process memory
+pub trait CekUnwrapper: Sync {
+    /// Select the index of a recipient verkey held by this key store
+    fn find_recipient<'a>(
+        &'a self,
+        recipients: &'a [EncodedVerKey],
+    ) -> UnwrapFuture<'a, Option<usize>>;
+
+    /// Open an anonymous (sealed) box addressed to the recipient
+    fn seal_open<'a>(
+        &'a self,
+        recipient: &'a EncodedVerKey,
+        ciphertext: &'a [u8],
+    ) -> UnwrapFuture<'a, Vec<u8>>;
+
+    /// Open an authenticated box sent from `sender` to the recipient
+    fn box_open<'a>(
+        &'a self,
+        recipient: &'a EncodedVerKey,
+        sender: &'a EncodedVerKey,
+        ciphertext: &'a [u8],
+        nonce: &'a [u8],
+    ) -> UnwrapFuture<'a, Vec<u8>>;
+}
+
+/// The software implementation, using a private key held in memory
+impl CekUnwrapper for PrivateKey {
+    fn find_recipient<'a>(
+        &'a self,
+        recipients: &'a [EncodedVerKey],
+    ) -> UnwrapFuture<'a, Option<usize>> {
+        Box::pin(async move {
+            let verkey = self.public_key()?.as_base58()?;
+            Ok(recipients.iter().position(|vk| vk == &verkey))
+        })
+    }
+
+    fn seal_open<'a>(
+        &'a self,
+        _recipient: &'a EncodedVerKey,
+        ciphertext: &'a [u8],
+    ) -> UnwrapFuture<'a, Vec<u8>> {
+        Box::pin(async move {
+            crypto_box_seal_open(
+                self.public_key()?.key_exchange()?.as_ref(),
+                self.key_exchange()?.as_ref(),
+                ciphertext,
+            )
+        })
+    }
+
+    fn box_open<'a>(
+        &'a self,
+        _recipient: &'a EncodedVerKey,
+        sender: &'a EncodedVerKey,
+        ciphertext: &'a [u8],
+        nonce: &'a [u8],
+    ) -> UnwrapFuture<'a, Vec<u8>> {
+        Box::pin(async move {
+            crypto_box_open(
+                self.key_exchange()?.as_ref(),
+                sender.key_exchange()?.as_ref(),
+                ciphertext,
+                nonce,
+            )
+        })
+    }
+}
+
+/// A trait for delegating the sender side of authcrypt key wrapping to an
+/// external key store, such as an HSM or KMS, so that the sender private key
+/// never needs to be exported
+pub trait SenderCrypto: Sync {
+    /// Get the ed25519 verkey of the sender
+    fn public_key<'a>(&'a self) -> UnwrapFuture<'a, VerKey>;
+
+    /// Perform an X25519 key exchange between the sender key and the X25519
+    /// public key of a recipient, returning the raw shared secret
+    fn key_exchange_with<'a>(
+        &'a self,
+        their_vk: &'a VerKey,
+    ) -> UnwrapFuture<'a, Zeroizing<Vec<u8>>>;
+}
+
+/// The software implementation, using a private key held in memory
+impl SenderCrypto for PrivateKey {
+    fn public_key<'a>(&'a self) -> UnwrapFuture<'a, VerKey> {
+        Box::pin(async move { PrivateKey::public_key(self) })
+    }
+
+    fn key_exchange_with<'a>(
+        &'a self,
+        their_vk: &'a VerKey,
+    ) -> UnwrapFuture<'a, Zeroizing<Vec<u8>>> {
+        Box::pin(async move {
+            let shared = crypto_box_key_exchange(self.key_exchange()?.as_ref(), their_vk.as_ref())?;
+            Ok(Zeroizing::new(shared.to_vec()))
+        })
+    }
+}
@@ -0,0 +1,1133 @@
+use aead::generic_array::typenum::{Unsigned, U32};
+use rand::{CryptoRng, RngCore};
+use ursa::hash::sha2::{Digest, Sha256};
+use x25519_dalek::{PublicKey as XPublicKey, StaticSecret};
+use zeroize::{Zeroize, Zeroizing};
+
+use super::alg::{
+    check_header_size, check_recipient_count, decode_b64, find_recipient_key, parse_kid,
+    protected_json_string, recipient_verkeys, select_recipient, take_sender_hint,
+    verify_signed_content,
+};
+use super::cipher::{
+    decrypt_cbc_hs512, decrypt_content, enc_header_value, encrypt_cbc_hs512, encrypt_content,
+    parse_enc_header, A256CBC_HS512_KEY_SIZE, PROTECTED_HEADER_ENC_A256CBC_HS512,
+};
+use super::error::{PackError, UnpackError};
+use super::kw::{aes_key_unwrap, aes_key_wrap, KW_KEY_SIZE};
+use super::types::*;
+use crate::base64;
+use crate::did::key::verkey_to_kid;
+use crate::error::ConversionError;
+use crate::kdf::{concat_kdf, concat_kdf_tagged};
+#[cfg(feature = "p256")]
+use crate::keys::nist_p256;
+use crate::keys::{ArrayKey, EncodedVerKey, KeyType, PrivateKey};
+
+pub const PROTECTED_HEADER_TYP_V2: &'static str = "application/didcomm-encrypted+json";
+pub const PROTECTED_HEADER_ALG_ECDH_ES: &'static str = "ECDH-ES+A256KW";
+pub const PROTECTED_HEADER_ALG_ECDH_1PU: &'static str = "ECDH-1PU+A256KW";
+
+type KeySize = U32;
+
+/// Pack a DIDComm v2 envelope. Recipients and the sender are identified by
+/// did:key DID URLs, while unpacking also accepts base58 verkey identifiers.
+pub fn pack_message_v2<M: AsRef<[u8]>, G: CryptoRng + RngCore>(
+    message: M,
+    receiver_list: Vec<RecipientKey>,
+    sender_key: Option<PrivateKey>,
+    enc_alg: EnvelopeAlg,
+    extra_headers: &HeaderMap,
+    rng: &mut G,
+) -> Result<Vec<u8>, PackError> {
+    let (mut jwe, ciphertext) = pack_jwe_v2(
+        message,
+        receiver_list,
+        sender_key,
+        enc_alg,
+        extra_headers,
+        rng,
+    )?;
+    jwe.ciphertext = base64::encode_urlsafe_nopad(&ciphertext);
+    Ok(serde_json::to_vec(&jwe)?)
+}
+
+/// Produce a v2 envelope with an empty `ciphertext` field, along with the
+/// raw encrypted payload. Authcrypt envelopes always use A256CBC-HS512 for
+/// content encryption, so `enc_alg` only applies to anoncrypt envelopes.
+pub(super) fn pack_jwe_v2<M: AsRef<[u8]>, G: CryptoRng + RngCore>(
+    message: M,
+    receiver_list: Vec<RecipientKey>,
+    sender_key: Option<PrivateKey>,
+    enc_alg: EnvelopeAlg,
+    extra_headers: &HeaderMap,
+    rng: &mut G,
+) -> Result<(JWEv2, Vec<u8>), PackError> {
+    if receiver_list.is_empty() {
+        return Err(PackError::NoRecipients);
+    }
+
+    trace_span!(
+        "prepare_recipients",
+        format = "didcomm_v2",
+        recipients = receiver_list.len(),
+        authcrypt = sender_key.is_some(),
+    );
+    let agreement = KeyAgreementV2::new(&receiver_list, sender_key.as_ref(), enc_alg, rng)?;
+    let (mut encrypted, ciphertext) = agreement.encrypt(message, extra_headers, rng)?;
+    for their_vk in receiver_list.iter() {
+        encrypted.add_recipient(their_vk)?;
+    }
+    Ok((encrypted.finish(), ciphertext))
+}
+
+/// The content encryption algorithm of a v2 envelope
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ContentAlg {
+    Aead(EnvelopeAlg),
+    /// A256CBC-HS512, required for authcrypt envelopes
+    CbcHs512,
+}
+
+impl ContentAlg {
+    fn parse(enc: &str) -> Result<Self, UnpackError> {
+        if enc == PROTECTED_HEADER_ENC_A256CBC_HS512 {
+            Ok(Self::CbcHs512)
+        } else {
+            Ok(Self::Aead(parse_enc_header(enc, PackFormat::DIDCommV2)?))
+        }
+    }
+
+    fn header_value(self) -> &'static str {
+        match self {
+            Self::Aead(enc_alg) => enc_header_value(enc_alg, PackFormat::DIDCommV2),
+            Self::CbcHs512 => PROTECTED_HEADER_ENC_A256CBC_HS512,
+        }
+    }
+
+    fn key_size(self) -> usize {
+        match self {
+            Self::Aead(_) => KeySize::USIZE,
+            Self::CbcHs512 => A256CBC_HS512_KEY_SIZE,
+        }
+    }
+}
+
+/// Check the content encryption algorithm of a v2 envelope, which must be
+/// A256CBC-HS512 for authcrypt envelopes
+pub(super) fn check_enc_v2(alg: &str, enc: &str) -> Result<(), UnpackError> {
+    let content_alg = ContentAlg::parse(enc)?;
+    if alg == PROTECTED_HEADER_ALG_ECDH_1PU && content_alg != ContentAlg::CbcHs512 {
+        return Err(UnpackError::UnsupportedAlg(format!("{}/{}", alg, enc)));
+    }
+    Ok(())
+}
+
+/// The content encryption key and key agreement parameters shared by the
+/// recipients of a v2 envelope
+pub(super) struct KeyAgreementV2 {
+    cek: Zeroizing<Vec<u8>>,
+    content_alg: ContentAlg,
+    epk_secret: AgreementSecret,
+    sender_secret: Option<AgreementSecret>,
+    alg: &'static str,
+    skid: Option<String>,
+    apv: Vec<u8>,
+}
+
+impl KeyAgreementV2 {
+    /// Generate the content encryption key and ephemeral key agreement key
+    pub(super) fn new<G: CryptoRng + RngCore>(
+        receiver_list: &[RecipientKey],
+        sender_key: Option<&PrivateKey>,
+        enc_alg: EnvelopeAlg,
+        rng: &mut G,
+    ) -> Result<Self, PackError> {
+        let curve = recipient_curve(receiver_list)?;
+        let epk_secret = AgreementSecret::random(curve, rng)?;
+
+        let (alg, skid, sender_secret, content_alg) = match sender_key {
+            Some(sk) => {
+                let sender_secret =
+                    AgreementSecret::from_private_key(sk).map_err(PackError::InvalidKey)?;
+                if sender_secret.curve() != curve {
+                    return Err(PackError::InvalidKey(ConversionError::from_msg(
+                        "Sender key does not match the key agreement curve of the recipients",
+                    )));
+                }
+                (
+                    PROTECTED_HEADER_ALG_ECDH_1PU,
+                    Some(
+                        sk.public_key()
+                            .and_then(|pk| pk.as_base58())
+                            .and_then(|pk| verkey_to_kid(&pk))
+                            .map_err(PackError::InvalidKey)?,
+                    ),
+                    Some(sender_secret),
+                    ContentAlg::CbcHs512,
+                )
+            }
+            None => (
+                PROTECTED_HEADER_ALG_ECDH_ES,
+                None,
+                None,
+                ContentAlg::Aead(enc_alg),
+            ),
+        };
+        let mut cek = Zeroizing::new(vec![0u8; content_alg.key_size()]);
+        rng.fill_bytes(&mut cek);
+        let kids = receiver_list
+            .iter()
+            .map(RecipientKey::kid)
+            .collect::<Result<Vec<String>, _>>()
+            .map_err(PackError::InvalidKey)?;
+        let apv = apv_for_kids(&kids);
+
+        Ok(Self {
+            cek,
+            content_alg,
+            epk_secret,
+            sender_secret,
+            alg,
+            skid,
+            apv,
+        })
+    }
+
+    /// Build the protected header and encrypt the message payload. The
+    /// content is encrypted before the key is wrapped for each recipient,
+    /// as ECDH-1PU key wrapping depends on the content authentication tag.
+    pub(super) fn encrypt<M: AsRef<[u8]>, G: CryptoRng + RngCore>(
+        self,
+        message: M,
+        extra_headers: &HeaderMap,
+        rng: &mut G,
+    ) -> Result<(EncryptedV2, Vec<u8>), PackError> {
+        let protected = ProtectedV2 {
+            typ: PROTECTED_HEADER_TYP_V2.to_string(),
+            alg: self.alg.to_string(),
+            enc: self.content_alg.header_value().to_string(),
+            epk: self.epk_secret.public_jwk(),
+            apu: self
+                .skid
+                .as_ref()
+                .map(|skid| base64::encode_urlsafe_nopad(skid)),
+            apv: base64::encode_urlsafe_nopad(&self.apv),
+            skid: self.skid.clone(),
+            extra: extra_headers.clone(),
+        };
+        let protected = serde_json::to_vec(&protected)?;
+        let base64_protected = base64::encode_urlsafe_nopad(&protected);
+
+        // encrypt `message` with the protected header as associated data
+        let content = match self.content_alg {
+            ContentAlg::Aead(enc_alg) => encrypt_content(
+                enc_alg,
+                PackFormat::DIDCommV2,
+                &self.cek,
+                base64_protected.as_bytes(),
+                message.as_ref(),
+                rng,
+            )?,
+            ContentAlg::CbcHs512 => encrypt_cbc_hs512(
+                &self.cek,
+                base64_protected.as_bytes(),
+                message.as_ref(),
+                rng,
+            )?,
+        };
+
+        let jwe = JWEv2 {
+            protected: base64_protected,
+            recipients: vec![],
+            iv: base64::encode_urlsafe_nopad(&content.iv),
+            ciphertext: String::new(),
+            tag: base64::encode_urlsafe_nopad(&content.tag),
+        };
+        Ok((
+            EncryptedV2 {
+                agreement: self,
+                jwe,
+                tag: content.tag,
+            },
+            content.ciphertext,
+        ))
+    }
+}
+
+/// A v2 envelope with encrypted content, to which recipients are added
+pub(super) struct EncryptedV2 {
+    agreement: KeyAgreementV2,
+    jwe: JWEv2,
+    tag: Vec<u8>,
+}
+
+impl EncryptedV2 {
+    /// Wrap the content encryption key for a single recipient
+    pub(super) fn add_recipient(&mut self, their_vk: &RecipientKey) -> Result<(), PackError> {
+        let agreement = &self.agreement;
+        let their_pk = their_vk.key_exchange().map_err(PackError::InvalidKey)?;
+        let mut z = Zeroizing::new(Vec::with_capacity(64));
+        z.extend_from_slice(
+            &agreement
+                .epk_secret
+                .diffie_hellman(&their_pk.key)
+                .map_err(PackError::InvalidKey)?,
+        );
+        let kek = match (agreement.sender_secret.as_ref(), agreement.skid.as_ref()) {
+            (Some(sender_secret), Some(skid)) => {
+                z.extend_from_slice(
+                    &sender_secret
+                        .diffie_hellman(&their_pk.key)
+                        .map_err(PackError::InvalidKey)?,
+                );
+                concat_kdf_tagged(
+                    &z,
+                    agreement.alg,
+                    skid.as_bytes(),
+                    &agreement.apv,
+                    &self.tag,
+                    KW_KEY_SIZE,
+                )
+            }
+            _ => concat_kdf(&z, agreement.alg, &[], &agreement.apv, KW_KEY_SIZE),
+        };
+        let enc_cek = aes_key_wrap(&kek, &agreement.cek).map_err(|_| PackError::KeyWrapFailure)?;
+        self.jwe.recipients.push(RecipientV2 {
+            encrypted_key: base64::encode_urlsafe_nopad(enc_cek),
+            header: HeaderV2 {
+                kid: their_vk.kid().map_err(PackError::InvalidKey)?,
+            },
+        });
+        Ok(())
+    }
+
+    /// The envelope, with an empty `ciphertext` field
+    pub(super) fn finish(self) -> JWEv2 {
+        self.jwe
+    }
+}
+
+/// Unpack a v2 envelope having a detached payload
+pub async fn unpack_jwe_v2_detached<'f>(
+    jwe_struct: &JWEv2,
+    ciphertext: &[u8],
+    lookup: impl KeyLookup<'f>,
+    options: &UnpackOptions,
+) -> Result<(Vec<u8>, UnpackMeta), UnpackError> {
+    if !jwe_struct.ciphertext.is_empty() {
+        return Err(UnpackError::InvalidEnvelope(
+            "Envelope does not have a detached payload".to_string(),
+        ));
+    }
+    if ciphertext.len() > options.limits.max_message {
+        return Err(UnpackError::LimitExceeded("ciphertext"));
+    }
+    unpack_jwe_v2(jwe_struct, ciphertext.to_vec(), lookup, options).await
+}
+
+pub async fn unpack_jwe_v2<'f>(
+    jwe_struct: &JWEv2,
+    ciphertext: Vec<u8>,
+    lookup: impl KeyLookup<'f>,
+    options: &UnpackOptions,
+) -> Result<(Vec<u8>, UnpackMeta), UnpackError> {
+    check_recipient_count(jwe_struct.recipients.len(), &options.limits)?;
+    let recip_vks = recipient_verkeys(jwe_struct.recipients.iter().map(|r| r.header.kid.as_str()))?;
+    unpack_jwe_v2_for_keys(jwe_struct, recip_vks, ciphertext, lookup, options).await
+}
+
+/// Unpack a v2 envelope whose recipient key identifiers have already been
+/// resolved to `recip_vks`, such as DID URLs resolved through a DID Document
+pub(super) async fn unpack_jwe_v2_for_keys<'f>(
+    jwe_struct: &JWEv2,
+    recip_vks: Vec<EncodedVerKey>,
+    ciphertext: Vec<u8>,
+    lookup: impl KeyLookup<'f>,
+    options: &UnpackOptions,
+) -> Result<(Vec<u8>, UnpackMeta), UnpackError> {
+    // decode protected data
+    let (protected, protected_json, sender_vk) = decode_protected_v2(jwe_struct, options)?;
+
+    // extract recipient that matches a key in the wallet
+    let found = find_recipient_key(lookup, &recip_vks).await?;
+
+    decrypt_jwe_v2_for(
+        jwe_struct,
+        protected,
+        protected_json,
+        ciphertext,
+        sender_vk,
+        recip_vks,
+        found,
+    )
+}
+
+pub fn unpack_jwe_v2_sync<F>(
+    jwe_struct: &JWEv2,
+    lookup: F,
+    options: &UnpackOptions,
+) -> Result<(Vec<u8>, UnpackMeta), UnpackError>
+where
+    F: Fn(&Vec<EncodedVerKey>) -> Option<(usize, PrivateKey)>,
+{
+    // decode protected data
+    let (protected, protected_json, sender_vk) = decode_protected_v2(jwe_struct, options)?;
+
+    // extract recipient that matches a known key
+    let recip_vks = recipient_verkeys(jwe_struct.recipients.iter().map(|r| r.header.kid.as_str()))?;
+    let found = lookup(&recip_vks);
+
+    let ciphertext = decode_b64(&jwe_struct.ciphertext, "ciphertext")?;
+    decrypt_jwe_v2_for(
+        jwe_struct,
+        protected,
+        protected_json,
+        ciphertext,
+        sender_vk,
+        recip_vks,
+        found,
+    )
+}
+
+fn decode_protected_v2(
+    jwe_struct: &JWEv2,
+    options: &UnpackOptions,
+) -> Result<(ProtectedV2, String, Option<EncodedVerKey>), UnpackError> {
+    check_header_size(&jwe_struct.protected, &options.limits)?;
+    check_recipient_count(jwe_struct.recipients.len(), &options.limits)?;
+    let protected_decoded = decode_b64(&jwe_struct.protected, "protected")?;
+    let protected: ProtectedV2 = serde_json::from_slice(&protected_decoded)
+        .map_err(|err| UnpackError::InvalidProtectedHeader(err.to_string()))?;
+    check_enc_v2(&protected.alg, &protected.enc)?;
+    let sender_vk = match protected.alg.as_str() {
+        PROTECTED_HEADER_ALG_ECDH_ES => {
+            if protected.skid.is_some() && !options.lenient_alg {
+                return Err(UnpackError::InvalidProtectedHeader(format!(
+                    "Sender key identifier not permitted for alg: {}",
+                    protected.alg
+                )));
+            }
+            None
+        }
+        PROTECTED_HEADER_ALG_ECDH_1PU => {
+            let skid = unwrap_opt_or_return!(
+                protected.skid.as_ref(),
+                Err(UnpackError::InvalidProtectedHeader(
+                    "Missing sender key identifier".to_string()
+                ))
+            );
+            Some(parse_kid(skid).map_err(|_| {
+                UnpackError::InvalidProtectedHeader("Invalid sender key identifier".to_string())
+            })?)
+        }
+        _ => return Err(UnpackError::UnsupportedAlg(protected.alg)),
+    };
+    if !supported_epk(&protected.epk) {
+        return Err(UnpackError::UnsupportedAlg(format!(
+            "{}/{}",
+            protected.epk.kty, protected.epk.crv
+        )));
+    }
+    let protected_json = protected_json_string(protected_decoded)?;
+    Ok((protected, protected_json, sender_vk))
+}
+
+/// Decrypt the envelope for the selected recipient and collect its metadata
+fn decrypt_jwe_v2_for(
+    jwe_struct: &JWEv2,
+    protected: ProtectedV2,
+    protected_json: String,
+    ciphertext: Vec<u8>,
+    sender_vk: Option<EncodedVerKey>,
+    recip_vks: Vec<EncodedVerKey>,
+    found: Option<(usize, PrivateKey)>,
+) -> Result<(Vec<u8>, UnpackMeta), UnpackError> {
+    let (idx, recip_sk) = select_recipient(&recip_vks, found)?;
+
+    let message = decrypt_jwe_v2(
+        jwe_struct,
+        &protected,
+        ciphertext,
+        sender_vk.as_ref(),
+        idx,
+        &recip_sk,
+    )?;
+    let mut extra = protected.extra;
+    let sender_hint = take_sender_hint(&mut extra);
+    let meta = UnpackMeta {
+        format: PackFormat::DIDCommV2,
+        alg: protected.alg,
+        enc: protected.enc,
+        typ: protected.typ,
+        recipients: recip_vks,
+        recipient_index: idx,
+        sender: sender_vk,
+        sender_hint,
+        signature_verified: false,
+        protected: protected_json,
+        extra,
+    };
+    verify_signed_content(message, meta)
+}
+
+fn decrypt_jwe_v2(
+    jwe_struct: &JWEv2,
+    protected: &ProtectedV2,
+    ciphertext: Vec<u8>,
+    sender_vk: Option<&EncodedVerKey>,
+    recip_idx: usize,
+    recip_sk: &PrivateKey,
+) -> Result<Vec<u8>, UnpackError> {
+    let recipient = &jwe_struct.recipients[recip_idx];
+
+    // check the key agreement parameters before deriving any keys
+    let apu = match (protected.skid.as_ref(), protected.apu.as_ref()) {
+        (Some(skid), Some(apu)) => {
+            let apu = decode_b64(apu, "apu")?;
+            if apu != skid.as_bytes() {
+                return Err(UnpackError::InvalidProtectedHeader(
+                    "apu does not match the sender key identifier".to_string(),
+                ));
+            }
+            apu
+        }
+        (Some(_), None) => {
+            return Err(UnpackError::InvalidProtectedHeader(
+                "Missing apu for the sender key identifier".to_string(),
+            ))
+        }
+        (None, Some(apu)) => decode_b64(apu, "apu")?,
+        (None, None) => vec![],
+    };
+    let apv = decode_b64(&protected.apv, "apv")?;
+    let kids = jwe_struct
+        .recipients
+        .iter()
+        .map(|r| r.header.kid.clone())
+        .collect::<Vec<String>>();
+    if apv != apv_for_kids(&kids) {
+        return Err(UnpackError::InvalidProtectedHeader(
+            "apv does not match the recipient key identifiers".to_string(),
+        ));
+    }
+
+    // derive key encryption key and unwrap cek
+    let recip_secret =
+        AgreementSecret::from_private_key(recip_sk).map_err(UnpackError::InvalidKey)?;
+    if recip_secret.curve() != protected.epk.crv {
+        return Err(UnpackError::KeyUnwrapFailure);
+    }
+    let epk = epk_public_bytes(&protected.epk)?;
+    let mut z = Zeroizing::new(Vec::with_capacity(64));
+    z.extend_from_slice(
+        &recip_secret
+            .diffie_hellman(&epk)
+            .map_err(|_| UnpackError::MalformedField("epk".to_string()))?,
+    );
+    if let Some(sender_vk) = sender_vk {
+        let sender_pk = RecipientKey::from(sender_vk.clone())
+            .key_exchange()
+            .map_err(|_| {
+                UnpackError::InvalidProtectedHeader("Invalid sender key identifier".to_string())
+            })?;
+        z.extend_from_slice(&recip_secret.diffie_hellman(&sender_pk.key).map_err(|_| {
+            UnpackError::InvalidProtectedHeader("Invalid sender key identifier".to_string())
+        })?);
+    }
+    let tag = decode_b64(&jwe_struct.tag, "tag")?;
+    let kek = if sender_vk.is_some() {
+        concat_kdf_tagged(&z, protected.alg.as_str(), &apu, &apv, &tag, KW_KEY_SIZE)
+    } else {
+        concat_kdf(&z, protected.alg.as_str(), &apu, &apv, KW_KEY_SIZE)
+    };
+    let cek = Zeroizing::new(
+        aes_key_unwrap(
+            &kek,
+            &decode_b64(&recipient.encrypted_key, "encrypted_key")?,
+        )
+        .map_err(|_| UnpackError::KeyUnwrapFailure)?,
+    );
+
+    // decrypt message
+    let iv = decode_b64(&jwe_struct.iv, "iv")?;
+    let message = match ContentAlg::parse(&protected.enc)? {
+        ContentAlg::Aead(enc_alg) => decrypt_content(
+            enc_alg,
+            PackFormat::DIDCommV2,
+            &cek,
+            jwe_struct.protected.as_bytes(),
+            &iv,
+            ciphertext,
+            &tag,
+        )?,
+        ContentAlg::CbcHs512 => decrypt_cbc_hs512(
+            &cek,
+            jwe_struct.protected.as_bytes(),
+            &iv,
+            &ciphertext,
+            &tag,
+        )?,
+    };
+
+    Ok(message)
+}
+
+const EPK_CRV_X25519: &str = "X25519";
+#[cfg(feature = "p256")]
+const EPK_CRV_P256: &str = "P-256";
+
+/// A key agreement secret on one of the curves supported by v2 envelopes
+enum AgreementSecret {
+    X25519(StaticSecret),
+    #[cfg(feature = "p256")]
+    P256(p256::SecretKey),
+}
+
+impl AgreementSecret {
+    /// Generate a new ephemeral secret on the given curve
+    fn random<G: CryptoRng + RngCore>(curve: &str, rng: &mut G) -> Result<Self, PackError> {
+        match curve {
+            EPK_CRV_X25519 => Ok(Self::X25519(
+                x25519_secret(ArrayKey::<KeySize>::random_with_rng(rng).as_slice())
+                    .map_err(PackError::InvalidKey)?,
+            )),
+            #[cfg(feature = "p256")]
+            EPK_CRV_P256 => Ok(Self::P256(nist_p256::random_secret(rng))),
+            _ => Err(PackError::InvalidKey(ConversionError::from_msg(format!(
+                "Unsupported key agreement curve: {}",
+                curve
+            )))),
+        }
+    }
+
+    /// Load the key agreement secret for a private key, converting Ed25519
+    /// keys to X25519
+    fn from_private_key(sk: &PrivateKey) -> Result<Self, ConversionError> {
+        match sk.alg {
+            KeyType::X25519 => Ok(Self::X25519(x25519_secret(&sk.key)?)),
+            #[cfg(feature = "p256")]
+            KeyType::P256 => Ok(Self::P256(nist_p256::secret_key(&sk.key)?)),
+            _ => Ok(Self::X25519(x25519_secret(sk.key_exchange()?)?)),
+        }
+    }
+
+    /// The JWK curve name of the secret
+    fn curve(&self) -> &'static str {
+        match self {
+            Self::X25519(_) => EPK_CRV_X25519,
+            #[cfg(feature = "p256")]
+            Self::P256(_) => EPK_CRV_P256,
+        }
+    }
+
+    /// Perform a key agreement with a public key on the same curve
+    fn diffie_hellman(&self, their_pk: &[u8]) -> Result<Zeroizing<Vec<u8>>, ConversionError> {
+        match self {
+            Self::X25519(secret) => {
+                let their_pk = XPublicKey::from(x25519_bytes(their_pk)?);
+                Ok(Zeroizing::new(
+                    secret.diffie_hellman(&their_pk).as_bytes().to_vec(),
+                ))
+            }
+            #[cfg(feature = "p256")]
+            Self::P256(secret) => Ok(nist_p256::key_agreement(
+                secret,
+                &nist_p256::public_key(their_pk)?,
+            )),
+        }
+    }
+
+    /// The public key of the secret, in JWK format
+    fn public_jwk(&self) -> EphemeralKey {
+        match self {
+            Self::X25519(secret) => EphemeralKey {
+                kty: "OKP".to_string(),
+                crv: EPK_CRV_X25519.to_string(),
+                x: base64::encode_urlsafe_nopad(XPublicKey::from(secret).as_bytes()),
+                y: None,
+            },
+            #[cfg(feature = "p256")]
+            Self::P256(secret) => {
+                let (x, y) = nist_p256::public_key_coordinates(&secret.public_key());
+                EphemeralKey {
+                    kty: "EC".to_string(),
+                    crv: EPK_CRV_P256.to_string(),
+                    x: base64::encode_urlsafe_nopad(x),
+                    y: Some(base64::encode_urlsafe_nopad(y)),
+                }
+            }
+        }
+    }
+}
+
+/// Select the key agreement curve for a set of recipients, which must not
+/// mix P-256 keys with Ed25519 or X25519 keys
+fn recipient_curve(receiver_list: &[RecipientKey]) -> Result<&'static str, PackError> {
+    let p256_count = receiver_list
+        .iter()
+        .filter(|recip| matches!(recip, RecipientKey::P256(_)))
+        .count();
+    if p256_count == 0 {
+        Ok(EPK_CRV_X25519)
+    } else if p256_count == receiver_list.len() {
+        #[cfg(feature = "p256")]
+        return Ok(EPK_CRV_P256);
+        #[cfg(not(feature = "p256"))]
+        return Err(PackError::InvalidKey(ConversionError::from_msg(
+            "P-256 support is not enabled",
+        )));
+    } else {
+        Err(PackError::InvalidKey(ConversionError::from_msg(
+            "Recipient keys must share a single key agreement curve",
+        )))
+    }
+}
+
+/// Check that the ephemeral key is of a supported type
+fn supported_epk(epk: &EphemeralKey) -> bool {
+    match (epk.kty.as_str(), epk.crv.as_str()) {
+        ("OKP", EPK_CRV_X25519) => epk.y.is_none(),
+        #[cfg(feature = "p256")]
+        ("EC", EPK_CRV_P256) => epk.y.is_some(),
+        _ => false,
+    }
+}
+
+/// Decode the ephemeral public key: raw bytes for X25519, or an uncompressed
+/// SEC1 point for P-256
+fn epk_public_bytes(epk: &EphemeralKey) -> Result<Vec<u8>, UnpackError> {
+    let x = decode_b64(&epk.x, "epk")?;
+    match epk.y.as_ref() {
+        Some(y) => {
+            let y = decode_b64(y, "epk")?;
+            let mut point = Vec::with_capacity(1 + x.len() + y.len());
+            point.push(0x04);
+            point.extend_from_slice(&x);
+            point.extend_from_slice(&y);
+            Ok(point)
+        }
+        None => Ok(x),
+    }
+}
+
+fn x25519_bytes<K: AsRef<[u8]>>(key: K) -> Result<[u8; 32], ConversionError> {
+    let key = key.as_ref();
+    if key.len() != 32 {
+        return Err("Invalid length for X25519 key".into());
+    }
+    let mut result = [0u8; 32];
+    result.copy_from_slice(key);
+    Ok(result)
+}
+
+fn x25519_secret<K: AsRef<[u8]>>(key: K) -> Result<StaticSecret, ConversionError> {
+    let mut bytes = x25519_bytes(key)?;
+    let secret = StaticSecret::from(bytes);
+    bytes.zeroize();
+    Ok(secret)
+}
+
+/// The `apv` value is derived from the sorted list of recipient key identifiers
+fn apv_for_kids(kids: &[String]) -> Vec<u8> {
+    let mut kids = kids.iter().map(String::as_str).collect::<Vec<&str>>();
+    kids.sort_unstable();
+    Sha256::digest(kids.join(".").as_bytes()).to_vec()
+}
+
+/// Pack an anoncrypt envelope identifying the recipients by `kids` rather
+/// than did:key DID URLs, as other implementations may do
+#[cfg(test)]
+pub(super) fn pack_message_v2_with_kids(
+    message: &[u8],
+    receiver_list: Vec<RecipientKey>,
+    kids: &[String],
+) -> Vec<u8> {
+    use rand::rngs::OsRng;
+
+    let mut agreement =
+        KeyAgreementV2::new(&receiver_list, None, EnvelopeAlg::default(), &mut OsRng).unwrap();
+    agreement.apv = apv_for_kids(kids);
+    let (mut encrypted, ciphertext) = agreement
+        .encrypt(message, &HeaderMap::new(), &mut OsRng)
+        .unwrap();
+    for their_vk in receiver_list.iter() {
+        encrypted.add_recipient(their_vk).unwrap();
+    }
+    let mut jwe = encrypted.finish();
+    for (recipient, kid) in jwe.recipients.iter_mut().zip(kids) {
+        recipient.header.kid = kid.clone();
+    }
+    jwe.ciphertext = base64::encode_urlsafe_nopad(&ciphertext);
+    serde_json::to_vec(&jwe).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use async_global_executor::block_on;
+    use rand::rngs::OsRng;
+
+    use super::super::cipher::PROTECTED_HEADER_ENC_XC20P;
+    use super::*;
+    use crate::keys::test_fixtures::{TEST3, TEST4};
+    use crate::keys::KidFormat;
+
+    #[test]
+    fn test_anon_pack_v2_round_trip() {
+        let sk1 = TEST3.private_key();
+        let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
+
+        let input_msg = b"hello there";
+        let packed = pack_message_v2(
+            &input_msg,
+            vec![pk1.clone().into()],
+            None,
+            EnvelopeAlg::default(),
+            &HeaderMap::new(),
+            &mut OsRng,
+        )
+        .unwrap();
+        let jwe: JWEv2 = serde_json::from_slice(&packed).unwrap();
+
+        let lookup = |find_pks: &Vec<EncodedVerKey>| {
+            find_pks
+                .iter()
+                .position(|pk| pk == &pk1)
+                .map(|idx| (idx, sk1.clone()))
+        };
+        let lookup_fn = key_lookup_fn(lookup);
+        let ciphertext = base64::decode_urlsafe(&jwe.ciphertext).unwrap();
+        let (msg, meta) = block_on(unpack_jwe_v2(
+            &jwe,
+            ciphertext,
+            &lookup_fn,
+            &UnpackOptions::default(),
+        ))
+        .unwrap();
+        assert_eq!(msg, input_msg);
+        assert_eq!(meta.recipient(), &pk1);
+        assert_eq!(meta.sender, None);
+        assert_eq!(meta.alg, PROTECTED_HEADER_ALG_ECDH_ES);
+    }
+
+    #[test]
+    fn test_unpack_v2_base58_kid() {
+        let sk1 = TEST3.private_key();
+        let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
+
+        let packed =
+            pack_message_v2_with_kids(b"hello there", vec![pk1.clone().into()], &[pk1.to_string()]);
+        let jwe: JWEv2 = serde_json::from_slice(&packed).unwrap();
+        assert_eq!(jwe.recipients[0].header.kid, pk1.to_string());
+
+        let lookup = |find_pks: &Vec<EncodedVerKey>| {
+            find_pks
+                .iter()
+                .position(|pk| pk == &pk1)
+                .map(|idx| (idx, sk1.clone()))
+        };
+        let lookup_fn = key_lookup_fn(lookup);
+        let ciphertext = base64::decode_urlsafe(&jwe.ciphertext).unwrap();
+        let (msg, meta) = block_on(unpack_jwe_v2(
+            &jwe,
+            ciphertext,
+            &lookup_fn,
+            &UnpackOptions::default(),
+        ))
+        .unwrap();
+        assert_eq!(msg, b"hello there");
+        assert_eq!(meta.recipient(), &pk1);
+    }
+
+    #[test]
+    fn test_unpack_v2_checks_apv() {
+        let sk1 = TEST3.private_key();
+        let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
+        let pk2 = TEST4.encoded_verkey();
+        let lookup = |find_pks: &Vec<EncodedVerKey>| {
+            find_pks
+                .iter()
+                .position(|pk| pk == &pk1)
+                .map(|idx| (idx, sk1.clone()))
+        };
+
+        let packed = pack_message_v2(
+            b"hello there",
+            vec![pk1.clone().into(), pk2.into()],
+            None,
+            EnvelopeAlg::default(),
+            &HeaderMap::new(),
+            &mut OsRng,
+        )
+        .unwrap();
+        let jwe: JWEv2 = serde_json::from_slice(&packed).unwrap();
+        assert!(unpack_jwe_v2_sync(&jwe, lookup, &UnpackOptions::default()).is_ok());
+
+        // a recipient identifier rewritten after packing
+        let mut rewritten = jwe.clone();
+        rewritten.recipients[0].header.kid = pk1.to_string();
+        assert!(matches!(
+            unpack_jwe_v2_sync(&rewritten, lookup, &UnpackOptions::default()),
+            Err(UnpackError::InvalidProtectedHeader(_))
+        ));
+
+        // a recipient removed from the envelope
+        let mut removed = jwe.clone();
+        removed.recipients.pop();
+        assert!(matches!(
+            unpack_jwe_v2_sync(&removed, lookup, &UnpackOptions::default()),
+            Err(UnpackError::InvalidProtectedHeader(_))
+        ));
+    }
+
+    #[test]
+    fn test_unpack_v2_checks_apu() {
+        let sk1 = TEST3.private_key();
+        let sk2 = TEST4.private_key();
+        let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
+        let lookup = |find_pks: &Vec<EncodedVerKey>| {
+            find_pks
+                .iter()
+                .position(|pk| pk == &pk2)
+                .map(|idx| (idx, sk2.clone()))
+        };
+
+        let packed = pack_message_v2(
+            b"hello there",
+            vec![pk2.clone().into()],
+            Some(sk1),
+            EnvelopeAlg::default(),
+            &HeaderMap::new(),
+            &mut OsRng,
+        )
+        .unwrap();
+        let jwe: JWEv2 = serde_json::from_slice(&packed).unwrap();
+        let protected: serde_json::Value =
+            serde_json::from_slice(&base64::decode_urlsafe(&jwe.protected).unwrap()).unwrap();
+
+        let with_protected = |protected: &serde_json::Value| {
+            let mut jwe = jwe.clone();
+            jwe.protected = base64::encode_urlsafe_nopad(serde_json::to_vec(protected).unwrap());
+            jwe
+        };
+
+        // an apu naming a different sender
+        let mut wrong_apu = protected.clone();
+        wrong_apu["apu"] = base64::encode_urlsafe_nopad(
+            TEST4.encoded_verkey().kid(Some(KidFormat::DidKey)).unwrap(),
+        )
+        .into();
+        assert!(matches!(
+            unpack_jwe_v2_sync(
+                &with_protected(&wrong_apu),
+                lookup,
+                &UnpackOptions::default()
+            ),
+            Err(UnpackError::InvalidProtectedHeader(_))
+        ));
+
+        // a missing apu
+        let mut missing_apu = protected.clone();
+        missing_apu.as_object_mut().unwrap().remove("apu");
+        assert!(matches!(
+            unpack_jwe_v2_sync(
+                &with_protected(&missing_apu),
+                lookup,
+                &UnpackOptions::default()
+            ),
+            Err(UnpackError::InvalidProtectedHeader(_))
+        ));
+    }
+
+    #[test]
+    fn test_auth_pack_v2_round_trip() {
+        let sk1 = TEST3.private_key();
+        let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
+        let sk2 = TEST4.private_key();
+        let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
+
+        let input_msg = b"hello there";
+        let packed = pack_message_v2(
+            &input_msg,
+            vec![pk2.clone().into()],
+            Some(sk1.clone()),
+            EnvelopeAlg::default(),
+            &HeaderMap::new(),
+            &mut OsRng,
+        )
+        .unwrap();
+        let jwe: JWEv2 = serde_json::from_slice(&packed).unwrap();
+        let protected: ProtectedV2 =
+            serde_json::from_slice(&base64::decode_urlsafe(&jwe.protected).unwrap()).unwrap();
+        assert_eq!(protected.alg, PROTECTED_HEADER_ALG_ECDH_1PU);
+        assert_eq!(protected.enc, PROTECTED_HEADER_ENC_A256CBC_HS512);
+        assert_eq!(
+            protected.skid,
+            Some(pk1.kid(Some(KidFormat::DidKey)).unwrap())
+        );
+        // JOSE fields use base64url without padding
+        for field in &[
+            &jwe.protected,
+            &jwe.iv,
+            &jwe.ciphertext,
+            &jwe.tag,
+            &jwe.recipients[0].encrypted_key,
+            &protected.epk.x,
+            &protected.apv,
+        ] {
+            assert!(!field.contains('='));
+        }
+
+        let lookup = |find_pks: &Vec<EncodedVerKey>| {
+            find_pks
+                .iter()
+                .position(|pk| pk == &pk2)
+                .map(|idx| (idx, sk2.clone()))
+        };
+        let lookup_fn = key_lookup_fn(lookup);
+        let ciphertext = base64::decode_urlsafe(&jwe.ciphertext).unwrap();
+        let (msg, meta) = block_on(unpack_jwe_v2(
+            &jwe,
+            ciphertext,
+            &lookup_fn,
+            &UnpackOptions::default(),
+        ))
+        .unwrap();
+        assert_eq!(msg, input_msg);
+        assert_eq!(meta.recipient(), &pk2);
+        assert_eq!(meta.sender, Some(pk1));
+    }
+
+    #[test]
+    fn test_pack_v2_x25519_recipient() {
+        let sk1 = TEST3.private_key();
+        let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
+        let x_sk1 = sk1.key_exchange().unwrap();
+        let x_pk1 = pk1.key_exchange_encoded().unwrap();
+        assert_eq!(x_pk1.alg, KeyType::X25519);
+
+        let input_msg = b"hello there";
+        let packed = pack_message_v2(
+            &input_msg,
+            vec![x_pk1.clone().into()],
+            None,
+            EnvelopeAlg::default(),
+            &HeaderMap::new(),
+            &mut OsRng,
+        )
+        .unwrap();
+        let jwe: JWEv2 = serde_json::from_slice(&packed).unwrap();
+        assert_eq!(
+            jwe.recipients[0].header.kid,
+            x_pk1.kid(Some(KidFormat::DidKey)).unwrap()
+        );
+
+        let lookup = |find_pks: &Vec<EncodedVerKey>| {
+            find_pks
+                .iter()
+                .position(|pk| pk == &x_pk1)
+                .map(|idx| (idx, x_sk1.clone()))
+        };
+        let lookup_fn = key_lookup_fn(lookup);
+        let ciphertext = base64::decode_urlsafe(&jwe.ciphertext).unwrap();
+        let (msg, meta) = block_on(unpack_jwe_v2(
+            &jwe,
+            ciphertext,
+            &lookup_fn,
+            &UnpackOptions::default(),
+        ))
+        .unwrap();
+        assert_eq!(msg, input_msg);
+        assert_eq!(meta.recipient(), &x_pk1);
+    }
+
+    #[cfg(feature = "p256")]
+    #[test]
+    fn test_auth_pack_v2_p256() {
+        let sk1 = PrivateKey::generate(Some(KeyType::P256)).unwrap();
+        let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
+        let sk2 = PrivateKey::generate(Some(KeyType::P256)).unwrap();
+        let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
+
+        let input_msg = b"hello there";
+        let packed = pack_message_v2(
+            &input_msg,
+            vec![pk2.clone().into()],
+            Some(sk1.clone()),
+            EnvelopeAlg::default(),
+            &HeaderMap::new(),
+            &mut OsRng,
+        )
+        .unwrap();
+        let jwe: JWEv2 = serde_json::from_slice(&packed).unwrap();
+        let protected: ProtectedV2 =
+            serde_json::from_slice(&base64::decode_urlsafe(&jwe.protected).unwrap()).unwrap();
+        assert_eq!(protected.epk.kty, "EC");
+        assert_eq!(protected.epk.crv, EPK_CRV_P256);
+        assert!(protected.epk.y.is_some());
+
+        let lookup = |find_pks: &Vec<EncodedVerKey>| {
+            find_pks
+                .iter()
+                .position(|pk| pk == &pk2)
+                .map(|idx| (idx, sk2.clone()))
+        };
+        let lookup_fn = key_lookup_fn(lookup);
+        let ciphertext = base64::decode_urlsafe(&jwe.ciphertext).unwrap();
+        let (msg, meta) = block_on(unpack_jwe_v2(
+            &jwe,
+            ciphertext,
+            &lookup_fn,
+            &UnpackOptions::default(),
+        ))
+        .unwrap();
+        assert_eq!(msg, input_msg);
+        assert_eq!(meta.sender, Some(pk1));
+
+        // mixing curves between recipients is rejected
+        let ed_sk = TEST3.private_key();
+        let ed_pk = ed_sk.public_key().unwrap().as_base58().unwrap();
+        assert!(pack_message_v2(
+            &input_msg,
+            vec![pk2.into(), ed_pk.into()],
+            None,
+            EnvelopeAlg::default(),
+            &HeaderMap::new(),
+            &mut OsRng,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_unpack_v2_authcrypt_requires_cbc_hs512() {
+        let sk1 = TEST3.private_key();
+        let sk2 = TEST4.private_key();
+        let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
+
+        let packed = pack_message_v2(
+            b"hello there",
+            vec![pk2.clone().into()],
+            Some(sk1),
+            EnvelopeAlg::XChaCha20Poly1305,
+            &HeaderMap::new(),
+            &mut OsRng,
+        )
+        .unwrap();
+        let mut jwe: JWEv2 = serde_json::from_slice(&packed).unwrap();
+        let mut protected: serde_json::Value =
+            serde_json::from_slice(&base64::decode_urlsafe(&jwe.protected).unwrap()).unwrap();
+        protected["enc"] = PROTECTED_HEADER_ENC_XC20P.into();
+        jwe.protected = base64::encode_urlsafe_nopad(serde_json::to_vec(&protected).unwrap());
+
+        let lookup = |find_pks: &Vec<EncodedVerKey>| {
+            find_pks
+                .iter()
+                .position(|pk| pk == &pk2)
+                .map(|idx| (idx, sk2.clone()))
+        };
+        assert!(matches!(
+            unpack_jwe_v2_sync(&jwe, lookup, &UnpackOptions::default()),
+            Err(UnpackError::UnsupportedAlg(_))
+        ));
+    }
+}
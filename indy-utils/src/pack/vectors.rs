@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+
+use serde_json::{Map, Value};
+
+use super::alg::{
+    parse_kid, unpack_message_sync, PROTECTED_HEADER_ALG_ANON, PROTECTED_HEADER_ALG_AUTH,
+    PROTECTED_HEADER_TYP,
+};
+use super::cipher::parse_enc_header;
+use super::error::UnpackError;
+use super::types::PackFormat;
+use super::v2::{check_enc_v2, PROTECTED_HEADER_ALG_ECDH_1PU, PROTECTED_HEADER_ALG_ECDH_ES};
+use crate::base58;
+use crate::base64;
+use crate::error::ConversionError;
+use crate::keys::{EncodedVerKey, KeyType, PrivateKey};
+
+/// A pack test vector, in the JSON format used by the indy-sdk and Aries
+/// interop suites:
+/// `{"name", "message", "envelope", "recipient_secret", "sender_verkey"}`,
+/// where `recipient_secret` is the base58-encoded ed25519 secret key.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct PackVector {
+    #[serde(default)]
+    pub name: String,
+    pub message: String,
+    pub envelope: Value,
+    pub recipient_secret: String,
+    #[serde(default)]
+    pub sender_verkey: Option<String>,
+}
+
+impl PackVector {
+    /// Check the envelope format and unpack it, comparing the results
+    pub fn verify(&self) -> Result<(), ConversionError> {
+        let envelope = serde_json::to_vec(&self.envelope)
+            .map_err(|err| ConversionError::from_msg_err("Invalid envelope", err))?;
+        verify_envelope_format(&envelope)?;
+
+        let secret = PrivateKey::new(
+            base58::decode(&self.recipient_secret)?,
+            Some(KeyType::ED25519),
+        );
+        let mut keys = HashMap::new();
+        keys.insert(secret.public_key()?.as_base58()?, secret);
+        let (message, _, sender) = unpack_message_sync(&envelope, &keys)?;
+
+        if message != self.message.as_bytes() {
+            return Err(ConversionError::from_msg(format!(
+                "Message mismatch for test vector: {}",
+                self.name
+            )));
+        }
+        let sender = sender.map(|vk| vk.to_string());
+        if sender != self.sender_verkey {
+            return Err(ConversionError::from_msg(format!(
+                "Sender mismatch for test vector: {}",
+                self.name
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Load pack test vectors from a JSON document containing a single vector
+/// or a list of vectors
+pub fn load_vectors(json: &[u8]) -> Result<Vec<PackVector>, ConversionError> {
+    let value: Value = serde_json::from_slice(json)
+        .map_err(|err| ConversionError::from_msg_err("Invalid test vector file", err))?;
+    let result = if value.is_array() {
+        serde_json::from_value(value)
+    } else {
+        serde_json::from_value(value).map(|vector| vec![vector])
+    };
+    result.map_err(|err| ConversionError::from_msg_err("Invalid test vector", err))
+}
+
+/// Validate an envelope against the JWM/1.0 or DIDComm v2 grammar without
+/// decrypting it, reporting the first malformed field
+pub fn verify_envelope_format(message: &[u8]) -> Result<PackFormat, UnpackError> {
+    let envelope: Value = serde_json::from_slice(message)
+        .map_err(|err| UnpackError::InvalidEnvelope(err.to_string()))?;
+    let envelope = object_field(&envelope, "envelope")?;
+    for field in &["iv", "ciphertext", "tag"] {
+        b64_field(envelope, field, field)?;
+    }
+    let protected = b64_field(envelope, "protected", "protected")?;
+    let protected: Value = serde_json::from_slice(&protected)
+        .map_err(|_| UnpackError::MalformedField("protected".to_string()))?;
+    let protected = object_field(&protected, "protected")?;
+
+    if envelope.contains_key("recipients") {
+        verify_v2(envelope, protected)?;
+        Ok(PackFormat::DIDCommV2)
+    } else {
+        verify_legacy(protected)?;
+        Ok(PackFormat::Legacy)
+    }
+}
+
+fn verify_legacy(protected: &Map<String, Value>) -> Result<(), UnpackError> {
+    if str_field(protected, "typ", "protected.typ")? != PROTECTED_HEADER_TYP {
+        return Err(UnpackError::MalformedField("protected.typ".to_string()));
+    }
+    parse_enc_header(
+        str_field(protected, "enc", "protected.enc")?,
+        PackFormat::Legacy,
+    )?;
+    let authcrypt = match str_field(protected, "alg", "protected.alg")? {
+        PROTECTED_HEADER_ALG_AUTH => true,
+        PROTECTED_HEADER_ALG_ANON => false,
+        alg => return Err(UnpackError::UnsupportedAlg(alg.to_string())),
+    };
+    let recipients = array_field(protected, "recipients", "protected.recipients")?;
+    for (idx, recipient) in recipients.iter().enumerate() {
+        let path = format!("protected.recipients[{}]", idx);
+        let header = recipient_header(recipient, &path)?;
+        if authcrypt {
+            b64_field(header, "sender", &format!("{}.header.sender", path))?;
+            b64_field(header, "iv", &format!("{}.header.iv", path))?;
+        }
+    }
+    Ok(())
+}
+
+fn verify_v2(
+    envelope: &Map<String, Value>,
+    protected: &Map<String, Value>,
+) -> Result<(), UnpackError> {
+    str_field(protected, "typ", "protected.typ")?;
+    check_enc_v2(
+        str_field(protected, "alg", "protected.alg")?,
+        str_field(protected, "enc", "protected.enc")?,
+    )?;
+    match str_field(protected, "alg", "protected.alg")? {
+        PROTECTED_HEADER_ALG_ECDH_ES => (),
+        PROTECTED_HEADER_ALG_ECDH_1PU => {
+            str_field(protected, "skid", "protected.skid")?;
+        }
+        alg => return Err(UnpackError::UnsupportedAlg(alg.to_string())),
+    }
+    b64_field(protected, "apv", "protected.apv")?;
+    let epk = object_field(
+        protected
+            .get("epk")
+            .ok_or_else(|| UnpackError::MalformedField("protected.epk".to_string()))?,
+        "protected.epk",
+    )?;
+    let has_y = match (
+        str_field(epk, "kty", "protected.epk.kty")?,
+        str_field(epk, "crv", "protected.epk.crv")?,
+    ) {
+        ("OKP", "X25519") => false,
+        ("EC", "P-256") => true,
+        ("OKP", _) | ("EC", _) => {
+            return Err(UnpackError::MalformedField("protected.epk.crv".to_string()))
+        }
+        _ => return Err(UnpackError::MalformedField("protected.epk.kty".to_string())),
+    };
+    if b64_field(epk, "x", "protected.epk.x")?.len() != 32 {
+        return Err(UnpackError::MalformedField("protected.epk.x".to_string()));
+    }
+    if has_y && b64_field(epk, "y", "protected.epk.y")?.len() != 32 {
+        return Err(UnpackError::MalformedField("protected.epk.y".to_string()));
+    }
+
+    let recipients = array_field(envelope, "recipients", "recipients")?;
+    for (idx, recipient) in recipients.iter().enumerate() {
+        let path = format!("recipients[{}]", idx);
+        recipient_header(recipient, &path)?;
+    }
+    Ok(())
+}
+
+/// Check the common fields of a recipient entry, returning its header
+fn recipient_header<'v>(
+    recipient: &'v Value,
+    path: &str,
+) -> Result<&'v Map<String, Value>, UnpackError> {
+    let recipient = object_field(recipient, path)?;
+    b64_field(
+        recipient,
+        "encrypted_key",
+        &format!("{}.encrypted_key", path),
+    )?;
+    let header_path = format!("{}.header", path);
+    let header = object_field(
+        recipient
+            .get("header")
+            .ok_or_else(|| UnpackError::MalformedField(header_path.clone()))?,
+        &header_path,
+    )?;
+    kid_field(header, &format!("{}.kid", header_path))?;
+    Ok(header)
+}
+
+fn object_field<'v>(value: &'v Value, path: &str) -> Result<&'v Map<String, Value>, UnpackError> {
+    value
+        .as_object()
+        .ok_or_else(|| UnpackError::MalformedField(path.to_string()))
+}
+
+fn str_field<'v>(
+    value: &'v Map<String, Value>,
+    name: &str,
+    path: &str,
+) -> Result<&'v str, UnpackError> {
+    value
+        .get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| UnpackError::MalformedField(path.to_string()))
+}
+
+fn array_field<'v>(
+    value: &'v Map<String, Value>,
+    name: &str,
+    path: &str,
+) -> Result<&'v Vec<Value>, UnpackError> {
+    match value.get(name).and_then(Value::as_array) {
+        Some(items) if !items.is_empty() => Ok(items),
+        _ => Err(UnpackError::MalformedField(path.to_string())),
+    }
+}
+
+fn b64_field(value: &Map<String, Value>, name: &str, path: &str) -> Result<Vec<u8>, UnpackError> {
+    base64::decode_urlsafe(str_field(value, name, path)?)
+        .map_err(|_| UnpackError::MalformedField(path.to_string()))
+}
+
+fn kid_field(value: &Map<String, Value>, path: &str) -> Result<EncodedVerKey, UnpackError> {
+    let kid = str_field(value, "kid", path)?;
+    match parse_kid(kid) {
+        Ok(vk)
+            if vk
+                .key_bytes()
+                .map(|k| k.len() == kid_key_size(&vk))
+                .unwrap_or(false) =>
+        {
+            Ok(vk)
+        }
+        _ => Err(UnpackError::MalformedField(path.to_string())),
+    }
+}
+
+/// The expected size of a recipient key: compressed P-256 keys are 33 bytes
+fn kid_key_size(vk: &EncodedVerKey) -> usize {
+    if vk.alg == KeyType::P256 {
+        33
+    } else {
+        32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{pack_message, PackBuilder};
+    use super::*;
+    use crate::keys::test_fixtures::{TEST3, TEST4};
+
+    #[test]
+    fn test_vector_round_trip() {
+        let sk1 = TEST3.private_key();
+        let pk1 = sk1.public_key().unwrap().as_base58().unwrap();
+        let sk2 = TEST4.private_key();
+        let pk2 = sk2.public_key().unwrap().as_base58().unwrap();
+
+        let mut vectors = vec![];
+        for format in &[PackFormat::Legacy, PackFormat::DIDCommV2] {
+            let packed = PackBuilder::new()
+                .recipient(pk2.clone())
+                .sender(sk1.clone())
+                .format(*format)
+                .build()
+                .unwrap()
+                .pack(b"hello there")
+                .unwrap();
+            assert_eq!(verify_envelope_format(&packed).unwrap(), *format);
+            vectors.push(serde_json::json!({
+                "name": format!("{:?}", format),
+                "message": "hello there",
+                "envelope": serde_json::from_slice::<Value>(&packed).unwrap(),
+                "recipient_secret": base58::encode(&sk2.key),
+                "sender_verkey": pk1.to_string(),
+            }));
+        }
+
+        let vectors = load_vectors(&serde_json::to_vec(&vectors).unwrap()).unwrap();
+        assert_eq!(vectors.len(), 2);
+        for vector in vectors {
+            vector.verify().unwrap();
+        }
+    }
+
+    #[test]
+    fn test_verify_envelope_malformed() {
+        let sk = TEST4.private_key();
+        let pk = sk.public_key().unwrap().as_base58().unwrap();
+        let packed = pack_message(b"hello there", vec![pk], None).unwrap();
+
+        let mut envelope: Value = serde_json::from_slice(&packed).unwrap();
+        envelope["iv"] = serde_json::json!("not base64!");
+        match verify_envelope_format(&serde_json::to_vec(&envelope).unwrap()) {
+            Err(UnpackError::MalformedField(field)) => assert_eq!(field, "iv"),
+            other => panic!("Unexpected result: {:?}", other),
+        }
+    }
+}
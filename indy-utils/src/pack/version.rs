@@ -0,0 +1,153 @@
+use serde_json::{Map, Value};
+
+use super::alg::PROTECTED_HEADER_TYP;
+use super::error::UnpackError;
+use super::types::UnpackLimits;
+
+/// The envelope versions which can be produced and consumed by this crate
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum EnvelopeVersion {
+    /// Legacy JWM/1.0 envelope (Aries RFC 0019)
+    JwmV1,
+    /// DIDComm v2 JWE in the JSON serialization
+    DidcommV2,
+    /// Single-recipient JWE in the compact serialization
+    CompactJwe,
+    /// Unencrypted JWM/1.0 message
+    PlaintextJwm,
+}
+
+impl EnvelopeVersion {
+    /// Classify a received message by its envelope structure, without
+    /// decrypting or fully validating it
+    pub fn detect(message: &[u8]) -> Result<Self, UnpackError> {
+        if message.len() > UnpackLimits::default().max_message {
+            return Err(UnpackError::LimitExceeded("message"));
+        }
+        if message.iter().find(|b| !b.is_ascii_whitespace()) != Some(&b'{') {
+            let message = std::str::from_utf8(message).map_err(|_| {
+                UnpackError::InvalidEnvelope("Invalid UTF-8 in envelope".to_string())
+            })?;
+            return if message.trim().split('.').count() == 5 {
+                Ok(Self::CompactJwe)
+            } else {
+                Err(UnpackError::InvalidEnvelope(
+                    "Unrecognized envelope format".to_string(),
+                ))
+            };
+        }
+
+        let message: Map<String, Value> = serde_json::from_slice(message)
+            .map_err(|err| UnpackError::InvalidEnvelope(err.to_string()))?;
+        let is_jwe = ["protected", "iv", "ciphertext", "tag"]
+            .iter()
+            .all(|field| message.get(*field).map(Value::is_string).unwrap_or(false));
+        if is_jwe {
+            if message
+                .get("recipients")
+                .map(Value::is_array)
+                .unwrap_or(false)
+            {
+                Ok(Self::DidcommV2)
+            } else {
+                Ok(Self::JwmV1)
+            }
+        } else if message.get("typ").and_then(Value::as_str) == Some(PROTECTED_HEADER_TYP) {
+            Ok(Self::PlaintextJwm)
+        } else {
+            Err(UnpackError::InvalidEnvelope(
+                "Unrecognized envelope format".to_string(),
+            ))
+        }
+    }
+}
+
+/// The envelope versions supported by an agent, in order of preference,
+/// which may be exchanged to negotiate the version used for messaging
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct EnvelopeCapabilities {
+    pub versions: Vec<EnvelopeVersion>,
+}
+
+impl EnvelopeCapabilities {
+    /// The envelope versions supported by this build
+    pub fn supported() -> Self {
+        Self {
+            versions: vec![
+                EnvelopeVersion::JwmV1,
+                EnvelopeVersion::DidcommV2,
+                EnvelopeVersion::CompactJwe,
+                EnvelopeVersion::PlaintextJwm,
+            ],
+        }
+    }
+
+    /// Determine whether an envelope version is supported
+    pub fn supports(&self, version: EnvelopeVersion) -> bool {
+        self.versions.contains(&version)
+    }
+
+    /// Select the most preferred version which is also supported by the
+    /// other party
+    pub fn negotiate(&self, other: &EnvelopeCapabilities) -> Option<EnvelopeVersion> {
+        self.versions
+            .iter()
+            .find(|version| other.supports(**version))
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{pack_message_compact, pack_plaintext, PackBuilder, PackFormat};
+    use super::*;
+    use crate::keys::test_fixtures::TEST1;
+    use crate::keys::PrivateKey;
+
+    #[test]
+    fn test_detect_version() {
+        let pk = TEST1
+            .private_key()
+            .public_key()
+            .unwrap()
+            .as_base58()
+            .unwrap();
+        for (format, version) in &[
+            (PackFormat::Legacy, EnvelopeVersion::JwmV1),
+            (PackFormat::DIDCommV2, EnvelopeVersion::DidcommV2),
+        ] {
+            let packed = PackBuilder::new()
+                .recipient(pk.clone())
+                .format(*format)
+                .build()
+                .unwrap()
+                .pack(b"hello there")
+                .unwrap();
+            assert_eq!(EnvelopeVersion::detect(&packed).unwrap(), *version);
+        }
+        let packed = pack_message_compact(b"hello there", pk, None, None, None).unwrap();
+        assert_eq!(
+            EnvelopeVersion::detect(&packed).unwrap(),
+            EnvelopeVersion::CompactJwe
+        );
+        let packed = pack_plaintext(br#"{"@type": "test"}"#).unwrap();
+        assert_eq!(
+            EnvelopeVersion::detect(&packed).unwrap(),
+            EnvelopeVersion::PlaintextJwm
+        );
+        assert!(EnvelopeVersion::detect(br#"{"@type": "test"}"#).is_err());
+    }
+
+    #[test]
+    fn test_negotiate_version() {
+        let ours = EnvelopeCapabilities::supported();
+        let theirs: EnvelopeCapabilities =
+            serde_json::from_str(r#"{"versions": ["didcomm_v2", "plaintext_jwm"]}"#).unwrap();
+        assert_eq!(ours.negotiate(&theirs), Some(EnvelopeVersion::DidcommV2));
+        assert_eq!(
+            ours.negotiate(&EnvelopeCapabilities { versions: vec![] }),
+            None
+        );
+    }
+}
@@ -0,0 +1,545 @@
+//! RDF datasets and the URDNA2015 dataset canonicalization algorithm
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::{self, Display, Formatter};
+
+use crate::error::ConversionError;
+use crate::hash::SHA256;
+
+pub const RDF_TYPE: &'static str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#type";
+pub const RDF_FIRST: &'static str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#first";
+pub const RDF_REST: &'static str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#rest";
+pub const RDF_NIL: &'static str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#nil";
+pub const RDF_LANG_STRING: &'static str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#langString";
+pub const XSD_BOOLEAN: &'static str = "http://www.w3.org/2001/XMLSchema#boolean";
+pub const XSD_DOUBLE: &'static str = "http://www.w3.org/2001/XMLSchema#double";
+pub const XSD_INTEGER: &'static str = "http://www.w3.org/2001/XMLSchema#integer";
+pub const XSD_STRING: &'static str = "http://www.w3.org/2001/XMLSchema#string";
+
+/// The maximum number of blank node paths explored while canonicalizing a
+/// single dataset, limiting the work spent on blank nodes which cannot be
+/// told apart by their neighbours
+const MAX_PATHS: usize = 100_000;
+
+/// A node or literal in an RDF dataset
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Term {
+    Iri(String),
+    /// A blank node, identified without the `_:` prefix
+    Blank(String),
+    Literal {
+        value: String,
+        datatype: String,
+        language: Option<String>,
+    },
+}
+
+impl Term {
+    pub fn iri<S: Into<String>>(iri: S) -> Self {
+        Self::Iri(iri.into())
+    }
+
+    /// Create a literal with a datatype IRI
+    pub fn typed<S: Into<String>, T: Into<String>>(value: S, datatype: T) -> Self {
+        Self::Literal {
+            value: value.into(),
+            datatype: datatype.into(),
+            language: None,
+        }
+    }
+
+    fn blank_id(&self) -> Option<&str> {
+        match self {
+            Self::Blank(id) => Some(id.as_str()),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Term {
+    /// Serialize the term in canonical N-Quads form
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Iri(iri) => write!(f, "<{}>", iri),
+            Self::Blank(id) => write!(f, "_:{}", id),
+            Self::Literal {
+                value,
+                datatype,
+                language,
+            } => {
+                f.write_str("\"")?;
+                for c in value.chars() {
+                    match c {
+                        '"' => f.write_str("\\\"")?,
+                        '\\' => f.write_str("\\\\")?,
+                        '\n' => f.write_str("\\n")?,
+                        '\r' => f.write_str("\\r")?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                f.write_str("\"")?;
+                if let Some(language) = language {
+                    write!(f, "@{}", language)
+                } else if datatype != XSD_STRING {
+                    write!(f, "^^<{}>", datatype)
+                } else {
+                    Ok(())
+                }
+            }
+        }
+    }
+}
+
+/// A statement in an RDF dataset, in the default graph when `graph` is `None`
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Quad {
+    pub subject: Term,
+    pub predicate: Term,
+    pub object: Term,
+    pub graph: Option<Term>,
+}
+
+impl Quad {
+    /// Apply a mapping to each blank node of the quad
+    fn map_blank<F: Fn(&str) -> String>(&self, f: F) -> Self {
+        let map = |term: &Term| match term {
+            Term::Blank(id) => Term::Blank(f(id)),
+            other => other.clone(),
+        };
+        Self {
+            subject: map(&self.subject),
+            predicate: self.predicate.clone(),
+            object: map(&self.object),
+            graph: self.graph.as_ref().map(map),
+        }
+    }
+}
+
+impl Display for Quad {
+    /// Serialize the quad as a line of canonical N-Quads
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {} ", self.subject, self.predicate, self.object)?;
+        if let Some(graph) = &self.graph {
+            write!(f, "{} ", graph)?;
+        }
+        f.write_str(".\n")
+    }
+}
+
+/// Canonicalize an RDF dataset with the URDNA2015 algorithm, returning the
+/// sorted canonical N-Quads. Duplicate quads are removed.
+pub fn canonicalize(quads: &[Quad]) -> Result<String, ConversionError> {
+    let mut state = State {
+        blank_quads: HashMap::new(),
+        canonical: IdentifierIssuer::new("c14n"),
+        paths: 0,
+    };
+    let mut quads = quads.to_vec();
+    quads.sort();
+    quads.dedup();
+    for quad in &quads {
+        for term in blank_terms(quad) {
+            let entry = state.blank_quads.entry(term.to_owned()).or_default();
+            if entry.last() != Some(quad) {
+                entry.push(quad.clone());
+            }
+        }
+    }
+
+    // issue identifiers for blank nodes with a unique first degree hash
+    let mut by_hash = BTreeMap::<String, Vec<String>>::new();
+    let mut blank_ids = state.blank_quads.keys().cloned().collect::<Vec<_>>();
+    blank_ids.sort();
+    for id in blank_ids {
+        let hash = state.hash_first_degree(&id);
+        by_hash.entry(hash).or_default().push(id);
+    }
+    let mut shared = vec![];
+    for (_, ids) in by_hash {
+        if ids.len() == 1 {
+            state.canonical.issue(&ids[0]);
+        } else {
+            shared.push(ids);
+        }
+    }
+
+    // distinguish the remaining blank nodes by their neighbourhoods
+    for ids in shared {
+        let mut paths = vec![];
+        for id in ids {
+            if state.canonical.get(&id).is_some() {
+                continue;
+            }
+            let mut issuer = IdentifierIssuer::new("b");
+            issuer.issue(&id);
+            paths.push(state.hash_n_degree(&id, issuer)?);
+        }
+        paths.sort_by(|(left, _), (right, _)| left.cmp(right));
+        for (_, issuer) in paths {
+            for id in issuer.order {
+                state.canonical.issue(&id);
+            }
+        }
+    }
+
+    let mut lines = quads
+        .iter()
+        .map(|quad| {
+            quad.map_blank(|id| state.canonical.get(id).unwrap_or_default().to_owned())
+                .to_string()
+        })
+        .collect::<Vec<_>>();
+    lines.sort();
+    lines.dedup();
+    Ok(lines.concat())
+}
+
+fn blank_terms(quad: &Quad) -> impl Iterator<Item = &str> {
+    quad.subject
+        .blank_id()
+        .into_iter()
+        .chain(quad.object.blank_id())
+        .chain(quad.graph.as_ref().and_then(Term::blank_id))
+}
+
+fn sha256_hex(input: &str) -> String {
+    hex::encode(SHA256::digest(input.as_bytes()))
+}
+
+/// Issues sequential blank node identifiers, remembering the issue order
+#[derive(Clone, Debug)]
+struct IdentifierIssuer {
+    prefix: &'static str,
+    issued: HashMap<String, String>,
+    order: Vec<String>,
+}
+
+impl IdentifierIssuer {
+    fn new(prefix: &'static str) -> Self {
+        Self {
+            prefix,
+            issued: HashMap::new(),
+            order: vec![],
+        }
+    }
+
+    fn get(&self, id: &str) -> Option<&str> {
+        self.issued.get(id).map(String::as_str)
+    }
+
+    fn issue(&mut self, id: &str) -> String {
+        if let Some(issued) = self.issued.get(id) {
+            return issued.clone();
+        }
+        let issued = format!("{}{}", self.prefix, self.order.len());
+        self.issued.insert(id.to_owned(), issued.clone());
+        self.order.push(id.to_owned());
+        issued
+    }
+}
+
+struct State {
+    blank_quads: HashMap<String, Vec<Quad>>,
+    canonical: IdentifierIssuer,
+    paths: usize,
+}
+
+impl State {
+    fn hash_first_degree(&self, id: &str) -> String {
+        let mut lines = self.blank_quads[id]
+            .iter()
+            .map(|quad| {
+                quad.map_blank(|other| if other == id { "a" } else { "z" }.to_owned())
+                    .to_string()
+            })
+            .collect::<Vec<_>>();
+        lines.sort();
+        sha256_hex(&lines.concat())
+    }
+
+    fn hash_related(
+        &self,
+        related: &str,
+        quad: &Quad,
+        issuer: &IdentifierIssuer,
+        position: &str,
+    ) -> String {
+        let identifier = match self.canonical.get(related).or_else(|| issuer.get(related)) {
+            Some(issued) => format!("_:{}", issued),
+            None => self.hash_first_degree(related),
+        };
+        let mut input = position.to_owned();
+        if position != "g" {
+            input.push_str(&quad.predicate.to_string());
+        }
+        input.push_str(&identifier);
+        sha256_hex(&input)
+    }
+
+    fn hash_n_degree(
+        &mut self,
+        id: &str,
+        mut issuer: IdentifierIssuer,
+    ) -> Result<(String, IdentifierIssuer), ConversionError> {
+        let mut related = BTreeMap::<String, Vec<String>>::new();
+        for quad in &self.blank_quads[id] {
+            let positions = [
+                (quad.subject.blank_id(), "s"),
+                (quad.object.blank_id(), "o"),
+                (quad.graph.as_ref().and_then(Term::blank_id), "g"),
+            ];
+            for (component, position) in positions.iter() {
+                if let Some(component) = component {
+                    if *component != id {
+                        let hash = self.hash_related(component, quad, &issuer, position);
+                        related
+                            .entry(hash)
+                            .or_default()
+                            .push((*component).to_owned());
+                    }
+                }
+            }
+        }
+
+        let mut data = String::new();
+        for (hash, nodes) in related {
+            data.push_str(&hash);
+            let mut chosen: Option<(String, IdentifierIssuer)> = None;
+            'permutations: for permutation in Permutations::new(&nodes) {
+                self.paths += 1;
+                if self.paths > MAX_PATHS {
+                    return Err("Dataset blank nodes are too costly to canonicalize".into());
+                }
+                let mut issuer_copy = issuer.clone();
+                let mut path = String::new();
+                let mut recursion = vec![];
+                for node in permutation {
+                    if let Some(canonical) = self.canonical.get(node) {
+                        path.push_str("_:");
+                        path.push_str(canonical);
+                    } else {
+                        if issuer_copy.get(node).is_none() {
+                            recursion.push(node);
+                        }
+                        path.push_str("_:");
+                        path.push_str(&issuer_copy.issue(node));
+                    }
+                    if exceeds(&path, &chosen) {
+                        continue 'permutations;
+                    }
+                }
+                for node in recursion {
+                    let (hash, result) = self.hash_n_degree(node, issuer_copy.clone())?;
+                    path.push_str("_:");
+                    path.push_str(&issuer_copy.issue(node));
+                    path.push('<');
+                    path.push_str(&hash);
+                    path.push('>');
+                    issuer_copy = result;
+                    if exceeds(&path, &chosen) {
+                        continue 'permutations;
+                    }
+                }
+                let shorter = match &chosen {
+                    Some((chosen, _)) => path < *chosen,
+                    None => true,
+                };
+                if shorter {
+                    chosen = Some((path, issuer_copy));
+                }
+            }
+            if let Some((path, chosen)) = chosen {
+                data.push_str(&path);
+                issuer = chosen;
+            }
+        }
+        Ok((sha256_hex(&data), issuer))
+    }
+}
+
+/// Whether a candidate path can no longer be chosen over the current best
+fn exceeds(path: &str, chosen: &Option<(String, IdentifierIssuer)>) -> bool {
+    match chosen {
+        Some((chosen, _)) => path.len() >= chosen.len() && path > chosen.as_str(),
+        None => false,
+    }
+}
+
+/// Iterates over all orderings of a list of blank nodes (Heap's algorithm)
+struct Permutations<'a> {
+    nodes: Vec<&'a str>,
+    counters: Vec<usize>,
+    idx: usize,
+    started: bool,
+}
+
+impl<'a> Permutations<'a> {
+    fn new(nodes: &'a [String]) -> Self {
+        Self {
+            nodes: nodes.iter().map(String::as_str).collect(),
+            counters: vec![0; nodes.len()],
+            idx: 1,
+            started: false,
+        }
+    }
+}
+
+impl<'a> Iterator for Permutations<'a> {
+    type Item = Vec<&'a str>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            return Some(self.nodes.clone());
+        }
+        while self.idx < self.nodes.len() {
+            if self.counters[self.idx] < self.idx {
+                let swap = if self.idx & 1 == 0 {
+                    0
+                } else {
+                    self.counters[self.idx]
+                };
+                self.nodes.swap(swap, self.idx);
+                self.counters[self.idx] += 1;
+                self.idx = 1;
+                return Some(self.nodes.clone());
+            }
+            self.counters[self.idx] = 0;
+            self.idx += 1;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quad(subject: Term, predicate: &str, object: Term) -> Quad {
+        Quad {
+            subject,
+            predicate: Term::iri(predicate),
+            object,
+            graph: None,
+        }
+    }
+
+    fn blank(id: &str) -> Term {
+        Term::Blank(id.to_owned())
+    }
+
+    #[test]
+    fn serialize_literals() {
+        let quads = [
+            quad(
+                Term::iri("http://example.com/s"),
+                "http://example.com/p",
+                Term::typed("line\n\"quoted\"\\", XSD_STRING),
+            ),
+            quad(
+                Term::iri("http://example.com/s"),
+                "http://example.com/p",
+                Term::typed("1", XSD_INTEGER),
+            ),
+        ];
+        assert_eq!(
+            canonicalize(&quads).unwrap(),
+            "<http://example.com/s> <http://example.com/p> \"1\"^^<http://www.w3.org/2001/XMLSchema#integer> .\n\
+             <http://example.com/s> <http://example.com/p> \"line\\n\\\"quoted\\\"\\\\\" .\n"
+        );
+    }
+
+    #[test]
+    fn hash_first_degree_quads() {
+        // the unique hashes example of the RDF dataset canonicalization spec
+        let p = Term::iri("http://example.com/#p");
+        let u = Term::iri("http://example.com/#u");
+        let quads = [
+            quad(p.clone(), "http://example.com/#q", blank("e0")),
+            quad(p.clone(), "http://example.com/#r", blank("e1")),
+            quad(blank("e0"), "http://example.com/#s", u.clone()),
+            quad(blank("e1"), "http://example.com/#t", u),
+        ];
+        assert_eq!(
+            canonicalize(&quads).unwrap(),
+            "<http://example.com/#p> <http://example.com/#q> _:c14n0 .\n\
+             <http://example.com/#p> <http://example.com/#r> _:c14n1 .\n\
+             _:c14n0 <http://example.com/#s> <http://example.com/#u> .\n\
+             _:c14n1 <http://example.com/#t> <http://example.com/#u> .\n"
+        );
+        let mut state = State {
+            blank_quads: HashMap::new(),
+            canonical: IdentifierIssuer::new("c14n"),
+            paths: 0,
+        };
+        state
+            .blank_quads
+            .insert("e0".to_owned(), vec![quads[0].clone(), quads[2].clone()]);
+        assert_eq!(
+            state.hash_first_degree("e0"),
+            "21d1dd5ba21f3dee9d76c0c00c260fa6f5d5d65315099e553026f4828d0dc77a"
+        );
+    }
+
+    #[test]
+    fn label_unique_blank_nodes() {
+        let quads = [
+            quad(
+                blank("x"),
+                "http://example.com/p",
+                Term::iri("http://example.com/o"),
+            ),
+            quad(blank("y"), "http://example.com/q", blank("x")),
+        ];
+        let expected = canonicalize(&quads).unwrap();
+        assert!(!expected.contains("_:x") && !expected.contains("_:y"));
+        // the labelling only depends on the structure of the dataset
+        let renamed = [
+            quad(blank("b"), "http://example.com/q", blank("a")),
+            quad(
+                blank("a"),
+                "http://example.com/p",
+                Term::iri("http://example.com/o"),
+            ),
+        ];
+        assert_eq!(canonicalize(&renamed).unwrap(), expected);
+    }
+
+    #[test]
+    fn label_symmetric_blank_nodes() {
+        let quads = [
+            quad(blank("x"), "http://example.com/p", blank("y")),
+            quad(blank("y"), "http://example.com/p", blank("x")),
+        ];
+        assert_eq!(
+            canonicalize(&quads).unwrap(),
+            "_:c14n0 <http://example.com/p> _:c14n1 .\n\
+             _:c14n1 <http://example.com/p> _:c14n0 .\n"
+        );
+    }
+
+    #[test]
+    fn label_blank_node_cycles() {
+        let cycle = |ids: [&str; 4]| {
+            (0..4)
+                .map(|idx| {
+                    quad(
+                        blank(ids[idx]),
+                        "http://example.com/next",
+                        blank(ids[(idx + 1) % 4]),
+                    )
+                })
+                .chain(std::iter::once(quad(
+                    blank(ids[0]),
+                    "http://example.com/label",
+                    Term::typed("start", XSD_STRING),
+                )))
+                .collect::<Vec<_>>()
+        };
+        let expected = canonicalize(&cycle(["a", "b", "c", "d"])).unwrap();
+        assert_eq!(
+            canonicalize(&cycle(["d", "a", "c", "b"])).unwrap(),
+            expected
+        );
+    }
+}
@@ -1,7 +1,9 @@
 use serde_json::Value as SJsonValue;
 
-use super::error::ValidationError;
+use super::base58;
+use super::error::{ConversionError, ValidationError};
 use super::hash::SHA256;
+use super::keys::AsyncSigner;
 
 const ATTRIB: &str = "100";
 const GET_ATTR: &str = "104";
@@ -65,6 +67,26 @@ fn _serialize_signature(
     }
 }
 
+/// Sign a ledger transaction request with a local or remote signer, setting
+/// the top-level `signature` field of the request
+pub async fn sign_request<S>(request: &mut SJsonValue, signer: &S) -> Result<(), ConversionError>
+where
+    S: AsyncSigner + ?Sized,
+{
+    let serialized = serialize_signature(request)?;
+    let signature = signer.sign(serialized.as_bytes()).await?;
+    match request.as_object_mut() {
+        Some(obj) => {
+            obj.insert(
+                "signature".to_string(),
+                SJsonValue::String(base58::encode(signature)),
+            );
+            Ok(())
+        }
+        None => Err("Ledger request must be a JSON object".into()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -175,4 +197,21 @@ mod tests {
         let serialized = serialize_signature(&msg).unwrap();
         assert_eq!(serialized, "");
     }
+
+    #[cfg(feature = "ed25519")]
+    #[test]
+    fn sign_request_works() {
+        use crate::keys::test_fixtures::TEST3;
+
+        let sk = TEST3.private_key();
+        let mut req: SJsonValue =
+            serde_json::from_str(r#"{"operation": {"type": "105", "dest": "abc"}, "reqId": 1}"#)
+                .unwrap();
+        async_global_executor::block_on(sign_request(&mut req, &sk)).unwrap();
+        let signature = base58::decode(req["signature"].as_str().unwrap()).unwrap();
+        let vk = sk.public_key().unwrap();
+        assert!(vk
+            .verify_signature(serialize_signature(&req).unwrap(), signature)
+            .unwrap());
+    }
 }
@@ -0,0 +1,596 @@
+//! W3C Verifiable Credentials and Presentations (data model 1.1) secured
+//! with Ed25519Signature2020 proofs.
+//!
+//! Documents and proof options are converted to RDF and canonicalized with
+//! URDNA2015 before hashing. The contexts they reference are resolved by a
+//! [`ContextLoader`], which must hold every context other than the
+//! credentials and Ed25519 2020 suite contexts.
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::base58;
+use crate::did::document::ED25519_2020_CONTEXT;
+use crate::error::ConversionError;
+use crate::hash::SHA256;
+use crate::jsonld::{canonicalize, ContextLoader};
+use crate::keys::{KeyType, PrivateKey, VerKey};
+use crate::{Validatable, ValidationError};
+
+pub const CREDENTIALS_CONTEXT_V1: &'static str = "https://www.w3.org/2018/credentials/v1";
+pub const VERIFIABLE_CREDENTIAL: &'static str = "VerifiableCredential";
+pub const VERIFIABLE_PRESENTATION: &'static str = "VerifiablePresentation";
+pub const ED25519_SIGNATURE_2020: &'static str = "Ed25519Signature2020";
+pub const DATA_INTEGRITY_PROOF: &'static str = "DataIntegrityProof";
+
+/// The proof purpose of credentials signed by their issuer
+pub const PROOF_PURPOSE_ASSERTION: &'static str = "assertionMethod";
+/// The proof purpose of presentations signed by their holder
+pub const PROOF_PURPOSE_AUTHENTICATION: &'static str = "authentication";
+
+/// A W3C Verifiable Credential
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifiableCredential {
+    #[serde(rename = "@context")]
+    pub context: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(rename = "type")]
+    pub type_: Vec<String>,
+    pub issuer: String,
+    pub issuance_date: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expiration_date: Option<String>,
+    pub credential_subject: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential_status: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credential_schema: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proof: Option<Proof>,
+}
+
+impl VerifiableCredential {
+    /// Create an unsigned credential with the base context and type
+    pub fn new(issuer: &str, issuance_date: &str, credential_subject: Value) -> Self {
+        Self {
+            context: vec![CREDENTIALS_CONTEXT_V1.to_owned()],
+            id: None,
+            type_: vec![VERIFIABLE_CREDENTIAL.to_owned()],
+            issuer: issuer.to_owned(),
+            issuance_date: issuance_date.to_owned(),
+            expiration_date: None,
+            credential_subject,
+            credential_status: None,
+            credential_schema: None,
+            proof: None,
+        }
+    }
+
+    pub fn id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_owned());
+        self
+    }
+
+    /// Add a JSON-LD context, such as the context defining the subject claims
+    pub fn context(mut self, context: &str) -> Self {
+        self.context.push(context.to_owned());
+        self
+    }
+
+    /// Add a credential type, such as `UniversityDegreeCredential`
+    pub fn type_(mut self, type_: &str) -> Self {
+        self.type_.push(type_.to_owned());
+        self
+    }
+
+    pub fn expiration_date(mut self, expiration_date: &str) -> Self {
+        self.expiration_date = Some(expiration_date.to_owned());
+        self
+    }
+
+    pub fn credential_schema(mut self, credential_schema: Value) -> Self {
+        self.credential_schema = Some(credential_schema);
+        self
+    }
+
+    /// Add an Ed25519Signature2020 proof for the issuer verification method
+    /// `verification_method`, created at the RFC 3339 date-time `created`
+    pub fn sign(
+        mut self,
+        loader: &ContextLoader,
+        key: &PrivateKey,
+        verification_method: &str,
+        created: &str,
+    ) -> Result<Self, ConversionError> {
+        self.proof = None;
+        add_context(&mut self.context);
+        let options = Proof::new(verification_method, PROOF_PURPOSE_ASSERTION, created);
+        self.proof = Some(create_proof(&self, &self.context, options, loader, key)?);
+        Ok(self)
+    }
+
+    /// Verify the proof of the credential against the issuer's verkey
+    pub fn verify(&self, loader: &ContextLoader, verkey: &VerKey) -> Result<(), ConversionError> {
+        self.validate()?;
+        let proof = self
+            .proof
+            .as_ref()
+            .ok_or_else(|| ConversionError::from_msg("Credential is not signed"))?;
+        let mut unsigned = self.clone();
+        unsigned.proof = None;
+        verify_proof(
+            &unsigned,
+            &self.context,
+            proof,
+            PROOF_PURPOSE_ASSERTION,
+            loader,
+            verkey,
+        )
+    }
+}
+
+impl Validatable for VerifiableCredential {
+    fn validate(&self) -> Result<(), ValidationError> {
+        validate_base(&self.context, &self.type_, VERIFIABLE_CREDENTIAL)?;
+        if self.issuer.is_empty() {
+            return Err(invalid!("Credential issuer must not be empty"));
+        }
+        if !self.credential_subject.is_object() && !self.credential_subject.is_array() {
+            return Err(invalid!("Credential subject must be an object or array"));
+        }
+        Ok(())
+    }
+}
+
+/// A W3C Verifiable Presentation
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct VerifiablePresentation {
+    #[serde(rename = "@context")]
+    pub context: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    #[serde(rename = "type")]
+    pub type_: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub holder: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub verifiable_credential: Vec<VerifiableCredential>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proof: Option<Proof>,
+}
+
+impl VerifiablePresentation {
+    /// Create an unsigned presentation of a set of credentials
+    pub fn new(holder: Option<&str>, credentials: Vec<VerifiableCredential>) -> Self {
+        Self {
+            context: vec![CREDENTIALS_CONTEXT_V1.to_owned()],
+            id: None,
+            type_: vec![VERIFIABLE_PRESENTATION.to_owned()],
+            holder: holder.map(str::to_owned),
+            verifiable_credential: credentials,
+            proof: None,
+        }
+    }
+
+    pub fn id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_owned());
+        self
+    }
+
+    /// Add an Ed25519Signature2020 proof for the holder verification method
+    /// `verification_method`, bound to the verifier's `challenge` and
+    /// optional `domain`
+    pub fn sign(
+        mut self,
+        loader: &ContextLoader,
+        key: &PrivateKey,
+        verification_method: &str,
+        created: &str,
+        challenge: &str,
+        domain: Option<&str>,
+    ) -> Result<Self, ConversionError> {
+        self.proof = None;
+        add_context(&mut self.context);
+        let mut options = Proof::new(verification_method, PROOF_PURPOSE_AUTHENTICATION, created);
+        options.challenge = Some(challenge.to_owned());
+        options.domain = domain.map(str::to_owned);
+        self.proof = Some(create_proof(&self, &self.context, options, loader, key)?);
+        Ok(self)
+    }
+
+    /// Verify the proof of the presentation against the holder's verkey, the
+    /// expected challenge and, when given, the verifier's domain. The proofs
+    /// of the presented credentials must be verified separately against the
+    /// keys of their issuers.
+    pub fn verify(
+        &self,
+        loader: &ContextLoader,
+        verkey: &VerKey,
+        challenge: &str,
+        expected_domain: Option<&str>,
+    ) -> Result<(), ConversionError> {
+        self.validate()?;
+        let proof = self
+            .proof
+            .as_ref()
+            .ok_or_else(|| ConversionError::from_msg("Presentation is not signed"))?;
+        if proof.challenge.as_deref() != Some(challenge) {
+            return Err("Presentation challenge does not match".into());
+        }
+        if let Some(domain) = expected_domain {
+            if proof.domain.as_deref() != Some(domain) {
+                return Err("Presentation domain does not match".into());
+            }
+        }
+        let mut unsigned = self.clone();
+        unsigned.proof = None;
+        verify_proof(
+            &unsigned,
+            &self.context,
+            proof,
+            PROOF_PURPOSE_AUTHENTICATION,
+            loader,
+            verkey,
+        )
+    }
+}
+
+impl Validatable for VerifiablePresentation {
+    fn validate(&self) -> Result<(), ValidationError> {
+        validate_base(&self.context, &self.type_, VERIFIABLE_PRESENTATION)?;
+        for credential in &self.verifiable_credential {
+            credential.validate()?;
+        }
+        Ok(())
+    }
+}
+
+/// A linked data proof attached to a credential or presentation
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct Proof {
+    #[serde(rename = "type")]
+    pub type_: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cryptosuite: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created: Option<String>,
+    pub verification_method: String,
+    pub proof_purpose: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub challenge: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub proof_value: String,
+}
+
+impl Proof {
+    fn new(verification_method: &str, proof_purpose: &str, created: &str) -> Self {
+        Self {
+            type_: ED25519_SIGNATURE_2020.to_owned(),
+            cryptosuite: None,
+            created: Some(created.to_owned()),
+            verification_method: verification_method.to_owned(),
+            proof_purpose: proof_purpose.to_owned(),
+            challenge: None,
+            domain: None,
+            proof_value: String::new(),
+        }
+    }
+}
+
+fn validate_base(context: &[String], types: &[String], type_: &str) -> Result<(), ValidationError> {
+    if context.first().map(String::as_str) != Some(CREDENTIALS_CONTEXT_V1) {
+        return Err(invalid!(
+            "The first context must be {}",
+            CREDENTIALS_CONTEXT_V1
+        ));
+    }
+    if !types.iter().any(|t| t == type_) {
+        return Err(invalid!("Expected type {}", type_));
+    }
+    Ok(())
+}
+
+fn add_context(context: &mut Vec<String>) {
+    if !context.iter().any(|ctx| ctx == ED25519_2020_CONTEXT) {
+        context.push(ED25519_2020_CONTEXT.to_owned());
+    }
+}
+
+fn create_proof<T: Serialize>(
+    document: &T,
+    context: &[String],
+    mut options: Proof,
+    loader: &ContextLoader,
+    key: &PrivateKey,
+) -> Result<Proof, ConversionError> {
+    if key.alg != KeyType::ED25519 {
+        return Err("Ed25519Signature2020 proofs require an ed25519 key".into());
+    }
+    let signature = key.sign(signing_input(document, context, &options, loader)?)?;
+    options.proof_value = format!("z{}", base58::encode(signature));
+    Ok(options)
+}
+
+fn verify_proof<T: Serialize>(
+    document: &T,
+    context: &[String],
+    proof: &Proof,
+    proof_purpose: &str,
+    loader: &ContextLoader,
+    verkey: &VerKey,
+) -> Result<(), ConversionError> {
+    if proof.type_ != ED25519_SIGNATURE_2020 {
+        return Err(format!("Unsupported proof type: {}", proof.type_).into());
+    }
+    if proof.proof_purpose != proof_purpose {
+        return Err(format!("Unexpected proof purpose: {}", proof.proof_purpose).into());
+    }
+    if verkey.alg != KeyType::ED25519 {
+        return Err("Ed25519Signature2020 proofs require an ed25519 key".into());
+    }
+    if !proof.proof_value.starts_with('z') {
+        return Err("Expected a base58btc multibase proof value".into());
+    }
+    let signature = base58::decode(&proof.proof_value[1..])?;
+    let mut options = proof.clone();
+    options.proof_value = String::new();
+    if verkey.verify_signature(
+        signing_input(document, context, &options, loader)?,
+        signature,
+    )? {
+        Ok(())
+    } else {
+        Err("Invalid proof signature".into())
+    }
+}
+
+/// The hash of the canonical proof options, which share the document
+/// context, followed by the hash of the canonical document
+fn signing_input<T: Serialize>(
+    document: &T,
+    context: &[String],
+    options: &Proof,
+    loader: &ContextLoader,
+) -> Result<Vec<u8>, ConversionError> {
+    let mut options = serde_json::to_value(options)?;
+    options["@context"] = context.into();
+    let mut input = SHA256::digest(canonicalize(&options, loader)?);
+    input.extend_from_slice(&SHA256::digest(canonicalize(document, loader)?));
+    Ok(input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::did::key::{did_key, resolve_verkey};
+    use crate::did::DID;
+    use crate::keys::test_fixtures::{TEST1, TEST2};
+    use serde_json::json;
+
+    const CREATED: &'static str = "2021-01-01T00:00:00Z";
+    const EXAMPLES_CONTEXT: &'static str = "https://example.org/examples/v1";
+    const CHALLENGE: &'static str = "99612b24-63d9-11ea-b99f-4f66f3e4f81a";
+
+    fn loader() -> ContextLoader {
+        ContextLoader::default()
+            .with_context(
+                EXAMPLES_CONTEXT,
+                json!({
+                    "@context": {
+                        "@protected": true,
+                        "UniversityDegreeCredential": "https://example.org/examples#UniversityDegreeCredential",
+                        "BachelorDegree": "https://example.org/examples#BachelorDegree",
+                        "degree": "https://example.org/examples#degree",
+                        "name": "https://schema.org/name"
+                    }
+                }),
+            )
+            .unwrap()
+    }
+
+    fn unsigned_credential(issuer: &str) -> VerifiableCredential {
+        VerifiableCredential::new(
+            issuer,
+            CREATED,
+            json!({
+                "id": "did:example:holder",
+                "degree": {"type": "BachelorDegree", "name": "Bachelor of Science"}
+            }),
+        )
+        .id("urn:uuid:3978344f-8596-4c3a-a978-8fcaba3903c5")
+        .context(EXAMPLES_CONTEXT)
+        .type_("UniversityDegreeCredential")
+    }
+
+    fn credential() -> (VerifiableCredential, String) {
+        let did = did_key(&TEST1.encoded_verkey()).unwrap();
+        let method = format!("{}#{}", did, did.id);
+        let credential = unsigned_credential(&did.to_string())
+            .sign(&loader(), &TEST1.private_key(), &method, CREATED)
+            .unwrap();
+        (credential, method)
+    }
+
+    #[test]
+    fn credential_round_trip() {
+        let loader = loader();
+        let (credential, method) = credential();
+        let json = serde_json::to_value(&credential).unwrap();
+        assert_eq!(json["@context"][2], ED25519_2020_CONTEXT);
+        assert_eq!(json["proof"]["type"], ED25519_SIGNATURE_2020);
+        assert!(json["proof"].get("cryptosuite").is_none());
+        assert_eq!(json["proof"]["verificationMethod"], method);
+        assert_eq!(json["proof"]["proofPurpose"], PROOF_PURPOSE_ASSERTION);
+        assert!(json["proof"]["proofValue"]
+            .as_str()
+            .unwrap()
+            .starts_with('z'));
+
+        let credential: VerifiableCredential = serde_json::from_value(json).unwrap();
+        credential.verify(&loader, &TEST1.verkey()).unwrap();
+        assert!(credential.verify(&loader, &TEST2.verkey()).is_err());
+        // the example context is needed to interpret the subject claims
+        assert!(credential
+            .verify(&ContextLoader::default(), &TEST1.verkey())
+            .is_err());
+
+        let mut tampered = credential.clone();
+        tampered.credential_subject["degree"]["name"] = "Doctor of Science".into();
+        assert!(tampered.verify(&loader, &TEST1.verkey()).is_err());
+        let mut tampered = credential.clone();
+        tampered.proof.as_mut().unwrap().type_ = DATA_INTEGRITY_PROOF.to_owned();
+        assert!(tampered.verify(&loader, &TEST1.verkey()).is_err());
+        let mut tampered = credential;
+        tampered.proof.as_mut().unwrap().created = Some("2022-01-01T00:00:00Z".to_owned());
+        assert!(tampered.verify(&loader, &TEST1.verkey()).is_err());
+    }
+
+    /// A credential signed for the RFC 8032 test key with an independent
+    /// Ed25519 implementation, over the canonical N-Quads given below
+    #[test]
+    fn credential_external_vector() {
+        let credential: VerifiableCredential = serde_json::from_value(json!({
+            "@context": [CREDENTIALS_CONTEXT_V1, ED25519_2020_CONTEXT],
+            "id": "http://example.edu/credentials/1872",
+            "type": ["VerifiableCredential"],
+            "issuer": "did:key:z6MktwupdmLXVVqTzCw4i46r4uGyosGXRnR3XjN4Zq7oMMsw",
+            "issuanceDate": "2010-01-01T19:23:24Z",
+            "credentialSubject": {"id": "did:example:ebfeb1f712ebc6f1c276e12ec21"},
+            "proof": {
+                "type": "Ed25519Signature2020",
+                "created": "2010-01-01T19:23:24Z",
+                "verificationMethod": "did:key:z6MktwupdmLXVVqTzCw4i46r4uGyosGXRnR3XjN4Zq7oMMsw#z6MktwupdmLXVVqTzCw4i46r4uGyosGXRnR3XjN4Zq7oMMsw",
+                "proofPurpose": "assertionMethod",
+                "proofValue": "z5WTLcton48NzgtVhfxcNk8XC1Cvy6hQxBwonaX1iSivK2rAffpct5c7k1D48omVYsnZDxssernhZVvzH222z4kRd"
+            }
+        }))
+        .unwrap();
+        let loader = ContextLoader::default();
+        let verkey = resolve_verkey(&DID::parse(&credential.issuer).unwrap()).unwrap();
+        assert_eq!(
+            verkey.as_base58().unwrap().key,
+            "FVen3X669xLzsi6N2V91DoiyzHzg1uAgqiT8jZ9nS96Z"
+        );
+
+        let mut unsigned = credential.clone();
+        unsigned.proof = None;
+        assert_eq!(
+            canonicalize(&unsigned, &loader).unwrap(),
+            "<http://example.edu/credentials/1872> <http://www.w3.org/1999/02/22-rdf-syntax-ns#type> <https://www.w3.org/2018/credentials#VerifiableCredential> .\n\
+             <http://example.edu/credentials/1872> <https://www.w3.org/2018/credentials#credentialSubject> <did:example:ebfeb1f712ebc6f1c276e12ec21> .\n\
+             <http://example.edu/credentials/1872> <https://www.w3.org/2018/credentials#issuanceDate> \"2010-01-01T19:23:24Z\"^^<http://www.w3.org/2001/XMLSchema#dateTime> .\n\
+             <http://example.edu/credentials/1872> <https://www.w3.org/2018/credentials#issuer> <did:key:z6MktwupdmLXVVqTzCw4i46r4uGyosGXRnR3XjN4Zq7oMMsw> .\n"
+        );
+        credential.verify(&loader, &verkey).unwrap();
+
+        // re-signing with the same key reproduces the proof value
+        let seed =
+            crate::hex::decode("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60")
+                .unwrap();
+        let key = PrivateKey::from_seed(&seed).unwrap();
+        let resigned = unsigned
+            .sign(
+                &loader,
+                &key,
+                &credential.proof.as_ref().unwrap().verification_method,
+                "2010-01-01T19:23:24Z",
+            )
+            .unwrap();
+        assert_eq!(resigned, credential);
+    }
+
+    #[test]
+    fn credential_undefined_terms() {
+        // claims which are not defined by a context cannot be signed
+        let mut credential = unsigned_credential("did:example:issuer");
+        credential.credential_subject["grade"] = "A".into();
+        assert!(credential
+            .sign(
+                &loader(),
+                &TEST1.private_key(),
+                "did:example:issuer#key-1",
+                CREATED
+            )
+            .is_err());
+    }
+
+    fn signed_presentation(domain: Option<&str>) -> VerifiablePresentation {
+        let (credential, _) = credential();
+        let holder = did_key(&TEST2.encoded_verkey()).unwrap();
+        let method = format!("{}#{}", holder, holder.id);
+        VerifiablePresentation::new(Some(&holder.to_string()), vec![credential])
+            .sign(
+                &loader(),
+                &TEST2.private_key(),
+                &method,
+                CREATED,
+                CHALLENGE,
+                domain,
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn presentation_round_trip() {
+        let loader = loader();
+        let presentation = signed_presentation(Some("example.com"));
+        let json = serde_json::to_value(&presentation).unwrap();
+        assert_eq!(json["type"][0], VERIFIABLE_PRESENTATION);
+        assert_eq!(json["proof"]["challenge"], CHALLENGE);
+        assert_eq!(json["proof"]["domain"], "example.com");
+
+        let presentation: VerifiablePresentation = serde_json::from_value(json).unwrap();
+        presentation
+            .verify(&loader, &TEST2.verkey(), CHALLENGE, Some("example.com"))
+            .unwrap();
+        presentation
+            .verify(&loader, &TEST2.verkey(), CHALLENGE, None)
+            .unwrap();
+        presentation.verifiable_credential[0]
+            .verify(&loader, &TEST1.verkey())
+            .unwrap();
+        assert!(presentation
+            .verify(&loader, &TEST2.verkey(), "other", None)
+            .is_err());
+        assert!(presentation
+            .verify(&loader, &TEST1.verkey(), CHALLENGE, None)
+            .is_err());
+        // the presented credentials are covered by the presentation proof
+        let mut tampered = presentation.clone();
+        tampered.verifiable_credential[0].credential_subject["degree"]["name"] =
+            "Doctor of Science".into();
+        assert!(tampered
+            .verify(&loader, &TEST2.verkey(), CHALLENGE, None)
+            .is_err());
+        // a credential proof cannot be used as a presentation proof
+        let mut forged = VerifiablePresentation::new(None, vec![]);
+        forged.proof = presentation.verifiable_credential[0].proof.clone();
+        assert!(forged
+            .verify(&loader, &TEST1.verkey(), CHALLENGE, None)
+            .is_err());
+    }
+
+    #[test]
+    fn presentation_domain() {
+        let loader = loader();
+        // a presentation made for one verifier is rejected by another
+        let presentation = signed_presentation(Some("example.com"));
+        assert!(presentation
+            .verify(&loader, &TEST2.verkey(), CHALLENGE, Some("other.example"))
+            .is_err());
+        // the domain is required when the verifier expects one
+        let presentation = signed_presentation(None);
+        presentation
+            .verify(&loader, &TEST2.verkey(), CHALLENGE, None)
+            .unwrap();
+        assert!(presentation
+            .verify(&loader, &TEST2.verkey(), CHALLENGE, Some("example.com"))
+            .is_err());
+    }
+}